@@ -0,0 +1,169 @@
+//! Time each way of sizing a tree, to help choose between them.
+//!
+//! Run with `cargo run --release --example bench -- PATH`, adding
+//! `--features rayon` to include parallel scans.  The tree is walked once
+//! beforehand so every strategy sees a warm metadata cache; drop caches and
+//! run with a single strategy to measure cold performance instead.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use filesize::{PathExt, Scanner, SizeCache};
+
+struct Timing {
+    name: String,
+    entries: u64,
+    physical: u64,
+    elapsed: Duration,
+}
+
+fn time<F>(name: &str, f: F) -> Timing
+where
+    F: FnOnce() -> io::Result<(u64, u64)>,
+{
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    let (entries, physical) = result.unwrap_or_else(|e| {
+        eprintln!("{}: {}", name, e);
+        (0, 0)
+    });
+
+    Timing {
+        name: name.to_string(),
+        entries,
+        physical,
+        elapsed,
+    }
+}
+
+/// Size each path with `file_real_size`, which queries it afresh.
+fn per_path(paths: &[PathBuf]) -> io::Result<(u64, u64)> {
+    let mut physical = 0;
+    for path in paths {
+        physical += path.size_on_disk()?;
+    }
+    Ok((paths.len() as u64, physical))
+}
+
+/// Size each path with `file_real_size_fast` and metadata from a stat.
+fn fast(paths: &[PathBuf]) -> io::Result<(u64, u64)> {
+    let mut physical = 0;
+    for path in paths {
+        let metadata = path.symlink_metadata()?;
+        physical += path.size_on_disk_fast(&metadata)?;
+    }
+    Ok((paths.len() as u64, physical))
+}
+
+/// Size each path relative to an open handle on its parent directory.
+#[cfg(unix)]
+fn at(paths: &[PathBuf]) -> io::Result<(u64, u64)> {
+    let mut physical = 0;
+    let mut dir: Option<(PathBuf, std::fs::File)> = None;
+
+    for path in paths {
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) if parent.as_os_str().is_empty() => (Path::new("."), name),
+            (Some(parent), Some(name)) => (parent, name),
+            _ => {
+                physical += path.size_on_disk()?;
+                continue;
+            }
+        };
+
+        if dir.as_ref().is_none_or(|(open, _)| open != parent) {
+            dir = Some((parent.to_path_buf(), std::fs::File::open(parent)?));
+        }
+        if let Some((_, handle)) = &dir {
+            physical += filesize::file_real_size_at(handle, name)?;
+        }
+    }
+    Ok((paths.len() as u64, physical))
+}
+
+/// A scanner which counts directories as the per-path strategies do.
+fn scanner(root: &Path) -> Scanner {
+    Scanner::new(root).directory_sizes(true)
+}
+
+fn scan(scanner: Scanner) -> io::Result<(u64, u64)> {
+    let usage = scanner.total()?;
+    Ok((usage.entries(), usage.physical()))
+}
+
+fn main() -> io::Result<()> {
+    let root = match std::env::args_os().nth(1) {
+        Some(root) => PathBuf::from(root),
+        None => {
+            eprintln!("usage: bench PATH");
+            std::process::exit(1);
+        }
+    };
+
+    // Walk the tree once, both to list it and to warm the metadata cache.
+    let paths: Vec<PathBuf> = scanner(&root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+
+    let mut timings = vec![
+        time("per-path", || per_path(&paths)),
+        time("fast", || fast(&paths)),
+    ];
+    #[cfg(unix)]
+    timings.push(time("at", || at(&paths)));
+    timings.push(time("scanner", || scan(scanner(&root))));
+
+    let cache = Arc::new(SizeCache::new());
+    scan(scanner(&root).cache(Arc::clone(&cache)))?;
+    timings.push(time("scanner, cached", || {
+        scan(scanner(&root).cache(cache))
+    }));
+
+    #[cfg(feature = "rayon")]
+    {
+        let available = std::thread::available_parallelism().map_or(4, |n| n.get());
+        let mut threads = 1;
+        loop {
+            let name = format!("parallel, {} threads", threads);
+            let parallelism = filesize::Parallelism::new().threads(threads);
+            timings.push(time(&name, || {
+                scan(scanner(&root).parallelism(parallelism))
+            }));
+            if threads >= available {
+                break;
+            }
+            threads = (threads * 2).min(available);
+        }
+    }
+
+    report(&root, &timings);
+    Ok(())
+}
+
+fn report(root: &Path, timings: &[Timing]) {
+    println!("{}", root.display());
+    println!(
+        "{:<24} {:>10} {:>16} {:>10} {:>12}",
+        "Strategy", "Entries", "Physical", "Seconds", "Entries/sec"
+    );
+
+    for timing in timings {
+        let secs = timing.elapsed.as_secs_f64();
+        let rate = if secs > 0.0 {
+            timing.entries as f64 / secs
+        } else {
+            0.0
+        };
+
+        println!(
+            "{:<24} {:>10} {:>16} {:>10.3} {:>12.0}",
+            timing.name, timing.entries, timing.physical, secs, rate
+        );
+    }
+}
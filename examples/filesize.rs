@@ -1,33 +1,111 @@
+use std::collections::HashMap;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use filesize::PathExt;
+use filesize::{PathExt, Scanner};
 
-fn display_path<P: AsRef<Path>>(path: P) -> io::Result<()> {
-    let path = path.as_ref();
-    let meta = path.symlink_metadata()?;
-    let logical = meta.len();
-    let physical = path.size_on_disk_fast(&meta)?;
+#[derive(Default, Clone, Copy)]
+struct Usage {
+    logical: u64,
+    physical: u64,
+}
+
+fn display(usage: Usage, path: &Path) {
+    let ratio = if usage.logical == 0 {
+        format!("{:>9} ", "-")
+    } else {
+        format!("{:>9.2}x", usage.physical as f64 / usage.logical as f64)
+    };
 
     println!(
-        "{:>9} {:>9} {:>9.2}x {}",
-        logical,
-        physical,
-        physical as f64 / logical as f64,
+        "{:>9} {:>9} {} {}",
+        usage.logical,
+        usage.physical,
+        ratio,
         path.display()
     );
+}
 
-    Ok(())
+fn file_usage(path: &Path) -> io::Result<Usage> {
+    let meta = path.symlink_metadata()?;
+
+    Ok(Usage {
+        logical: meta.len(),
+        physical: path.size_on_disk_fast(&meta)?,
+    })
+}
+
+/// Walk the directory at `path`, returning the subtotal of each directory in
+/// it, largest first.
+fn directory_usage(path: &Path) -> Vec<(PathBuf, Usage)> {
+    let mut dirs: HashMap<PathBuf, Usage> = HashMap::new();
+
+    for entry in Scanner::new(path) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+
+        if entry.file_type().is_dir() {
+            dirs.entry(entry.path().to_path_buf()).or_default();
+            continue;
+        }
+
+        let info = entry.size_info();
+        for ancestor in entry.path().ancestors().skip(1) {
+            match dirs.get_mut(ancestor) {
+                Some(usage) => {
+                    usage.logical += info.logical();
+                    usage.physical += info.physical();
+                }
+                None => break,
+            }
+        }
+    }
+
+    let mut dirs: Vec<_> = dirs.into_iter().collect();
+    dirs.sort_by(|a, b| b.1.physical.cmp(&a.1.physical).then_with(|| a.0.cmp(&b.0)));
+    dirs
 }
 
 fn main() -> io::Result<()> {
     println!("{:>9} {:>9} {:>9} Path", "Logical", "Physical", "Ratio");
 
+    let mut total = Usage::default();
+
     for path in std::env::args_os().skip(1) {
-        if let Err(e) = display_path(&path) {
-            eprintln!("{}: {}", path.to_string_lossy(), e);
-        }
+        let path = Path::new(&path);
+
+        let usage = if path.is_dir() {
+            let dirs = directory_usage(path);
+            for (dir, usage) in &dirs {
+                display(*usage, dir);
+            }
+            dirs.iter()
+                .find(|(dir, _)| dir == path)
+                .map(|(_, usage)| *usage)
+                .unwrap_or_default()
+        } else {
+            match file_usage(path) {
+                Ok(usage) => {
+                    display(usage, path);
+                    usage
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", path.display(), e);
+                    continue;
+                }
+            }
+        };
+
+        total.logical += usage.logical;
+        total.physical += usage.physical;
     }
 
+    display(total, Path::new("total"));
+
     Ok(())
 }
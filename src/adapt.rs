@@ -0,0 +1,137 @@
+//! Adding on-disk sizes to iterators of paths.
+//!
+//! [`DiskSizesExt::with_disk_sizes`] turns any iterator of paths or directory
+//! entries into one of paths and their [`SizeInfo`], for bolting sizes onto
+//! glob results, the output of `git ls-files`, or anything else which yields
+//! paths.
+//!
+//! ```rust
+//! use std::path::PathBuf;
+//! use filesize::DiskSizesExt;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let paths = vec![PathBuf::from("Cargo.toml"), PathBuf::from("README.md")];
+//! for sized in paths.into_iter().with_disk_sizes() {
+//!     let (path, info) = sized?;
+//!     println!("{:>10} {}", info.physical(), path.display());
+//! }
+//!
+//! for sized in std::fs::read_dir("src")?.with_disk_sizes() {
+//!     let (path, info) = sized?;
+//!     println!("{:>10} {}", info.physical(), path.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::DirEntry;
+use std::io;
+use std::path::PathBuf;
+
+use crate::{Error, Result, SizeInfo, SizeOptions};
+
+/// An item which can be sized by [`WithDiskSizes`]: a path, or a directory
+/// entry, whose metadata is used where the platform provides it cheaply.
+pub trait SizeSource {
+    /// Find the path of this item and its sizes with `options`.
+    fn size_with(self, options: &SizeOptions) -> Result<(PathBuf, SizeInfo)>;
+}
+
+impl SizeSource for PathBuf {
+    fn size_with(self, options: &SizeOptions) -> Result<(PathBuf, SizeInfo)> {
+        let info = options.size_info(&self)?;
+        Ok((self, info))
+    }
+}
+
+impl SizeSource for DirEntry {
+    fn size_with(self, options: &SizeOptions) -> Result<(PathBuf, SizeInfo)> {
+        let path = self.path();
+        let metadata = self.metadata().map_err(|e| Error::from_io(&path, e))?;
+        let info = options.size_info_fast(&path, &metadata)?;
+        Ok((path, info))
+    }
+}
+
+/// Errors from the underlying iterator carry no path, so are reported as
+/// [`Error::Unnamed`].
+impl<T: SizeSource> SizeSource for io::Result<T> {
+    fn size_with(self, options: &SizeOptions) -> Result<(PathBuf, SizeInfo)> {
+        self.map_err(Error::unnamed)?.size_with(options)
+    }
+}
+
+/// An extension trait for iterators of paths and directory entries, adding
+/// their on-disk sizes.
+pub trait DiskSizesExt: Iterator + Sized
+where
+    Self::Item: SizeSource,
+{
+    /// Yield each path along with its sizes, or the error encountered
+    /// finding them.  Symlinks are not followed.
+    fn with_disk_sizes(self) -> WithDiskSizes<Self> {
+        WithDiskSizes {
+            inner: self,
+            options: SizeOptions::new(),
+        }
+    }
+}
+
+impl<I: Iterator> DiskSizesExt for I where I::Item: SizeSource {}
+
+/// An iterator of paths and their sizes, from
+/// [`DiskSizesExt::with_disk_sizes`].
+#[derive(Debug, Clone)]
+pub struct WithDiskSizes<I> {
+    inner: I,
+    options: SizeOptions,
+}
+
+impl<I> WithDiskSizes<I> {
+    /// Find sizes as configured by `options`, rather than the defaults.
+    pub fn options(mut self, options: SizeOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl<I: Iterator> Iterator for WithDiskSizes<I>
+where
+    I::Item: SizeSource,
+{
+    type Item = Result<(PathBuf, SizeInfo)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        Some(item.size_with(&self.options))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[test]
+fn iterators_are_adapted() {
+    let paths = vec![PathBuf::from("Cargo.toml"), PathBuf::from("missing")];
+    let sized: Vec<_> = paths.into_iter().with_disk_sizes().collect();
+
+    let entries: Vec<_> = std::fs::read_dir("src")
+        .unwrap()
+        .with_disk_sizes()
+        .map(Result::unwrap)
+        .collect();
+    let lib = entries
+        .iter()
+        .find(|(path, _)| path.ends_with("lib.rs"))
+        .unwrap();
+
+    assert_eq!(sized.len(), 2);
+    assert_eq!(
+        sized[0].as_ref().unwrap().1,
+        SizeOptions::new().size_info("Cargo.toml").unwrap()
+    );
+    assert!(sized[1].is_err());
+    assert_eq!(lib.1, SizeOptions::new().size_info("src/lib.rs").unwrap());
+    assert_eq!(entries.len(), std::fs::read_dir("src").unwrap().count());
+}
@@ -0,0 +1,181 @@
+//! Sizes of the members of tar and zip archives.
+//!
+//! [`archive_members`] lists the files in an archive with a [`SizeInfo`] for
+//! each, giving the space it occupies within the archive as its physical size
+//! and its unpacked length as its logical size, so archived files can be
+//! audited with the same code as files on disk.
+//!
+//! ```rust,no_run
+//! # fn main() -> std::io::Result<()> {
+//! for member in filesize::archive_members("backup.zip")? {
+//!     let info = member.size_info();
+//!     println!(
+//!         "{}: {} of {} bytes",
+//!         member.path().display(),
+//!         info.physical(),
+//!         info.logical()
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result, SizeAccuracy, SizeInfo};
+
+const TAR_BLOCK: u64 = 512;
+
+/// A file or directory within an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    path: PathBuf,
+    is_dir: bool,
+    info: SizeInfo,
+}
+
+impl ArchiveMember {
+    /// The path of this member within the archive.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether this member is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// The space this member occupies within the archive.
+    ///
+    /// For zip archives this is the compressed size of its data.  For tar
+    /// archives it is the size of its header and data, padded to the 512
+    /// byte blocks tar uses.
+    pub fn size_on_disk(&self) -> u64 {
+        self.info.physical()
+    }
+
+    /// The space this member occupies within the archive, and its unpacked
+    /// length.
+    pub fn size_info(&self) -> SizeInfo {
+        self.info
+    }
+}
+
+/// List the members of the tar or zip archive at `path`, with their sizes.
+///
+/// The format is detected from the contents of the file.  Compressed tar
+/// archives, such as `.tar.gz`, are not supported, as their members have no
+/// compressed size of their own.
+pub fn archive_members<P: AsRef<Path>>(path: P) -> Result<Vec<ArchiveMember>> {
+    let path = path.as_ref();
+    let mut file = File::open(path).map_err(|e| Error::from_io(path, e))?;
+
+    let mut magic = [0; 262];
+    let read = read_prefix(&mut file, &mut magic).map_err(|e| Error::from_io(path, e))?;
+    file.seek(SeekFrom::Start(0))
+        .map_err(|e| Error::from_io(path, e))?;
+
+    if magic[..read].starts_with(b"PK\x03\x04") || magic[..read].starts_with(b"PK\x05\x06") {
+        zip_members(file).map_err(|e| Error::from_io(path, e))
+    } else if read == magic.len() && &magic[257..262] == b"ustar" {
+        tar_members(file).map_err(|e| Error::from_io(path, e))
+    } else {
+        Err(Error::UnsupportedFileType {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+fn read_prefix(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn zip_members(file: File) -> io::Result<Vec<ArchiveMember>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut members = Vec::with_capacity(archive.len());
+
+    for i in 0..archive.len() {
+        let member = archive.by_index_raw(i)?;
+        members.push(ArchiveMember {
+            path: PathBuf::from(member.name()?.as_ref()),
+            is_dir: member.is_dir(),
+            info: SizeInfo::new(
+                member.compressed_size(),
+                member.size(),
+                SizeAccuracy::Archived,
+            ),
+        });
+    }
+
+    Ok(members)
+}
+
+fn tar_members(file: File) -> io::Result<Vec<ArchiveMember>> {
+    let mut archive = tar::Archive::new(file);
+    let mut members = vec![];
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let logical = entry.size();
+        let header = entry.raw_file_position() - entry.raw_header_position();
+        let data = logical.div_ceil(TAR_BLOCK).saturating_mul(TAR_BLOCK);
+
+        members.push(ArchiveMember {
+            path: entry.path()?.into_owned(),
+            is_dir: entry.header().entry_type().is_dir(),
+            info: SizeInfo::new(header + data, logical, SizeAccuracy::Archived),
+        });
+    }
+
+    Ok(members)
+}
+
+#[cfg(unix)]
+#[test]
+fn archive_members_are_sized() {
+    use std::io::Write;
+
+    let dir = crate::test_dir("archive");
+    let data = vec![0; 1000];
+
+    let mut tar = tar::Builder::new(File::create(dir.join("a.tar")).unwrap());
+    let mut header = tar::Header::new_ustar();
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, "file", &data[..]).unwrap();
+    tar.into_inner().unwrap();
+
+    let mut zip = zip::ZipWriter::new(File::create(dir.join("a.zip")).unwrap());
+    zip.start_file("file", zip::write::SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(&data).unwrap();
+    zip.finish().unwrap();
+
+    let tar = archive_members(dir.join("a.tar")).unwrap();
+    let zip = archive_members(dir.join("a.zip")).unwrap();
+    let other = archive_members(dir.join("missing"));
+    std::fs::write(dir.join("a.txt"), b"not an archive").unwrap();
+    let text = archive_members(dir.join("a.txt"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(tar.len(), 1);
+    assert_eq!(tar[0].path(), Path::new("file"));
+    assert_eq!(tar[0].size_info().logical(), 1000);
+    assert_eq!(tar[0].size_on_disk(), 512 + 1024);
+
+    assert_eq!(zip.len(), 1);
+    assert_eq!(zip[0].size_info().logical(), 1000);
+    assert_eq!(zip[0].size_info().accuracy(), SizeAccuracy::Archived);
+
+    assert!(matches!(other, Err(Error::NotFound { .. })));
+    assert!(matches!(text, Err(Error::UnsupportedFileType { .. })));
+}
@@ -0,0 +1,167 @@
+//! Pluggable providers of on-disk sizes.
+//!
+//! Everything in this crate finds sizes through [`OsBackend`], which asks the
+//! operating system.  Alternative providers, such as FUSE filesystems with
+//! their own ioctls, remote agents, or test fakes, can implement
+//! [`SizeBackend`] and be used with [`SizeOptions::size_info_with`] and
+//! [`Scanner::backend`].
+//!
+//! [`SizeOptions::size_info_with`]: crate::SizeOptions::size_info_with
+//! [`Scanner::backend`]: crate::Scanner::backend
+//!
+//! ```rust
+//! use std::fs::Metadata;
+//! use std::io;
+//! use std::path::Path;
+//! use filesize::{Scanner, SizeAccuracy, SizeBackend};
+//!
+//! /// Pretends every file is twice its length on disk.
+//! struct Doubled;
+//!
+//! impl SizeBackend for Doubled {
+//!     fn accuracy(&self) -> SizeAccuracy {
+//!         SizeAccuracy::Length
+//!     }
+//!
+//!     fn size_on_disk(&self, path: &Path) -> io::Result<u64> {
+//!         Ok(path.symlink_metadata()?.len() * 2)
+//!     }
+//!
+//!     fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+//!         Ok(path.metadata()?.len() * 2)
+//!     }
+//!
+//!     fn size_on_disk_fast(&self, _path: &Path, metadata: &Metadata) -> io::Result<u64> {
+//!         Ok(metadata.len() * 2)
+//!     }
+//! }
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let usage = Scanner::new("src").backend(Doubled).total()?;
+//! assert_eq!(usage.physical(), usage.logical() * 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::SizeAccuracy;
+
+/// A provider of on-disk sizes for files.
+///
+/// Only regular files, directories, and symlinks are passed to a backend by
+/// [`SizeOptions`](crate::SizeOptions) and [`Scanner`](crate::Scanner);
+/// special files and block devices are handled by their policies.
+pub trait SizeBackend: Send + Sync {
+    /// How the sizes reported by this backend are determined.
+    fn accuracy(&self) -> SizeAccuracy;
+
+    /// Get the on-disk size of the file at the given `path`, without
+    /// following symlinks.
+    fn size_on_disk(&self, path: &Path) -> io::Result<u64>;
+
+    /// Get the on-disk size of the file at the given `path`, following
+    /// symlinks.
+    fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64>;
+
+    /// Get the on-disk size of the file at the given `path`, using the
+    /// provided `std::fs::Metadata` instance if possible.
+    ///
+    /// The default implementation ignores the metadata.
+    fn size_on_disk_fast(&self, path: &Path, _metadata: &Metadata) -> io::Result<u64> {
+        self.size_on_disk(path)
+    }
+
+    /// Get the on-disk size of the file at the given `path`, without following
+    /// symlinks, after synchronising with the storage holding it, where that
+    /// can give a fresher answer.
+    ///
+    /// The default implementation calls `size_on_disk`.
+    fn size_on_disk_synced(&self, path: &Path) -> io::Result<u64> {
+        self.size_on_disk(path)
+    }
+}
+
+impl<T: SizeBackend + ?Sized> SizeBackend for Arc<T> {
+    fn accuracy(&self) -> SizeAccuracy {
+        (**self).accuracy()
+    }
+
+    fn size_on_disk(&self, path: &Path) -> io::Result<u64> {
+        (**self).size_on_disk(path)
+    }
+
+    fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+        (**self).size_on_disk_follow(path)
+    }
+
+    fn size_on_disk_fast(&self, path: &Path, metadata: &Metadata) -> io::Result<u64> {
+        (**self).size_on_disk_fast(path, metadata)
+    }
+
+    fn size_on_disk_synced(&self, path: &Path) -> io::Result<u64> {
+        (**self).size_on_disk_synced(path)
+    }
+}
+
+/// The default [`SizeBackend`], asking the operating system.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OsBackend;
+
+impl SizeBackend for OsBackend {
+    fn accuracy(&self) -> SizeAccuracy {
+        crate::imp::ACCURACY
+    }
+
+    fn size_on_disk(&self, path: &Path) -> io::Result<u64> {
+        #[cfg(feature = "mock")]
+        if let Some(size) = crate::mock::size_of(path) {
+            return Ok(size);
+        }
+        crate::imp::file_real_size(path)
+    }
+
+    fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+        #[cfg(feature = "mock")]
+        if let Some(size) = crate::mock::size_of(path) {
+            return Ok(size);
+        }
+        crate::imp::file_real_size_follow(path)
+    }
+
+    fn size_on_disk_fast(&self, path: &Path, metadata: &Metadata) -> io::Result<u64> {
+        #[cfg(feature = "mock")]
+        if let Some(size) = crate::mock::size_of(path) {
+            return Ok(size);
+        }
+        crate::imp::file_real_size_fast(path, metadata)
+    }
+
+    /// On Linux and Android this uses `statx()` with `AT_STATX_FORCE_SYNC`,
+    /// so network filesystems fetch fresh attributes from the server.
+    fn size_on_disk_synced(&self, path: &Path) -> io::Result<u64> {
+        #[cfg(feature = "mock")]
+        if let Some(size) = crate::mock::size_of(path) {
+            return Ok(size);
+        }
+        crate::imp::file_real_size_synced(path)
+    }
+}
+
+#[test]
+fn os_backend_matches_free_functions() {
+    let path = Path::new("Cargo.toml");
+    let metadata = path.symlink_metadata().unwrap();
+
+    assert_eq!(
+        OsBackend.size_on_disk(path).unwrap(),
+        crate::file_real_size(path).unwrap()
+    );
+    assert_eq!(
+        OsBackend.size_on_disk_fast(path, &metadata).unwrap(),
+        crate::file_real_size_fast(path, &metadata).unwrap()
+    );
+}
@@ -0,0 +1,217 @@
+//! Rescanning trees periodically on a thread of their own.
+//!
+//! [`Scanner::background`] hands a scanner to a [`BackgroundScanner`], which
+//! scans its roots into a fresh [`DiskUsageTree`] on a thread of its own,
+//! publishes it, waits for an interval or a call to
+//! [`rescan`](BackgroundScanner::rescan), and scans again, for daemons such as
+//! disk dashboards and quota enforcers which need the usage of a tree kept
+//! current for as long as they run.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let scanner = filesize::Scanner::new("/srv").background(Duration::from_secs(600))?;
+//!
+//! for tree in scanner {
+//!     let tree = tree?;
+//!     println!("{} bytes", tree.root().map_or(0, |root| root.size()));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::scan::Scan;
+use crate::{Collector, DiskUsageTree, Error, Result, Scanner};
+
+/// A scanner rescanning its tree on a thread of its own, created by
+/// [`Scanner::background`].
+///
+/// Iterating blocks until the next scan completes, while
+/// [`latest`](Self::latest) returns immediately.  Dropping it stops the
+/// thread, abandoning any scan in progress, and waits for it to finish.
+pub struct BackgroundScanner {
+    opts: Arc<Scanner>,
+    updates: Receiver<Result<DiskUsageTree>>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    stopped: AtomicBool,
+    rescan: Mutex<bool>,
+    wakeup: Condvar,
+}
+
+impl Shared {
+    /// Wait for `interval` to pass, or a rescan to be requested, returning
+    /// `false` if the scanner is stopped in the meantime.
+    fn wait(&self, interval: Duration) -> bool {
+        let rescan = match self.rescan.lock() {
+            Ok(rescan) => rescan,
+            Err(_) => return false,
+        };
+        let mut rescan = match self.wakeup.wait_timeout_while(rescan, interval, |rescan| {
+            !*rescan && !self.stopped.load(Ordering::Acquire)
+        }) {
+            Ok((rescan, _)) => rescan,
+            Err(_) => return false,
+        };
+        *rescan = false;
+        !self.stopped.load(Ordering::Acquire)
+    }
+
+    fn notify(&self) {
+        // Taking the lock orders this with the thread checking the condition,
+        // so it can't miss the wakeup.
+        let _rescan = self.rescan.lock();
+        self.wakeup.notify_all();
+    }
+}
+
+impl Scanner {
+    /// Scan the tree on a thread of its own, then again every `interval`
+    /// after each scan completes, publishing each tree to the returned
+    /// [`BackgroundScanner`].
+    ///
+    /// Each scan uses a single thread, whatever [`threads`](Self::threads) is
+    /// set to.  Errors are handled as by [`total`](Self::total), with a scan
+    /// failing on its first error publishing it in place of a tree, and the
+    /// next scan going ahead as usual.
+    pub fn background(self, interval: Duration) -> Result<BackgroundScanner> {
+        let opts = Arc::new(self);
+        let shared = Arc::new(Shared {
+            stopped: AtomicBool::new(false),
+            rescan: Mutex::new(false),
+            wakeup: Condvar::new(),
+        });
+        let (tx, updates) = mpsc::channel();
+
+        let thread = {
+            let (opts, shared) = (Arc::clone(&opts), Arc::clone(&shared));
+            thread::Builder::new()
+                .name("filesize-background".into())
+                .spawn(move || run(&opts, &shared, interval, &tx))
+        }
+        .map_err(|e| Error::from_io(opts.root(), e))?;
+
+        Ok(BackgroundScanner {
+            opts,
+            updates,
+            shared,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn run(
+    opts: &Arc<Scanner>,
+    shared: &Shared,
+    interval: Duration,
+    tx: &Sender<Result<DiskUsageTree>>,
+) {
+    loop {
+        let update = match scan(opts, &shared.stopped) {
+            Some(update) => update,
+            None => return,
+        };
+        if tx.send(update).is_err() || !shared.wait(interval) {
+            return;
+        }
+    }
+}
+
+/// Scan the tree once, or return `None` if the scanner is stopped first.
+fn scan(opts: &Arc<Scanner>, stopped: &AtomicBool) -> Option<Result<DiskUsageTree>> {
+    let mut tree = DiskUsageTree::new();
+    for entry in Scan::new(Arc::clone(opts), Arc::default()).root() {
+        if stopped.load(Ordering::Acquire) {
+            return None;
+        }
+        match entry {
+            Ok(entry) => tree.add(&entry),
+            Err(e) => return Some(Err(e)),
+        }
+    }
+    Some(Ok(tree))
+}
+
+impl fmt::Debug for BackgroundScanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackgroundScanner")
+            .field("root", &self.opts.root())
+            .field("stopped", &self.shared.stopped.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl BackgroundScanner {
+    /// The trees published by completed scans, oldest first, for waiting
+    /// with a timeout, or alongside other channels.
+    pub fn updates(&self) -> &Receiver<Result<DiskUsageTree>> {
+        &self.updates
+    }
+
+    /// The most recently published tree not yet retrieved, discarding any
+    /// older ones, if one is available without blocking.
+    pub fn latest(&self) -> Option<Result<DiskUsageTree>> {
+        self.updates.try_iter().last()
+    }
+
+    /// Start the next scan now, rather than once the interval has passed.
+    /// A scan in progress completes first, and is followed by another.
+    pub fn rescan(&self) {
+        if let Ok(mut rescan) = self.shared.rescan.lock() {
+            *rescan = true;
+        }
+        self.shared.notify();
+    }
+}
+
+impl Iterator for BackgroundScanner {
+    type Item = Result<DiskUsageTree>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.updates.recv().ok()
+    }
+}
+
+impl Drop for BackgroundScanner {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Release);
+        self.shared.notify();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[test]
+fn trees_are_rescanned_in_the_background() {
+    let dir = crate::test_dir("background");
+    std::fs::write(dir.join("a"), [1; 8192]).unwrap();
+
+    let timeout = Duration::from_secs(30);
+    let scanner = Scanner::new(&dir)
+        .apparent_size(true)
+        .background(Duration::from_secs(3600))
+        .unwrap();
+    let first = scanner.updates().recv_timeout(timeout).unwrap().unwrap();
+    assert_eq!(first.root().unwrap().size(), 8192);
+
+    std::fs::write(dir.join("b"), [1; 4096]).unwrap();
+    scanner.rescan();
+    let second = scanner.updates().recv_timeout(timeout).unwrap().unwrap();
+    assert_eq!(second.root().unwrap().size(), 8192 + 4096);
+    assert!(scanner.latest().is_none());
+
+    drop(scanner);
+    std::fs::remove_dir_all(dir).unwrap();
+}
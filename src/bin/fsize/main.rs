@@ -0,0 +1,391 @@
+//! A `du`-like command line interface to `filesize`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use filesize::{DuFormat, ErrorPolicy, Scanner};
+
+#[cfg(feature = "tui")]
+mod tui;
+
+const USAGE: &str = "\
+Usage: fsize [OPTIONS] [PATH]...
+
+Summarize the disk usage of each PATH, recursively for directories.
+
+Options:
+  -a, --all             Show files as well as directories
+  -s, --summarize       Show only a total for each PATH
+  -c, --total           Show a grand total
+  -d, --max-depth N     Show entries at most N levels below each PATH
+  -h, --human-readable  Show sizes in powers of 1024 (e.g. 1.5M)
+      --apparent-size   Show logical lengths rather than disk usage
+  -L, --dereference     Follow symbolic links
+      --exclude GLOB    Skip files and directories matching GLOB
+      --sort            Sort by size, largest first
+      --format FORMAT   Output as text, du, savings, json, ndjson,
+                        or csv
+  -i, --interactive     Browse the results interactively (tui feature)
+      --help            Show this help
+";
+
+#[derive(Debug, Default)]
+struct Args {
+    all: bool,
+    summarize: bool,
+    total: bool,
+    max_depth: Option<usize>,
+    human: bool,
+    apparent: bool,
+    dereference: bool,
+    exclude: Vec<String>,
+    sort: bool,
+    format: Format,
+    interactive: bool,
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    #[default]
+    Text,
+    Du,
+    Savings,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "du" => Ok(Format::Du),
+            "savings" => Ok(Format::Savings),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+fn parse_args() -> Result<Args, lexopt::Error> {
+    use lexopt::prelude::*;
+
+    let mut args = Args::default();
+    let mut parser = lexopt::Parser::from_env();
+
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Short('a') | Long("all") => args.all = true,
+            Short('s') | Long("summarize") => args.summarize = true,
+            Short('c') | Long("total") => args.total = true,
+            Short('d') | Long("max-depth") => args.max_depth = Some(parser.value()?.parse()?),
+            Short('h') | Long("human-readable") => args.human = true,
+            Long("apparent-size") => args.apparent = true,
+            Short('L') | Long("dereference") => args.dereference = true,
+            Long("exclude") => args.exclude.push(parser.value()?.string()?),
+            Long("sort") => args.sort = true,
+            Long("format") => args.format = parser.value()?.parse()?,
+            Short('i') | Long("interactive") => args.interactive = true,
+            Long("help") => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            Value(path) => args.paths.push(path.into()),
+            _ => return Err(arg.unexpected()),
+        }
+    }
+
+    if args.paths.is_empty() {
+        args.paths.push(PathBuf::from("."));
+    }
+
+    Ok(args)
+}
+
+#[derive(Debug)]
+struct Row {
+    path: PathBuf,
+    depth: usize,
+    physical: u64,
+    logical: u64,
+}
+
+impl Row {
+    fn size(&self, args: &Args) -> u64 {
+        if args.apparent {
+            self.logical
+        } else {
+            self.physical
+        }
+    }
+
+    /// The percentage of the logical size saved on disk, as
+    /// `DiskUsage::savings_percent` gives.
+    fn savings_percent(&self) -> f64 {
+        if self.logical == 0 {
+            return 0.0;
+        }
+        (1.0 - self.physical as f64 / self.logical as f64) * 100.0
+    }
+}
+
+/// Scan `root`, returning a row for each directory and, if requested, file,
+/// with directories totalling everything below them.  The root is first.
+fn scan(root: &Path, args: &Args, exclude: &GlobSet, failed: &mut bool) -> Vec<Row> {
+    let max_depth = if args.summarize {
+        0
+    } else {
+        args.max_depth.unwrap_or(usize::MAX)
+    };
+
+    let mut rows: Vec<Row> = vec![];
+    let mut dirs: HashMap<PathBuf, usize> = HashMap::new();
+    let scanner = Scanner::new(root)
+        .follow_symlinks(args.dereference)
+        .apparent_size(args.apparent)
+        .directory_sizes(true)
+        .exclude(exclude.clone())
+        .errors(ErrorPolicy::Yield);
+
+    for entry in scanner {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("fsize: {}", e);
+                *failed = true;
+                continue;
+            }
+        };
+
+        let info = entry.size_info();
+        let is_dir = entry.file_type().is_dir();
+
+        if is_dir || entry.depth() == 0 || (args.all && entry.depth() <= max_depth) {
+            if is_dir {
+                dirs.insert(entry.path().to_path_buf(), rows.len());
+            }
+            rows.push(Row {
+                path: entry.path().to_path_buf(),
+                depth: entry.depth(),
+                physical: info.physical(),
+                logical: info.logical(),
+            });
+        }
+
+        for ancestor in entry.path().ancestors().skip(1) {
+            match dirs.get(ancestor) {
+                Some(&i) => {
+                    rows[i].physical = rows[i].physical.saturating_add(info.physical());
+                    rows[i].logical = rows[i].logical.saturating_add(info.logical());
+                }
+                None => break,
+            }
+        }
+    }
+
+    rows.retain(|row| row.depth <= max_depth);
+    rows
+}
+
+fn format_size(size: u64, human: bool) -> String {
+    const UNITS: &[&str] = &["K", "M", "G", "T", "P", "E"];
+
+    if !human || size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn json_entry(row: &Row) -> String {
+    format!(
+        "{{\"type\":\"entry\",\"path\":{},\"depth\":{},\"physical\":{},\"logical\":{}}}",
+        json_string(&row.path.to_string_lossy()),
+        row.depth,
+        row.physical,
+        row.logical
+    )
+}
+
+fn json_total(total: &Row) -> String {
+    format!(
+        "{{\"type\":\"total\",\"physical\":{},\"logical\":{}}}",
+        total.physical, total.logical
+    )
+}
+
+fn print_rows(rows: &[Row], total: &Row, args: &Args) {
+    match args.format {
+        Format::Text => {
+            let shown = rows.iter().chain(Some(total).filter(|_| args.total));
+            for row in shown {
+                println!(
+                    "{}\t{}",
+                    format_size(row.size(args), args.human),
+                    row.path.display()
+                );
+            }
+        }
+        Format::Du => {
+            let du = DuFormat::new().human_readable(args.human);
+            let shown = rows.iter().chain(Some(total).filter(|_| args.total));
+            for row in shown {
+                println!("{}\t{}", du.format_size(row.size(args)), row.path.display());
+            }
+        }
+        Format::Savings => {
+            println!("Saved\tDisk Usage\tUncompressed\tPath");
+            let shown = rows.iter().chain(Some(total).filter(|_| args.total));
+            for row in shown {
+                println!(
+                    "{:.0}%\t{}\t{}\t{}",
+                    row.savings_percent(),
+                    format_size(row.physical, args.human),
+                    format_size(row.logical, args.human),
+                    row.path.display()
+                );
+            }
+        }
+        Format::Json => {
+            let entries: Vec<String> = rows.iter().map(json_entry).collect();
+            println!(
+                "{{\"entries\":[{}],\"total\":{}}}",
+                entries.join(","),
+                json_total(total)
+            );
+        }
+        Format::Ndjson => {
+            for row in rows {
+                println!("{}", json_entry(row));
+            }
+            println!("{}", json_total(total));
+        }
+        Format::Csv => {
+            println!("type,path,depth,physical,logical");
+            for row in rows {
+                println!(
+                    "entry,{},{},{},{}",
+                    csv_field(&row.path.to_string_lossy()),
+                    row.depth,
+                    row.physical,
+                    row.logical
+                );
+            }
+            println!("total,,,{},{}", total.physical, total.logical);
+        }
+    }
+}
+
+fn build_excludes(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("fsize: {}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let exclude = build_excludes(&args.exclude).unwrap_or_else(|e| {
+        eprintln!("fsize: {}", e);
+        process::exit(2);
+    });
+
+    if args.interactive {
+        #[cfg(feature = "tui")]
+        {
+            if let Err(e) = tui::browse(&args, &exclude) {
+                eprintln!("fsize: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+
+        #[cfg(not(feature = "tui"))]
+        {
+            eprintln!("fsize: interactive mode requires the tui feature");
+            process::exit(2);
+        }
+    }
+
+    let mut failed = false;
+    let mut rows = vec![];
+    let mut total = Row {
+        path: PathBuf::from("total"),
+        depth: 0,
+        physical: 0,
+        logical: 0,
+    };
+
+    for root in &args.paths {
+        let mut scanned = scan(root, &args, &exclude, &mut failed);
+        if let Some(row) = scanned.first().filter(|row| row.depth == 0) {
+            total.physical = total.physical.saturating_add(row.physical);
+            total.logical = total.logical.saturating_add(row.logical);
+        }
+        if args.format == Format::Du {
+            // du prints each directory after its contents.
+            scanned.reverse();
+        }
+        rows.extend(scanned);
+    }
+
+    if args.sort {
+        rows.sort_by_key(|row| std::cmp::Reverse(row.size(&args)));
+    }
+
+    print_rows(&rows, &total, &args);
+
+    if failed {
+        process::exit(1);
+    }
+}
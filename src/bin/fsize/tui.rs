@@ -0,0 +1,301 @@
+//! An ncdu-style interactive browser for a scanned tree.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use globset::GlobSet;
+
+use filesize::{ErrorPolicy, Scanner};
+
+use crate::{format_size, Args};
+
+struct Node {
+    name: String,
+    physical: u64,
+    logical: u64,
+    is_dir: bool,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// A scanned tree, with every directory totalling everything below it.
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    fn scan(roots: &[PathBuf], args: &Args, exclude: &GlobSet) -> Self {
+        let mut tree = Tree {
+            nodes: vec![Node {
+                name: String::from("total"),
+                physical: 0,
+                logical: 0,
+                is_dir: true,
+                parent: None,
+                children: vec![],
+            }],
+        };
+
+        for root in roots {
+            tree.add_root(root, args, exclude);
+        }
+
+        tree
+    }
+
+    fn add_root(&mut self, root: &Path, args: &Args, exclude: &GlobSet) {
+        let mut dirs: HashMap<PathBuf, usize> = HashMap::new();
+        let scanner = Scanner::new(root)
+            .follow_symlinks(args.dereference)
+            .directory_sizes(true)
+            .exclude(exclude.clone())
+            .errors(ErrorPolicy::Skip);
+
+        for entry in scanner.into_iter().flatten() {
+            let parent = match entry.path().parent().and_then(|p| dirs.get(p)) {
+                Some(&parent) if entry.depth() > 0 => parent,
+                _ => 0,
+            };
+            let name = if entry.depth() == 0 {
+                entry.path().display().to_string()
+            } else {
+                entry
+                    .path()
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            };
+
+            let index = self.nodes.len();
+            let info = entry.size_info();
+            let is_dir = entry.file_type().is_dir();
+            self.nodes.push(Node {
+                name,
+                physical: info.physical(),
+                logical: info.logical(),
+                is_dir,
+                parent: Some(parent),
+                children: vec![],
+            });
+            self.nodes[parent].children.push(index);
+
+            if is_dir {
+                dirs.insert(entry.path().to_path_buf(), index);
+            }
+
+            let mut ancestor = Some(parent);
+            while let Some(i) = ancestor {
+                let node = &mut self.nodes[i];
+                node.physical = node.physical.saturating_add(info.physical());
+                node.logical = node.logical.saturating_add(info.logical());
+                ancestor = self.nodes[i].parent;
+            }
+        }
+    }
+
+    /// The first node to show: the root of the scan if there's only one, or
+    /// the synthetic node holding them all.
+    fn top(&self) -> usize {
+        match self.nodes[0].children[..] {
+            [root] => root,
+            _ => 0,
+        }
+    }
+
+    fn path(&self, mut index: usize) -> String {
+        let mut parts = vec![];
+        while index != 0 {
+            let node = &self.nodes[index];
+            parts.push(node.name.as_str());
+            index = node.parent.unwrap_or(0);
+        }
+        if parts.is_empty() {
+            return self.nodes[0].name.clone();
+        }
+        parts.reverse();
+        parts.join("/")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Size,
+    Name,
+}
+
+struct Browser<'a> {
+    tree: Tree,
+    args: &'a Args,
+    top: usize,
+    current: usize,
+    selected: usize,
+    offset: usize,
+    sort: Sort,
+}
+
+impl Browser<'_> {
+    fn size(&self, node: &Node) -> u64 {
+        if self.args.apparent {
+            node.logical
+        } else {
+            node.physical
+        }
+    }
+
+    fn children(&self) -> Vec<usize> {
+        let mut children = self.tree.nodes[self.current].children.clone();
+        match self.sort {
+            Sort::Size => {
+                children.sort_by_key(|&c| std::cmp::Reverse(self.size(&self.tree.nodes[c])))
+            }
+            Sort::Name => {
+                children.sort_by(|&a, &b| self.tree.nodes[a].name.cmp(&self.tree.nodes[b].name))
+            }
+        }
+        children
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let rows = usize::from(height.saturating_sub(3)).max(1);
+        let children = self.children();
+        let total = self.size(&self.tree.nodes[self.current]).max(1);
+
+        self.selected = self.selected.min(children.len().saturating_sub(1));
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + rows {
+            self.offset = self.selected + 1 - rows;
+        }
+
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        queue!(
+            out,
+            SetAttribute(Attribute::Bold),
+            Print(truncate(
+                &format!(
+                    "--- {} --- {}",
+                    self.tree.path(self.current),
+                    format_size(self.size(&self.tree.nodes[self.current]), true)
+                ),
+                width
+            )),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        for (line, &child) in children.iter().enumerate().skip(self.offset).take(rows) {
+            let node = &self.tree.nodes[child];
+            let size = self.size(node);
+            let bar_len = (size as f64 / total as f64 * 10.0).round() as usize;
+            let text = format!(
+                "{:>9} [{:<10}] {}{}",
+                format_size(size, true),
+                "#".repeat(bar_len.min(10)),
+                node.name,
+                if node.is_dir { "/" } else { "" }
+            );
+
+            queue!(out, MoveTo(0, (line - self.offset + 1) as u16))?;
+            if line == self.selected {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                out,
+                Print(truncate(&text, width)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(1)),
+            Print(truncate(
+                "up/down: move  enter: open  left: back  s: sort  q: quit",
+                width
+            ))
+        )?;
+        out.flush()
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            self.draw(out)?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+
+            let children = self.children();
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => self.selected += 1,
+                KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+                KeyCode::PageDown => self.selected += 10,
+                KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                    if let Some(&child) = children.get(self.selected) {
+                        if self.tree.nodes[child].is_dir {
+                            self.current = child;
+                            self.selected = 0;
+                            self.offset = 0;
+                        }
+                    }
+                }
+                KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => {
+                    let parent = self.tree.nodes[self.current].parent;
+                    if let Some(parent) = parent.filter(|_| self.current != self.top) {
+                        let from = self.current;
+                        self.current = parent;
+                        self.selected =
+                            self.children().iter().position(|&c| c == from).unwrap_or(0);
+                    }
+                }
+                KeyCode::Char('s') => {
+                    self.sort = match self.sort {
+                        Sort::Size => Sort::Name,
+                        Sort::Name => Sort::Size,
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+fn truncate(s: &str, width: u16) -> String {
+    s.chars().take(usize::from(width)).collect()
+}
+
+/// Scan the paths given in `args`, then browse the results interactively.
+pub fn browse(args: &Args, exclude: &GlobSet) -> io::Result<()> {
+    eprintln!("Scanning...");
+    let tree = Tree::scan(&args.paths, args, exclude);
+    let top = tree.top();
+    let mut browser = Browser {
+        tree,
+        args,
+        top,
+        current: top,
+        selected: 0,
+        offset: 0,
+        sort: Sort::Size,
+    };
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+
+    let result = browser.run(&mut out);
+
+    execute!(out, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
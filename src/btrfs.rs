@@ -0,0 +1,175 @@
+//! The btrfs ioctls shared by qgroup usage and compressed size reporting.
+
+use std::convert::TryInto;
+use std::io;
+use std::os::unix::io::RawFd;
+
+const BTRFS_IOCTL_MAGIC: u8 = 0x94;
+const BTRFS_FIRST_FREE_OBJECTID: u64 = 256;
+
+#[repr(C)]
+pub struct InoLookupArgs {
+    treeid: u64,
+    objectid: u64,
+    name: [u8; 4080],
+}
+
+/// The range of keys to search for.
+///
+/// Keys are ordered by objectid, then type, then offset, and the range covers
+/// every key between the minimum and maximum in that order.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct SearchKey {
+    tree_id: u64,
+    min_objectid: u64,
+    max_objectid: u64,
+    min_offset: u64,
+    max_offset: u64,
+    min_transid: u64,
+    max_transid: u64,
+    min_type: u32,
+    max_type: u32,
+    nr_items: u32,
+    unused: u32,
+    unused1: u64,
+    unused2: u64,
+    unused3: u64,
+    unused4: u64,
+}
+
+impl SearchKey {
+    /// Search `tree_id` for keys from `min` to `max`, as (objectid, type,
+    /// offset) triples.
+    pub fn new(tree_id: u64, min: (u64, u32, u64), max: (u64, u32, u64)) -> Self {
+        Self {
+            tree_id,
+            min_objectid: min.0,
+            min_type: min.1,
+            min_offset: min.2,
+            max_objectid: max.0,
+            max_type: max.1,
+            max_offset: max.2,
+            max_transid: u64::MAX,
+            ..Self::default()
+        }
+    }
+}
+
+const SEARCH_KEY_SIZE: usize = std::mem::size_of::<SearchKey>();
+
+#[repr(C)]
+pub struct SearchArgs {
+    key: SearchKey,
+    buf: [u8; 4096 - SEARCH_KEY_SIZE],
+}
+
+const SEARCH_HEADER_SIZE: usize = 32;
+
+const BTRFS_IOC_TREE_SEARCH: libc::Ioctl = libc::_IOWR::<SearchArgs>(BTRFS_IOCTL_MAGIC as _, 17);
+const BTRFS_IOC_INO_LOOKUP: libc::Ioctl = libc::_IOWR::<InoLookupArgs>(BTRFS_IOCTL_MAGIC as _, 18);
+
+/// The ID of the subvolume containing the open file `fd`.
+pub fn subvolume(fd: RawFd) -> io::Result<u64> {
+    let mut lookup = InoLookupArgs {
+        treeid: 0,
+        objectid: BTRFS_FIRST_FREE_OBJECTID,
+        name: [0; 4080],
+    };
+    if unsafe { libc::ioctl(fd, BTRFS_IOC_INO_LOOKUP, &mut lookup) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(lookup.treeid)
+}
+
+/// Call `f` with the data of every item in the range given by `key`, in key
+/// order.
+///
+pub fn tree_search<F>(fd: RawFd, key: SearchKey, mut f: F) -> io::Result<()>
+where
+    F: FnMut(&[u8]),
+{
+    let mut args = SearchArgs {
+        key,
+        buf: [0; 4096 - SEARCH_KEY_SIZE],
+    };
+
+    loop {
+        args.key.nr_items = 4096;
+        if unsafe { libc::ioctl(fd, BTRFS_IOC_TREE_SEARCH, &mut args) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut pos = 0;
+        let mut last = None;
+        for _ in 0..args.key.nr_items {
+            let header = match args.buf.get(pos..pos + SEARCH_HEADER_SIZE) {
+                Some(header) => header,
+                None => break,
+            };
+            let objectid = le_u64(header, 8);
+            let offset = le_u64(header, 16);
+            let kind = le_u32(header, 24);
+            let len = le_u32(header, 28) as usize;
+
+            pos += SEARCH_HEADER_SIZE;
+            let data = match args.buf.get(pos..pos + len) {
+                Some(data) => data,
+                None => break,
+            };
+            pos += len;
+
+            f(data);
+            last = Some((objectid, kind, offset));
+        }
+
+        // Resume from the key after the last one returned.
+        let (objectid, kind, offset) = match last {
+            Some(last) => last,
+            None => return Ok(()),
+        };
+        let key = &mut args.key;
+        if offset < u64::MAX {
+            key.min_objectid = objectid;
+            key.min_type = kind;
+            key.min_offset = offset + 1;
+        } else if kind < u32::from(u8::MAX) {
+            key.min_objectid = objectid;
+            key.min_type = kind + 1;
+            key.min_offset = 0;
+        } else if objectid < u64::MAX {
+            key.min_objectid = objectid + 1;
+            key.min_type = 0;
+            key.min_offset = 0;
+        } else {
+            return Ok(());
+        }
+
+        if (key.min_objectid, key.min_type, key.min_offset)
+            > (key.max_objectid, key.max_type, key.max_offset)
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Read the little endian `u64` at `offset` in `buf`, or 0 if it's too short.
+pub fn le_u64(buf: &[u8], offset: usize) -> u64 {
+    buf.get(offset..offset + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes)
+        .unwrap_or(0)
+}
+
+fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    buf.get(offset..offset + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+        .unwrap_or(0)
+}
+
+#[test]
+fn ioctl_arguments_are_laid_out() {
+    assert_eq!(std::mem::size_of::<InoLookupArgs>(), 4096);
+    assert_eq!(std::mem::size_of::<SearchArgs>(), 4096);
+}
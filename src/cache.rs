@@ -0,0 +1,189 @@
+//! Remembering the sizes of unchanged files between scans.
+//!
+//! A [`SizeCache`] maps file identities to the sizes found for them, so
+//! repeated scans of a mostly unchanged tree can skip the per-file queries
+//! which make up most of the cost of a scan on some platforms.
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use filesize::{Scanner, SizeCache};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let cache = Arc::new(SizeCache::new());
+//!
+//! Scanner::new("src").cache(Arc::clone(&cache)).total()?;
+//! Scanner::new("src").cache(Arc::clone(&cache)).total()?;
+//!
+//! assert!(cache.hits() > 0);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::Path;
+#[cfg(not(unix))]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+#[cfg(not(unix))]
+use std::time::SystemTime;
+
+use crate::{OsBackend, Result, SizeBackend, SizeInfo, SizeOptions};
+
+/// The identity of a file, and enough of its state to tell if it has changed.
+///
+/// On Unix this is the device and inode, along with the modification time and
+/// length.  Elsewhere file IDs are not available from `Metadata`, so the path
+/// is used in place of the device and inode.
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FileKey {
+    dev: u64,
+    ino: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+    len: u64,
+}
+
+#[cfg(unix)]
+impl FileKey {
+    fn new(_path: &Path, metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some(Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            len: metadata.len(),
+        })
+    }
+}
+
+#[cfg(not(unix))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FileKey {
+    path: PathBuf,
+    modified: SystemTime,
+    len: u64,
+}
+
+#[cfg(not(unix))]
+impl FileKey {
+    fn new(path: &Path, metadata: &Metadata) -> Option<Self> {
+        Some(Self {
+            path: path.to_path_buf(),
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// A thread-safe cache of file sizes, keyed by file identity.
+///
+/// Entries are never invalidated, only superseded when a file changes, so a
+/// cache used for a long time against a changing tree should be
+/// [`clear`](Self::clear)ed occasionally.  Sizes depend on the
+/// [`SizeOptions`] and [`SizeBackend`] used to find them, so a cache should
+/// not be shared between scans with different options or backends.
+#[derive(Debug, Default)]
+pub struct SizeCache {
+    entries: Mutex<HashMap<FileKey, SizeInfo>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl SizeCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the sizes of the file at `path` from the cache, or with `options`
+    /// if it has not been seen before, or has changed since.
+    pub fn size_info_fast<P: AsRef<Path>>(
+        &self,
+        options: &SizeOptions,
+        path: P,
+        metadata: &Metadata,
+    ) -> Result<SizeInfo> {
+        self.lookup(&OsBackend, options, path.as_ref(), metadata)
+            .map(|(info, _)| info)
+    }
+
+    /// As `size_info_fast`, also returning whether the filesystem was queried.
+    pub(crate) fn lookup(
+        &self,
+        backend: &dyn SizeBackend,
+        options: &SizeOptions,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> Result<(SizeInfo, bool)> {
+        let key = FileKey::new(path, metadata);
+
+        if let Some(info) = key.as_ref().and_then(|key| self.get(key)) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok((info, false));
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let info = options.size_info_with(backend, path, metadata)?;
+        if let (Some(key), Ok(mut entries)) = (key, self.entries.lock()) {
+            entries.insert(key, info);
+        }
+        Ok((info, true))
+    }
+
+    fn get(&self, key: &FileKey) -> Option<SizeInfo> {
+        self.entries.lock().ok()?.get(key).copied()
+    }
+
+    /// The number of files in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.lock().map(|e| e.len()).unwrap_or(0)
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove everything from the cache.
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    /// The number of lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of lookups which had to query the filesystem.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[test]
+fn unchanged_files_are_cached() {
+    let cache = SizeCache::new();
+    let options = SizeOptions::new();
+    let metadata = std::fs::symlink_metadata("Cargo.toml").unwrap();
+
+    let first = cache
+        .size_info_fast(&options, "Cargo.toml", &metadata)
+        .unwrap();
+    let second = cache
+        .size_info_fast(&options, "Cargo.toml", &metadata)
+        .unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    assert_eq!(cache.len(), 1);
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,88 @@
+//! Size queries for the capability-based filesystem types of [`cap_std`],
+//! available with the `cap-std` feature.
+//!
+//! Paths are resolved by `cap_std` itself, so queries are confined to the
+//! directory in the same way as any other `cap_std` operation.
+//!
+//! ```rust
+//! use cap_std::ambient_authority;
+//! use cap_std::fs::Dir;
+//! use filesize::cap_std::DirExt;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let dir = Dir::open_ambient_dir(".", ambient_authority())?;
+//! let realsize = dir.size_on_disk("Cargo.toml")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`cap_std`]: https://docs.rs/cap-std
+
+use std::path::Path;
+
+use ::cap_std::fs::{Dir, File};
+
+use crate::{Error, Result};
+
+/// An extension trait for `cap_std::fs::Dir` to retrieve the on-disk size of
+/// files within it.
+pub trait DirExt {
+    /// Get the on-disk size of the file at the given `path`, relative to this
+    /// directory.
+    ///
+    /// On Unix, symlinks are not followed.  On Windows, as with
+    /// `file_real_size`, they are.
+    fn size_on_disk<P: AsRef<Path>>(&self, path: P) -> Result<u64>;
+}
+
+/// An extension trait for `cap_std::fs::File` to retrieve its on-disk size.
+pub trait FileExt {
+    /// Get the on-disk size of this file.
+    ///
+    /// Since the file has no path to report, any error carries an empty one.
+    fn size_on_disk(&self) -> Result<u64>;
+}
+
+#[cfg(unix)]
+impl DirExt for Dir {
+    fn size_on_disk<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        use ::cap_std::fs::MetadataExt;
+
+        let path = path.as_ref();
+        self.symlink_metadata(path)
+            .map(|metadata| metadata.blocks().saturating_mul(crate::imp::BLOCK_UNIT))
+            .map_err(|e| Error::from_io(path, e))
+    }
+}
+
+#[cfg(unix)]
+impl FileExt for File {
+    fn size_on_disk(&self) -> Result<u64> {
+        use ::cap_std::fs::MetadataExt;
+
+        self.metadata()
+            .map(|metadata| metadata.blocks().saturating_mul(crate::imp::BLOCK_UNIT))
+            .map_err(Error::unnamed)
+    }
+}
+
+#[cfg(windows)]
+impl DirExt for Dir {
+    fn size_on_disk<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        use std::os::windows::io::AsRawHandle;
+
+        let path = path.as_ref();
+        self.open(path)
+            .and_then(|file| crate::imp::file_real_size_handle(file.as_raw_handle()))
+            .map_err(|e| Error::from_io(path, e))
+    }
+}
+
+#[cfg(windows)]
+impl FileExt for File {
+    fn size_on_disk(&self) -> Result<u64> {
+        use std::os::windows::io::AsRawHandle;
+
+        crate::imp::file_real_size_handle(self.as_raw_handle()).map_err(Error::unnamed)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::SizeAccuracy;
+
+/// What this crate can find out on the current platform, from
+/// [`capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    accuracy: SizeAccuracy,
+    compressed_size: bool,
+    sparse_ranges: bool,
+    alternate_streams: bool,
+    allocation_granularity: bool,
+}
+
+impl Capabilities {
+    /// How the sizes of regular files are determined, before any options
+    /// such as [`SizeOptions::estimate_allocation`] are applied.
+    ///
+    /// [`SizeOptions::estimate_allocation`]: crate::SizeOptions::estimate_allocation
+    pub fn size_accuracy(&self) -> SizeAccuracy {
+        self.accuracy
+    }
+
+    /// Whether sizes are the space actually allocated, rather than estimates
+    /// from logical lengths.
+    pub fn exact_allocation(&self) -> bool {
+        self.accuracy.is_exact()
+    }
+
+    /// Whether the compressed size of a file can be found, through
+    /// `GetCompressedFileSizeW()` on Windows, and `compressed_size` on
+    /// Linux.
+    pub fn compressed_size(&self) -> bool {
+        self.compressed_size
+    }
+
+    /// Whether [`sparse_map`](crate::sparse_map) finds the holes in sparse
+    /// files, rather than reporting them as data throughout.
+    pub fn sparse_ranges(&self) -> bool {
+        self.sparse_ranges
+    }
+
+    /// Whether files may have alternate data streams, as on NTFS.  Their
+    /// space is not included in the size of a file.
+    pub fn alternate_streams(&self) -> bool {
+        self.alternate_streams
+    }
+
+    /// Whether [`allocation_granularity`](crate::allocation_granularity) can
+    /// find the cluster or block size of a filesystem.
+    pub fn allocation_granularity(&self) -> bool {
+        self.allocation_granularity
+    }
+}
+
+/// Find what this crate can report on the current platform, so applications
+/// can adapt what they show, such as labelling sizes as estimates, without
+/// knowing which platforms support what.
+///
+/// These describe the platform as a whole.  Individual filesystems may still
+/// fail queries, or lack compression or sparse files altogether.
+///
+/// ```rust
+/// let capabilities = filesize::capabilities();
+/// if !capabilities.exact_allocation() {
+///     println!("sizes on disk are estimated");
+/// }
+/// ```
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        accuracy: crate::imp::ACCURACY,
+        compressed_size: cfg!(any(windows, target_os = "linux")),
+        sparse_ranges: cfg!(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "illumos",
+            target_os = "solaris",
+            windows
+        )),
+        alternate_streams: cfg!(windows),
+        allocation_granularity: cfg!(any(unix, windows)),
+    }
+}
+
+#[test]
+fn capabilities_match_the_platform() {
+    let capabilities = capabilities();
+
+    assert_eq!(capabilities.size_accuracy(), crate::imp::ACCURACY);
+    assert_eq!(capabilities.exact_allocation(), cfg!(any(unix, windows)));
+    assert_eq!(
+        capabilities.allocation_granularity(),
+        crate::allocation_granularity("Cargo.toml").is_ok()
+    );
+    if cfg!(target_os = "linux") {
+        assert!(capabilities.compressed_size() && capabilities.sparse_ranges());
+        assert!(!capabilities.alternate_streams());
+    }
+}
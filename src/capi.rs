@@ -0,0 +1,125 @@
+//! A C API, available with the `capi` feature, and buildable as a C library
+//! with [`cargo-c`](https://github.com/lu-zero/cargo-c).
+//!
+//! Each function returns 0 on success, writing the size to `out_size`, or on
+//! failure returns a positive OS error code where one is available, and -1
+//! otherwise, such as when passed a null pointer or a path that is not valid
+//! for the platform.
+
+use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
+
+use crate::{Error, Result};
+
+fn to_status(result: Result<u64>, out_size: *mut u64) -> c_int {
+    match result {
+        Ok(size) => {
+            unsafe { *out_size = size };
+            0
+        }
+        Err(Error::NotFound { source, .. })
+        | Err(Error::PermissionDenied { source, .. })
+        | Err(Error::PlatformLimitation { source, .. })
+        | Err(Error::Io { source, .. })
+        | Err(Error::Unnamed { source }) => source.raw_os_error().filter(|&e| e > 0).unwrap_or(-1),
+        Err(_) => -1,
+    }
+}
+
+#[cfg(unix)]
+unsafe fn to_path(path: *const c_char) -> Option<PathBuf> {
+    use std::ffi::{CStr, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(OsStr::from_bytes(CStr::from_ptr(path).to_bytes()).into())
+}
+
+#[cfg(not(unix))]
+unsafe fn to_path(path: *const c_char) -> Option<PathBuf> {
+    std::ffi::CStr::from_ptr(path)
+        .to_str()
+        .ok()
+        .map(PathBuf::from)
+}
+
+/// Get the on-disk size of the file at the given NUL-terminated `path`, as
+/// `file_real_size` does.
+///
+/// On Windows, `path` must be UTF-8: see `filesize_real_size_w` for UTF-16.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string, and `out_size` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn filesize_real_size(path: *const c_char, out_size: *mut u64) -> c_int {
+    if path.is_null() || out_size.is_null() {
+        return -1;
+    }
+
+    match to_path(path) {
+        Some(path) => to_status(crate::file_real_size(path), out_size),
+        None => -1,
+    }
+}
+
+/// Get the on-disk size of the file at the given NUL-terminated `path`,
+/// following symlinks, as `file_real_size_follow` does.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string, and `out_size` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn filesize_real_size_follow(
+    path: *const c_char,
+    out_size: *mut u64,
+) -> c_int {
+    if path.is_null() || out_size.is_null() {
+        return -1;
+    }
+
+    match to_path(path) {
+        Some(path) => to_status(crate::file_real_size_follow(path), out_size),
+        None => -1,
+    }
+}
+
+/// Get the on-disk size of the file at the given NUL-terminated UTF-16 `path`,
+/// as `file_real_size` does.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated wide string, and `out_size` must be
+/// valid for writes.
+#[cfg(windows)]
+#[no_mangle]
+pub unsafe extern "C" fn filesize_real_size_w(path: *const u16, out_size: *mut u64) -> c_int {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+
+    if path.is_null() || out_size.is_null() {
+        return -1;
+    }
+
+    let len = (0..).take_while(|&i| *path.add(i) != 0).count();
+    let path = OsString::from_wide(std::slice::from_raw_parts(path, len));
+    to_status(crate::file_real_size(path), out_size)
+}
+
+#[test]
+fn it_returns_sizes_and_errors() {
+    let mut size = 0;
+
+    let status =
+        unsafe { filesize_real_size(b"Cargo.toml\0".as_ptr() as *const c_char, &mut size) };
+    assert_eq!(status, 0);
+    assert_eq!(size, crate::file_real_size("Cargo.toml").unwrap());
+
+    let status =
+        unsafe { filesize_real_size(b"does/not/exist\0".as_ptr() as *const c_char, &mut size) };
+    assert!(status > 0);
+
+    let status = unsafe { filesize_real_size(std::ptr::null(), &mut size) };
+    assert_eq!(status, -1);
+}
@@ -0,0 +1,152 @@
+//! Sending the results of a scan over a channel as it runs.
+//!
+//! [`Scanner::send_to`] passes each entry and error of a scan to a channel
+//! supplied by the caller, along with subtotals of the usage so far at a
+//! given interval, so results can flow into existing pipelines, such as a
+//! database writer or a UI thread, while the scan is still running.
+//!
+//! ```rust
+//! use std::sync::mpsc;
+//! use std::time::Duration;
+//! use filesize::{ScanUpdate, Scanner};
+//!
+//! let (tx, rx) = mpsc::channel();
+//! std::thread::spawn(move || Scanner::new("src").send_to(tx, Duration::from_millis(100)));
+//!
+//! for update in rx {
+//!     match update {
+//!         ScanUpdate::Entry(entry) => println!("{}", entry.path().display()),
+//!         ScanUpdate::Error(e) => eprintln!("{}", e),
+//!         ScanUpdate::Subtotal(usage) => eprintln!("{} bytes so far", usage.size()),
+//!     }
+//! }
+//! ```
+
+use std::sync::mpsc::{Sender, SyncSender};
+use std::time::{Duration, Instant};
+
+use crate::{DiskUsage, Entry, Error, Scanner};
+
+/// An update sent by [`Scanner::send_to`].
+#[derive(Debug)]
+pub enum ScanUpdate {
+    /// An entry the scan yielded.
+    Entry(Entry),
+    /// An error the scan yielded, as its [`ErrorPolicy`](crate::ErrorPolicy)
+    /// directs.
+    Error(Error),
+    /// The usage of everything the scan has seen so far, sent at the interval
+    /// given to [`Scanner::send_to`], and once more when it is complete.
+    Subtotal(DiskUsage),
+}
+
+/// The sending half of a channel [`Scanner::send_to`] can send updates to.
+///
+/// This is implemented for the senders of `std::sync::mpsc`, and with the
+/// `crossbeam-channel` feature, for those of `crossbeam_channel`.
+pub trait UpdateSender {
+    /// Send `update`, returning `false` if the receiver is gone, so the scan
+    /// should stop.
+    fn send_update(&self, update: ScanUpdate) -> bool;
+}
+
+impl UpdateSender for Sender<ScanUpdate> {
+    fn send_update(&self, update: ScanUpdate) -> bool {
+        self.send(update).is_ok()
+    }
+}
+
+/// A bounded channel holds the scan back while it is full.
+impl UpdateSender for SyncSender<ScanUpdate> {
+    fn send_update(&self, update: ScanUpdate) -> bool {
+        self.send(update).is_ok()
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl UpdateSender for crossbeam_channel::Sender<ScanUpdate> {
+    fn send_update(&self, update: ScanUpdate) -> bool {
+        self.send(update).is_ok()
+    }
+}
+
+impl<S: UpdateSender + ?Sized> UpdateSender for &S {
+    fn send_update(&self, update: ScanUpdate) -> bool {
+        (**self).send_update(update)
+    }
+}
+
+impl Scanner {
+    /// Run the scan to completion on this thread, sending each entry and
+    /// error to `sender` as it is found, with a [`ScanUpdate::Subtotal`] of
+    /// the usage so far whenever `interval` has passed since the last, and
+    /// once more at the end.  The final usage is also returned.
+    ///
+    /// Should the receiver be dropped, the scan stops, and the usage of what
+    /// it had seen is returned.
+    pub fn send_to<S: UpdateSender>(self, sender: S, interval: Duration) -> DiskUsage {
+        let mut scan = self.into_iter();
+        let mut last = Instant::now();
+
+        while let Some(entry) = scan.next() {
+            let update = match entry {
+                Ok(entry) => ScanUpdate::Entry(entry),
+                Err(e) => ScanUpdate::Error(e),
+            };
+            if !sender.send_update(update) {
+                return scan.usage();
+            }
+
+            if last.elapsed() >= interval {
+                last = Instant::now();
+                if !sender.send_update(ScanUpdate::Subtotal(scan.usage())) {
+                    return scan.usage();
+                }
+            }
+        }
+
+        let usage = scan.usage();
+        sender.send_update(ScanUpdate::Subtotal(usage));
+        usage
+    }
+}
+
+#[test]
+fn scans_are_sent() {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let scan = std::thread::spawn(move || Scanner::new("src").send_to(tx, Duration::ZERO));
+
+    let updates: Vec<ScanUpdate> = rx.iter().collect();
+    let usage = scan.join().unwrap();
+    let entries = updates
+        .iter()
+        .filter(|update| matches!(update, ScanUpdate::Entry(_)))
+        .count();
+
+    assert_eq!(entries, Scanner::new("src").into_iter().count());
+    assert_eq!(usage, Scanner::new("src").total().unwrap());
+    assert!(matches!(updates.last(), Some(ScanUpdate::Subtotal(last)) if *last == usage));
+    assert!(
+        updates
+            .iter()
+            .filter(|update| matches!(update, ScanUpdate::Subtotal(_)))
+            .count()
+            > 1
+    );
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    drop(rx);
+    assert_eq!(
+        Scanner::new("src").send_to(&tx, Duration::ZERO).entries(),
+        1
+    );
+}
+
+#[cfg(feature = "crossbeam-channel")]
+#[test]
+fn scans_are_sent_to_crossbeam() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let usage = Scanner::new("src").send_to(tx, Duration::from_secs(3600));
+
+    assert!(matches!(rx.iter().last(), Some(ScanUpdate::Subtotal(last)) if last == usage));
+}
@@ -0,0 +1,185 @@
+//! Saving the state of a scan to resume it later.
+//!
+//! [`Scan::checkpoint`](crate::Scan::checkpoint) records the totals of a scan
+//! so far and what is left of it to walk in a [`Checkpoint`], and
+//! [`Scanner::resume`] carries on from one.  With the `serde` feature
+//! checkpoints can be saved to disk, so a scan of a huge filer taking hours
+//! isn't lost to a reboot.
+//!
+//! ```rust
+//! use std::time::{Duration, Instant};
+//! use filesize::Scanner;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let mut scan = Scanner::new("src").into_iter();
+//! let mut saved = Instant::now();
+//! let mut checkpoint = None;
+//!
+//! while let Some(entry) = scan.next() {
+//!     entry?;
+//!     if saved.elapsed() > Duration::from_secs(600) {
+//!         checkpoint = Some(scan.checkpoint());
+//!         saved = Instant::now();
+//!     }
+//! }
+//!
+//! // After a restart:
+//! if let Some(checkpoint) = checkpoint {
+//!     let mut scan = Scanner::new("src").resume(checkpoint)?;
+//!     for entry in &mut scan {
+//!         entry?;
+//!     }
+//!     println!("{} bytes", scan.usage().physical());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::scan::DirId;
+use crate::{DiskUsage, Error, Result, Scan, Scanner};
+
+/// The state of a scan part of the way through, from
+/// [`Scan::checkpoint`](crate::Scan::checkpoint).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    pub(crate) root: PathBuf,
+    pub(crate) usage: DiskUsage,
+    pub(crate) pending: Vec<(PathBuf, usize)>,
+    pub(crate) queue: Vec<(PathBuf, usize)>,
+    pub(crate) errors: u64,
+    pub(crate) skipped: u64,
+    /// The directories visited, when following links or scanning several
+    /// roots, so none is counted twice.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) visited: Vec<DirId>,
+    /// The device and inode of each file with hard links counted, for
+    /// `dedup_hard_links`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) links: Vec<(u64, u64)>,
+}
+
+impl Checkpoint {
+    /// The root of the scan.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The usage of everything counted before the checkpoint.
+    pub fn usage(&self) -> DiskUsage {
+        self.usage
+    }
+
+    /// The number of entries and directories left to visit, not counting
+    /// anything within them.
+    pub fn remaining(&self) -> usize {
+        self.pending.len() + self.queue.len()
+    }
+
+    /// Whether the scan had finished.
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+impl Scanner {
+    /// Resume the scan `checkpoint` was taken from, yielding only what was
+    /// left of it, with the usage totals carrying on from the checkpoint.
+    ///
+    /// The scanner should be configured as the original was.  Directories
+    /// visited and hard-linked files counted before the checkpoint are
+    /// remembered, so aren't counted again if reached through another link.
+    /// Fails if the checkpoint is of a scan with a different root.
+    pub fn resume(self, checkpoint: Checkpoint) -> Result<Scan> {
+        if checkpoint.root != self.root() {
+            return Err(Error::from_io(
+                &checkpoint.root,
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "checkpoint is of a scan with a different root",
+                ),
+            ));
+        }
+
+        Ok(Scan::new(Arc::new(self), Arc::default()).resumed(checkpoint))
+    }
+}
+
+#[test]
+fn scans_are_resumed_from_checkpoints() {
+    let dir = crate::test_dir("checkpoint");
+    for sub in &["a", "b", "c"] {
+        std::fs::create_dir(dir.join(sub)).unwrap();
+        for file in &["1", "2", "3"] {
+            std::fs::write(dir.join(sub).join(file), b"data").unwrap();
+        }
+    }
+
+    let everything = Scanner::new(&dir).total().unwrap();
+
+    let mut scan = Scanner::new(&dir).into_iter();
+    let mut seen = vec![];
+    for entry in scan.by_ref().take(4) {
+        seen.push(entry.unwrap().path().to_path_buf());
+    }
+    let checkpoint = scan.checkpoint();
+    drop(scan);
+
+    let mut resumed = Scanner::new(&dir).resume(checkpoint.clone()).unwrap();
+    for entry in resumed.by_ref() {
+        seen.push(entry.unwrap().path().to_path_buf());
+    }
+    let finished = resumed.checkpoint();
+    let elsewhere = Scanner::new(dir.join("a")).resume(checkpoint.clone());
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(!checkpoint.is_finished());
+    assert!(finished.is_finished());
+    assert_eq!(resumed.usage(), everything);
+    assert_eq!(seen.len() as u64, everything.entries());
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen.len() as u64, everything.entries());
+    assert!(elsewhere.is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn resumed_scans_remember_links() {
+    let dir = crate::test_dir("checkpoint-links");
+    std::fs::create_dir(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/file"), b"data").unwrap();
+    std::fs::hard_link(dir.join("sub/file"), dir.join("hard")).unwrap();
+    std::os::unix::fs::symlink(dir.join("sub"), dir.join("link")).unwrap();
+
+    let scanner = || {
+        Scanner::new(&dir)
+            .follow_symlinks(true)
+            .dedup_hard_links(true)
+            .sorted(true)
+    };
+    let everything = scanner().total().unwrap();
+
+    // Stop once "hard" and the directory through "link" have been counted,
+    // leaving the other links to them for the resumed scan.
+    let mut scan = scanner().into_iter();
+    for entry in scan.by_ref() {
+        if entry.unwrap().path().ends_with("link") {
+            break;
+        }
+    }
+    let checkpoint = scan.checkpoint();
+    drop(scan);
+
+    let mut resumed = scanner().resume(checkpoint).unwrap();
+    for entry in resumed.by_ref() {
+        entry.unwrap();
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(resumed.usage(), everything);
+}
@@ -0,0 +1,204 @@
+//! Detecting files sharing their blocks with clones.
+
+use std::path::Path;
+
+use crate::{Error, Result};
+
+/// How much of a file's allocation it shares with clones of it, from
+/// [`clone_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CloneInfo {
+    allocated: u64,
+    shared: u64,
+    may_share: bool,
+}
+
+impl CloneInfo {
+    /// Whether the file shares, or may share, blocks with another file, such
+    /// as an APFS clone made by `cp -c` or a reflink copy made by
+    /// `cp --reflink`.
+    pub fn is_clone(&self) -> bool {
+        self.may_share || self.shared > 0
+    }
+
+    /// The space allocated to the file, as from
+    /// [`file_real_size`](crate::file_real_size).
+    pub fn allocated_bytes(&self) -> u64 {
+        self.allocated
+    }
+
+    /// The part of the file's allocation shared with other files, which
+    /// deleting it would not free.
+    pub fn shared_bytes(&self) -> u64 {
+        self.shared
+    }
+
+    /// The part of the file's allocation belonging to it alone, which
+    /// deleting it would free.
+    pub fn private_bytes(&self) -> u64 {
+        self.allocated - self.shared
+    }
+}
+
+/// Find how much of the file at the given `path` is shared with clones of it.
+/// Symlinks are not followed.
+///
+/// On macOS and iOS this asks APFS for the space the file holds privately,
+/// and whether it may share blocks.  On Linux and Android it adds up the
+/// extents `FS_IOC_FIEMAP` marks as shared, which btrfs and XFS report for
+/// reflinked and snapshotted files.  Other platforms fail with
+/// [`Error::PlatformLimitation`].
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let info = filesize::clone_info("Cargo.toml")?;
+/// println!("{} bytes would be freed by deleting it", info.private_bytes());
+/// # Ok(())
+/// # }
+/// ```
+pub fn clone_info<P: AsRef<Path>>(path: P) -> Result<CloneInfo> {
+    let path = path.as_ref();
+    imp::clone_info(path).map_err(|e| Error::from_io(path, e))
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod imp {
+    use super::*;
+
+    use std::io;
+
+    // From <sys/attr.h>.
+    const EF_MAY_SHARE_BLOCKS: u64 = 0x0000_0001;
+
+    /// The attributes requested, in the order they are returned.
+    #[repr(C, packed(4))]
+    struct Attributes {
+        length: u32,
+        returned: libc::attribute_set_t,
+        private_size: libc::off_t,
+        ext_flags: u64,
+    }
+
+    pub fn clone_info(path: &Path) -> io::Result<CloneInfo> {
+        let cpath = crate::c_path(path)?;
+        let mut request = libc::attrlist {
+            bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+            reserved: 0,
+            commonattr: libc::ATTR_CMN_RETURNED_ATTRS,
+            volattr: 0,
+            dirattr: 0,
+            fileattr: 0,
+            forkattr: libc::ATTR_CMNEXT_PRIVATESIZE | libc::ATTR_CMNEXT_EXT_FLAGS,
+        };
+        let mut attrs: Attributes = unsafe { std::mem::zeroed() };
+
+        let options =
+            libc::FSOPT_NOFOLLOW | libc::FSOPT_PACK_INVAL_ATTRS | libc::FSOPT_ATTR_CMN_EXTENDED;
+        let ret = unsafe {
+            libc::getattrlist(
+                cpath.as_ptr(),
+                &mut request as *mut _ as *mut _,
+                &mut attrs as *mut _ as *mut _,
+                std::mem::size_of::<Attributes>(),
+                options,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let returned = attrs.returned;
+        let allocated = crate::imp::file_real_size(path)?;
+        // Filesystems without clones don't report a private size, and share
+        // nothing.
+        let private = if returned.forkattr & libc::ATTR_CMNEXT_PRIVATESIZE != 0 {
+            (attrs.private_size.max(0) as u64).min(allocated)
+        } else {
+            allocated
+        };
+        let may_share = returned.forkattr & libc::ATTR_CMNEXT_EXT_FLAGS != 0
+            && attrs.ext_flags & EF_MAY_SHARE_BLOCKS != 0;
+
+        Ok(CloneInfo {
+            allocated,
+            shared: allocated - private,
+            may_share,
+        })
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use super::*;
+
+    use std::io;
+
+    const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+
+    pub fn clone_info(path: &Path) -> io::Result<CloneInfo> {
+        let allocated = crate::imp::file_real_size(path)?;
+        if !path.symlink_metadata()?.is_file() {
+            return Ok(CloneInfo {
+                allocated,
+                shared: 0,
+                may_share: false,
+            });
+        }
+
+        let file = crate::extents::open(path)?;
+        let mut shared = 0u64;
+        crate::extents::for_each(&file, 0, |extent| {
+            if extent.flags & FIEMAP_EXTENT_SHARED != 0 {
+                shared = shared.saturating_add(extent.length);
+            }
+        })?;
+
+        Ok(CloneInfo {
+            allocated,
+            shared: shared.min(allocated),
+            may_share: false,
+        })
+    }
+}
+
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "android"
+)))]
+mod imp {
+    use super::*;
+
+    pub fn clone_info(path: &Path) -> std::io::Result<CloneInfo> {
+        path.symlink_metadata()?;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "clone detection is not available on this platform",
+        ))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn clones_are_detected() {
+    use std::io::Write;
+
+    let dir = crate::test_dir("clone");
+    let original = dir.join("original");
+    std::fs::File::create(&original)
+        .and_then(|mut file| file.write_all(&vec![1u8; 1 << 16]).and(file.sync_all()))
+        .unwrap();
+
+    let info = clone_info(&original).unwrap();
+    let allocated = crate::file_real_size(&original).unwrap();
+    let missing = clone_info(dir.join("missing"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(info.allocated_bytes(), allocated);
+    assert_eq!(
+        info.private_bytes() + info.shared_bytes(),
+        info.allocated_bytes()
+    );
+    assert!(missing.is_err());
+}
@@ -0,0 +1,567 @@
+//! Accumulating scan results as they stream past.
+//!
+//! A [`Collector`] is fed each entry yielded by a [`Scanner`] in turn, so
+//! summaries of very large trees can be built without holding every entry in
+//! memory at once.
+//!
+//! ```rust
+//! use filesize::{Scanner, TopK};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let mut largest = TopK::new(10);
+//! Scanner::new("src").collect_into(&mut largest)?;
+//!
+//! for entry in largest.into_sorted_vec() {
+//!     println!("{:>10} {}", entry.size_on_disk(), entry.path().display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::{DiskUsage, Entry, Result, Scanner};
+
+/// Something that accumulates the entries of a scan.
+pub trait Collector {
+    /// Add an entry yielded by the scan.
+    fn add(&mut self, entry: &Entry);
+}
+
+impl<A: Collector, B: Collector> Collector for (A, B) {
+    fn add(&mut self, entry: &Entry) {
+        self.0.add(entry);
+        self.1.add(entry);
+    }
+}
+
+impl Scanner {
+    /// Run the scan to completion, feeding each entry to `collector`, and
+    /// return the total disk usage of the tree.
+    ///
+    /// Errors are handled as with [`Scanner::total`].
+    pub fn collect_into<C: Collector + ?Sized>(self, collector: &mut C) -> Result<DiskUsage> {
+        let mut scan = self.into_iter();
+        for entry in &mut scan {
+            collector.add(&entry?);
+        }
+        Ok(scan.usage())
+    }
+}
+
+/// A collector keeping only the `k` largest files seen, by [`Entry::size`].
+///
+/// Directories are disregarded.
+#[derive(Debug, Clone)]
+pub struct TopK {
+    k: usize,
+    heap: BinaryHeap<Reverse<BySize>>,
+}
+
+impl TopK {
+    /// Create a collector for the `k` largest files.
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k.saturating_add(1).min(1024)),
+        }
+    }
+
+    /// The number of entries currently held.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Whether no entries are held.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The entries held, largest first.
+    pub fn into_sorted_vec(self) -> Vec<Entry> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(BySize(entry))| entry)
+            .collect()
+    }
+}
+
+impl Collector for TopK {
+    fn add(&mut self, entry: &Entry) {
+        if self.k == 0 || entry.file_type().is_dir() {
+            return;
+        }
+
+        if self.heap.len() == self.k {
+            match self.heap.peek() {
+                Some(Reverse(smallest)) if smallest.0.size() < entry.size() => {
+                    self.heap.pop();
+                }
+                _ => return,
+            }
+        }
+
+        self.heap.push(Reverse(BySize(entry.clone())));
+    }
+}
+
+/// A collector grouping the usage of files by a key derived from each entry,
+/// such as its extension.
+///
+/// Directories are disregarded.
+///
+/// ```rust
+/// use filesize::{GroupBy, Scanner};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut extensions = GroupBy::extension();
+/// Scanner::new("src").collect_into(&mut extensions)?;
+///
+/// if let Some(usage) = extensions.groups().get("rs") {
+///     println!("{} bytes of Rust", usage.physical());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct GroupBy<F> {
+    classify: F,
+    groups: HashMap<String, DiskUsage>,
+}
+
+impl<F> fmt::Debug for GroupBy<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupBy")
+            .field("groups", &self.groups)
+            .finish()
+    }
+}
+
+impl<F: FnMut(&Entry) -> Option<String>> GroupBy<F> {
+    /// Create a collector grouping files by the key returned by `classify`.
+    /// Files for which it returns `None` are left out.
+    pub fn new(classify: F) -> Self {
+        Self {
+            classify,
+            groups: HashMap::new(),
+        }
+    }
+}
+
+impl<F> GroupBy<F> {
+    /// The usage of each group seen so far.
+    pub fn groups(&self) -> &HashMap<String, DiskUsage> {
+        &self.groups
+    }
+
+    /// Consume the collector, returning the usage of each group.
+    pub fn into_groups(self) -> HashMap<String, DiskUsage> {
+        self.groups
+    }
+}
+
+impl GroupBy<fn(&Entry) -> Option<String>> {
+    /// Create a collector grouping files by their extension, as returned by
+    /// `Path::extension`.  Files without one are grouped under `""`.
+    pub fn extension() -> Self {
+        Self::new(|entry| {
+            Some(
+                entry
+                    .path()
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            )
+        })
+    }
+}
+
+#[cfg(unix)]
+impl GroupBy<fn(&Entry) -> Option<String>> {
+    /// Create a collector grouping files by the numeric user ID of their
+    /// owner.
+    pub fn owner() -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        Self::new(|entry| Some(entry.metadata().uid().to_string()))
+    }
+
+    /// Create a collector grouping files by their numeric group ID.
+    pub fn group() -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        Self::new(|entry| Some(entry.metadata().gid().to_string()))
+    }
+}
+
+#[cfg(windows)]
+impl GroupBy<fn(&Entry) -> Option<String>> {
+    /// Create a collector grouping files by the SID of their owner, in its
+    /// string form such as `S-1-5-32-544`.
+    ///
+    /// This looks up the security descriptor of each file, which is
+    /// considerably more expensive than the scan itself.  Files for which it
+    /// fails are left out.
+    pub fn owner() -> Self {
+        Self::new(|entry| owner_sid(entry.path()).ok())
+    }
+}
+
+#[cfg(windows)]
+fn owner_sid(path: &std::path::Path) -> std::io::Result<String> {
+    use std::os::windows::ffi::OsStrExt;
+
+    use crate::win::{
+        ConvertSidToStringSidW, GetNamedSecurityInfoW, LocalFree, OWNER_SECURITY_INFORMATION,
+        PSECURITY_DESCRIPTOR, PSID, SE_FILE_OBJECT,
+    };
+
+    let pathw: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut owner: PSID = std::ptr::null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+
+    let status = unsafe {
+        GetNamedSecurityInfoW(
+            pathw.as_ptr(),
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION,
+            &mut owner,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut descriptor,
+        )
+    };
+
+    if status != 0 {
+        return Err(std::io::Error::from_raw_os_error(status as i32));
+    }
+
+    let mut sid: *mut u16 = std::ptr::null_mut();
+    let ok = unsafe { ConvertSidToStringSidW(owner, &mut sid) };
+    let result = if ok == 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        let len = (0..).take_while(|&i| unsafe { *sid.add(i) } != 0).count();
+        let s = String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(sid, len) });
+        unsafe { LocalFree(sid as _) };
+        Ok(s)
+    };
+
+    unsafe { LocalFree(descriptor) };
+    result
+}
+
+impl<F: FnMut(&Entry) -> Option<String>> Collector for GroupBy<F> {
+    fn add(&mut self, entry: &Entry) {
+        if entry.file_type().is_dir() {
+            return;
+        }
+
+        if let Some(key) = (self.classify)(entry) {
+            self.groups.entry(key).or_default().add(entry);
+        }
+    }
+}
+
+/// A collector grouping files of identical size into sets of candidates for
+/// deduplication, such as with `duperemove` or reflink copies.
+///
+/// Files in a set only share sizes, and must still be compared byte for byte.
+/// By default sets are split by device, as deduplication can't cross
+/// filesystems, and empty files are disregarded, along with directories and
+/// symlinks.
+///
+/// ```rust
+/// use filesize::{DedupCandidates, Scanner};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut candidates = DedupCandidates::new().min_size(4096);
+/// Scanner::new("src").collect_into(&mut candidates)?;
+///
+/// for set in candidates.into_sets() {
+///     println!("{} files of {} bytes", set.paths().len(), set.physical());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DedupCandidates {
+    min_size: u64,
+    by_device: bool,
+    skip_shared: bool,
+    files: HashMap<DedupKey, Vec<PathBuf>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct DedupKey {
+    physical: u64,
+    logical: u64,
+    device: Option<u64>,
+}
+
+/// A set of files of identical size from [`DedupCandidates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupSet {
+    physical: u64,
+    logical: u64,
+    paths: Vec<PathBuf>,
+}
+
+impl DedupSet {
+    /// The space each file uses on disk.
+    pub fn physical(&self) -> u64 {
+        self.physical
+    }
+
+    /// The logical length of each file.
+    pub fn logical(&self) -> u64 {
+        self.logical
+    }
+
+    /// The files in the set, in the order they were found.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The space that would be freed if every file but one shared its data.
+    pub fn reclaimable(&self) -> u64 {
+        self.physical
+            .saturating_mul(self.paths.len().saturating_sub(1) as u64)
+    }
+}
+
+impl Default for DedupCandidates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DedupCandidates {
+    /// Create a collector for files of at least one byte, split by device.
+    pub fn new() -> Self {
+        Self {
+            min_size: 1,
+            by_device: true,
+            skip_shared: false,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Disregard files using less than `bytes` on disk.  Defaults to 1.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Split sets by the device files reside on, where the platform reports
+    /// one.  Defaults to `true`.
+    pub fn by_device(mut self, enable: bool) -> Self {
+        self.by_device = enable;
+        self
+    }
+
+    /// Leave out files whose data starts at the same place on disk as an
+    /// earlier file in their set, as they are already reflinked or
+    /// deduplicated.  Defaults to `false`.
+    ///
+    /// This maps the first extent of every candidate when the sets are
+    /// produced, and is only supported on Linux and Android.
+    pub fn skip_shared_extents(mut self, enable: bool) -> Self {
+        self.skip_shared = enable;
+        self
+    }
+
+    /// Consume the collector, returning every set of more than one file, with
+    /// those which could reclaim the most space first.
+    pub fn into_sets(self) -> Vec<DedupSet> {
+        let skip_shared = self.skip_shared;
+        let mut sets: Vec<DedupSet> = self
+            .files
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|(key, paths)| DedupSet {
+                physical: key.physical,
+                logical: key.logical,
+                paths: if skip_shared { unshared(paths) } else { paths },
+            })
+            .filter(|set| set.paths.len() > 1)
+            .collect();
+
+        sets.sort_by(|a, b| {
+            b.reclaimable()
+                .cmp(&a.reclaimable())
+                .then_with(|| a.paths.cmp(&b.paths))
+        });
+        sets
+    }
+}
+
+impl Collector for DedupCandidates {
+    fn add(&mut self, entry: &Entry) {
+        if !entry.file_type().is_file() || entry.size_on_disk() < self.min_size {
+            return;
+        }
+
+        let key = DedupKey {
+            physical: entry.size_on_disk(),
+            logical: entry.size_info().logical(),
+            device: if self.by_device {
+                crate::filesystem::device_id(entry.metadata())
+            } else {
+                None
+            },
+        };
+        self.files
+            .entry(key)
+            .or_default()
+            .push(entry.path().to_path_buf());
+    }
+}
+
+/// Keep only the first of `paths` to start at each physical offset.  Files
+/// which can't be mapped, or whose first extent has no location yet, such as
+/// data still awaiting delayed allocation, are kept.
+fn unshared(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    paths
+        .into_iter()
+        .filter(|path| first_extent(path).is_none_or(|offset| seen.insert(offset)))
+        .collect()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn first_extent(path: &Path) -> Option<u64> {
+    use crate::extents::FIEMAP_EXTENT_UNKNOWN;
+
+    let file = crate::extents::open(path).ok()?;
+    let mut first = None;
+    crate::extents::for_each(&file, 0, |extent| {
+        let known = extent.flags & FIEMAP_EXTENT_UNKNOWN == 0;
+        first.get_or_insert(Some(extent.physical).filter(|_| known));
+    })
+    .ok()?;
+    first.flatten()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn first_extent(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[derive(Debug, Clone)]
+struct BySize(Entry);
+
+impl PartialEq for BySize {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BySize {}
+
+impl PartialOrd for BySize {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BySize {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .size()
+            .cmp(&other.0.size())
+            .then_with(|| other.0.path().cmp(self.0.path()))
+    }
+}
+
+#[test]
+fn top_k_keeps_the_largest() {
+    let mut all = vec![];
+    let mut top = TopK::new(3);
+    let usage = Scanner::new("src").collect_into(&mut top).unwrap();
+    for entry in Scanner::new("src") {
+        let entry = entry.unwrap();
+        if !entry.file_type().is_dir() {
+            all.push(entry.size_on_disk());
+        }
+    }
+    all.sort_unstable_by(|a, b| b.cmp(a));
+    all.truncate(3);
+
+    let top = top.into_sorted_vec();
+    assert_eq!(top.iter().map(Entry::size_on_disk).collect::<Vec<_>>(), all);
+    assert!(usage.physical() >= all.iter().sum());
+}
+
+#[test]
+fn group_by_extension() {
+    let mut extensions = GroupBy::extension();
+    let usage = Scanner::new("src").collect_into(&mut extensions).unwrap();
+    let groups = extensions.into_groups();
+
+    assert!(groups.contains_key("rs"));
+    assert_eq!(
+        groups.values().map(DiskUsage::physical).sum::<u64>(),
+        usage.physical()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn group_by_owner() {
+    use std::os::unix::fs::MetadataExt;
+
+    let uid = std::fs::metadata("Cargo.toml").unwrap().uid().to_string();
+    let mut owners = GroupBy::owner();
+    Scanner::new("Cargo.toml")
+        .collect_into(&mut owners)
+        .unwrap();
+
+    assert!(owners.groups().contains_key(&uid));
+}
+
+#[test]
+fn dedup_candidates_share_sizes() {
+    let dir = crate::test_dir("dedup");
+    std::fs::write(dir.join("a"), vec![1u8; 8192]).unwrap();
+    std::fs::write(dir.join("b"), vec![2u8; 8192]).unwrap();
+    std::fs::write(dir.join("c"), vec![3u8; 100]).unwrap();
+    std::fs::write(dir.join("empty1"), b"").unwrap();
+    std::fs::write(dir.join("empty2"), b"").unwrap();
+
+    let mut candidates = DedupCandidates::new();
+    Scanner::new(&dir).collect_into(&mut candidates).unwrap();
+    let mut shared = DedupCandidates::new().skip_shared_extents(true);
+    Scanner::new(&dir).collect_into(&mut shared).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let sets = candidates.into_sets();
+    assert_eq!(sets.len(), 1);
+    let mut paths = sets[0].paths().to_vec();
+    paths.sort();
+    assert_eq!(paths, vec![dir.join("a"), dir.join("b")]);
+    assert_eq!(sets[0].logical(), 8192);
+    assert_eq!(sets[0].reclaimable(), sets[0].physical());
+
+    // Separate copies don't share extents.
+    assert_eq!(shared.into_sets().len(), 1);
+}
+
+#[test]
+fn unwritten_files_are_not_shared() {
+    let dir = crate::test_dir("dedup-unwritten");
+    let paths = vec![dir.join("a"), dir.join("b")];
+    std::fs::write(&paths[0], vec![1u8; 8192]).unwrap();
+    std::fs::write(&paths[1], vec![1u8; 8192]).unwrap();
+
+    // Neither is synced, so both may still be awaiting allocation.
+    let kept = unshared(paths.clone());
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(kept, paths);
+}
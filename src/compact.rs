@@ -0,0 +1,329 @@
+//! Estimating what NTFS compression would save before running `compact`.
+//!
+//! [`CompactEstimator`] reads a sample of each file's contents, compresses it
+//! with a simple LZ77 model in the chunk sizes NTFS and the Windows Overlay
+//! Filter use, and scales the result up to the whole file, giving a rough idea
+//! of how much `compact /c` or `compact /c /exe` would free without the hours
+//! of I/O of actually doing it.
+//!
+//! ```rust
+//! use filesize::{CompactAlgorithm, CompactEstimator, Scanner};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let estimate = CompactEstimator::new(CompactAlgorithm::Xpress4k)
+//!     .estimate(Scanner::new("src"))?;
+//! println!(
+//!     "{} bytes now, about {} compressed",
+//!     estimate.physical(),
+//!     estimate.estimated_physical()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::{Error, Result, Scanner};
+
+/// A compression algorithm offered by `compact` on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompactAlgorithm {
+    /// NTFS compression, as by `compact /c`, in 64 KiB compression units
+    /// which are only stored compressed if that saves a cluster.
+    Lznt1,
+    /// WOF compression with XPRESS in 4 KiB chunks, as by
+    /// `compact /c /exe:xpress4k`.
+    Xpress4k,
+    /// WOF compression with XPRESS in 8 KiB chunks.
+    Xpress8k,
+    /// WOF compression with XPRESS in 16 KiB chunks.
+    Xpress16k,
+    /// WOF compression with LZX in 32 KiB chunks.
+    Lzx,
+}
+
+impl CompactAlgorithm {
+    fn chunk_size(self) -> u64 {
+        match self {
+            CompactAlgorithm::Lznt1 => 64 * 1024,
+            CompactAlgorithm::Xpress4k => 4 * 1024,
+            CompactAlgorithm::Xpress8k => 8 * 1024,
+            CompactAlgorithm::Xpress16k => 16 * 1024,
+            CompactAlgorithm::Lzx => 32 * 1024,
+        }
+    }
+}
+
+/// The estimated effect of compression on a set of files, from
+/// [`CompactEstimator`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompactEstimate {
+    files: u64,
+    logical: u64,
+    physical: u64,
+    estimated: u64,
+    sampled: u64,
+}
+
+impl CompactEstimate {
+    fn add(&mut self, other: &CompactEstimate) {
+        self.files += other.files;
+        self.logical = self.logical.saturating_add(other.logical);
+        self.physical = self.physical.saturating_add(other.physical);
+        self.estimated = self.estimated.saturating_add(other.estimated);
+        self.sampled = self.sampled.saturating_add(other.sampled);
+    }
+
+    /// The number of regular files estimated.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The total length of the files.
+    pub fn logical(&self) -> u64 {
+        self.logical
+    }
+
+    /// The space the files use on disk now.
+    pub fn physical(&self) -> u64 {
+        self.physical
+    }
+
+    /// The space the files are estimated to use once compressed.  Files
+    /// which wouldn't shrink are counted at their current size, as `compact`
+    /// leaves them alone.
+    pub fn estimated_physical(&self) -> u64 {
+        self.estimated
+    }
+
+    /// The space compression is estimated to free.
+    pub fn estimated_savings(&self) -> u64 {
+        self.physical.saturating_sub(self.estimated)
+    }
+
+    /// The number of bytes of file contents read to make the estimate.
+    pub fn sampled_bytes(&self) -> u64 {
+        self.sampled
+    }
+}
+
+/// Estimates the space compression would save, by compressing a sample of
+/// each file.
+#[derive(Debug, Clone)]
+pub struct CompactEstimator {
+    algorithm: CompactAlgorithm,
+    samples: u64,
+    cluster_size: u64,
+}
+
+impl CompactEstimator {
+    /// Create an estimator for `algorithm`, reading up to 16 chunks of each
+    /// file, with 4 KiB clusters.
+    pub fn new(algorithm: CompactAlgorithm) -> Self {
+        Self {
+            algorithm,
+            samples: 16,
+            cluster_size: 4096,
+        }
+    }
+
+    /// Read up to `samples` chunks of each file, spread evenly through it.
+    /// More samples give a better estimate for files with mixed contents, at
+    /// the cost of more I/O.  At least one chunk is always read.
+    pub fn samples(mut self, samples: u64) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Round compressed data up to clusters of `bytes`, as the volume
+    /// allocates them.  This is 4096 on most NTFS volumes.
+    pub fn cluster_size(mut self, bytes: u64) -> Self {
+        self.cluster_size = bytes.max(1);
+        self
+    }
+
+    /// Estimate the effect of compression on every regular file `scanner`
+    /// finds.  Errors are handled as with [`Scanner::total`].
+    pub fn estimate(&self, scanner: Scanner) -> Result<CompactEstimate> {
+        let mut total = CompactEstimate::default();
+        for entry in scanner {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let info = entry.size_info();
+            let estimate = self
+                .estimate_contents(entry.path(), info.logical(), info.physical())
+                .map_err(|e| Error::from_io(entry.path(), e))?;
+            total.add(&estimate);
+        }
+        Ok(total)
+    }
+
+    /// Estimate the effect of compression on the file at `path`.
+    pub fn estimate_file<P: AsRef<Path>>(&self, path: P) -> Result<CompactEstimate> {
+        let path = path.as_ref();
+        let info = crate::SizeOptions::new().size_info(path)?;
+        self.estimate_contents(path, info.logical(), info.physical())
+            .map_err(|e| Error::from_io(path, e))
+    }
+
+    fn estimate_contents(
+        &self,
+        path: &Path,
+        logical: u64,
+        physical: u64,
+    ) -> io::Result<CompactEstimate> {
+        let chunk = self.algorithm.chunk_size();
+        let chunks = logical.div_ceil(chunk);
+        let sampled_chunks = chunks.min(self.samples);
+
+        let mut file = File::open(path)?;
+        let mut buf = vec![0; chunk as usize];
+        let mut sampled = 0;
+        let mut stored = 0;
+        for i in 0..sampled_chunks {
+            let index = i * chunks / sampled_chunks;
+            file.seek(SeekFrom::Start(index * chunk))?;
+            let len = read_up_to(&mut file, &mut buf)?;
+            if len == 0 {
+                break;
+            }
+            sampled += len as u64;
+            stored += self.stored_size(&buf[..len]);
+        }
+
+        let estimated = if sampled == 0 {
+            physical
+        } else {
+            let scaled = (stored as f64 * logical as f64 / sampled as f64) as u64;
+            let estimated = match self.algorithm {
+                CompactAlgorithm::Lznt1 => scaled,
+                // WOF stores the chunks back to back after a table of their
+                // offsets.
+                _ => round_up(scaled + chunks * 8, self.cluster_size),
+            };
+            estimated.min(physical)
+        };
+
+        Ok(CompactEstimate {
+            files: 1,
+            logical,
+            physical,
+            estimated,
+            sampled,
+        })
+    }
+
+    /// The space a chunk would occupy once compressed.
+    fn stored_size(&self, data: &[u8]) -> u64 {
+        let raw = data.len() as u64;
+        let compressed = compressed_len(data).min(raw);
+        match self.algorithm {
+            CompactAlgorithm::Lznt1 => {
+                let clusters = round_up(compressed, self.cluster_size);
+                clusters.min(round_up(raw, self.cluster_size))
+            }
+            _ => compressed,
+        }
+    }
+}
+
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+fn round_up(bytes: u64, unit: u64) -> u64 {
+    bytes.div_ceil(unit) * unit
+}
+
+/// The length of `data` compressed by a greedy LZ77 coder, with a flag bit
+/// and a byte for each literal and a flag bit and 16 bit token for each match
+/// of 3 or more bytes, as LZNT1 encodes them.  XPRESS and LZX do somewhat
+/// better with Huffman coding, but this is close enough for an estimate.
+fn compressed_len(data: &[u8]) -> u64 {
+    const HASH_BITS: u32 = 12;
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 4096;
+
+    let hash = |i: usize| {
+        let v = u32::from(data[i]) | u32::from(data[i + 1]) << 8 | u32::from(data[i + 2]) << 16;
+        (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    };
+
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut bits = 0u64;
+    let mut i = 0;
+    while i < data.len() {
+        let mut length = 0;
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            let candidate = table[h];
+            table[h] = i;
+            if candidate != usize::MAX {
+                let limit = (data.len() - i).min(MAX_MATCH);
+                while length < limit && data[candidate + length] == data[i + length] {
+                    length += 1;
+                }
+            }
+        }
+
+        if length >= MIN_MATCH {
+            bits += 17;
+            i += length;
+        } else {
+            bits += 9;
+            i += 1;
+        }
+    }
+
+    // Each chunk has a two byte header.
+    bits.div_ceil(8) + 2
+}
+
+#[test]
+fn compression_is_estimated() {
+    let dir = crate::test_dir("compact");
+    std::fs::write(dir.join("zeros"), vec![0u8; 1 << 20]).unwrap();
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let noise: Vec<u8> = (0..1 << 20)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    std::fs::write(dir.join("noise"), noise).unwrap();
+
+    let estimator = CompactEstimator::new(CompactAlgorithm::Lznt1);
+    let zeros = estimator.estimate_file(dir.join("zeros")).unwrap();
+    let noise = estimator.estimate_file(dir.join("noise")).unwrap();
+    let total = CompactEstimator::new(CompactAlgorithm::Xpress4k)
+        .samples(4)
+        .estimate(Scanner::new(&dir))
+        .unwrap();
+    let missing = estimator.estimate_file(dir.join("missing"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(zeros.logical(), 1 << 20);
+    assert!(zeros.estimated_physical() < zeros.physical() / 4);
+    assert_eq!(noise.estimated_physical(), noise.physical());
+    assert_eq!(noise.estimated_savings(), 0);
+    assert_eq!(noise.sampled_bytes(), 16 * 64 * 1024);
+    assert_eq!(total.files(), 2);
+    assert_eq!(total.sampled_bytes(), 2 * 4 * 4096);
+    assert!(total.estimated_savings() > 0);
+    assert!(missing.is_err());
+}
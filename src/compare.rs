@@ -0,0 +1,65 @@
+//! Comparing the on-disk sizes of two files.
+
+use std::path::Path;
+
+use crate::Result;
+
+/// Whether the files at `a` and `b` occupy the same amount of space on disk.
+/// Symlinks are not followed.
+///
+/// This compares sizes only, so is useful for checking that a copy kept the
+/// footprint of its original, sparse ranges and all.  Errors carry the path
+/// of whichever file couldn't be sized.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// assert!(filesize::same_allocation("Cargo.toml", "Cargo.toml")?);
+/// # Ok(())
+/// # }
+/// ```
+pub fn same_allocation<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<bool> {
+    Ok(allocation_delta(a, b)? == 0)
+}
+
+/// How many more bytes the file at `b` occupies on disk than the file at `a`,
+/// negative if it occupies fewer.  Symlinks are not followed.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let delta = filesize::allocation_delta("Cargo.toml", "README.md")?;
+/// println!("README.md uses {} bytes more than Cargo.toml", delta);
+/// # Ok(())
+/// # }
+/// ```
+pub fn allocation_delta<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<i128> {
+    let a = crate::file_real_size(a)?;
+    let b = crate::file_real_size(b)?;
+    Ok(i128::from(b) - i128::from(a))
+}
+
+#[test]
+fn allocations_are_compared() {
+    use std::io::Write;
+
+    let dir = crate::test_dir("compare");
+    let small = dir.join("small");
+    let large = dir.join("large");
+    std::fs::write(&small, b"data").unwrap();
+    std::fs::File::create(&large)
+        .and_then(|mut file| file.write_all(&vec![1u8; 1 << 20]).and(file.sync_all()))
+        .unwrap();
+
+    let delta = allocation_delta(&small, &large).unwrap();
+    let reverse = allocation_delta(&large, &small).unwrap();
+    let same = same_allocation(&small, &small).unwrap();
+    let missing = same_allocation(&small, dir.join("missing"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(delta > 0);
+    assert_eq!(reverse, -delta);
+    assert!(same);
+    match missing {
+        Err(e) => assert_eq!(e.path(), dir.join("missing")),
+        Ok(_) => panic!("missing file was sized"),
+    }
+}
@@ -0,0 +1,278 @@
+//! Compressed extent accounting for btrfs, on Linux.
+//!
+//! `st_blocks` on btrfs counts the uncompressed size of compressed extents,
+//! so the usual functions overstate the space used by compressed files.
+//! [`compressed_size`] reads the file's extents from the filesystem tree, as
+//! `compsize` does, to find the space actually occupied on disk.
+//!
+//! ```rust,no_run
+//! # fn main() -> std::io::Result<()> {
+//! let size = filesize::compressed_size("/var/log/syslog")?;
+//! println!("{} bytes on disk for {}", size.disk(), size.uncompressed());
+//! for usage in size.algorithms() {
+//!     println!("{:?}: {}", usage.algorithm(), usage.disk());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::io;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::btrfs::{self, SearchKey};
+use crate::{Error, Result};
+
+const BTRFS_EXTENT_DATA_KEY: u32 = 108;
+const BTRFS_FILE_EXTENT_INLINE: u8 = 0;
+
+/// The compression algorithm of a btrfs extent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum CompressionAlgorithm {
+    /// Stored uncompressed.
+    None,
+    /// Compressed with zlib.
+    Zlib,
+    /// Compressed with LZO.
+    Lzo,
+    /// Compressed with Zstandard.
+    Zstd,
+    /// An algorithm unknown to this crate, by its btrfs type number.
+    Other(u8),
+}
+
+impl From<u8> for CompressionAlgorithm {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => CompressionAlgorithm::None,
+            1 => CompressionAlgorithm::Zlib,
+            2 => CompressionAlgorithm::Lzo,
+            3 => CompressionAlgorithm::Zstd,
+            other => CompressionAlgorithm::Other(other),
+        }
+    }
+}
+
+/// The extents of a file using one compression algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlgorithmUsage {
+    algorithm: CompressionAlgorithm,
+    disk: u64,
+    uncompressed: u64,
+}
+
+impl AlgorithmUsage {
+    /// The compression algorithm.
+    pub fn algorithm(&self) -> CompressionAlgorithm {
+        self.algorithm
+    }
+
+    /// The space occupied on disk by extents using the algorithm.
+    pub fn disk(&self) -> u64 {
+        self.disk
+    }
+
+    /// The uncompressed size of extents using the algorithm.
+    pub fn uncompressed(&self) -> u64 {
+        self.uncompressed
+    }
+}
+
+/// The compressed and uncompressed sizes of a file, from [`compressed_size`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CompressedSize {
+    disk: u64,
+    uncompressed: u64,
+    referenced: u64,
+    algorithms: Vec<AlgorithmUsage>,
+}
+
+impl CompressedSize {
+    /// The space occupied on disk by the extents of the file.
+    ///
+    /// Extents referenced more than once by the file are counted once, while
+    /// extents shared with other files, as by snapshots or reflinks, are
+    /// counted in full.
+    pub fn disk(&self) -> u64 {
+        self.disk
+    }
+
+    /// The uncompressed size of the extents of the file.
+    pub fn uncompressed(&self) -> u64 {
+        self.uncompressed
+    }
+
+    /// The uncompressed size of the parts of extents the file refers to, which
+    /// may be less than their full size after partial overwrites.
+    pub fn referenced(&self) -> u64 {
+        self.referenced
+    }
+
+    /// The usage of each compression algorithm found, in the order of
+    /// [`CompressionAlgorithm`].
+    pub fn algorithms(&self) -> &[AlgorithmUsage] {
+        &self.algorithms
+    }
+
+    fn add(&mut self, algorithm: CompressionAlgorithm, disk: u64, uncompressed: u64) {
+        self.disk = self.disk.saturating_add(disk);
+        self.uncompressed = self.uncompressed.saturating_add(uncompressed);
+
+        match self
+            .algorithms
+            .binary_search_by_key(&algorithm, |usage| usage.algorithm)
+        {
+            Ok(i) => {
+                let usage = &mut self.algorithms[i];
+                usage.disk = usage.disk.saturating_add(disk);
+                usage.uncompressed = usage.uncompressed.saturating_add(uncompressed);
+            }
+            Err(i) => self.algorithms.insert(
+                i,
+                AlgorithmUsage {
+                    algorithm,
+                    disk,
+                    uncompressed,
+                },
+            ),
+        }
+    }
+}
+
+/// Get the compressed size of the file at `path` on btrfs.
+///
+/// This requires `CAP_SYS_ADMIN`, as searching the filesystem tree does, and
+/// fails on other filesystems.  Data which has not yet been written back has
+/// no extents, so is not counted.
+pub fn compressed_size<P: AsRef<Path>>(path: P) -> Result<CompressedSize> {
+    let path = path.as_ref();
+    imp(path).map_err(|e| Error::from_io(path, e))
+}
+
+fn imp(path: &Path) -> io::Result<CompressedSize> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?;
+    let ino = file.metadata()?.ino();
+    let subvolume = btrfs::subvolume(file.as_raw_fd())?;
+
+    let key = SearchKey::new(
+        subvolume,
+        (ino, BTRFS_EXTENT_DATA_KEY, 0),
+        (ino, BTRFS_EXTENT_DATA_KEY, u64::MAX),
+    );
+
+    let mut size = CompressedSize::default();
+    let mut seen = HashSet::new();
+    btrfs::tree_search(file.as_raw_fd(), key, |data| {
+        if let Some(extent) = Extent::parse(data) {
+            size.referenced = size.referenced.saturating_add(extent.referenced);
+            if extent.bytenr.is_none_or(|bytenr| seen.insert(bytenr)) {
+                size.add(extent.algorithm, extent.disk, extent.uncompressed);
+            }
+        }
+    })?;
+
+    Ok(size)
+}
+
+/// The sizes from a btrfs_file_extent_item.
+#[derive(Debug, PartialEq, Eq)]
+struct Extent {
+    /// The disk address of a regular extent, or `None` if it is inline.
+    bytenr: Option<u64>,
+    algorithm: CompressionAlgorithm,
+    disk: u64,
+    uncompressed: u64,
+    referenced: u64,
+}
+
+impl Extent {
+    /// Parse the item, which is laid out as generation, ram_bytes,
+    /// compression, encryption, other_encoding, and type, followed by inline
+    /// data or by disk_bytenr, disk_num_bytes, offset, and num_bytes.  Holes
+    /// have a disk_bytenr of 0, and are skipped.
+    fn parse(data: &[u8]) -> Option<Self> {
+        const HEADER: usize = 21;
+
+        let ram_bytes = btrfs::le_u64(data, 8);
+        let algorithm = CompressionAlgorithm::from(*data.get(16)?);
+        let kind = *data.get(20)?;
+
+        if kind == BTRFS_FILE_EXTENT_INLINE {
+            return Some(Extent {
+                bytenr: None,
+                algorithm,
+                disk: data.len().checked_sub(HEADER)? as u64,
+                uncompressed: ram_bytes,
+                referenced: ram_bytes,
+            });
+        }
+
+        if data.len() < HEADER + 32 {
+            return None;
+        }
+
+        let bytenr = btrfs::le_u64(data, HEADER);
+        if bytenr == 0 {
+            return None;
+        }
+
+        Some(Extent {
+            bytenr: Some(bytenr),
+            algorithm,
+            disk: btrfs::le_u64(data, HEADER + 8),
+            uncompressed: ram_bytes,
+            referenced: btrfs::le_u64(data, HEADER + 24),
+        })
+    }
+}
+
+#[test]
+fn extents_are_parsed() {
+    fn item(compression: u8, kind: u8, fields: &[u64]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&131072u64.to_le_bytes());
+        data.extend_from_slice(&[compression, 0, 0, 0, kind]);
+        for field in fields {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data
+    }
+
+    let regular = Extent::parse(&item(3, 1, &[4096, 8192, 0, 65536])).unwrap();
+    assert_eq!(
+        regular,
+        Extent {
+            bytenr: Some(4096),
+            algorithm: CompressionAlgorithm::Zstd,
+            disk: 8192,
+            uncompressed: 131072,
+            referenced: 65536,
+        }
+    );
+
+    assert_eq!(Extent::parse(&item(0, 1, &[0, 0, 0, 4096])), None);
+    assert_eq!(Extent::parse(&item(0, 1, &[4096])), None);
+
+    let mut inline = item(1, 0, &[]);
+    inline.extend_from_slice(&[0; 100]);
+    let inline = Extent::parse(&inline).unwrap();
+    assert_eq!(inline.bytenr, None);
+    assert_eq!(inline.algorithm, CompressionAlgorithm::Zlib);
+    assert_eq!(inline.disk, 100);
+
+    let mut size = CompressedSize::default();
+    size.add(CompressionAlgorithm::Zstd, 10, 100);
+    size.add(CompressionAlgorithm::None, 50, 50);
+    size.add(CompressionAlgorithm::Zstd, 10, 100);
+    assert_eq!((size.disk(), size.uncompressed()), (70, 250));
+    assert_eq!(size.algorithms().len(), 2);
+    assert_eq!(size.algorithms()[0].algorithm(), CompressionAlgorithm::None);
+    assert_eq!(size.algorithms()[1].disk(), 20);
+}
@@ -0,0 +1,228 @@
+//! Files deleted while still held open, on Linux.
+//!
+//! A file unlinked while a process holds it open keeps its space until the
+//! last descriptor is closed, but has no name for `du` or a [`Scanner`] to
+//! find, so the used space `df` and [`volume_space`] report can exceed the
+//! usage of everything reachable.  Log files deleted by rotation while a
+//! daemon still writes to them are the classic case.
+//! [`deleted_open_files`] finds them through the descriptors listed under
+//! `/proc/<pid>/fd`.
+//!
+//! ```rust,no_run
+//! # fn main() -> std::io::Result<()> {
+//! let deleted = filesize::deleted_open_files("/var")?;
+//! for file in deleted.files() {
+//!     println!("{:>10} {:?} held by {:?}", file.size(), file.path(), file.pids());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Scanner`]: crate::Scanner
+//! [`volume_space`]: crate::volume_space
+
+use std::collections::BTreeMap;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// A file deleted while still open, from [`deleted_open_files`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedFile {
+    path: PathBuf,
+    device: u64,
+    inode: u64,
+    len: u64,
+    size: u64,
+    pids: Vec<u32>,
+}
+
+impl DeletedFile {
+    /// The path the file had when it was deleted.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The device holding the file.
+    pub fn device(&self) -> u64 {
+        self.device
+    }
+
+    /// The inode number of the file.
+    pub fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    /// The logical length of the file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The space allocated to the file, which will be freed once every
+    /// process holding it closes it.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The IDs of the processes holding the file open, in ascending order.
+    pub fn pids(&self) -> &[u32] {
+        &self.pids
+    }
+}
+
+/// The files deleted while still open on a volume, from
+/// [`deleted_open_files`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeletedFiles {
+    files: Vec<DeletedFile>,
+    inaccessible: usize,
+}
+
+impl DeletedFiles {
+    /// The files found, largest first.
+    pub fn files(&self) -> &[DeletedFile] {
+        &self.files
+    }
+
+    /// The space allocated to all the files found.
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(DeletedFile::size).sum()
+    }
+
+    /// The number of processes whose descriptors could not be read, such as
+    /// those of other users without `CAP_SYS_PTRACE`.  Files they hold open
+    /// are missing from the report.
+    pub fn inaccessible_processes(&self) -> usize {
+        self.inaccessible
+    }
+
+    /// The files found, largest first.
+    pub fn into_files(self) -> Vec<DeletedFile> {
+        self.files
+    }
+}
+
+/// Find the files on the volume holding `path` which have been deleted but
+/// are still held open by some process, and so still occupy space.
+///
+/// Each file is reported once, however many descriptors refer to it.  Only
+/// the descriptors of processes the caller may inspect are seen, normally
+/// those of the same user, unless running as root.
+pub fn deleted_open_files<P: AsRef<Path>>(path: P) -> Result<DeletedFiles> {
+    let path = path.as_ref();
+    let device = path.metadata().map_err(|e| Error::from_io(path, e))?.dev();
+    scan("/proc".as_ref(), device).map_err(|e| Error::from_io("/proc", e))
+}
+
+fn scan(proc: &Path, device: u64) -> io::Result<DeletedFiles> {
+    let mut files = BTreeMap::new();
+    let mut inaccessible = 0;
+
+    for entry in proc.read_dir()? {
+        let entry = entry?;
+        let pid = match entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+
+        let fds = match entry.path().join("fd").read_dir() {
+            Ok(fds) => fds,
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                inaccessible += 1;
+                continue;
+            }
+            // The process has exited since /proc was listed.
+            Err(_) => continue,
+        };
+
+        for fd in fds.filter_map(|fd| fd.ok()) {
+            let fd = fd.path();
+            let target = match fd.read_link() {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            let original = match target.to_str().and_then(|s| s.strip_suffix(DELETED_SUFFIX)) {
+                Some(original) if original.starts_with('/') => original,
+                _ => continue,
+            };
+
+            // Following the descriptor's link reaches the open file itself.
+            // A link count of zero tells a deleted file from one merely
+            // named with the suffix.
+            let metadata = match fd.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.nlink() != 0 || metadata.dev() != device || !metadata.is_file() {
+                continue;
+            }
+
+            let file = files
+                .entry((metadata.dev(), metadata.ino()))
+                .or_insert_with(|| DeletedFile {
+                    path: PathBuf::from(original),
+                    device: metadata.dev(),
+                    inode: metadata.ino(),
+                    len: metadata.len(),
+                    size: metadata.blocks().saturating_mul(crate::imp::BLOCK_UNIT),
+                    pids: Vec::new(),
+                });
+            if file.pids.last() != Some(&pid) {
+                file.pids.push(pid);
+            }
+        }
+    }
+
+    let mut files: Vec<DeletedFile> = files.into_values().collect();
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    for file in &mut files {
+        file.pids.sort_unstable();
+    }
+
+    Ok(DeletedFiles {
+        files,
+        inaccessible,
+    })
+}
+
+#[test]
+fn deleted_open_files_are_found() {
+    use std::io::Write;
+
+    let dir = crate::test_dir("deleted_open_files");
+    let path = dir.join("rotated.log");
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.write_all(&[1; 64 << 10]).unwrap();
+    file.sync_all().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let deleted = deleted_open_files(&dir).unwrap();
+    let found = deleted
+        .files()
+        .iter()
+        .find(|found| found.path() == path)
+        .expect("deleted file is found");
+
+    assert_eq!(found.len(), 64 << 10);
+    assert!(found.pids().contains(&std::process::id()));
+    assert!(deleted.total_bytes() >= found.size());
+
+    drop(file);
+    let deleted = deleted_open_files(&dir).unwrap();
+    assert!(deleted.files().iter().all(|found| found.path() != path));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,241 @@
+//! Formatting sizes as GNU `du` prints them.
+
+use std::io::{self, Write};
+
+use crate::{DiskUsage, DiskUsageTree, Node};
+
+/// Formats sizes and trees in the output format of GNU `du`, for scripts which
+/// already parse it, in the style of `std::fs::OpenOptions`.
+///
+/// By default sizes are 1 KiB blocks of disk usage, rounded up, as GNU `du`
+/// prints without options.
+///
+/// ```rust
+/// use filesize::DuFormat;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let tree = filesize::Scanner::new("src").tree()?;
+/// DuFormat::new()
+///     .human_readable(true)
+///     .summarize(true)
+///     .write_tree(&tree, std::io::stdout())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuFormat {
+    human_readable: bool,
+    apparent_size: bool,
+    all: bool,
+    max_depth: Option<usize>,
+    block_size: u64,
+}
+
+impl Default for DuFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DuFormat {
+    /// Create a new format, matching `du` without options.
+    pub fn new() -> Self {
+        Self {
+            human_readable: false,
+            apparent_size: false,
+            all: false,
+            max_depth: None,
+            block_size: 1024,
+        }
+    }
+
+    /// Print sizes in powers of 1024 with a unit suffix, rounded up, like
+    /// `du -h`.
+    pub fn human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    /// Print logical lengths rather than disk usage, like `du --apparent-size`.
+    pub fn apparent_size(mut self, apparent_size: bool) -> Self {
+        self.apparent_size = apparent_size;
+        self
+    }
+
+    /// Print files as well as directories, like `du -a`.
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    /// Print entries at most `depth` levels below each root, like `du -d`.
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Print only a total for each root, like `du -s`.  This is the same as a
+    /// maximum depth of 0.
+    pub fn summarize(self, summarize: bool) -> Self {
+        self.max_depth(if summarize { Some(0) } else { None })
+    }
+
+    /// Print sizes in blocks of `unit` bytes, like `du -B`.  Defaults to 1024.
+    ///
+    /// In trees each file is rounded up to a whole block before being added
+    /// to its directory, as POSIX requires, so totals may exceed the total size
+    /// divided by `unit`.  Human readable sizes are not affected.
+    ///
+    /// ```rust
+    /// use filesize::DuFormat;
+    ///
+    /// assert_eq!(DuFormat::new().block_size(512).format_size(4097), "9");
+    /// ```
+    pub fn block_size(mut self, unit: u64) -> Self {
+        self.block_size = unit.max(1);
+        self
+    }
+
+    /// The size of `usage` this format prints.
+    pub fn size_of(&self, usage: &DiskUsage) -> u64 {
+        if self.apparent_size {
+            usage.logical()
+        } else {
+            usage.physical()
+        }
+    }
+
+    /// Format a size of `bytes` as `du` would print it.
+    ///
+    /// ```rust
+    /// use filesize::DuFormat;
+    ///
+    /// assert_eq!(DuFormat::new().format_size(4097), "5");
+    /// assert_eq!(DuFormat::new().human_readable(true).format_size(4097), "4.1K");
+    /// assert_eq!(DuFormat::new().human_readable(true).format_size(100), "100");
+    /// ```
+    pub fn format_size(&self, bytes: u64) -> String {
+        if !self.human_readable {
+            return bytes.div_ceil(self.block_size).to_string();
+        }
+
+        const UNITS: &[&str] = &["K", "M", "G", "T", "P", "E"];
+
+        if bytes < 1024 {
+            return bytes.to_string();
+        }
+
+        let mut value = bytes as f64 / 1024.0;
+        let mut unit = 0;
+        loop {
+            // du rounds up, to one decimal place below 10.
+            let rounded = if value < 10.0 {
+                (value * 10.0).ceil() / 10.0
+            } else {
+                value.ceil()
+            };
+
+            if rounded < 1024.0 || unit == UNITS.len() - 1 {
+                return if rounded < 10.0 {
+                    format!("{:.1}{}", rounded, UNITS[unit])
+                } else {
+                    format!("{:.0}{}", rounded, UNITS[unit])
+                };
+            }
+
+            value /= 1024.0;
+            unit += 1;
+        }
+    }
+
+    /// Write `tree` as `du` prints a scan, each directory after its contents,
+    /// with a tab between its size and its path.
+    pub fn write_tree<W: Write>(&self, tree: &DiskUsageTree, mut writer: W) -> io::Result<()> {
+        for root in tree.roots() {
+            self.write_node(&mut writer, root, 0)?;
+        }
+        writer.flush()
+    }
+
+    /// Write `node` and everything below it, returning its size in blocks.
+    fn write_node<W: Write>(
+        &self,
+        writer: &mut W,
+        node: Node<'_>,
+        depth: usize,
+    ) -> io::Result<u64> {
+        let info = node.size_info();
+        let own = if self.apparent_size {
+            info.logical()
+        } else {
+            info.physical()
+        };
+
+        let mut blocks = own.div_ceil(self.block_size);
+        for child in node.children() {
+            blocks = blocks.saturating_add(self.write_node(writer, child, depth + 1)?);
+        }
+
+        let shown = node.is_dir() || self.all || depth == 0;
+        if shown && self.max_depth.is_none_or(|max| depth <= max) {
+            let size = if self.human_readable {
+                self.format_size(self.size_of(&node.usage()))
+            } else {
+                blocks.to_string()
+            };
+            writeln!(writer, "{}\t{}", size, node.path().display())?;
+        }
+        Ok(blocks)
+    }
+}
+
+#[test]
+fn sizes_are_formatted_like_du() {
+    let du = DuFormat::new();
+    assert_eq!(du.format_size(0), "0");
+    assert_eq!(du.format_size(1), "1");
+    assert_eq!(du.format_size(4096), "4");
+
+    let du = du.human_readable(true);
+    assert_eq!(du.format_size(0), "0");
+    assert_eq!(du.format_size(1023), "1023");
+    assert_eq!(du.format_size(4096), "4.0K");
+    assert_eq!(du.format_size(10 * 1024 - 1), "10K");
+    assert_eq!(du.format_size(1024 * 1024 - 1), "1.0M");
+    assert_eq!(du.format_size(150 * 1024 * 1024 + 1), "151M");
+}
+
+#[test]
+fn trees_are_written_like_du() {
+    let dir = crate::test_dir("du");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/file"), vec![1u8; 3000]).unwrap();
+    std::fs::write(dir.join("file"), b"!").unwrap();
+
+    let tree = crate::Scanner::new(&dir).tree().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let write = |du: DuFormat| {
+        let mut out = vec![];
+        du.write_tree(&tree, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let du = DuFormat::new().apparent_size(true);
+    assert_eq!(
+        write(du),
+        format!("3\t{}\n4\t{}\n", dir.join("sub").display(), dir.display())
+    );
+    assert_eq!(write(du.summarize(true)), format!("4\t{}\n", dir.display()));
+    assert_eq!(
+        write(du.summarize(true).block_size(4096)),
+        format!("2\t{}\n", dir.display())
+    );
+
+    let all = write(du.all(true));
+    let lines: Vec<&str> = all.lines().collect();
+    let file = format!("3\t{}", dir.join("sub/file").display());
+    let sub = format!("3\t{}", dir.join("sub").display());
+    assert_eq!(lines.len(), 4);
+    assert!(lines.iter().position(|l| *l == file) < lines.iter().position(|l| *l == sub));
+}
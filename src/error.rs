@@ -0,0 +1,130 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A specialized `Result` type for `filesize` operations.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error encountered while finding the size of a file, carrying the path
+/// of the file involved.
+///
+/// This converts into a `std::io::Error`, so it can be propagated with `?`
+/// from functions returning `std::io::Result`.
+#[derive(Debug)]
+pub enum Error {
+    /// The file does not exist.
+    NotFound { path: PathBuf, source: io::Error },
+    /// The file exists, but we were not allowed to query it.
+    PermissionDenied { path: PathBuf, source: io::Error },
+    /// The file is of a type we do not report sizes for.
+    UnsupportedFileType { path: PathBuf },
+    /// The platform or filesystem does not support the query.
+    PlatformLimitation { path: PathBuf, source: io::Error },
+    /// Any other I/O error.
+    Io { path: PathBuf, source: io::Error },
+    /// An I/O error querying a file with no path to report, such as an open
+    /// file or its metadata.
+    Unnamed { source: io::Error },
+}
+
+impl Error {
+    /// Classify an `io::Error` encountered while querying `path`.
+    pub(crate) fn from_io<P: AsRef<Path>>(path: P, source: io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+
+        match source.kind() {
+            io::ErrorKind::NotFound => Error::NotFound { path, source },
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied { path, source },
+            io::ErrorKind::Unsupported => Error::PlatformLimitation { path, source },
+            _ => Error::Io { path, source },
+        }
+    }
+
+    /// Wrap an `io::Error` encountered while querying a file by other means
+    /// than its path.
+    pub(crate) fn unnamed(source: io::Error) -> Self {
+        Error::Unnamed { source }
+    }
+
+    /// The path of the file involved, which is empty for
+    /// [`Unnamed`](Error::Unnamed) errors.
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::NotFound { path, .. }
+            | Error::PermissionDenied { path, .. }
+            | Error::UnsupportedFileType { path }
+            | Error::PlatformLimitation { path, .. }
+            | Error::Io { path, .. } => path,
+            Error::Unnamed { .. } => Path::new(""),
+        }
+    }
+
+    /// The kind of this error, as it would be classified by `std::io`.
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::NotFound { source, .. }
+            | Error::PermissionDenied { source, .. }
+            | Error::PlatformLimitation { source, .. }
+            | Error::Io { source, .. }
+            | Error::Unnamed { source } => source.kind(),
+            Error::UnsupportedFileType { .. } => io::ErrorKind::InvalidInput,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound { path, source }
+            | Error::PermissionDenied { path, source }
+            | Error::PlatformLimitation { path, source }
+            | Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::UnsupportedFileType { path } => {
+                write!(f, "{}: unsupported file type", path.display())
+            }
+            Error::Unnamed { source } => source.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NotFound { source, .. }
+            | Error::PermissionDenied { source, .. }
+            | Error::PlatformLimitation { source, .. }
+            | Error::Io { source, .. }
+            | Error::Unnamed { source } => Some(source),
+            Error::UnsupportedFileType { .. } => None,
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        io::Error::new(err.kind(), err)
+    }
+}
+
+#[test]
+fn errors_carry_their_path() {
+    let err = crate::file_real_size("does/not/exist").unwrap_err();
+
+    assert!(matches!(err, Error::NotFound { .. }));
+    assert_eq!(err.path(), Path::new("does/not/exist"));
+    assert!(io::Error::from(err)
+        .to_string()
+        .starts_with("does/not/exist: "));
+}
+
+#[test]
+fn unnamed_errors_have_no_path() {
+    let err = Error::unnamed(io::Error::from(io::ErrorKind::NotFound));
+
+    assert_eq!(err.path(), Path::new(""));
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(
+        err.to_string(),
+        io::Error::from(io::ErrorKind::NotFound).to_string()
+    );
+}
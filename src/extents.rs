@@ -0,0 +1,101 @@
+//! Mapping the extents of files with the `FS_IOC_FIEMAP` ioctl.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+const FIEMAP_EXTENT_LAST: u32 = 0x0001;
+pub const FIEMAP_EXTENT_UNKNOWN: u32 = 0x0002;
+const EXTENTS_PER_CALL: usize = 64;
+
+/// A range of a file mapped to disk, from struct fiemap_extent.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Extent {
+    pub logical: u64,
+    pub physical: u64,
+    pub length: u64,
+    reserved64: [u64; 2],
+    pub flags: u32,
+    reserved: [u32; 3],
+}
+
+#[repr(C)]
+struct Fiemap {
+    start: u64,
+    length: u64,
+    flags: u32,
+    mapped_extents: u32,
+    extent_count: u32,
+    reserved: u32,
+    extents: [Extent; EXTENTS_PER_CALL],
+}
+
+// The ioctl is defined against the header alone, without the extents.
+const FS_IOC_FIEMAP: libc::Ioctl = libc::_IOWR::<[u64; 4]>(b'f' as _, 11);
+
+/// Open the regular file at `path` for mapping, without following symlinks.
+pub fn open(path: &Path) -> io::Result<File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)
+}
+
+/// Call `f` with each extent of `file` overlapping the range from `start`
+/// to the end of the file and beyond, in order.
+pub fn for_each<F>(file: &File, start: u64, mut f: F) -> io::Result<()>
+where
+    F: FnMut(&Extent),
+{
+    let mut map = Fiemap {
+        start,
+        length: u64::MAX - start,
+        flags: 0,
+        mapped_extents: 0,
+        extent_count: EXTENTS_PER_CALL as u32,
+        reserved: 0,
+        extents: [Extent::default(); EXTENTS_PER_CALL],
+    };
+
+    loop {
+        if unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP, &mut map) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mapped = &map.extents[..(map.mapped_extents as usize).min(EXTENTS_PER_CALL)];
+        let last = match mapped.last() {
+            Some(last) => *last,
+            None => return Ok(()),
+        };
+        mapped.iter().for_each(&mut f);
+
+        let next = last.logical.saturating_add(last.length);
+        if last.flags & FIEMAP_EXTENT_LAST != 0 || next <= map.start {
+            return Ok(());
+        }
+        map.length = map.length.saturating_sub(next - map.start);
+        map.start = next;
+        map.mapped_extents = 0;
+    }
+}
+
+/// The number of bytes mapped from `start` onwards in the file at `path`.
+pub fn mapped_from(path: &Path, start: u64) -> io::Result<u64> {
+    let file = open(path)?;
+    let mut total = 0u64;
+    for_each(&file, start, |extent| {
+        let from = extent.logical.max(start);
+        let end = extent.logical.saturating_add(extent.length);
+        total = total.saturating_add(end.saturating_sub(from));
+    })?;
+    Ok(total)
+}
+
+#[test]
+fn fiemap_is_laid_out() {
+    assert_eq!(std::mem::size_of::<Extent>(), 56);
+    assert_eq!(std::mem::size_of::<Fiemap>(), 32 + 56 * EXTENTS_PER_CALL);
+}
@@ -0,0 +1,347 @@
+use std::fs::Metadata;
+use std::path::Path;
+#[cfg(any(target_os = "android", test))]
+use std::path::{Component, PathBuf};
+
+use crate::{Error, Result};
+
+/// A broad classification of the filesystem a file resides on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FilesystemKind {
+    /// A filesystem not recognised as any of the below, normally one backed
+    /// by real storage.
+    Regular,
+    /// A pseudo-filesystem such as procfs, sysfs, or devfs, which exposes
+    /// kernel state as files that report zero or meaningless sizes.
+    Virtual,
+    /// A network filesystem such as NFS or SMB, whose block counts may be
+    /// stale, or fabricated by the server.
+    Network,
+    /// A filesystem implemented in userspace through FUSE, which may report
+    /// sizes however it likes.
+    Userspace,
+    /// A filesystem such as ZFS which only allocates space for data as it is
+    /// written back, seconds later, so recently written files may report
+    /// little or no space used.
+    Deferred,
+    /// A filesystem such as tmpfs or ramfs held in memory, whose files
+    /// consume RAM and swap rather than disk space.
+    Memory,
+    /// Android's emulated shared storage, such as `/storage/emulated/0`,
+    /// which sdcardfs or a FUSE daemon serves from the real storage beneath
+    /// it, and which may report block counts derived from file lengths rather
+    /// than the space allocated.
+    ///
+    /// Directories apps keep their own files in, `Android/data` and
+    /// `Android/obb`, are mounted directly from the real storage on Android 11
+    /// and later, so report accurate sizes.
+    Emulated,
+}
+
+impl FilesystemKind {
+    /// Whether sizes reported by this kind of filesystem can be taken at face
+    /// value.
+    ///
+    /// Sizes from network and deferred allocation filesystems may be made
+    /// fresher with [`SizeOptions::force_sync`](crate::SizeOptions::force_sync).
+    /// Memory filesystems report accurate sizes, though not of disk space.
+    /// Sizes from emulated storage can be found more accurately at the real
+    /// path beneath it, where permitted, from `emulated_storage_source`.
+    pub fn has_reliable_sizes(&self) -> bool {
+        matches!(self, FilesystemKind::Regular | FilesystemKind::Memory)
+    }
+}
+
+/// Classify the filesystem containing the file at the given `path`.
+///
+/// This is supported on Linux, Android, macOS, iOS, and the BSDs.  Windows
+/// detects network drives only, and other platforms always report
+/// `FilesystemKind::Regular`.
+///
+/// ```rust
+/// use filesize::filesystem_kind;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let kind = filesystem_kind("Cargo.toml")?;
+/// println!("{:?}, reliable sizes: {}", kind, kind.has_reliable_sizes());
+/// # Ok(())
+/// # }
+/// ```
+pub fn filesystem_kind<P: AsRef<Path>>(path: P) -> Result<FilesystemKind> {
+    let path = path.as_ref();
+    imp::filesystem_kind(path).map_err(|e| Error::from_io(path, e))
+}
+
+/// Find the path on Android's real storage that the file at `path` on
+/// emulated shared storage is served from, such as
+/// `/data/media/0/DCIM/photo.jpg` for `/sdcard/DCIM/photo.jpg`, or `None` if
+/// it isn't on emulated storage.  Symlinks are resolved.
+///
+/// Only the system, and tools running as root or through `adb shell`, may
+/// normally read this path, but sizes found there reflect the space actually
+/// allocated, where those of [`FilesystemKind::Emulated`] storage may not.
+#[cfg(target_os = "android")]
+pub fn emulated_storage_source<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    source_of(&path.as_ref().canonicalize().ok()?)
+}
+
+/// The real storage behind the canonical `path`, if it's under
+/// `/storage/emulated/<user>`.
+#[cfg(any(target_os = "android", test))]
+fn source_of(path: &Path) -> Option<PathBuf> {
+    let mut components = path.strip_prefix("/storage/emulated").ok()?.components();
+    let user = match components.next()? {
+        Component::Normal(user) if user.to_str()?.bytes().all(|b| b.is_ascii_digit()) => user,
+        _ => return None,
+    };
+    Some(
+        Path::new("/data/media")
+            .join(user)
+            .join(components.as_path()),
+    )
+}
+
+/// An identifier for the device holding a file, if the platform provides one.
+///
+/// Files with the same device are on the same filesystem.
+#[cfg(unix)]
+pub(crate) fn device_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn device_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Whether the regular file at `path` is held in memory, on a memory
+/// filesystem or as a memfd.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn is_memory_backed(path: &Path, metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    // Memory filesystems have anonymous device numbers, with a major number of
+    // zero, which rules out files on disks without a statfs().
+    metadata.is_file()
+        && libc::major(metadata.dev() as libc::dev_t) == 0
+        && imp::filesystem_kind(path).ok() == Some(FilesystemKind::Memory)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn is_memory_backed(_path: &Path, _metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use super::*;
+
+    // Filesystem magic numbers from linux/magic.h
+    const VIRTUAL: &[u32] = &[
+        0x0000_9fa0, // PROC_SUPER_MAGIC
+        0x6265_6572, // SYSFS_MAGIC
+        0x0000_1cd1, // DEVPTS_SUPER_MAGIC
+        0x6462_6720, // DEBUGFS_MAGIC
+        0x7472_6163, // TRACEFS_MAGIC
+        0x7363_6673, // SECURITYFS_MAGIC
+        0x0027_e0eb, // CGROUP_SUPER_MAGIC
+        0x6367_7270, // CGROUP2_SUPER_MAGIC
+        0x6165_676c, // PSTOREFS_MAGIC
+        0xcafe_4a11, // BPF_FS_MAGIC
+        0x6265_6570, // CONFIGFS_MAGIC
+        0xde5e_81e4, // EFIVARFS_MAGIC
+        0x6573_5543, // FUSE_CTL_SUPER_MAGIC
+        0x4249_4e4d, // BINFMTFS_MAGIC
+        0xf97c_ff8c, // SELINUX_MAGIC
+        0x1980_0202, // MQUEUE_MAGIC
+    ];
+
+    const NETWORK: &[u32] = &[
+        0x0000_6969, // NFS_SUPER_MAGIC
+        0x0000_517b, // SMB_SUPER_MAGIC
+        0xff53_4d42, // CIFS_SUPER_MAGIC
+        0xfe53_4d42, // SMB2_SUPER_MAGIC
+        0x0102_1997, // V9FS_MAGIC
+        0x00c3_6400, // CEPH_SUPER_MAGIC
+        0x7375_7245, // CODA_SUPER_MAGIC
+        0x6b41_4653, // AFS_FS_MAGIC
+        0x0000_564c, // NCP_SUPER_MAGIC
+        0x0bd0_0bd0, // LL_SUPER_MAGIC (Lustre)
+    ];
+
+    const MEMORY: &[u32] = &[
+        0x0102_1994, // TMPFS_MAGIC, also used by devtmpfs
+        0x8584_58f6, // RAMFS_MAGIC
+        0x9584_58f6, // HUGETLBFS_MAGIC
+    ];
+
+    const FUSE_SUPER_MAGIC: u32 = 0x6573_5546;
+    const SDCARDFS_SUPER_MAGIC: u32 = 0x5dca_2df5;
+    const ZFS_SUPER_MAGIC: u32 = 0x2fc1_2fc1;
+
+    pub fn filesystem_kind(path: &Path) -> std::io::Result<FilesystemKind> {
+        let cpath = crate::c_path(path)?;
+        let mut buf: libc::statfs = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::statfs(cpath.as_ptr(), &mut buf) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let magic = buf.f_type as u32;
+        if magic == SDCARDFS_SUPER_MAGIC || (magic == FUSE_SUPER_MAGIC && is_emulated(path)) {
+            Ok(FilesystemKind::Emulated)
+        } else if VIRTUAL.contains(&magic) {
+            Ok(FilesystemKind::Virtual)
+        } else if NETWORK.contains(&magic) {
+            Ok(FilesystemKind::Network)
+        } else if MEMORY.contains(&magic) {
+            Ok(FilesystemKind::Memory)
+        } else if magic == FUSE_SUPER_MAGIC {
+            Ok(FilesystemKind::Userspace)
+        } else if magic == ZFS_SUPER_MAGIC {
+            Ok(FilesystemKind::Deferred)
+        } else {
+            Ok(FilesystemKind::Regular)
+        }
+    }
+
+    /// Whether `path` is on Android's emulated storage, which Android 11 and
+    /// later serve through FUSE rather than sdcardfs.
+    #[cfg(target_os = "android")]
+    fn is_emulated(path: &Path) -> bool {
+        path.canonicalize()
+            .is_ok_and(|path| source_of(&path).is_some())
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn is_emulated(_path: &Path) -> bool {
+        false
+    }
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd"
+))]
+mod imp {
+    use super::*;
+
+    const VIRTUAL: &[&[u8]] = &[
+        b"devfs",
+        b"fdescfs",
+        b"kernfs",
+        b"linprocfs",
+        b"linsysfs",
+        b"mqueuefs",
+        b"procfs",
+        b"ptyfs",
+    ];
+
+    const NETWORK: &[&[u8]] = &[b"afpfs", b"cifs", b"nfs", b"smbfs", b"webdav"];
+
+    const MEMORY: &[&[u8]] = &[b"mfs", b"tmpfs"];
+
+    const USERSPACE: &[&[u8]] = &[b"fusefs", b"macfuse", b"osxfuse"];
+
+    pub fn filesystem_kind(path: &Path) -> std::io::Result<FilesystemKind> {
+        let path = crate::c_path(path)?;
+        let mut buf: libc::statfs = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::statfs(path.as_ptr(), &mut buf) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let name = unsafe { std::ffi::CStr::from_ptr(buf.f_fstypename.as_ptr()) };
+
+        let name = name.to_bytes();
+        if VIRTUAL.contains(&name) {
+            Ok(FilesystemKind::Virtual)
+        } else if NETWORK.contains(&name) {
+            Ok(FilesystemKind::Network)
+        } else if MEMORY.contains(&name) {
+            Ok(FilesystemKind::Memory)
+        } else if USERSPACE.iter().any(|fuse| name.starts_with(fuse)) {
+            // FreeBSD reports subtypes such as fusefs.sshfs.
+            Ok(FilesystemKind::Userspace)
+        } else if name == b"zfs" {
+            Ok(FilesystemKind::Deferred)
+        } else {
+            Ok(FilesystemKind::Regular)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+
+    use crate::win::{GetDriveTypeW, DRIVE_REMOTE};
+
+    pub fn filesystem_kind(path: &Path) -> std::io::Result<FilesystemKind> {
+        path.symlink_metadata()?;
+
+        let root = crate::verbatim::volume_root(path)?;
+
+        if unsafe { GetDriveTypeW(root.as_ptr()) } == DRIVE_REMOTE {
+            Ok(FilesystemKind::Network)
+        } else {
+            Ok(FilesystemKind::Regular)
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    windows
+)))]
+mod imp {
+    use super::*;
+
+    pub fn filesystem_kind(path: &Path) -> std::io::Result<FilesystemKind> {
+        path.symlink_metadata().map(|_| FilesystemKind::Regular)
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn procfs_is_virtual() {
+    assert_eq!(
+        filesystem_kind("/proc/self").unwrap(),
+        FilesystemKind::Virtual
+    );
+    // The checkout may be on any filesystem, but not a pseudo-filesystem.
+    assert_ne!(
+        filesystem_kind("Cargo.toml").unwrap(),
+        FilesystemKind::Virtual
+    );
+    assert!(!FilesystemKind::Virtual.has_reliable_sizes());
+    assert!(FilesystemKind::Regular.has_reliable_sizes());
+}
+
+#[test]
+fn emulated_storage_is_traced_to_its_source() {
+    assert_eq!(
+        source_of(Path::new("/storage/emulated/0/DCIM/photo.jpg")),
+        Some(PathBuf::from("/data/media/0/DCIM/photo.jpg"))
+    );
+    assert_eq!(
+        source_of(Path::new("/storage/emulated/10")),
+        Some(PathBuf::from("/data/media/10"))
+    );
+    assert_eq!(source_of(Path::new("/storage/emulated")), None);
+    assert_eq!(source_of(Path::new("/storage/emulated/obb/file")), None);
+    assert_eq!(source_of(Path::new("/storage/1234-ABCD/file")), None);
+    assert!(!FilesystemKind::Emulated.has_reliable_sizes());
+}
@@ -0,0 +1,80 @@
+//! Exporting a [`DiskUsageTree`] as folded stacks for flame graphs.
+
+use std::io::{self, Write};
+
+use crate::{DiskUsageTree, Node};
+
+impl DiskUsageTree {
+    /// Write the tree in the folded stack format read by `flamegraph.pl`,
+    /// inferno, and speedscope, giving a flame graph or icicle chart of where
+    /// space is used.
+    ///
+    /// Each line is a path with `;` between its components, followed by a
+    /// space and the number of bytes the file or directory itself uses on
+    /// disk, or its length with
+    /// [`Scanner::apparent_size`](crate::Scanner::apparent_size); the tools
+    /// add up the rest.  Entries using no space are left out, and `;` and line breaks
+    /// in names are replaced with `_`.
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// let tree = filesize::Scanner::new("/usr").tree()?;
+    /// let file = std::fs::File::create("usr.folded")?;
+    /// tree.write_folded(std::io::BufWriter::new(file))?;
+    /// // inferno-flamegraph --countname bytes usr.folded > usr.svg
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_folded<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut stack = String::new();
+        for root in self.roots() {
+            write_stacks(&mut writer, root, &mut stack)?;
+        }
+        writer.flush()
+    }
+}
+
+fn write_stacks<W: Write>(writer: &mut W, node: Node<'_>, stack: &mut String) -> io::Result<()> {
+    let len = stack.len();
+    if !stack.is_empty() {
+        stack.push(';');
+    }
+    stack.extend(node.name().to_string_lossy().chars().map(|c| match c {
+        ';' | '\n' | '\r' => '_',
+        c => c,
+    }));
+
+    let size = node.own_size();
+    if size > 0 {
+        writeln!(writer, "{} {}", stack, size)?;
+    }
+    for child in node.children() {
+        write_stacks(writer, child, stack)?;
+    }
+
+    stack.truncate(len);
+    Ok(())
+}
+
+#[test]
+fn trees_are_exported_as_folded_stacks() {
+    use std::io::Write;
+
+    let dir = crate::test_dir("folded");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::File::create(dir.join("sub/a;b"))
+        .and_then(|mut file| file.write_all(&[1u8; 8192]).and(file.sync_all()))
+        .unwrap();
+    std::fs::write(dir.join("empty"), b"").unwrap();
+
+    let tree = crate::Scanner::new(&dir).tree().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut out = vec![];
+    tree.write_folded(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    let size = tree.get(dir.join("sub")).unwrap().usage().physical();
+    assert!(out.contains(&format!("{};sub;a_b {}\n", dir.display(), size)));
+    assert!(!out.contains("empty"));
+}
@@ -0,0 +1,479 @@
+//! How fragmented the files of a tree are on disk.
+//!
+//! [`extent_map`] lists where each part of a file lies on disk,
+//! [`fragmentation`] counts the extents of a single file, and a
+//! [`FragmentationReport`] collects them for every file of a scan, so heavily
+//! fragmented large files can be found during the same walk that measures
+//! their size.
+//!
+//! ```rust
+//! use filesize::{FragmentationReport, Scanner};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let mut report = FragmentationReport::new(10).min_size(64 << 20);
+//! Scanner::new("src").collect_into(&mut report)?;
+//!
+//! for (path, fragmentation) in report.into_sorted_vec() {
+//!     println!("{:>6} {}", fragmentation.fragment_count(), path.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::{Collector, Entry, Error, Result};
+
+/// A run of a file stored contiguously on disk, from [`extent_map`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileExtent {
+    logical: u64,
+    physical: Option<u64>,
+    length: u64,
+}
+
+impl FileExtent {
+    /// The offset within the file the extent starts at.
+    pub fn logical(&self) -> u64 {
+        self.logical
+    }
+
+    /// The offset on the volume the extent starts at, or `None` where the
+    /// filesystem hasn't decided yet, as for data awaiting delayed
+    /// allocation.
+    pub fn physical(&self) -> Option<u64> {
+        self.physical
+    }
+
+    /// The length of the extent in bytes.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+}
+
+/// How the space of a file is laid out on disk, from [`fragmentation`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fragmentation {
+    extents: u64,
+    fragments: u64,
+    mapped: u64,
+}
+
+impl Fragmentation {
+    /// Tally `runs` of a file, in logical order, as their physical offset,
+    /// or `None` where it isn't known, and length in bytes.
+    fn from_runs<I: IntoIterator<Item = (Option<u64>, u64)>>(runs: I) -> Self {
+        let mut fragmentation = Self::default();
+        let mut next = None;
+        for (physical, length) in runs {
+            fragmentation.extents += 1;
+            fragmentation.mapped = fragmentation.mapped.saturating_add(length);
+            if physical.is_none() || physical != next {
+                fragmentation.fragments += 1;
+            }
+            next = physical.map(|physical| physical.saturating_add(length));
+        }
+        fragmentation
+    }
+
+    /// The number of extents the filesystem reports, as `filefrag` counts
+    /// them.
+    pub fn extent_count(&self) -> u64 {
+        self.extents
+    }
+
+    /// The number of physically contiguous runs the file is stored in.
+    ///
+    /// Filesystems split large files into extents of a maximum size even when
+    /// they're contiguous, such as 128 MiB on ext4, so extents continuing
+    /// where the last left off are counted together.
+    pub fn fragment_count(&self) -> u64 {
+        self.fragments
+    }
+
+    /// The number of bytes mapped to disk.
+    pub fn mapped_bytes(&self) -> u64 {
+        self.mapped
+    }
+
+    /// Whether the file is stored in more than one run.
+    pub fn is_fragmented(&self) -> bool {
+        self.fragments > 1
+    }
+
+    /// A simple measure of fragmentation: 0 for a file stored in one run, or
+    /// none, approaching 1 as it is split into more, as `1 - 1 / fragments`.
+    pub fn score(&self) -> f64 {
+        if self.fragments == 0 {
+            0.0
+        } else {
+            1.0 - 1.0 / self.fragments as f64
+        }
+    }
+}
+
+/// List the extents of the file at `path`, in order of their offset within
+/// it, for analysis of its physical layout.  Holes are left out, as are files
+/// small enough to be stored within the filesystem's metadata.  Symlinks are
+/// followed, and anything other than a regular file fails with
+/// [`Error::UnsupportedFileType`].
+///
+/// On Linux and Android the extents are read with `FS_IOC_FIEMAP`, and on
+/// Windows `FSCTL_GET_RETRIEVAL_POINTERS` gives the runs of clusters.
+/// Filesystems without extent maps, and other platforms, fail with
+/// [`Error::PlatformLimitation`].
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// if let Ok(extents) = filesize::extent_map("Cargo.toml") {
+///     for extent in extents {
+///         println!("{} bytes at {:?}", extent.length(), extent.physical());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn extent_map<P: AsRef<Path>>(path: P) -> Result<Vec<FileExtent>> {
+    let path = path.as_ref();
+    let metadata = path.metadata().map_err(|e| Error::from_io(path, e))?;
+    if !metadata.is_file() {
+        return Err(Error::UnsupportedFileType {
+            path: path.to_path_buf(),
+        });
+    }
+
+    File::open(path)
+        .and_then(|file| imp::file_extents(path, &file))
+        .map_err(|e| Error::from_io(path, e))
+}
+
+/// Find how fragmented the file at `path` is, from its [`extent_map`].
+/// Symlinks are followed, and anything other than a regular file fails with
+/// [`Error::UnsupportedFileType`].
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// match filesize::fragmentation("Cargo.toml") {
+///     Ok(fragmentation) => println!("{} extents", fragmentation.extent_count()),
+///     Err(filesize::Error::PlatformLimitation { .. }) => println!("extents unavailable"),
+///     Err(e) => return Err(e.into()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn fragmentation<P: AsRef<Path>>(path: P) -> Result<Fragmentation> {
+    let extents = extent_map(path)?;
+    Ok(Fragmentation::from_runs(
+        extents
+            .iter()
+            .map(|extent| (extent.physical, extent.length)),
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use super::*;
+
+    use crate::extents::FIEMAP_EXTENT_UNKNOWN;
+
+    pub fn file_extents(_path: &Path, file: &File) -> std::io::Result<Vec<FileExtent>> {
+        let mut extents = vec![];
+        crate::extents::for_each(file, 0, |extent| {
+            let known = extent.flags & FIEMAP_EXTENT_UNKNOWN == 0;
+            extents.push(FileExtent {
+                logical: extent.logical,
+                physical: Some(extent.physical).filter(|_| known),
+                length: extent.length,
+            });
+        })?;
+        Ok(extents)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+
+    use crate::win::{
+        DeviceIoControl, ERROR_HANDLE_EOF, ERROR_MORE_DATA, FSCTL_GET_RETRIEVAL_POINTERS,
+    };
+
+    const RUNS_PER_CALL: usize = 64;
+
+    /// A run of clusters, from RETRIEVAL_POINTERS_BUFFER.
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct Run {
+        next_vcn: i64,
+        lcn: i64,
+    }
+
+    #[repr(C)]
+    struct RetrievalPointers {
+        extent_count: u32,
+        starting_vcn: i64,
+        extents: [Run; RUNS_PER_CALL],
+    }
+
+    /// Runs of clusters which are holes, or the unused tail of a compression
+    /// unit, have no location on the volume.
+    const VIRTUAL_LCN: i64 = -1;
+
+    pub fn file_extents(path: &Path, file: &File) -> io::Result<Vec<FileExtent>> {
+        let cluster = crate::allocation_granularity(path)
+            .map_err(io::Error::from)?
+            .block_size();
+        let mut extents = vec![];
+        let mut starting_vcn: i64 = 0;
+        let mut buf = RetrievalPointers {
+            extent_count: 0,
+            starting_vcn: 0,
+            extents: [Run::default(); RUNS_PER_CALL],
+        };
+
+        loop {
+            let mut returned: u32 = 0;
+            let ok = unsafe {
+                DeviceIoControl(
+                    file.as_raw_handle() as _,
+                    FSCTL_GET_RETRIEVAL_POINTERS,
+                    &mut starting_vcn as *mut _ as *mut _,
+                    std::mem::size_of::<i64>() as u32,
+                    &mut buf as *mut _ as *mut _,
+                    std::mem::size_of::<RetrievalPointers>() as u32,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                )
+            };
+
+            // Files stored within the MFT have no clusters at all.
+            let more = if ok == 0 {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(code) if code == ERROR_MORE_DATA as i32 => true,
+                    Some(code) if code == ERROR_HANDLE_EOF as i32 => return Ok(extents),
+                    _ => return Err(err),
+                }
+            } else {
+                false
+            };
+
+            let count = (buf.extent_count as usize).min(RUNS_PER_CALL);
+            let mut vcn = buf.starting_vcn;
+            for run in &buf.extents[..count] {
+                if run.lcn != VIRTUAL_LCN {
+                    extents.push(FileExtent {
+                        logical: vcn as u64 * cluster,
+                        physical: Some(run.lcn as u64 * cluster),
+                        length: (run.next_vcn - vcn) as u64 * cluster,
+                    });
+                }
+                vcn = run.next_vcn;
+            }
+
+            if !more || count == 0 {
+                return Ok(extents);
+            }
+            starting_vcn = vcn;
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+mod imp {
+    use super::*;
+
+    pub fn file_extents(_path: &Path, _file: &File) -> std::io::Result<Vec<FileExtent>> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "extent maps are not available on this platform",
+        ))
+    }
+}
+
+/// A collector of the fragmentation of the files of a scan, keeping the `k`
+/// most fragmented.
+///
+/// Directories and other non-files are disregarded, as are files which
+/// can't be mapped, though they are counted by
+/// [`FragmentationReport::unmapped`].
+#[derive(Debug, Clone)]
+pub struct FragmentationReport {
+    k: usize,
+    min_size: u64,
+    files: u64,
+    extents: u64,
+    fragmented: u64,
+    unmapped: u64,
+    heap: BinaryHeap<Reverse<ByFragments>>,
+}
+
+impl FragmentationReport {
+    /// Create a collector keeping the `k` most fragmented files.
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            min_size: 0,
+            files: 0,
+            extents: 0,
+            fragmented: 0,
+            unmapped: 0,
+            heap: BinaryHeap::with_capacity(k.saturating_add(1).min(1024)),
+        }
+    }
+
+    /// Only map files of at least `bytes`, by [`Entry::size`], as mapping
+    /// costs a query of its own per file.  Defaults to 0.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// The number of files mapped.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The total number of extents of the files mapped.
+    pub fn extents(&self) -> u64 {
+        self.extents
+    }
+
+    /// The number of files mapped which are stored in more than one run.
+    pub fn fragmented_files(&self) -> u64 {
+        self.fragmented
+    }
+
+    /// The number of files which couldn't be mapped, as on filesystems
+    /// without extent maps.
+    pub fn unmapped(&self) -> u64 {
+        self.unmapped
+    }
+
+    /// The most fragmented files held, by [`Fragmentation::fragment_count`],
+    /// most fragmented first.
+    pub fn into_sorted_vec(self) -> Vec<(PathBuf, Fragmentation)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(ByFragments(path, fragmentation))| (path, fragmentation))
+            .collect()
+    }
+}
+
+impl Collector for FragmentationReport {
+    fn add(&mut self, entry: &Entry) {
+        if !entry.file_type().is_file() || entry.size() < self.min_size {
+            return;
+        }
+
+        let fragmentation = match fragmentation(entry.path()) {
+            Ok(fragmentation) => fragmentation,
+            Err(_) => {
+                self.unmapped += 1;
+                return;
+            }
+        };
+        self.files += 1;
+        self.extents += fragmentation.extents;
+        self.fragmented += u64::from(fragmentation.is_fragmented());
+
+        if self.k == 0 {
+            return;
+        }
+        if self.heap.len() == self.k {
+            match self.heap.peek() {
+                Some(Reverse(least)) if least.1.fragments < fragmentation.fragments => {
+                    self.heap.pop();
+                }
+                _ => return,
+            }
+        }
+        self.heap.push(Reverse(ByFragments(
+            entry.path().to_path_buf(),
+            fragmentation,
+        )));
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ByFragments(PathBuf, Fragmentation);
+
+impl PartialEq for ByFragments {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByFragments {}
+
+impl PartialOrd for ByFragments {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ByFragments {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.1
+            .fragments
+            .cmp(&other.1.fragments)
+            .then_with(|| other.0.cmp(&self.0))
+    }
+}
+
+#[test]
+fn runs_are_tallied() {
+    let fragmentation = Fragmentation::from_runs(vec![
+        (Some(0), 4096),
+        (Some(4096), 4096),
+        (Some(1 << 20), 8192),
+        (None, 4096),
+    ]);
+
+    assert_eq!(fragmentation.extent_count(), 4);
+    assert_eq!(fragmentation.fragment_count(), 3);
+    assert_eq!(fragmentation.mapped_bytes(), 20480);
+    assert!(fragmentation.is_fragmented());
+    assert!((fragmentation.score() - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(Fragmentation::default().score(), 0.0);
+    assert!(!Fragmentation::from_runs(vec![(Some(0), 1)]).is_fragmented());
+}
+
+#[test]
+fn scans_report_fragmentation() {
+    let dir = crate::test_dir("fragmentation");
+    std::fs::write(dir.join("file"), vec![1u8; 64 << 10]).unwrap();
+    File::open(dir.join("file")).unwrap().sync_all().unwrap();
+    std::fs::write(dir.join("small"), b"!").unwrap();
+
+    let single = fragmentation(dir.join("file"));
+    let extents = extent_map(dir.join("file"));
+    let mut report = FragmentationReport::new(1).min_size(32 << 10);
+    crate::Scanner::new(&dir).collect_into(&mut report).unwrap();
+    let directory = fragmentation(&dir);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(matches!(directory, Err(Error::UnsupportedFileType { .. })));
+    match single {
+        Ok(single) => {
+            let extents = extents.unwrap();
+            assert!(single.extent_count() >= 1);
+            assert_eq!(extents.len() as u64, single.extent_count());
+            assert_eq!(extents[0].logical(), 0);
+            assert!(extents.iter().map(FileExtent::length).sum::<u64>() >= 64 << 10);
+            assert_eq!(report.files(), 1);
+            assert_eq!(report.extents(), single.extent_count());
+            let most = report.into_sorted_vec();
+            assert_eq!(most, vec![(dir.join("file"), single)]);
+        }
+        Err(Error::PlatformLimitation { .. }) => assert_eq!(report.unmapped(), 1),
+        Err(e) => panic!("{}", e),
+    }
+}
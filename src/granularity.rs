@@ -0,0 +1,295 @@
+use std::path::Path;
+
+use crate::{Error, Result};
+
+/// The units in which a filesystem and the disk beneath it allocate space,
+/// from [`allocation_granularity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Granularity {
+    block_size: u64,
+    io_size: Option<u64>,
+    logical_sector_size: Option<u64>,
+    physical_sector_size: Option<u64>,
+}
+
+impl Granularity {
+    /// The unit in which the filesystem allocates space to files: the
+    /// fragment size from `statvfs()` on Unix, and the cluster size on
+    /// Windows, such as 4 KiB for NTFS or 64 KiB for some ReFS volumes.
+    ///
+    /// A file of any non-zero length normally occupies a multiple of this,
+    /// unless the filesystem packs small files or tails together.
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// The preferred size for I/O to the file, from `st_blksize` on Unix.
+    /// This is often, but not always, the same as the block size.
+    pub fn io_size(&self) -> Option<u64> {
+        self.io_size
+    }
+
+    /// The sector size the disk accepts I/O in, if known.  This is 512 bytes
+    /// on most disks, including 512e disks emulating 512-byte sectors over
+    /// larger physical ones, and 4096 on 4Kn disks.
+    pub fn logical_sector_size(&self) -> Option<u64> {
+        self.logical_sector_size
+    }
+
+    /// The sector size the disk actually writes, if known, which is 4096
+    /// bytes on most modern disks.
+    pub fn physical_sector_size(&self) -> Option<u64> {
+        self.physical_sector_size
+    }
+}
+
+/// Find the allocation granularity of the filesystem and disk holding the
+/// file at the given `path`.  Symlinks are followed.
+///
+/// Sizes from this crate are always in bytes, so these units are not needed
+/// to interpret them; the 512-byte blocks Unix counts in are a fixed unit,
+/// whatever the sector size of the disk.  They are useful to explain them,
+/// or to estimate the allocation of files which don't exist yet.
+///
+/// Sector sizes are found on Linux, Android, and Windows.  Other platforms
+/// without `statvfs()` fail with [`Error::PlatformLimitation`].
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let granularity = filesize::allocation_granularity("Cargo.toml")?;
+/// assert!(granularity.block_size() >= 512);
+/// # Ok(())
+/// # }
+/// ```
+pub fn allocation_granularity<P: AsRef<Path>>(path: P) -> Result<Granularity> {
+    let path = path.as_ref();
+    imp::allocation_granularity(path).map_err(|e| Error::from_io(path, e))
+}
+
+/// The space allocated to a file, in the units its platform counts it in,
+/// from [`raw_allocation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawAllocation {
+    blocks: u64,
+    block_unit: u64,
+}
+
+impl RawAllocation {
+    /// The number of units allocated: `st_blocks` on Unix, and clusters on
+    /// Windows.
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// The size of each unit in bytes: 512 on every Unix supported, whatever
+    /// the block size of the filesystem, and the cluster size on Windows.
+    pub fn block_unit(&self) -> u64 {
+        self.block_unit
+    }
+
+    /// The space allocated in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.blocks.saturating_mul(self.block_unit)
+    }
+}
+
+/// Find the space allocated to the file at the given `path` as a count of
+/// blocks and the size of each, for tools matching `du` or needing POSIX
+/// `st_blocks` semantics.  Symlinks are not followed.
+///
+/// On Unix this is `st_blocks`, in 512-byte units.  On Windows it's the size
+/// on disk in clusters, rounded up.  Other platforms fail with
+/// [`Error::PlatformLimitation`].
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let allocation = filesize::raw_allocation("Cargo.toml")?;
+/// println!("{} blocks of {} bytes", allocation.blocks(), allocation.block_unit());
+/// # Ok(())
+/// # }
+/// ```
+pub fn raw_allocation<P: AsRef<Path>>(path: P) -> Result<RawAllocation> {
+    let path = path.as_ref();
+    imp::raw_allocation(path).map_err(|e| Error::from_io(path, e))
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::*;
+
+    use std::os::unix::fs::MetadataExt;
+
+    pub fn raw_allocation(path: &Path) -> std::io::Result<RawAllocation> {
+        Ok(RawAllocation {
+            blocks: path.symlink_metadata()?.blocks(),
+            block_unit: crate::imp::BLOCK_UNIT,
+        })
+    }
+
+    pub fn allocation_granularity(path: &Path) -> std::io::Result<Granularity> {
+        let metadata = path.metadata()?;
+        let cpath = crate::c_path(path)?;
+        let mut buf: libc::statvfs = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::statvfs(cpath.as_ptr(), &mut buf) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let block_size = match buf.f_frsize as u64 {
+            0 => buf.f_bsize as u64,
+            frsize => frsize,
+        };
+        let (logical_sector_size, physical_sector_size) = sector_sizes(metadata.dev());
+
+        Ok(Granularity {
+            block_size: block_size.max(1),
+            io_size: Some(metadata.blksize()),
+            logical_sector_size,
+            physical_sector_size,
+        })
+    }
+
+    /// Read the sector sizes of the block device `dev` from sysfs, where only
+    /// whole disks have a `queue` directory, not their partitions.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn sector_sizes(dev: u64) -> (Option<u64>, Option<u64>) {
+        let dev = format!(
+            "/sys/dev/block/{}:{}",
+            libc::major(dev as libc::dev_t),
+            libc::minor(dev as libc::dev_t)
+        );
+        let read = |name: &str| {
+            ["queue", "../queue"].iter().find_map(|queue| {
+                std::fs::read_to_string(format!("{}/{}/{}", dev, queue, name))
+                    .ok()
+                    .and_then(|size| size.trim().parse().ok())
+            })
+        };
+        (read("logical_block_size"), read("physical_block_size"))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn sector_sizes(_dev: u64) -> (Option<u64>, Option<u64>) {
+        (None, None)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+
+    use crate::win::{
+        FileStorageInfo, GetDiskFreeSpaceW, GetFileInformationByHandleEx,
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_STORAGE_INFO,
+    };
+
+    pub fn raw_allocation(path: &Path) -> std::io::Result<RawAllocation> {
+        let size = crate::imp::file_real_size(path)?;
+        let cluster = allocation_granularity(path)?.block_size;
+        Ok(RawAllocation {
+            blocks: size.div_ceil(cluster),
+            block_unit: cluster,
+        })
+    }
+
+    pub fn allocation_granularity(path: &Path) -> std::io::Result<Granularity> {
+        let file = std::fs::OpenOptions::new()
+            .access_mode(0)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)?;
+
+        let root = crate::verbatim::volume_root(path)?;
+
+        let (mut sectors_per_cluster, mut bytes_per_sector, mut free, mut total) = (0, 0, 0, 0);
+        let ok = unsafe {
+            GetDiskFreeSpaceW(
+                root.as_ptr(),
+                &mut sectors_per_cluster,
+                &mut bytes_per_sector,
+                &mut free,
+                &mut total,
+            )
+        };
+
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        // Storage information is unavailable before Windows 8, and on some
+        // network and third-party filesystems.
+        let mut info: FILE_STORAGE_INFO = unsafe { std::mem::zeroed() };
+        let ok = unsafe {
+            GetFileInformationByHandleEx(
+                file.as_raw_handle() as _,
+                FileStorageInfo,
+                &mut info as *mut _ as *mut _,
+                std::mem::size_of::<FILE_STORAGE_INFO>() as u32,
+            )
+        };
+        let (logical, physical) = if ok == 0 {
+            (Some(u64::from(bytes_per_sector)), None)
+        } else {
+            (
+                Some(u64::from(info.LogicalBytesPerSector)),
+                Some(u64::from(info.PhysicalBytesPerSectorForPerformance)),
+            )
+        };
+
+        Ok(Granularity {
+            block_size: (u64::from(sectors_per_cluster) * u64::from(bytes_per_sector)).max(1),
+            io_size: None,
+            logical_sector_size: logical,
+            physical_sector_size: physical,
+        })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use super::*;
+
+    pub fn allocation_granularity(path: &Path) -> std::io::Result<Granularity> {
+        path.metadata()?;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "allocation granularity is not available on this platform",
+        ))
+    }
+
+    pub fn raw_allocation(path: &Path) -> std::io::Result<RawAllocation> {
+        path.symlink_metadata()?;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "block counts are not available on this platform",
+        ))
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn granularity_is_found() {
+    let granularity = allocation_granularity("Cargo.toml").unwrap();
+    assert!(granularity.block_size() >= 512);
+    assert!(granularity.io_size().is_some());
+    if let Some(sector) = granularity.logical_sector_size() {
+        assert!(sector.is_power_of_two());
+    }
+    assert!(allocation_granularity("does-not-exist").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn raw_allocation_is_found() {
+    use crate::PathExt;
+
+    let allocation = raw_allocation("Cargo.toml").unwrap();
+    assert_eq!(allocation.block_unit(), 512);
+    assert_eq!(
+        allocation.bytes(),
+        std::path::Path::new("Cargo.toml").size_on_disk().unwrap()
+    );
+    assert!(raw_allocation("does-not-exist").is_err());
+}
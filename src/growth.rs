@@ -0,0 +1,278 @@
+//! Tracking how directories grow over a series of snapshots.
+//!
+//! A [`UsageSnapshot`] records the size of every directory in a
+//! [`DiskUsageTree`] at a point in time.  With the `serde` feature these can
+//! be saved after each scan, and a [`GrowthReport`] over a series of them
+//! gives the rate each directory is growing at, for capacity planning.
+//!
+//! ```rust
+//! use std::time::{Duration, SystemTime};
+//! use filesize::{GrowthReport, Scanner};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let last_week = Scanner::new("src")
+//!     .tree()?
+//!     .snapshot()
+//!     .taken_at(SystemTime::now() - Duration::from_secs(7 * 86400));
+//! let today = Scanner::new("src").tree()?.snapshot();
+//!
+//! let report = GrowthReport::new(&[last_week, today]);
+//! for growth in report.top_growers(5) {
+//!     println!("{:+.0} bytes/day {}", growth.bytes_per_day(), growth.path().display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::DiskUsageTree;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// The sizes of the directories of a tree at a point in time, from
+/// [`DiskUsageTree::snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsageSnapshot {
+    taken: SystemTime,
+    sizes: BTreeMap<PathBuf, u64>,
+}
+
+impl UsageSnapshot {
+    /// Create an empty snapshot taken now.
+    pub fn new() -> Self {
+        Self {
+            taken: SystemTime::now(),
+            sizes: BTreeMap::new(),
+        }
+    }
+
+    /// Record the snapshot as taken at `time`, as when importing the results
+    /// of older scans.
+    pub fn taken_at(mut self, time: SystemTime) -> Self {
+        self.taken = time;
+        self
+    }
+
+    /// Record the size of the directory at `path`.
+    pub fn insert<P: Into<PathBuf>>(&mut self, path: P, size: u64) {
+        self.sizes.insert(path.into(), size);
+    }
+
+    /// When the snapshot was taken.
+    pub fn taken(&self) -> SystemTime {
+        self.taken
+    }
+
+    /// The size of the directory at `path`, if it was recorded.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<u64> {
+        self.sizes.get(path.as_ref()).copied()
+    }
+
+    /// The directories recorded and their sizes, in order of path.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, u64)> + '_ {
+        self.sizes
+            .iter()
+            .map(|(path, &size)| (path.as_path(), size))
+    }
+
+    /// How much larger each directory is in `later` than in this snapshot,
+    /// negative if smaller, with directories missing from either counted as
+    /// empty.  Unchanged directories are left out.
+    pub fn diff(&self, later: &UsageSnapshot) -> BTreeMap<PathBuf, i128> {
+        let mut changes = BTreeMap::new();
+        for (path, &size) in &self.sizes {
+            changes.insert(path.clone(), -i128::from(size));
+        }
+        for (path, &size) in &later.sizes {
+            *changes.entry(path.clone()).or_default() += i128::from(size);
+        }
+        changes.retain(|_, change| *change != 0);
+        changes
+    }
+}
+
+impl Default for UsageSnapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiskUsageTree {
+    /// Record the size of every directory in the tree, as counted by
+    /// [`Scanner::apparent_size`](crate::Scanner::apparent_size).
+    pub fn snapshot(&self) -> UsageSnapshot {
+        let mut snapshot = UsageSnapshot::new();
+        for node in self.walk().filter(|node| node.is_dir()) {
+            snapshot.insert(node.path(), node.size());
+        }
+        snapshot
+    }
+}
+
+/// The growth of one directory, from [`GrowthReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryGrowth {
+    path: PathBuf,
+    bytes_per_day: f64,
+    latest: u64,
+}
+
+impl DirectoryGrowth {
+    /// The path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The rate the directory is growing at, negative if it is shrinking.
+    pub fn bytes_per_day(&self) -> f64 {
+        self.bytes_per_day
+    }
+
+    /// The size of the directory in the latest snapshot recording it.
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+}
+
+/// The growth rates of directories over a series of snapshots.
+#[derive(Debug, Clone)]
+pub struct GrowthReport {
+    growth: Vec<DirectoryGrowth>,
+}
+
+impl GrowthReport {
+    /// Find the growth rate of each directory across `snapshots`, in any
+    /// order, by fitting a line through its sizes over time.
+    ///
+    /// This smooths over the odd spike from a temporary file, unlike
+    /// comparing only the first and last snapshots.  Directories recorded by
+    /// fewer than two snapshots taken at different times have no rate.
+    pub fn new(snapshots: &[UsageSnapshot]) -> Self {
+        let start = snapshots
+            .iter()
+            .map(|snapshot| snapshot.taken)
+            .min()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut ordered: Vec<&UsageSnapshot> = snapshots.iter().collect();
+        ordered.sort_by_key(|snapshot| snapshot.taken);
+
+        let mut series: BTreeMap<&Path, Vec<(f64, u64)>> = BTreeMap::new();
+        for snapshot in ordered {
+            let day = snapshot
+                .taken
+                .duration_since(start)
+                .map_or(0.0, |elapsed| elapsed.as_secs_f64() / SECONDS_PER_DAY);
+            for (path, size) in snapshot.iter() {
+                series.entry(path).or_default().push((day, size));
+            }
+        }
+
+        let growth = series
+            .into_iter()
+            .filter_map(|(path, points)| {
+                Some(DirectoryGrowth {
+                    path: path.to_path_buf(),
+                    bytes_per_day: slope(&points)?,
+                    latest: points.last()?.1,
+                })
+            })
+            .collect();
+
+        Self { growth }
+    }
+
+    /// The growth of every directory with a rate, in order of path.
+    pub fn rates(&self) -> &[DirectoryGrowth] {
+        &self.growth
+    }
+
+    /// The growth of the directory at `path`.
+    pub fn rate<P: AsRef<Path>>(&self, path: P) -> Option<&DirectoryGrowth> {
+        let path = path.as_ref();
+        self.growth
+            .binary_search_by(|growth| growth.path.as_path().cmp(path))
+            .ok()
+            .map(|index| &self.growth[index])
+    }
+
+    /// The `n` fastest growing directories, fastest first.
+    pub fn top_growers(&self, n: usize) -> Vec<&DirectoryGrowth> {
+        let mut growers: Vec<&DirectoryGrowth> = self.growth.iter().collect();
+        growers.sort_by(|a, b| b.bytes_per_day.total_cmp(&a.bytes_per_day));
+        growers.truncate(n);
+        growers
+    }
+}
+
+/// The least-squares slope of `points`, if they span any time.
+fn slope(points: &[(f64, u64)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|&(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|&(_, y)| y as f64).sum::<f64>() / n;
+
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for &(x, y) in points {
+        covariance += (x - mean_x) * (y as f64 - mean_y);
+        variance += (x - mean_x) * (x - mean_x);
+    }
+
+    if variance > 0.0 {
+        Some(covariance / variance)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn growth_is_reported() {
+    use std::time::Duration;
+
+    let day = |n: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(n * 86400);
+    let snapshot = |time, sizes: &[(&str, u64)]| {
+        let mut snapshot = UsageSnapshot::new().taken_at(time);
+        for &(path, size) in sizes {
+            snapshot.insert(path, size);
+        }
+        snapshot
+    };
+
+    let snapshots = [
+        snapshot(day(2), &[("logs", 2000), ("cache", 900), ("new", 10)]),
+        snapshot(day(0), &[("logs", 0), ("cache", 1000)]),
+        snapshot(day(1), &[("logs", 1000), ("cache", 950)]),
+    ];
+    let report = GrowthReport::new(&snapshots);
+    let top = report.top_growers(1);
+    let diff = snapshots[1].diff(&snapshots[0]);
+
+    assert_eq!(report.rates().len(), 2);
+    assert_eq!(report.rate("logs").unwrap().bytes_per_day(), 1000.0);
+    assert_eq!(report.rate("logs").unwrap().latest(), 2000);
+    assert_eq!(report.rate("cache").unwrap().bytes_per_day(), -50.0);
+    assert!(report.rate("new").is_none());
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0].path(), Path::new("logs"));
+    assert_eq!(diff.get(Path::new("logs")), Some(&2000));
+    assert_eq!(diff.get(Path::new("new")), Some(&10));
+}
+
+#[test]
+fn trees_are_snapshotted() {
+    let dir = crate::test_dir("growth");
+    std::fs::create_dir(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/file"), b"data").unwrap();
+
+    let tree = crate::Scanner::new(&dir).tree().unwrap();
+    let snapshot = tree.snapshot();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(snapshot.iter().count(), 2);
+    assert_eq!(
+        snapshot.get(dir.join("sub")),
+        Some(tree.get(dir.join("sub")).unwrap().size())
+    );
+}
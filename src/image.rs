@@ -0,0 +1,549 @@
+//! Allocation within raw, qcow2, and VHDX disk images.
+//!
+//! A virtual machine's disk is a file on the host, whose length says little
+//! about how much of the disk the guest has written: a 100 GiB qcow2 image may
+//! hold a few hundred megabytes, and a raw image may be sparse.
+//! [`disk_image_info`] reads the image's own allocation map, its L1 and L2
+//! tables for qcow2 or its block allocation table for VHDX, and the host
+//! filesystem's holes for raw images, and reports the space allocated to the
+//! guest's data as the physical size of a [`SizeInfo`], and the size of the
+//! disk the guest sees as its logical size, alongside the space the image
+//! takes up on the host.
+//!
+//! ```rust,no_run
+//! # fn main() -> std::io::Result<()> {
+//! let image = filesize::disk_image_info("/var/lib/libvirt/images/guest.qcow2")?;
+//! println!(
+//!     "{} of {} bytes allocated, {} on the host",
+//!     image.size_info().physical(),
+//!     image.size_info().logical(),
+//!     image.host_size()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::{Error, Result, SizeAccuracy, SizeInfo};
+
+const QCOW2_MAGIC: &[u8] = b"QFI\xfb";
+const QCOW2_OFFSET_MASK: u64 = 0x00ff_ffff_ffff_fe00;
+const QCOW2_COMPRESSED: u64 = 1 << 62;
+const QCOW2_ZERO: u64 = 1;
+const QCOW2_EXTENDED_L2: u64 = 1 << 4;
+
+const VHDX_SIGNATURE: &[u8] = b"vhdxfile";
+const VHDX_REGION_TABLES: [u64; 2] = [192 << 10, 256 << 10];
+const VHDX_BLOCK_FULLY_PRESENT: u64 = 6;
+const VHDX_BLOCK_PARTIALLY_PRESENT: u64 = 7;
+
+// GUIDs from the VHDX specification, with their first three fields stored
+// little-endian, as they are on disk.
+const VHDX_BAT: [u8; 16] = *b"\x66\x77\xc2\x2d\x23\xf6\x00\x42\x9d\x64\x11\x5e\x9b\xfd\x4a\x08";
+const VHDX_METADATA: [u8; 16] =
+    *b"\x06\xa2\x7c\x8b\x90\x47\x9a\x4b\xb8\xfe\x57\x5f\x05\x0f\x88\x6e";
+const VHDX_FILE_PARAMETERS: [u8; 16] =
+    *b"\x37\x67\xa1\xca\x36\xfa\x43\x4d\xb3\xb6\x33\xf0\xaa\x44\xe7\x6b";
+const VHDX_VIRTUAL_DISK_SIZE: [u8; 16] =
+    *b"\x24\x42\xa5\x2f\x1b\xcd\x76\x48\xb2\x11\x5d\xbe\xd8\x3b\xf4\xb8";
+const VHDX_LOGICAL_SECTOR_SIZE: [u8; 16] =
+    *b"\x1d\xbf\x41\x81\x6f\xa9\x09\x47\xba\x47\xf2\x33\xa8\xfa\xab\x5f";
+
+/// Tables larger than this are taken as corruption rather than read.
+const MAX_TABLE: u64 = 256 << 20;
+
+/// The format of a disk image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DiskImageFormat {
+    /// A raw image, holding the disk byte for byte, and allocated wherever
+    /// the host filesystem has data rather than holes.
+    Raw,
+    /// A QEMU copy-on-write image, version 2 or 3.
+    Qcow2,
+    /// A Hyper-V virtual hard disk.
+    Vhdx,
+}
+
+/// The allocation of a disk image, from [`disk_image_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskImage {
+    format: DiskImageFormat,
+    info: SizeInfo,
+    host_size: u64,
+    backed: bool,
+}
+
+impl DiskImage {
+    /// The format of the image.
+    pub fn format(&self) -> DiskImageFormat {
+        self.format
+    }
+
+    /// The space allocated to the guest's data within the image as the
+    /// physical size, and the size of the disk the guest sees as the logical
+    /// size.
+    ///
+    /// Parts of a qcow2 or VHDX image marked as reading as zeros count as
+    /// unallocated, and compressed qcow2 clusters count at their
+    /// uncompressed size.
+    pub fn size_info(&self) -> SizeInfo {
+        self.info
+    }
+
+    /// The space the image file occupies on the host, as found by
+    /// [`SizeOptions::size_on_disk`](crate::SizeOptions::size_on_disk),
+    /// including the image's own metadata.
+    pub fn host_size(&self) -> u64 {
+        self.host_size
+    }
+
+    /// Whether the image is an overlay on a backing qcow2 image or a parent
+    /// VHDX, which parts unallocated in this one are read from.
+    pub fn has_backing_image(&self) -> bool {
+        self.backed
+    }
+}
+
+/// Find how much of the disk image at `path` is allocated, from its
+/// allocation map.
+///
+/// The format is detected from the contents of the file, with files which
+/// are neither qcow2 nor VHDX read as raw images.  Images in other formats
+/// which can be recognised, such as VMDK, VDI, VHD, and the original qcow,
+/// fail with [`Error::UnsupportedFileType`].
+pub fn disk_image_info<P: AsRef<Path>>(path: P) -> Result<DiskImage> {
+    let path = path.as_ref();
+    let mut file = File::open(path).map_err(|e| Error::from_io(path, e))?;
+    let host_size = crate::SizeOptions::new().size_on_disk(path)?;
+
+    let mut header = [0; 512];
+    let read = read_prefix(&mut file, &mut header).map_err(|e| Error::from_io(path, e))?;
+    let header = &header[..read];
+
+    let (format, layout) =
+        if header.starts_with(QCOW2_MAGIC) && header.len() >= 72 && be32(header, 4) >= 2 {
+            let layout = qcow2_layout(&mut file, header).map_err(|e| Error::from_io(path, e))?;
+            (DiskImageFormat::Qcow2, layout)
+        } else if header.starts_with(VHDX_SIGNATURE) {
+            let layout = vhdx_layout(&mut file).map_err(|e| Error::from_io(path, e))?;
+            (DiskImageFormat::Vhdx, layout)
+        } else if is_unsupported(header) {
+            return Err(Error::UnsupportedFileType {
+                path: path.to_path_buf(),
+            });
+        } else {
+            let map = crate::sparse_map(path)?;
+            let layout = Layout {
+                virtual_size: map.len(),
+                allocated: map.data_bytes(),
+                backed: false,
+            };
+            (DiskImageFormat::Raw, layout)
+        };
+
+    Ok(DiskImage {
+        format,
+        info: SizeInfo::new(
+            layout.allocated.min(layout.virtual_size),
+            layout.virtual_size,
+            SizeAccuracy::DiskImage,
+        ),
+        host_size,
+        backed: layout.backed,
+    })
+}
+
+struct Layout {
+    virtual_size: u64,
+    allocated: u64,
+    backed: bool,
+}
+
+/// Whether `header` starts an image in a format with an allocation map this
+/// module doesn't read, which would be misreported as a raw image.
+fn is_unsupported(header: &[u8]) -> bool {
+    header.starts_with(b"KDMV")
+        || header.starts_with(b"# Disk DescriptorFile")
+        || header.starts_with(b"conectix")
+        || header.starts_with(QCOW2_MAGIC)
+        || header.get(64..68) == Some(&[0x7f, 0x10, 0xda, 0xbe])
+}
+
+fn qcow2_layout(file: &mut File, header: &[u8]) -> io::Result<Layout> {
+    let backed = be64(header, 8) != 0;
+    let cluster_bits = be32(header, 20);
+    let virtual_size = be64(header, 24);
+    let l1_size = u64::from(be32(header, 36));
+    let l1_offset = be64(header, 40);
+    let incompatible = if be32(header, 4) >= 3 && header.len() >= 80 {
+        be64(header, 72)
+    } else {
+        0
+    };
+
+    if !(9..=21).contains(&cluster_bits) {
+        return Err(invalid("invalid qcow2 cluster size"));
+    }
+    let cluster_size = 1u64 << cluster_bits;
+    let extended = incompatible & QCOW2_EXTENDED_L2 != 0;
+    let entry_size = if extended { 16 } else { 8 };
+    let l2_entries = cluster_size / entry_size;
+
+    let l1 = read_table(file, l1_offset, l1_size * 8)?;
+    let mut l2 = vec![0; cluster_size as usize];
+    let mut allocated = 0;
+    for (i, l1_entry) in l1.chunks_exact(8).enumerate() {
+        let l2_offset = be64(l1_entry, 0) & QCOW2_OFFSET_MASK;
+        if l2_offset == 0 {
+            continue;
+        }
+        read_at(file, l2_offset, &mut l2)?;
+
+        for (j, l2_entry) in l2.chunks_exact(entry_size as usize).enumerate() {
+            let start = (i as u64 * l2_entries + j as u64) * cluster_size;
+            if start >= virtual_size {
+                break;
+            }
+            let len = cluster_size.min(virtual_size - start);
+            let entry = be64(l2_entry, 0);
+
+            allocated += if entry & QCOW2_COMPRESSED != 0 {
+                len
+            } else if extended {
+                // The low half of the bitmap marks allocated subclusters.
+                let bitmap = be64(l2_entry, 8);
+                let subcluster = cluster_size / 32;
+                (0..32)
+                    .filter(|bit| bitmap & (1 << bit) != 0)
+                    .map(|bit| subcluster.min(len.saturating_sub(bit * subcluster)))
+                    .sum()
+            } else if entry & QCOW2_OFFSET_MASK != 0 && entry & QCOW2_ZERO == 0 {
+                len
+            } else {
+                0
+            };
+        }
+    }
+
+    Ok(Layout {
+        virtual_size,
+        allocated,
+        backed,
+    })
+}
+
+fn vhdx_layout(file: &mut File) -> io::Result<Layout> {
+    let mut regions = vec![0; 64 << 10];
+    let mut found = false;
+    for offset in VHDX_REGION_TABLES {
+        read_at(file, offset, &mut regions)?;
+        if regions.starts_with(b"regi") {
+            found = true;
+            break;
+        }
+    }
+    if !found {
+        return Err(invalid("no VHDX region table"));
+    }
+
+    let (mut bat, mut metadata) = (None, None);
+    let count = (le32(&regions, 8) as usize).min((regions.len() - 16) / 32);
+    for entry in regions[16..].chunks_exact(32).take(count) {
+        let region = (le64(entry, 16), u64::from(le32(entry, 24)));
+        if entry[..16] == VHDX_BAT {
+            bat = Some(region);
+        } else if entry[..16] == VHDX_METADATA {
+            metadata = Some(region);
+        }
+    }
+    let (bat_offset, bat_len) = bat.ok_or_else(|| invalid("no VHDX block allocation table"))?;
+    let (metadata_offset, metadata_len) =
+        metadata.ok_or_else(|| invalid("no VHDX metadata region"))?;
+
+    let metadata = read_table(file, metadata_offset, metadata_len)?;
+    // The table header is 32 bytes, including the count of its entries.
+    if metadata.len() < 32 || !metadata.starts_with(b"metadata") {
+        return Err(invalid("invalid VHDX metadata region"));
+    }
+    let item = |id: [u8; 16], len: usize| -> io::Result<&[u8]> {
+        let count = usize::from(le16(&metadata, 10));
+        metadata
+            .get(32..)
+            .unwrap_or_default()
+            .chunks_exact(32)
+            .take(count)
+            .find(|entry| entry[..16] == id)
+            .and_then(|entry| {
+                let start = le32(entry, 16) as usize;
+                metadata.get(start..start.checked_add(len)?)
+            })
+            .ok_or_else(|| invalid("missing VHDX metadata item"))
+    };
+    let parameters = item(VHDX_FILE_PARAMETERS, 8)?;
+    let block_size = u64::from(le32(parameters, 0));
+    let backed = le32(parameters, 4) & 2 != 0;
+    let virtual_size = le64(item(VHDX_VIRTUAL_DISK_SIZE, 8)?, 0);
+    let sector_size = u64::from(le32(item(VHDX_LOGICAL_SECTOR_SIZE, 4)?, 0));
+
+    if !block_size.is_power_of_two()
+        || !(1 << 20..=256 << 20).contains(&block_size)
+        || !(sector_size == 512 || sector_size == 4096)
+    {
+        return Err(invalid("invalid VHDX block or sector size"));
+    }
+    // Each sector bitmap block covers this many payload blocks, and follows
+    // their entries in the table.
+    let chunk_ratio = (1 << 23) * sector_size / block_size;
+    let blocks = virtual_size.div_ceil(block_size);
+
+    let bat = read_table(file, bat_offset, bat_len)?;
+    let entry = |index: u64| -> io::Result<u64> {
+        index
+            .checked_mul(8)
+            .and_then(|start| usize::try_from(start).ok())
+            .and_then(|start| bat.get(start..start + 8))
+            .map(|entry| le64(entry, 0))
+            .ok_or_else(|| invalid("truncated VHDX block allocation table"))
+    };
+
+    let mut bitmaps: HashMap<u64, Vec<u8>> = HashMap::new();
+    let mut allocated = 0;
+    for block in 0..blocks {
+        let len = block_size.min(virtual_size - block * block_size);
+        let state = entry(block + block / chunk_ratio)?;
+        allocated += match state & 7 {
+            VHDX_BLOCK_FULLY_PRESENT => len,
+            VHDX_BLOCK_PARTIALLY_PRESENT => {
+                let chunk = block / chunk_ratio;
+                let bitmap = match bitmaps.get(&chunk) {
+                    Some(bitmap) => bitmap,
+                    None => {
+                        let sbm = entry((chunk + 1) * (chunk_ratio + 1) - 1)?;
+                        let mut bitmap = vec![0; 1 << 20];
+                        if sbm & 7 == VHDX_BLOCK_FULLY_PRESENT {
+                            read_at(file, sbm >> 20 << 20, &mut bitmap)?;
+                        }
+                        bitmaps.entry(chunk).or_insert(bitmap)
+                    }
+                };
+                let sectors = len.div_ceil(sector_size);
+                let first = (block % chunk_ratio) * (block_size / sector_size);
+                let present = (first..first + sectors)
+                    .filter(|sector| bitmap[(sector / 8) as usize] & (1 << (sector % 8)) != 0)
+                    .count() as u64;
+                (present * sector_size).min(len)
+            }
+            _ => 0,
+        };
+    }
+
+    Ok(Layout {
+        virtual_size,
+        allocated,
+        backed,
+    })
+}
+
+fn read_prefix(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+fn read_table(file: &mut File, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    if len > MAX_TABLE {
+        return Err(invalid("disk image table too large"));
+    }
+    let mut table = vec![0; len as usize];
+    read_at(file, offset, &mut table)?;
+    Ok(table)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn be32(buf: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn be64(buf: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+fn le16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(buf[at..at + 2].try_into().unwrap())
+}
+
+fn le32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn le64(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+fn write_at(path: &Path, offset: u64, data: &[u8]) {
+    use std::io::Write;
+
+    let mut file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(data).unwrap();
+}
+
+#[test]
+fn qcow2_images_are_mapped() {
+    let dir = crate::test_dir("image-qcow2");
+    let path = dir.join("disk.qcow2");
+    let cluster = 64 << 10;
+    File::create(&path).unwrap().set_len(4 * cluster).unwrap();
+
+    let mut header = vec![];
+    header.extend_from_slice(QCOW2_MAGIC);
+    header.extend_from_slice(&3u32.to_be_bytes());
+    header.extend_from_slice(&[0; 12]);
+    header.extend_from_slice(&16u32.to_be_bytes());
+    header.extend_from_slice(&(1u64 << 20).to_be_bytes());
+    header.extend_from_slice(&0u32.to_be_bytes());
+    header.extend_from_slice(&1u32.to_be_bytes());
+    header.extend_from_slice(&cluster.to_be_bytes());
+    header.resize(104, 0);
+    write_at(&path, 0, &header);
+    write_at(&path, cluster, &(2 * cluster).to_be_bytes());
+
+    // A data cluster, a cluster reading as zeros, and a compressed cluster.
+    let l2: Vec<u8> = [3 * cluster, QCOW2_ZERO, QCOW2_COMPRESSED | (3 * cluster)]
+        .iter()
+        .flat_map(|entry| entry.to_be_bytes())
+        .collect();
+    write_at(&path, 2 * cluster, &l2);
+
+    let image = disk_image_info(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(image.format(), DiskImageFormat::Qcow2);
+    assert_eq!(image.size_info().logical(), 1 << 20);
+    assert_eq!(image.size_info().physical(), 2 * cluster);
+    assert_eq!(image.size_info().accuracy(), SizeAccuracy::DiskImage);
+    assert!(!image.has_backing_image());
+}
+
+#[test]
+fn vhdx_images_are_mapped() {
+    let dir = crate::test_dir("image-vhdx");
+    let path = dir.join("disk.vhdx");
+    let mib = 1u64 << 20;
+    File::create(&path).unwrap().set_len(6 * mib).unwrap();
+    write_at(&path, 0, VHDX_SIGNATURE);
+
+    let mut regions = b"regi\0\0\0\0\x02\0\0\0\0\0\0\0".to_vec();
+    for (id, offset, len) in [(VHDX_BAT, mib, mib), (VHDX_METADATA, 2 * mib, 64 << 10)] {
+        regions.extend_from_slice(&id);
+        regions.extend_from_slice(&offset.to_le_bytes());
+        regions.extend_from_slice(&(len as u32).to_le_bytes());
+        regions.extend_from_slice(&1u32.to_le_bytes());
+    }
+    write_at(&path, VHDX_REGION_TABLES[0], &regions);
+
+    let mut metadata = b"metadata\0\0\x03\0".to_vec();
+    metadata.resize(32, 0);
+    for (id, offset, len) in [
+        (VHDX_FILE_PARAMETERS, 256u32, 8u32),
+        (VHDX_VIRTUAL_DISK_SIZE, 264, 8),
+        (VHDX_LOGICAL_SECTOR_SIZE, 272, 4),
+    ] {
+        metadata.extend_from_slice(&id);
+        metadata.extend_from_slice(&offset.to_le_bytes());
+        metadata.extend_from_slice(&len.to_le_bytes());
+        metadata.extend_from_slice(&[0; 8]);
+    }
+    metadata.resize(256, 0);
+    metadata.extend_from_slice(&(mib as u32).to_le_bytes());
+    metadata.extend_from_slice(&2u32.to_le_bytes());
+    metadata.extend_from_slice(&(4 * mib).to_le_bytes());
+    metadata.extend_from_slice(&512u32.to_le_bytes());
+    write_at(&path, 2 * mib, &metadata);
+
+    // Two blocks present, one reading as zeros, and one partly present in a
+    // differencing disk, with its first 128 sectors in the sector bitmap.
+    let states = [
+        (3 * mib) | VHDX_BLOCK_FULLY_PRESENT,
+        2,
+        (4 * mib) | VHDX_BLOCK_FULLY_PRESENT,
+        VHDX_BLOCK_PARTIALLY_PRESENT,
+    ];
+    let bat: Vec<u8> = states.iter().flat_map(|e| e.to_le_bytes()).collect();
+    write_at(&path, mib, &bat);
+    let bitmap_entry = (5 * mib) | VHDX_BLOCK_FULLY_PRESENT;
+    write_at(&path, mib + 4096 * 8, &bitmap_entry.to_le_bytes());
+    write_at(&path, 5 * mib + 3 * 2048 / 8, &[0xff; 16]);
+
+    let image = disk_image_info(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(image.format(), DiskImageFormat::Vhdx);
+    assert_eq!(image.size_info().logical(), 4 * mib);
+    assert_eq!(image.size_info().physical(), 2 * mib + 128 * 512);
+    assert!(image.has_backing_image());
+}
+
+#[test]
+fn truncated_vhdx_metadata_is_invalid() {
+    let dir = crate::test_dir("image-vhdx-truncated");
+    let path = dir.join("disk.vhdx");
+    let mib = 1u64 << 20;
+    File::create(&path).unwrap().set_len(3 * mib).unwrap();
+    write_at(&path, 0, VHDX_SIGNATURE);
+
+    // A metadata region too short to hold the count of its entries.
+    let mut regions = b"regi\0\0\0\0\x02\0\0\0\0\0\0\0".to_vec();
+    for (id, offset, len) in [(VHDX_BAT, mib, mib), (VHDX_METADATA, 2 * mib, 8)] {
+        regions.extend_from_slice(&id);
+        regions.extend_from_slice(&offset.to_le_bytes());
+        regions.extend_from_slice(&(len as u32).to_le_bytes());
+        regions.extend_from_slice(&1u32.to_le_bytes());
+    }
+    write_at(&path, VHDX_REGION_TABLES[0], &regions);
+    write_at(&path, 2 * mib, b"metadata");
+
+    let image = disk_image_info(&path);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(image.unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn raw_images_are_mapped_by_their_holes() {
+    let dir = crate::test_dir("image-raw");
+    let path = dir.join("disk.img");
+    File::create(&path).unwrap().set_len(1 << 20).unwrap();
+    write_at(&path, 0, &[1; 4096]);
+    std::fs::write(dir.join("disk.vmdk"), b"KDMV\x01\0\0\0").unwrap();
+
+    let image = disk_image_info(&path).unwrap();
+    let map = crate::sparse_map(&path).unwrap();
+    let vmdk = disk_image_info(dir.join("disk.vmdk"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(image.format(), DiskImageFormat::Raw);
+    assert_eq!(image.size_info().logical(), 1 << 20);
+    assert_eq!(image.size_info().physical(), map.data_bytes());
+    assert!(matches!(vmdk, Err(Error::UnsupportedFileType { .. })));
+}
@@ -0,0 +1,129 @@
+use crate::ReparseTag;
+
+/// How the on-disk size in a [`SizeInfo`] was determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SizeAccuracy {
+    /// Exact, from the number of blocks allocated to the file.
+    Blocks,
+    /// Exact, from the compressed size reported by the filesystem.
+    CompressedSize,
+    /// Exact, from the capacity of a block device.
+    DeviceCapacity,
+    /// Estimated from the logical length of the file, on platforms where the
+    /// allocated size is unavailable.
+    Length,
+    /// Estimated by rounding the logical length of the file up to the
+    /// allocation unit given to `SizeOptions::estimate_allocation`.
+    RoundedLength,
+    /// Substituted by a policy, such as `SpecialFilePolicy::Zero`.
+    Policy,
+    /// Exact, from the space a member occupies within an archive.
+    Archived,
+    /// Exact, from the space a file deduplicated by Windows Server Data
+    /// Deduplication holds locally, excluding its share of the chunk store.
+    Deduplicated,
+    /// Exact, from the allocation map of a disk image, counting the space
+    /// allocated to the guest's data.
+    DiskImage,
+}
+
+impl SizeAccuracy {
+    /// Whether the size reflects what is actually allocated on disk.
+    pub fn is_exact(&self) -> bool {
+        match self {
+            SizeAccuracy::Blocks
+            | SizeAccuracy::CompressedSize
+            | SizeAccuracy::DeviceCapacity
+            | SizeAccuracy::Archived
+            | SizeAccuracy::Deduplicated
+            | SizeAccuracy::DiskImage => true,
+            SizeAccuracy::Length | SizeAccuracy::RoundedLength | SizeAccuracy::Policy => false,
+        }
+    }
+}
+
+/// The size of a file, both on disk and logically, and how it was determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeInfo {
+    physical: u64,
+    logical: u64,
+    accuracy: SizeAccuracy,
+    preallocated: u64,
+    reparse_tag: Option<ReparseTag>,
+    memory_backed: bool,
+}
+
+impl SizeInfo {
+    pub(crate) fn new(physical: u64, logical: u64, accuracy: SizeAccuracy) -> Self {
+        Self {
+            physical,
+            logical,
+            accuracy,
+            preallocated: 0,
+            reparse_tag: None,
+            memory_backed: false,
+        }
+    }
+
+    pub(crate) fn with_preallocated(mut self, preallocated: u64) -> Self {
+        self.preallocated = preallocated;
+        self
+    }
+
+    pub(crate) fn with_reparse_tag(mut self, reparse_tag: Option<ReparseTag>) -> Self {
+        self.reparse_tag = reparse_tag;
+        self
+    }
+
+    pub(crate) fn with_memory_backed(mut self, memory_backed: bool) -> Self {
+        self.memory_backed = memory_backed;
+        self
+    }
+
+    /// The space used on disk.
+    pub fn physical(&self) -> u64 {
+        self.physical
+    }
+
+    /// The logical length of the file, as reported by `Metadata::len()`.
+    pub fn logical(&self) -> u64 {
+        self.logical
+    }
+
+    /// How the physical size was determined.
+    pub fn accuracy(&self) -> SizeAccuracy {
+        self.accuracy
+    }
+
+    /// The part of the physical size allocated beyond the end of the file,
+    /// as preallocated with `fallocate()` and `FALLOC_FL_KEEP_SIZE`.
+    ///
+    /// This is only found on Unix platforms, when the size is counted in
+    /// blocks.  Outside Linux and Android it is estimated from how far the
+    /// physical size exceeds the length, so preallocation in sparse files may
+    /// be missed, and filesystems which count metadata blocks against files
+    /// may appear to preallocate.
+    pub fn preallocated_bytes(&self) -> u64 {
+        self.preallocated
+    }
+
+    /// The kind of reparse point the file is, if it is one, which may explain
+    /// an unexpected physical size.  This is always `None` outside Windows.
+    pub fn reparse_tag(&self) -> Option<ReparseTag> {
+        self.reparse_tag
+    }
+
+    /// Whether the file is held in RAM and swap rather than on disk, as are
+    /// files on tmpfs, ramfs, and hugetlbfs, such as those in `/dev/shm`, and
+    /// memfds.  Their physical size is then the memory their pages occupy,
+    /// which only counts pages once written.
+    ///
+    /// This is found for the entries of a [`Scanner`](crate::Scanner) on any
+    /// platform [`filesystem_kind`](crate::filesystem_kind) recognises memory
+    /// filesystems on, but only for files in `/dev/shm` and memfds on Linux
+    /// and Android for [`SizeOptions::size_info`](crate::SizeOptions::size_info).
+    pub fn is_memory_backed(&self) -> bool {
+        self.memory_backed
+    }
+}
@@ -6,11 +6,23 @@
 //! and as of version 0.2, a `std::path::Path` extension trait offering identical
 //! functions named `size_on_disk` and `size_on_disk_fast`.
 //!
+//! Like `std::fs::symlink_metadata()`, these do not follow symbolic links: the
+//! size reported for a symlink is that of the link itself.  The `_follow` variants,
+//! `file_real_size_follow` and `size_on_disk_follow`, resolve symlinks and report
+//! the allocation of their target instead.  [`link_sizes`] reports both at once,
+//! for file managers showing links alongside their targets.
+//!
+//! [`same_allocation`] and [`allocation_delta`] compare the on-disk sizes of two
+//! files, as when checking a copy kept the footprint of its original.
+//!
 //! The `_fast` variants accept a `std::fs::Metadata` reference which will be used
 //! to cheaply calculate the size on disk if the platform supports that.  This is
 //! intended for cases such as directory traversal, where metadata is available
 //! anyway, and where metadata is needed for other purposes.
 //!
+//! When metadata isn't to hand but will be needed, `stat_with_size` fetches it
+//! along with the size on disk in as few calls as the platform allows.
+//!
 //! ## Example
 //!
 //! ```rust
@@ -23,53 +35,691 @@
 //!
 //! let realsize = path.size_on_disk()?;
 //! let realsize = path.size_on_disk_fast(&metadata)?;
+//! let realsize = path.size_on_disk_follow()?;
 //!
 //! // Older interface
-//! use filesize::{file_real_size, file_real_size_fast};
+//! use filesize::{file_real_size, file_real_size_fast, file_real_size_follow};
 //!
 //! let realsize = file_real_size(path)?;
 //! let realsize = file_real_size_fast(path, &metadata)?;
+//! let realsize = file_real_size_follow(path)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`SizeOnDisk`] offers `real_size` for paths, open files, directory
+//! entries, and on Unix metadata, so all of them can be sized alike.
+//! [`SizeOnDisk::real_size_or`] and [`real_size_or_len`] give a best-effort
+//! number instead of an error, for user interfaces with nowhere to report one.
+//!
+//! On Unix and WASI, `file_real_size_at` additionally supports finding sizes
+//! relative to an open directory, as `fstatat()` does.
+//!
+//! [`DiskSizesExt::with_disk_sizes`] adds sizes to any iterator of paths or
+//! directory entries, such as glob results or the output of `git ls-files`,
+//! reusing the metadata of directory entries where it is cheap.
+//!
+//! ## Options
+//!
+//! Where the defaults aren't suitable, [`SizeOptions`] offers more control over
+//! how sizes are determined, such as how to treat special files like FIFOs and
+//! devices, for which the platform reports meaningless sizes:
+//!
+//! ```rust
+//! use filesize::{SizeOptions, SpecialFilePolicy};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let options = SizeOptions::new().special_files(SpecialFilePolicy::Zero);
+//! let realsize = options.size_on_disk("Cargo.toml")?;
 //! # Ok(())
 //! # }
 //! ```
 //!
+//! [`SizeOptions::size_info`] returns a [`SizeInfo`], which includes the logical
+//! size of the file, and a [`SizeAccuracy`] describing how the size on disk was
+//! determined, so callers can tell whether it is exact or an estimate.
+//!
+//! Where the real allocation is unavailable, [`SizeOptions::estimate_allocation`]
+//! can be used to round logical lengths up to a given allocation unit, instead
+//! of understating the usage of many small files, or [`SizeOptions::strict`] to
+//! fail with [`Error::PlatformLimitation`] rather than report any estimate.
+//!
+//! [`allocation_granularity`] finds the unit a filesystem allocates in, such as
+//! the cluster size on Windows, along with the sector sizes of the disk.
+//! [`raw_allocation`] gives the allocation of a file as a count of blocks and
+//! their size, `st_blocks` on Unix and clusters on Windows, for matching `du`.
+//! [`volume_space`] finds the capacity and free space of a volume, and
+//! [`DiskUsage::percent_of_volume`] how much of it a scan's usage makes up.
+//! On Linux, `deleted_open_files` finds files deleted while still held open,
+//! which occupy space no scan can find, the usual reason `df` reports more in
+//! use than `du`.
+//!
+//! [`capabilities`] describes what can be found on the current platform, such
+//! as whether sizes are exact and sparse files' holes can be located, so
+//! applications can adapt without hard-coding knowledge of each platform.
+//!
+//! [`SizeOptions::retry`] retries queries which fail transiently, such as on
+//! files briefly locked by antivirus software on Windows.
+//! [`Scanner::timeout`] gives up on queries and directory reads taking too
+//! long, running them on worker threads, so a hung NFS server or disconnected
+//! SMB share stalls only the part of a scan on it.
+//!
+//! Sizes are found by a [`SizeBackend`], by default [`OsBackend`], which asks
+//! the operating system.  Other providers, such as remote agents or test
+//! fakes, can be used through [`SizeOptions::size_info_with`] and
+//! [`Scanner::backend`].
+//!
+//! ## Scanning
+//!
+//! For measuring whole directory trees, a [`Scanner`] walks the tree, yielding
+//! each file it finds along with its size on disk, and totals them up into a
+//! [`DiskUsage`]:
+//!
+//! ```rust
+//! # fn main() -> std::io::Result<()> {
+//! let usage = filesize::Scanner::new("src").follow_symlinks(true).total()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Entries are annotated with the [`FilesystemKind`] they reside on, so files on
+//! pseudo-filesystems like procfs and sysfs, which report meaningless sizes, can
+//! be identified, or skipped entirely with [`Scanner::skip_virtual_filesystems`].
+//! Network and FUSE filesystems are flagged too, as their sizes may be stale or
+//! fabricated; [`SizeOptions::force_sync`] fetches fresh sizes where possible.
+//! Files on memory filesystems such as tmpfs are flagged as well, and
+//! [`DiskUsage::disk_bytes`] leaves them out of the total, as they take up RAM
+//! rather than disk; [`Scanner::skip_memory_filesystems`] skips them instead.
+//! [`SizeInfo::is_memory_backed`] flags them too, along with files in
+//! `/dev/shm` and memfds sized by [`SizeOptions::size_info`] on Linux and
+//! Android, where descriptor links such as `/proc/self/fd/3` are sized as the
+//! open file they stand for.
+//!
+//! Below the root, scans leave out [`DEFAULT_EXCLUDED_PATHS`], such as `/proc`,
+//! `/sys`, and `/dev` on Unix and the Recycle Bin on Windows, so scans of a whole
+//! disk neither hang on nor miscount pseudo-files.  [`Scanner::excluded_paths`]
+//! replaces the list, and [`Scanner::exclude_path`] adds to it.  Like
+//! `tar --exclude-caches`, [`Scanner::exclude_caches`] leaves out the contents of
+//! directories tagged with a `CACHEDIR.TAG`.
+//!
+//! [`Scanner::sorted`] visits the entries of each directory in order of name,
+//! so scans, and the trees and reports built from them, are reproducible
+//! across runs and machines.
+//!
+//! Each [`Entry`] carries the `Metadata` the scan found for it alongside its
+//! sizes, so permissions and timestamps can be read without another `stat()`.
+//!
+//! Long-running scans can report their [`Progress`] to a callback at a given
+//! interval with [`Scanner::progress`], and be cancelled from another thread
+//! with [`Scanner::cancel_on`].  Given the number of entries a previous scan
+//! found with [`Scanner::expected_entries`], or counted by listing the tree
+//! first with [`Scanner::count_first`], progress includes a percentage complete
+//! and an estimate of the time left.  Throughput counters can be exported
+//! through a [`MetricsSink`] given to [`Scanner::metrics`], and
+//! [`Scanner::summary`] returns a [`ScanSummary`] of the totals, errors,
+//! skipped entries, and time taken.  Repeated scans can share a [`SizeCache`]
+//! with [`Scanner::cache`], so files unchanged since the last scan are not
+//! queried again.  [`Scan::checkpoint`] records the state of a scan, which
+//! [`Scanner::resume`] carries on from, so scans taking hours can survive a
+//! restart.  [`Scanner::background`] rescans a tree periodically on a thread of
+//! its own, publishing each [`DiskUsageTree`] from a [`BackgroundScanner`], for
+//! daemons keeping usage current as they run.  [`Scanner::send_to`] sends each
+//! entry of a scan, with periodic subtotals, to a caller's channel as it runs,
+//! to feed database writers or UI threads.
+//!
+//! [`Scanner::estimate`] estimates the size of a tree from random walks
+//! through it, giving a [`SizeEstimate`] with a 95% confidence interval in
+//! seconds, where scanning every entry of a tree of millions would take hours.
+//!
+//! Summaries of a scan can be built as it runs by passing a [`Collector`] to
+//! [`Scanner::collect_into`], such as [`TopK`] to find the largest files
+//! without holding every entry in memory, [`GroupBy`] to total up usage by
+//! file extension or owner, or [`DedupCandidates`] to find sets of files of
+//! identical size to feed to deduplication tools.
+//!
+//! [`fragmentation`] counts the extents of a file and how many separate runs
+//! they're stored in, and [`FragmentationReport`] collects them during a scan
+//! to find the most fragmented large files.  [`extent_map`] lists where each
+//! part of a file lies on disk.  All of them need `FS_IOC_FIEMAP` on Linux and
+//! Android, or `FSCTL_GET_RETRIEVAL_POINTERS` on Windows.
+//!
+//! [`Scanner::tree`] records the whole hierarchy in a [`DiskUsageTree`], with the
+//! usage of every directory, for tools such as treemaps which need more than a
+//! flat total.  Each directory also records its newest modification time, as
+//! `du --time` shows, and its largest file, to find large untouched trees.
+//! [`DiskUsageTree::write_ncdu`] exports it in ncdu's JSON format, so a scan
+//! taken on a remote server can be browsed locally with `ncdu -f`, and
+//! [`DiskUsageTree::write_folded`] as folded stacks for flame graph tools such as
+//! inferno and speedscope.
+//! [`DiskUsageTree::merge`] combines trees scanned separately, on different
+//! threads or machines, matching entries by path so overlaps count once.
+//!
+//! [`DiskUsageTree::snapshot`] records the size of every directory at a point
+//! in time, and a [`GrowthReport`] over a series of saved snapshots gives the
+//! bytes per day each directory is growing by, and the fastest growers.
+//!
+//! [`DuFormat`] formats sizes and trees as GNU `du` prints them, in 1 KiB
+//! blocks or with `-h` suffixes, for scripts which already parse its output.
+//!
+//! On compressed filesystems such as NTFS, btrfs, and ZFS, the gap between
+//! physical and logical totals is the space compression saves.
+//! [`DiskUsage::savings_percent`] gives it as a percentage, and
+//! [`DiskUsageTree::write_savings`] reports it for every directory, as
+//! `compsize` does.
+//!
+//! On Linux, filesystems tracking quotas can report the usage of a whole tree
+//! in milliseconds: [`btrfs_qgroup_usage`] for btrfs subvolumes, and
+//! [`project_quota_usage`] for ext4 and XFS project quotas.
+//!
+//! [`sparse_map`] finds which parts of a file hold data and which are holes,
+//! giving a [`SparseMap`] which can be compared with others, or persisted with
+//! the `serde` feature to recreate the layout elsewhere.
+//! [`is_fully_allocated`] checks that a file has no holes at all, counting
+//! preallocated space, to verify preallocation before relying on it.
+//!
+//! ## Optional Features
+//!
+//! * `archive`: [`archive_members`], listing the members of tar and zip
+//!   archives with the space each occupies within the archive.
+//! * `disk-image`: [`disk_image_info`], reading the allocation maps of raw,
+//!   qcow2, and VHDX disk images to compare the space allocated to a guest
+//!   with the size of its disk and the space the image takes up on the host.
+//! * `cap-std`: extension traits in [`cap_std`](crate::cap_std) for measuring files
+//!   through `cap_std::fs::Dir` and `File`, without ambient path authority.
+//! * `crossbeam-channel`: an implementation of [`UpdateSender`] for
+//!   `crossbeam_channel::Sender`, so [`Scanner::send_to`] can send to it.
+//! * `camino`: an implementation of [`PathExt`] for `camino::Utf8Path`, and so
+//!   `Utf8PathBuf`.
+//! * `globset`: include and exclude glob patterns for [`Scanner`].
+//! * `cli`: an `fsize` binary, a `du`-like command line tool built on `Scanner`,
+//!   with human-readable units, sorting, depth limits, exclusions, and JSON,
+//!   ndjson, or CSV output for scripts and monitoring.
+//! * `tracing`: `tracing` events from scans and size queries, with per-directory
+//!   timings, how each file was sized, and errors passed over by an `ErrorPolicy`.
+//! * `tui`: an ncdu-style interactive browser for `fsize`, enabled with `-i`.
+//! * `notify`: keeping a completed scan up to date as files change, with
+//!   `Scanner::watch` yielding the old and new size of each changed file, and
+//!   calling back when a directory's total crosses a threshold.
+//! * `mock`: [`MockSizes`], for substituting fake on-disk sizes in tests of
+//!   code built on this crate.
+//! * `rayon`: totalling large trees across multiple threads, with a thread
+//!   count, task granularity, and pool set by a `Parallelism` given to
+//!   `Scanner::parallelism`.  Without it, [`Scanner::threads`] totals trees
+//!   on scoped threads of the standard library instead.
+//! * `serde`: `Serialize` and `Deserialize` for [`SparseMap`], [`DiskUsage`],
+//!   [`Checkpoint`], and [`UsageSnapshot`].
+//! * `testing`: helpers in [`testing`](crate::testing) for creating sparse and
+//!   compressed files, to exercise code built on this crate.
+//! * `async`: `Scanner::into_stream`, running a scan on a thread of its own
+//!   and yielding its entries as a `futures_core::Stream`, with backpressure,
+//!   and an extension trait in [`tokio`](crate::tokio) for sizing the entries
+//!   of `tokio::fs::read_dir`.
+//! * `winapi` (default): Win32 bindings from the `winapi` crate.  Without it,
+//!   the handful of Win32 functions this crate calls are declared by the
+//!   crate itself, for builds keeping external dependencies to a minimum,
+//!   with identical behaviour.
+//! * `capi`: a C API exporting `filesize_real_size()` and friends, which can be
+//!   built into a C library with [`cargo-c`](https://github.com/lu-zero/cargo-c).
+//!
 //! ## Platform-specific Behaviour
 //!
 //! On Unix platforms this is a thin wrapper around [`std::fs::symlink_metadata()`]
-//! and [`std::os::unix::fs::MetadataExt`], simply returning `blocks() * 512`.  The
-//! `_fast` functions disregard the file path entirely and use the passed metadata
-//! directly.
+//! and [`std::os::unix::fs::MetadataExt`], simply returning `blocks() * 512`.
+//! Block counts are always in 512-byte units, whatever the sector size of the
+//! disk, so this holds on 4Kn disks too.  The `_fast` functions disregard the
+//! file path entirely and use the passed metadata directly.
 //!
 //! On Windows, it wraps [`GetCompressedFileSizeW()`], and the `_fast` functions
-//! disregard the passed metadata entirely.
+//! only use the passed metadata to spot deduplicated files.  It always resolves
+//! symlinks, so the `_follow` functions behave identically to the standard ones.
+//!
+//! The Win32 API is reached through the `winapi` crate by default.  Building
+//! without default features declares the few functions used within the crate
+//! instead, with identical behaviour, for builds avoiding the dependency.
+//!
+//! Paths are passed to it in verbatim form, with a `\\?\` prefix, so they may
+//! exceed `MAX_PATH`, rather than being canonicalized.  UNC paths such as
+//! `\\server\share\dir` become `\\?\UNC\server\share\dir`, and paths already in
+//! the verbatim namespace, such as `\\?\Volume{GUID}\dir` for volumes without
+//! a drive letter, are used as they are.  `verbatim_path` gives the form used.
+//!
+//! Volume Shadow Copies can be scanned through their device paths, such as
+//! `\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1\`, and `shadow_copy_path`
+//! finds where a file lies within one, for comparing the allocation of live
+//! files with their snapshots.
+//!
+//! `GetCompressedFileSizeW()` fails on directories, so their allocation size is
+//! reported instead.  This is the space used by the directory's index, and is
+//! zero for small directories whose entries fit in their MFT record.
+//!
+//! `file_quota_size` reports the allocation NTFS charges against disk quotas,
+//! which for compressed files is that of the uncompressed data.
+//!
+//! `file_valid_data_length` uses `FSCTL_QUERY_FILE_REGIONS` to report how much
+//! of a file has been written, distinguishing preallocated tail space from data.
+//!
+//! Files optimized by Windows Server Data Deduplication are reparse points whose
+//! data lives in a shared chunk store, for which `GetCompressedFileSizeW()`
+//! reports the full logical size.  Their allocation size is reported instead,
+//! which is normally close to zero, and [`SizeOptions`] flags them with
+//! [`SizeAccuracy::Deduplicated`].
+//!
+//! [`reparse_tag`] identifies the other kinds of reparse point, such as OneDrive
+//! placeholders, WOF-compressed files, and junctions, which can explain sizes
+//! that seem wrong, and [`SizeInfo::reparse_tag`] reports it alongside a size.
+//!
+//! Administrators can measure files their account can't read by enabling
+//! `SeBackupPrivilege` with `enable_backup_privilege` and setting
+//! [`SizeOptions::backup_semantics`], rather than collecting access denied errors.
+//!
+//! Btrfs counts compressed extents at their uncompressed size in `st_blocks`.
+//! On Linux, [`compressed_size`] reads a file's extents from the filesystem
+//! tree, as `compsize` does, to find the space they occupy on disk with a
+//! breakdown by compression algorithm.
+//!
+//! Clones made by `cp -c` on APFS and reflink copies on btrfs and XFS share
+//! blocks with their originals, but each reports the full allocation.
+//! [`clone_info`] finds how much of a file is shared, and so how much deleting
+//! it would actually free, on macOS, iOS, Linux, and Android.
+//!
+//! [`CompactEstimator`] estimates how much NTFS compression or the Windows
+//! Overlay Filter would save on a tree before running `compact`, by
+//! compressing a sample of each file with a simple LZ77 model.  It works on
+//! any platform, so a tree can be assessed before moving it to Windows.
+//!
+//! [`SizeProjector`] projects the space a tree would need once copied to a
+//! volume with a given cluster size, and optionally an assumed compression
+//! ratio, as a tree of many small files can need several times its current
+//! space on a volume with 64 KiB clusters.
+//!
+//! On macOS and iOS, `read_dir_sizes` lists a directory along with the
+//! allocated size and length of each entry using `getattrlistbulk()`, which
+//! fills a buffer with many entries per call rather than needing an `lstat()`
+//! for each.  [`Scanner`] still calls `lstat()`, as its entries carry full
+//! metadata.
+//!
+//! ZFS reports block counts after compression, but only updates them as
+//! transaction groups commit, every few seconds, so files written moments ago
+//! may appear to occupy little or no space.  Its dataset-wide `logicalused` and
+//! `compressratio` properties have no per-file equivalent.  [`filesystem_kind`]
+//! reports such filesystems as [`FilesystemKind::Deferred`], and
+//! [`SizeOptions::force_sync`] flushes regular files with `fsync()` before
+//! sizing them on Unix platforms.
+//!
+//! Android's shared storage, `/sdcard` or `/storage/emulated/0`, is served by
+//! sdcardfs or a FUSE daemon, which may report block counts derived from file
+//! lengths.  [`filesystem_kind`] reports it as [`FilesystemKind::Emulated`],
+//! and on Android `emulated_storage_source` finds the path beneath it on
+//! `/data/media`, which root and `adb shell` may size accurately.  Since
+//! Android 11, apps' own `Android/data` and `Android/obb` directories are
+//! mounted directly from that storage, so their sizes are accurate already.
+//!
+//! On Unix platforms, [`SizeInfo::preallocated_bytes`] reports space allocated
+//! beyond the end of a file with `fallocate()` and `FALLOC_FL_KEEP_SIZE`, which
+//! Linux and Android confirm with the `FS_IOC_FIEMAP` ioctl.
+//!
+//! Fuchsia is a Unix platform as far as Rust is concerned, and uses the Unix
+//! implementation: its `stat()` derives `st_blocks` from the storage size reported
+//! by the filesystem, in the same 512-byte units.
+//!
+//! illumos, Solaris, and AIX also count `st_blocks` in 512-byte units, which
+//! the Unix implementation confirms for each of them rather than assuming.  On
+//! ZFS the count includes compression and the padding of small records, but
+//! lags data not yet written back, which [`SizeOptions::force_sync`] flushes.
+//!
+//! On WASI, files have no notion of allocated space: `filestat` carries only the
+//! logical size, so that is what is reported, with an accuracy of
+//! [`SizeAccuracy::Length`].  `file_real_size_at` uses `path_filestat_get()`
+//! directly, and block devices report the size given by `fd_filestat_get()`.
 //!
 //! On any other platforms, it wraps [`std::fs::symlink_metadata()`] and only returns
 //! `len()`, while the `_fast` variants also disregard the path and use the passed
 //! metadata directly.
 //!
+//! On all platforms other than Windows, the `_follow` functions use
+//! [`std::fs::metadata()`] in place of [`std::fs::symlink_metadata()`].
+//!
+//! When [`SizeOptions::block_device_capacity`] is enabled, the capacity of block
+//! devices is retrieved with the `BLKGETSIZE64` ioctl on Linux, the
+//! `DKIOCGETBLOCKCOUNT` and `DKIOCGETBLOCKSIZE` ioctls on macOS, and
+//! `IOCTL_DISK_GET_LENGTH_INFO` on Windows, where device namespace paths such as
+//! `\\.\PhysicalDrive0` are treated as block devices.  Other Unix platforms
+//! seek to the end of the device.
+//!
+//! ## Errors
+//!
+//! Failures are reported with an [`Error`] carrying the path of the file
+//! involved, classified as not found, permission denied, unsupported file type,
+//! or a platform limitation.  It converts into a `std::io::Error`, so the `?`
+//! operator works as before in functions returning `std::io::Result`.
+//!
 //!
 //! [`GetCompressedFileSizeW()`]: https://docs.microsoft.com/en-us/windows/desktop/api/fileapi/nf-fileapi-getcompressedfilesizew
+//! [`std::fs::metadata()`]: https://doc.rust-lang.org/std/fs/fn.metadata.html
 //! [`std::fs::symlink_metadata()`]: https://doc.rust-lang.org/std/fs/fn.symlink_metadata.html
 //! [`std::os::unix::fs::MetadataExt`]: https://doc.rust-lang.org/std/os/unix/fs/trait.MetadataExt.html
 
 use std::fs::Metadata;
 use std::path::Path;
 
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(target_os = "wasi")]
+use std::os::wasi::io::AsRawFd;
+
+// Emit a `tracing` event when the `tracing` feature is enabled.
+macro_rules! event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!($($arg)*);
+    };
+}
+
+mod adapt;
+#[cfg(feature = "archive")]
+mod archive;
+mod backend;
+mod background;
+#[cfg(target_os = "linux")]
+mod btrfs;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod bulk;
+mod cache;
+#[cfg(feature = "cap-std")]
+pub mod cap_std;
+mod capabilities;
+#[cfg(feature = "capi")]
+mod capi;
+mod channel;
+mod checkpoint;
+mod clone;
+mod collect;
+mod compact;
+mod compare;
+#[cfg(target_os = "linux")]
+mod compressed;
+#[cfg(target_os = "linux")]
+mod deleted;
+mod du;
+mod error;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod extents;
+mod filesystem;
+mod folded;
+mod fragmentation;
+mod granularity;
+mod growth;
+#[cfg(feature = "disk-image")]
+mod image;
+mod info;
+mod link;
+mod metrics;
+#[cfg(feature = "mock")]
+mod mock;
+mod ncdu;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod priority;
+#[cfg(windows)]
+mod privilege;
+mod projection;
+#[cfg(target_os = "linux")]
+mod quota;
+mod reparse;
+mod sample;
+mod savings;
+mod scan;
+mod sized;
+mod sparse;
+#[cfg(feature = "async")]
+mod stream;
+mod summary;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod threads;
+mod timeout;
+#[cfg(feature = "async")]
+pub mod tokio;
+mod tree;
+#[cfg(windows)]
+mod verbatim;
+mod volume;
+#[cfg(feature = "notify")]
+mod watch;
+#[cfg(windows)]
+mod win;
+
+pub use crate::adapt::{DiskSizesExt, SizeSource, WithDiskSizes};
+#[cfg(feature = "archive")]
+pub use crate::archive::{archive_members, ArchiveMember};
+pub use crate::backend::{OsBackend, SizeBackend};
+pub use crate::background::BackgroundScanner;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub use crate::bulk::{read_dir_sizes, DirEntrySize, ReadDirSizes};
+pub use crate::cache::SizeCache;
+pub use crate::capabilities::{capabilities, Capabilities};
+pub use crate::channel::{ScanUpdate, UpdateSender};
+pub use crate::checkpoint::Checkpoint;
+pub use crate::clone::{clone_info, CloneInfo};
+pub use crate::collect::{Collector, DedupCandidates, DedupSet, GroupBy, TopK};
+pub use crate::compact::{CompactAlgorithm, CompactEstimate, CompactEstimator};
+pub use crate::compare::{allocation_delta, same_allocation};
+#[cfg(target_os = "linux")]
+pub use crate::compressed::{
+    compressed_size, AlgorithmUsage, CompressedSize, CompressionAlgorithm,
+};
+#[cfg(target_os = "linux")]
+pub use crate::deleted::{deleted_open_files, DeletedFile, DeletedFiles};
+pub use crate::du::DuFormat;
+pub use crate::error::{Error, Result};
+#[cfg(target_os = "android")]
+pub use crate::filesystem::emulated_storage_source;
+pub use crate::filesystem::{filesystem_kind, FilesystemKind};
+pub use crate::fragmentation::{
+    extent_map, fragmentation, FileExtent, Fragmentation, FragmentationReport,
+};
+pub use crate::granularity::{allocation_granularity, raw_allocation, Granularity, RawAllocation};
+pub use crate::growth::{DirectoryGrowth, GrowthReport, UsageSnapshot};
+#[cfg(feature = "disk-image")]
+pub use crate::image::{disk_image_info, DiskImage, DiskImageFormat};
+pub use crate::info::{SizeAccuracy, SizeInfo};
+pub use crate::link::{link_sizes, LinkSizes};
+pub use crate::metrics::{MetricsSink, ScanMetrics};
+#[cfg(feature = "mock")]
+pub use crate::mock::{MockGuard, MockSizes};
+pub use crate::options::{SizeOptions, SpecialFilePolicy};
+#[cfg(feature = "rayon")]
+pub use crate::parallel::Parallelism;
+#[cfg(windows)]
+pub use crate::privilege::enable_backup_privilege;
+pub use crate::projection::{SizeProjection, SizeProjector};
+#[cfg(target_os = "linux")]
+pub use crate::quota::{btrfs_qgroup_usage, project_quota_usage, ProjectUsage, QgroupUsage};
+pub use crate::reparse::{reparse_tag, ReparseTag};
+pub use crate::sample::SizeEstimate;
+pub use crate::scan::{
+    DiskUsage, Entry, ErrorPolicy, LinkPolicy, Order, Progress, Scan, Scanner, Timestamp,
+    DEFAULT_EXCLUDED_PATHS,
+};
+pub use crate::sized::SizeOnDisk;
+pub use crate::sparse::{is_fully_allocated, sparse_map, SparseMap};
+#[cfg(feature = "async")]
+pub use crate::stream::ScanStream;
+pub use crate::summary::ScanSummary;
+pub use crate::tree::{DiskUsageTree, Node};
+#[cfg(windows)]
+pub use crate::verbatim::{shadow_copy_path, verbatim_path};
+pub use crate::volume::{volume_space, VolumeSpace};
+#[cfg(feature = "notify")]
+pub use crate::watch::{SizeChange, ThresholdCrossing, Watch};
+
+#[cfg(unix)]
+fn c_path(path: &Path) -> std::io::Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
 #[cfg(unix)]
 mod imp {
     use super::*;
 
     use std::os::unix::fs::MetadataExt;
 
+    pub const ACCURACY: SizeAccuracy = SizeAccuracy::Blocks;
+
+    /// The unit of `st_blocks`, which POSIX leaves unspecified.  Every Unix
+    /// supported counts 512-byte blocks, whatever the sector size of the disk:
+    /// `DEV_BSIZE` on illumos and Solaris, and `UBSIZE` on AIX.  ZFS counts
+    /// the compressed size of each record, but only once written back, so
+    /// recent writes are missing unless flushed first.
+    pub const BLOCK_UNIT: u64 = 512;
+
     pub fn file_real_size<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
-        Ok(path.as_ref().symlink_metadata()?.blocks() * 512)
+        Ok(path
+            .as_ref()
+            .symlink_metadata()?
+            .blocks()
+            .saturating_mul(BLOCK_UNIT))
+    }
+
+    pub fn file_real_size_follow<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+        Ok(path
+            .as_ref()
+            .metadata()?
+            .blocks()
+            .saturating_mul(BLOCK_UNIT))
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", any(target_env = "gnu", target_env = "musl")),
+        target_os = "android"
+    ))]
+    pub fn file_real_size_synced(path: &Path) -> std::io::Result<u64> {
+        flush(path);
+
+        let path = c_path(path)?;
+        let mut stat: libc::statx = unsafe { std::mem::zeroed() };
+
+        let flags = libc::AT_SYMLINK_NOFOLLOW | libc::AT_STATX_FORCE_SYNC;
+        if unsafe {
+            libc::statx(
+                libc::AT_FDCWD,
+                path.as_ptr(),
+                flags,
+                libc::STATX_BLOCKS,
+                &mut stat,
+            )
+        } != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(stat.stx_blocks.saturating_mul(BLOCK_UNIT))
+    }
+
+    #[cfg(not(any(
+        all(target_os = "linux", any(target_env = "gnu", target_env = "musl")),
+        target_os = "android"
+    )))]
+    pub fn file_real_size_synced(path: &Path) -> std::io::Result<u64> {
+        flush(path);
+        file_real_size(path)
+    }
+
+    /// Flush any dirty data of the regular file at `path`, so filesystems
+    /// which allocate space as data is written back, such as ZFS, count it.
+    ///
+    /// Failures are ignored, as they only leave the size as fresh as it would
+    /// have been anyway.
+    fn flush(path: &Path) {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if !path.symlink_metadata().is_ok_and(|m| m.is_file()) {
+            return;
+        }
+
+        if let Ok(file) = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+            .open(path)
+        {
+            let _ = file.sync_all();
+        }
+    }
+
+    pub fn stat_with_size(path: &Path) -> std::io::Result<(Metadata, u64)> {
+        let metadata = path.symlink_metadata()?;
+        let size = file_real_size_fast(path, &metadata)?;
+        Ok((metadata, size))
     }
 
     pub fn file_real_size_fast<P: AsRef<Path>>(
         _path: P,
         metadata: &Metadata,
     ) -> std::io::Result<u64> {
-        Ok(metadata.blocks() * 512)
+        Ok(metadata.blocks().saturating_mul(BLOCK_UNIT))
+    }
+
+    pub fn file_real_size_at(dir: std::os::unix::io::RawFd, path: &Path) -> std::io::Result<u64> {
+        let path = c_path(path)?;
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::fstatat(dir, path.as_ptr(), &mut stat, libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok((stat.st_blocks as u64).saturating_mul(BLOCK_UNIT))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn block_device_size(path: &Path) -> std::io::Result<u64> {
+        use std::os::unix::io::AsRawFd;
+
+        const BLKGETSIZE64: libc::Ioctl = libc::_IOR::<u64>(0x12, 114);
+
+        let file = std::fs::File::open(path)?;
+        let mut size: u64 = 0;
+        if unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64, &mut size) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(size)
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn block_device_size(path: &Path) -> std::io::Result<u64> {
+        use std::os::unix::io::AsRawFd;
+
+        const DKIOCGETBLOCKSIZE: libc::c_ulong = libc::_IOR::<u32>(b'd' as libc::c_ulong, 24);
+        const DKIOCGETBLOCKCOUNT: libc::c_ulong = libc::_IOR::<u64>(b'd' as libc::c_ulong, 25);
+
+        let file = std::fs::File::open(path)?;
+        let mut block_size: u32 = 0;
+        let mut block_count: u64 = 0;
+        unsafe {
+            if libc::ioctl(file.as_raw_fd(), DKIOCGETBLOCKSIZE, &mut block_size) < 0
+                || libc::ioctl(file.as_raw_fd(), DKIOCGETBLOCKCOUNT, &mut block_count) < 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        Ok(block_count * u64::from(block_size))
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios"
+    )))]
+    pub fn block_device_size(path: &Path) -> std::io::Result<u64> {
+        use std::io::{Seek, SeekFrom};
+
+        std::fs::File::open(path)?.seek(SeekFrom::End(0))
     }
 }
 
@@ -77,16 +727,17 @@ mod imp {
 mod imp {
     use super::*;
 
-    use std::os::windows::ffi::OsStrExt;
+    use crate::win::{GetCompressedFileSizeW, INVALID_FILE_SIZE, NO_ERROR};
 
-    use winapi::shared::winerror::NO_ERROR;
-    use winapi::um::fileapi::{GetCompressedFileSizeW, INVALID_FILE_SIZE};
+    pub const ACCURACY: SizeAccuracy = SizeAccuracy::CompressedSize;
 
     pub fn file_real_size<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
-        let path = std::fs::canonicalize(path)?.into_os_string();
-        let mut pathw: Vec<u16> = Vec::with_capacity(path.len() + 1);
-        pathw.extend(path.encode_wide());
-        pathw.push(0);
+        let path = path.as_ref();
+        file_real_size_fast(path, &path.symlink_metadata()?)
+    }
+
+    fn compressed_file_size(path: &Path) -> std::io::Result<u64> {
+        let pathw = crate::verbatim::wide_path(path)?;
 
         let mut high: u32 = 0;
         let low = unsafe { GetCompressedFileSizeW(pathw.as_ptr(), &mut high) };
@@ -101,28 +752,332 @@ mod imp {
         Ok(u64::from(high) << 32 | u64::from(low))
     }
 
+    pub fn file_real_size_follow<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+        file_real_size(path)
+    }
+
+    pub fn file_real_size_synced(path: &Path) -> std::io::Result<u64> {
+        file_real_size(path)
+    }
+
+    pub fn file_quota_size(path: &Path) -> std::io::Result<u64> {
+        use std::os::windows::fs::OpenOptionsExt;
+        use std::os::windows::io::AsRawHandle;
+
+        use crate::win::{
+            FileStandardInfo, GetFileInformationByHandleEx, FILE_FLAG_BACKUP_SEMANTICS,
+            FILE_FLAG_OPEN_REPARSE_POINT, FILE_STANDARD_INFO,
+        };
+
+        let file = std::fs::OpenOptions::new()
+            .access_mode(0)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+            .open(path)?;
+
+        let mut info: FILE_STANDARD_INFO = unsafe { std::mem::zeroed() };
+        let ok = unsafe {
+            GetFileInformationByHandleEx(
+                file.as_raw_handle() as _,
+                FileStandardInfo,
+                &mut info as *mut _ as *mut _,
+                std::mem::size_of::<FILE_STANDARD_INFO>() as u32,
+            )
+        };
+
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(crate::win::large_integer(&info.AllocationSize) as u64)
+    }
+
+    pub fn file_valid_data_length(path: &Path) -> std::io::Result<u64> {
+        use std::os::windows::fs::OpenOptionsExt;
+        use std::os::windows::io::AsRawHandle;
+
+        use crate::win::{
+            DeviceIoControl, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT,
+            FSCTL_QUERY_FILE_REGIONS,
+        };
+
+        const FILE_REGION_USAGE_VALID_CACHED_DATA: u32 = 1;
+
+        #[repr(C)]
+        struct FileRegionInput {
+            offset: i64,
+            length: i64,
+            usage: u32,
+        }
+
+        #[repr(C)]
+        struct FileRegionOutput {
+            flags: u32,
+            total_regions: u32,
+            regions: u32,
+            reserved: u32,
+            offset: i64,
+            length: i64,
+            usage: u32,
+            reserved1: u32,
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+            .open(path)?;
+
+        let len = file.metadata()?.len();
+        if len == 0 {
+            return Ok(0);
+        }
+
+        // Data up to the valid data length is a single region from the start
+        // of the file.
+        let input = FileRegionInput {
+            offset: 0,
+            length: len as i64,
+            usage: FILE_REGION_USAGE_VALID_CACHED_DATA,
+        };
+        let mut output: FileRegionOutput = unsafe { std::mem::zeroed() };
+        let mut returned: u32 = 0;
+        let ok = unsafe {
+            DeviceIoControl(
+                file.as_raw_handle() as _,
+                FSCTL_QUERY_FILE_REGIONS,
+                &input as *const _ as *mut _,
+                std::mem::size_of::<FileRegionInput>() as u32,
+                &mut output as *mut _ as *mut _,
+                std::mem::size_of::<FileRegionOutput>() as u32,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        if output.regions == 0 {
+            Ok(0)
+        } else {
+            Ok((output.offset + output.length) as u64)
+        }
+    }
+
+    pub fn stat_with_size(path: &Path) -> std::io::Result<(Metadata, u64)> {
+        use std::os::windows::fs::OpenOptionsExt;
+        use std::os::windows::io::AsRawHandle;
+
+        use crate::win::{
+            FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, FILE_READ_ATTRIBUTES,
+        };
+
+        let file = std::fs::OpenOptions::new()
+            .access_mode(FILE_READ_ATTRIBUTES)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+            .open(path)?;
+        let metadata = file.metadata()?;
+
+        let size = if metadata.is_dir() || is_deduplicated(path, &metadata) {
+            file_quota_size(path)?
+        } else {
+            file_real_size_handle(file.as_raw_handle())?
+        };
+        Ok((metadata, size))
+    }
+
+    pub fn file_real_size_handle(handle: std::os::windows::io::RawHandle) -> std::io::Result<u64> {
+        use crate::win::{
+            FileCompressionInfo, GetFileInformationByHandleEx, FILE_COMPRESSION_INFO,
+        };
+
+        let mut info: FILE_COMPRESSION_INFO = unsafe { std::mem::zeroed() };
+        let ok = unsafe {
+            GetFileInformationByHandleEx(
+                handle as _,
+                FileCompressionInfo,
+                &mut info as *mut _ as *mut _,
+                std::mem::size_of::<FILE_COMPRESSION_INFO>() as u32,
+            )
+        };
+
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(crate::win::large_integer(&info.CompressedFileSize) as u64)
+    }
+
+    /// Deduplicated files are reparse points whose data lives in the chunk
+    /// store, for which `GetCompressedFileSizeW()` reports the logical size.
+    /// Their allocation size is the space they still hold locally.
+    ///
+    /// `GetCompressedFileSizeW()` fails on directories, which have an
+    /// allocation size of their own.
     pub fn file_real_size_fast<P: AsRef<Path>>(
         path: P,
-        _metadata: &Metadata,
+        metadata: &Metadata,
     ) -> std::io::Result<u64> {
+        let path = path.as_ref();
+        if metadata.is_dir() || is_deduplicated(path, metadata) {
+            file_quota_size(path)
+        } else {
+            compressed_file_size(path)
+        }
+    }
+
+    pub fn is_deduplicated(path: &Path, metadata: &Metadata) -> bool {
+        crate::reparse::tag_of(path, metadata) == Some(ReparseTag::Dedup)
+    }
+
+    pub fn reparse_tag(path: &Path) -> std::io::Result<u32> {
+        use std::os::windows::fs::OpenOptionsExt;
+        use std::os::windows::io::AsRawHandle;
+
+        use crate::win::{
+            FileAttributeTagInfo, GetFileInformationByHandleEx, FILE_ATTRIBUTE_TAG_INFO,
+            FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT,
+        };
+
+        let file = std::fs::OpenOptions::new()
+            .access_mode(0)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+            .open(path)?;
+
+        let mut info: FILE_ATTRIBUTE_TAG_INFO = unsafe { std::mem::zeroed() };
+        let ok = unsafe {
+            GetFileInformationByHandleEx(
+                file.as_raw_handle() as _,
+                FileAttributeTagInfo,
+                &mut info as *mut _ as *mut _,
+                std::mem::size_of::<FILE_ATTRIBUTE_TAG_INFO>() as u32,
+            )
+        };
+
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(info.ReparseTag)
+    }
+
+    pub fn block_device_size(path: &Path) -> std::io::Result<u64> {
+        use std::os::windows::io::AsRawHandle;
+
+        use crate::win::{DeviceIoControl, GET_LENGTH_INFORMATION, IOCTL_DISK_GET_LENGTH_INFO};
+
+        let file = std::fs::File::open(path)?;
+        let mut info: GET_LENGTH_INFORMATION = unsafe { std::mem::zeroed() };
+        let mut returned: u32 = 0;
+        let ok = unsafe {
+            DeviceIoControl(
+                file.as_raw_handle() as _,
+                IOCTL_DISK_GET_LENGTH_INFO,
+                std::ptr::null_mut(),
+                0,
+                &mut info as *mut _ as *mut _,
+                std::mem::size_of::<GET_LENGTH_INFORMATION>() as u32,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(crate::win::large_integer(&info.Length) as u64)
+    }
+}
+
+#[cfg(target_os = "wasi")]
+mod imp {
+    use super::*;
+
+    pub const ACCURACY: SizeAccuracy = SizeAccuracy::Length;
+
+    pub fn file_real_size<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+        Ok(path.as_ref().symlink_metadata()?.len())
+    }
+
+    pub fn file_real_size_follow<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+        Ok(path.as_ref().metadata()?.len())
+    }
+
+    pub fn file_real_size_synced(path: &Path) -> std::io::Result<u64> {
         file_real_size(path)
     }
+
+    pub fn stat_with_size(path: &Path) -> std::io::Result<(Metadata, u64)> {
+        let metadata = path.symlink_metadata()?;
+        let size = file_real_size_fast(path, &metadata)?;
+        Ok((metadata, size))
+    }
+
+    pub fn file_real_size_fast<P: AsRef<Path>>(
+        _path: P,
+        metadata: &Metadata,
+    ) -> std::io::Result<u64> {
+        Ok(metadata.len())
+    }
+
+    pub fn file_real_size_at(dir: std::os::wasi::io::RawFd, path: &Path) -> std::io::Result<u64> {
+        let path = path.to_str().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "path is not valid UTF-8")
+        })?;
+
+        unsafe { wasi::path_filestat_get(dir as wasi::Fd, 0, path) }
+            .map(|stat| stat.size)
+            .map_err(|e| std::io::Error::from_raw_os_error(e.raw() as i32))
+    }
+
+    pub fn block_device_size(path: &Path) -> std::io::Result<u64> {
+        use std::os::wasi::io::AsRawFd;
+
+        let file = std::fs::File::open(path)?;
+        unsafe { wasi::fd_filestat_get(file.as_raw_fd() as wasi::Fd) }
+            .map(|stat| stat.size)
+            .map_err(|e| std::io::Error::from_raw_os_error(e.raw() as i32))
+    }
 }
 
-#[cfg(not(any(windows, unix)))]
+#[cfg(not(any(windows, unix, target_os = "wasi")))]
 mod imp {
     use super::*;
 
+    pub const ACCURACY: SizeAccuracy = SizeAccuracy::Length;
+
     pub fn file_real_size<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
         Ok(path.as_ref().symlink_metadata()?.len())
     }
 
+    pub fn file_real_size_follow<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+        Ok(path.as_ref().metadata()?.len())
+    }
+
+    pub fn file_real_size_synced(path: &Path) -> std::io::Result<u64> {
+        file_real_size(path)
+    }
+
+    pub fn stat_with_size(path: &Path) -> std::io::Result<(Metadata, u64)> {
+        let metadata = path.symlink_metadata()?;
+        let size = file_real_size_fast(path, &metadata)?;
+        Ok((metadata, size))
+    }
+
     pub fn file_real_size_fast<P: AsRef<Path>>(
         _path: P,
         metadata: &Metadata,
     ) -> std::io::Result<u64> {
         Ok(metadata.len())
     }
+
+    pub fn block_device_size(_path: &Path) -> std::io::Result<u64> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "block devices are not supported on this platform",
+        ))
+    }
 }
 
 /// Get the on-disk size of the file at the given `path`.
@@ -133,8 +1088,50 @@ mod imp {
 /// # Ok(())
 /// # }
 /// ```
-pub fn file_real_size<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
-    self::imp::file_real_size(path)
+pub fn file_real_size<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path.as_ref();
+    OsBackend
+        .size_on_disk(path)
+        .map_err(|e| Error::from_io(path, e))
+}
+
+/// Get the on-disk size of the file at the given `path`, falling back to its
+/// logical length, or 0 if that can't be found either.
+///
+/// This is for user interfaces which would rather show a best-effort number
+/// than handle errors while rendering.  Symlinks are not followed.
+///
+/// ```rust
+/// let size = filesize::real_size_or_len("Cargo.toml");
+/// assert_eq!(filesize::real_size_or_len("no such file"), 0);
+/// ```
+pub fn real_size_or_len<P: AsRef<Path>>(path: P) -> u64 {
+    let path = path.as_ref();
+    file_real_size(path)
+        .or_else(|_| path.symlink_metadata().map(|metadata| metadata.len()))
+        .unwrap_or(0)
+}
+
+/// Get the metadata of the file at the given `path`, along with its on-disk
+/// size, without following symlinks.
+///
+/// This is the cheapest way to get both: on Unix the size is derived from the
+/// metadata of a single `lstat()`, while on Windows both come from one open
+/// handle, rather than from separate metadata and size queries by path.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let (metadata, realsize) = filesize::stat_with_size("Cargo.toml")?;
+/// println!("{} bytes, {} on disk", metadata.len(), realsize);
+/// # Ok(())
+/// # }
+/// ```
+pub fn stat_with_size<P: AsRef<Path>>(path: P) -> Result<(Metadata, u64)> {
+    let path = path.as_ref();
+    let (metadata, size) = imp::stat_with_size(path).map_err(|e| Error::from_io(path, e))?;
+    #[cfg(feature = "mock")]
+    let size = crate::mock::size_of(path).unwrap_or(size);
+    Ok((metadata, size))
 }
 
 /// Get the on-disk size of the file at the given `path`, using the provided
@@ -153,12 +1150,99 @@ pub fn file_real_size<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
 /// # Ok(())
 /// # }
 /// ```
-pub fn file_real_size_fast<P: AsRef<Path>>(path: P, metadata: &Metadata) -> std::io::Result<u64> {
-    self::imp::file_real_size_fast(path, metadata)
+pub fn file_real_size_fast<P: AsRef<Path>>(path: P, metadata: &Metadata) -> Result<u64> {
+    let path = path.as_ref();
+    OsBackend
+        .size_on_disk_fast(path, metadata)
+        .map_err(|e| Error::from_io(path, e))
+}
+
+/// Get the on-disk size of the file at the given `path`, following symlinks.
+///
+/// Where `file_real_size` reports the size of a symlink itself, this reports
+/// the size of the file it points to.  To get the same behaviour from
+/// `file_real_size_fast`, pass it metadata from `std::fs::metadata()` rather
+/// than `std::fs::symlink_metadata()`.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let realsize = filesize::file_real_size_follow("Cargo.toml")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn file_real_size_follow<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path.as_ref();
+    OsBackend
+        .size_on_disk_follow(path)
+        .map_err(|e| Error::from_io(path, e))
+}
+
+/// Get the on-disk size of the file at the given `path`, relative to the
+/// directory `dir`.
+///
+/// This uses `fstatat()`, or `path_filestat_get()` on WASI, so it is not
+/// subject to races with renames of the directory's ancestors, nor limited by
+/// `PATH_MAX`.  As with `file_real_size`, symlinks are not followed.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let dir = std::fs::File::open(".")?;
+/// let realsize = filesize::file_real_size_at(&dir, "Cargo.toml")?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(any(unix, target_os = "wasi"))]
+pub fn file_real_size_at<D: AsRawFd, P: AsRef<Path>>(dir: &D, path: P) -> Result<u64> {
+    let path = path.as_ref();
+    self::imp::file_real_size_at(dir.as_raw_fd(), path).map_err(|e| Error::from_io(path, e))
+}
+
+/// Get the size charged against disk quotas for the file at the given `path`.
+///
+/// NTFS charges quotas for the space a file would occupy uncompressed, so for
+/// compressed files this is larger than `file_real_size`, which reports the
+/// space actually allocated.  Symlinks are not followed.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let charged = filesize::file_quota_size("Cargo.toml")?;
+/// assert!(charged >= filesize::file_real_size("Cargo.toml")?);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(windows)]
+pub fn file_quota_size<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path.as_ref();
+    self::imp::file_quota_size(path).map_err(|e| Error::from_io(path, e))
+}
+
+/// Get the Valid Data Length of the file at the given `path`.
+///
+/// NTFS and ReFS track how much of a file has actually been written, and read
+/// anything beyond that as zeros without touching the disk.  Space allocated
+/// for a file with `SetEndOfFile()` or `SetFileValidData()` but not yet
+/// written lies between this and `file_quota_size`.  Symlinks are not
+/// followed, and reading the file must be permitted.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let valid = filesize::file_valid_data_length("Cargo.toml")?;
+/// assert!(valid <= std::fs::metadata("Cargo.toml")?.len());
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(windows)]
+pub fn file_valid_data_length<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path.as_ref();
+    self::imp::file_valid_data_length(path).map_err(|e| Error::from_io(path, e))
 }
 
 /// An extension trait for `std::path::Path` to retrieve the on-disk size of a
 /// given file.
+///
+/// This is also implemented for `PathBuf`, `Cow<Path>`, `Box<Path>`,
+/// `Rc<Path>`, `Arc<Path>`, and `OsStr`, so generic code can require it of
+/// whichever of them it holds.
 pub trait PathExt {
     /// Get the on-disk size of the file at the given `Path`.
     ///
@@ -171,7 +1255,7 @@ pub trait PathExt {
     /// # Ok(())
     /// # }
     /// ```
-    fn size_on_disk(&self) -> std::io::Result<u64>;
+    fn size_on_disk(&self) -> Result<u64>;
 
     /// Get the on-disk size of the file at the given `Path`, using the provided
     /// `std::fs::Metadata` instance if possible.
@@ -191,17 +1275,114 @@ pub trait PathExt {
     /// # Ok(())
     /// # }
     /// ```
-    fn size_on_disk_fast(&self, metadata: &Metadata) -> std::io::Result<u64>;
+    fn size_on_disk_fast(&self, metadata: &Metadata) -> Result<u64>;
+
+    /// Get the on-disk size of the file at the given `Path`, following symlinks.
+    ///
+    /// ```rust
+    /// use std::path::Path;
+    /// use filesize::PathExt;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let realsize = Path::new("Cargo.toml").size_on_disk_follow()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The default implementation sizes the path the type refers to through
+    /// `AsRef<Path>`, so implementations for other types predating this method
+    /// keep compiling.
+    fn size_on_disk_follow(&self) -> Result<u64>
+    where
+        Self: AsRef<Path>,
+    {
+        file_real_size_follow(self)
+    }
+}
+
+// Implement `PathExt` for a path-like type by way of its `AsRef<Path>`.
+macro_rules! impl_path_ext {
+    ($($(#[$attr:meta])* $ty:ty),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            impl PathExt for $ty {
+                fn size_on_disk(&self) -> Result<u64> {
+                    file_real_size(self)
+                }
+
+                fn size_on_disk_fast(&self, metadata: &Metadata) -> Result<u64> {
+                    file_real_size_fast(self, metadata)
+                }
+            }
+        )*
+    };
 }
 
-impl PathExt for Path {
-    fn size_on_disk(&self) -> std::io::Result<u64> {
-        file_real_size(self)
+impl_path_ext!(
+    Path,
+    std::path::PathBuf,
+    std::borrow::Cow<'_, Path>,
+    Box<Path>,
+    std::rc::Rc<Path>,
+    std::sync::Arc<Path>,
+    std::ffi::OsStr,
+    #[cfg(feature = "camino")]
+    camino::Utf8Path,
+    #[cfg(feature = "camino")]
+    camino::Utf8PathBuf,
+);
+
+#[test]
+fn path_like_types_are_sized() {
+    fn size<P: PathExt + AsRef<Path> + ?Sized>(path: &P) -> u64 {
+        path.size_on_disk_follow().expect("size_on_disk_follow")
     }
 
-    fn size_on_disk_fast(&self, metadata: &Metadata) -> std::io::Result<u64> {
-        file_real_size_fast(self, metadata)
+    let path = Path::new("Cargo.toml");
+    let expected = size(path);
+    assert_eq!(size(&path.to_path_buf()), expected);
+    assert_eq!(size(&std::borrow::Cow::Borrowed(path)), expected);
+    assert_eq!(size(&Box::<Path>::from(path)), expected);
+    assert_eq!(size(&std::rc::Rc::<Path>::from(path)), expected);
+    assert_eq!(size(&std::sync::Arc::<Path>::from(path)), expected);
+    assert_eq!(size(path.as_os_str()), expected);
+
+    // Implementations written before `size_on_disk_follow` still compile.
+    struct Manifest;
+    impl AsRef<Path> for Manifest {
+        fn as_ref(&self) -> &Path {
+            Path::new("Cargo.toml")
+        }
+    }
+    impl PathExt for Manifest {
+        fn size_on_disk(&self) -> Result<u64> {
+            file_real_size(self)
+        }
+
+        fn size_on_disk_fast(&self, metadata: &Metadata) -> Result<u64> {
+            file_real_size_fast(self, metadata)
+        }
     }
+    assert_eq!(size(&Manifest), expected);
+}
+
+#[test]
+fn real_sizes_fall_back_to_lengths() {
+    assert_eq!(
+        real_size_or_len("Cargo.toml"),
+        file_real_size("Cargo.toml").unwrap()
+    );
+    assert_eq!(real_size_or_len("no such file"), 0);
+}
+
+#[test]
+fn stat_with_size_matches_separate_calls() {
+    let (metadata, size) = stat_with_size("Cargo.toml").expect("stat_with_size");
+    assert_eq!(
+        metadata.len(),
+        std::fs::metadata("Cargo.toml").unwrap().len()
+    );
+    assert_eq!(size, file_real_size("Cargo.toml").expect("file_real_size"));
 }
 
 #[test]
@@ -214,3 +1395,101 @@ fn it_seems_to_work() {
                 .expect("size_on_disk_fast")
     );
 }
+
+#[cfg(test)]
+fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("filesize-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).expect("create_dir");
+    dir
+}
+
+#[cfg(unix)]
+#[test]
+fn block_counts_are_in_block_units() {
+    use std::io::Write;
+
+    // Incompressible data, so compressing filesystems such as ZFS and btrfs
+    // allocate it in full.
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let data: Vec<u8> = (0..1 << 20)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+
+    let dir = test_dir("block-units");
+    let path = dir.join("file");
+    let mut file = std::fs::File::create(&path).expect("create");
+    file.write_all(&data).expect("write_all");
+    file.sync_all().expect("sync_all");
+    drop(file);
+
+    let size = SizeOptions::new().force_sync(true).size_on_disk(&path);
+    let _ = std::fs::remove_dir_all(&dir);
+    let size = size.expect("size_on_disk");
+
+    // A unit twice too small or large would halve or double the size, well
+    // outside the slack for metadata and record padding.
+    let len = data.len() as u64;
+    assert_eq!(size % imp::BLOCK_UNIT, 0);
+    assert!(size <= len + len / 2, "{} bytes for {}", size, len);
+    assert!(size >= len - len / 4, "{} bytes for {}", size, len);
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_resolves_symlinks() {
+    let dir = test_dir("follow");
+    let link = dir.join("link");
+    std::os::unix::fs::symlink(std::env::current_dir().unwrap().join("Cargo.toml"), &link)
+        .expect("symlink");
+
+    let followed = link.size_on_disk_follow().expect("size_on_disk_follow");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(
+        followed,
+        Path::new("Cargo.toml")
+            .size_on_disk()
+            .expect("size_on_disk")
+    );
+}
+
+#[test]
+fn sparse_files_are_smaller() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let dir = test_dir("sparse");
+    let path = dir.join("sparse");
+    let mut file = testing::create_sparse(&path, 64 << 20).expect("create_sparse");
+    file.seek(SeekFrom::Start(1 << 20)).expect("seek");
+    file.write_all(b"data").expect("write_all");
+    file.sync_all().expect("sync_all");
+    drop(file);
+
+    let size = path.size_on_disk().expect("size_on_disk");
+    let len = path.metadata().expect("metadata").len();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(len, 64 << 20);
+    if imp::ACCURACY != SizeAccuracy::Length {
+        assert!(size < len);
+    }
+}
+
+#[test]
+fn directories_can_be_sized() {
+    let path = Path::new("src");
+    let size = path.size_on_disk().expect("size_on_disk");
+    let metadata = path.symlink_metadata().expect("stat");
+    assert_eq!(
+        size,
+        path.size_on_disk_fast(&metadata)
+            .expect("size_on_disk_fast")
+    );
+    assert_eq!(stat_with_size(path).expect("stat_with_size").1, size);
+}
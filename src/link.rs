@@ -0,0 +1,110 @@
+//! Sizing symlinks along with the files they point to.
+
+use std::path::Path;
+
+use crate::{Error, Result};
+
+/// The on-disk size of a symlink, and of its target if it could be resolved,
+/// from [`link_sizes`].
+#[derive(Debug)]
+pub struct LinkSizes {
+    is_symlink: bool,
+    link: u64,
+    target: Result<u64>,
+}
+
+impl LinkSizes {
+    /// Whether the path was a symlink.  If not, the link and target sizes are
+    /// both the size of the file itself.
+    pub fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+
+    /// The space used by the symlink itself.
+    pub fn link(&self) -> u64 {
+        self.link
+    }
+
+    /// The space used by the target of the symlink, or `None` if it could not
+    /// be resolved or sized.
+    pub fn target(&self) -> Option<u64> {
+        self.target.as_ref().ok().copied()
+    }
+
+    /// Why the target could not be sized, such as the link dangling or
+    /// forming a loop.
+    pub fn target_error(&self) -> Option<&Error> {
+        self.target.as_ref().err()
+    }
+}
+
+/// Get the on-disk size of the symlink at `path`, along with that of its
+/// target.
+///
+/// Only failure to size the link itself is returned as an error; a target
+/// which is missing or unreadable is reported through
+/// [`LinkSizes::target_error`].
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let sizes = filesize::link_sizes("Cargo.toml")?;
+/// match sizes.target() {
+///     Some(target) if sizes.is_symlink() => println!("{} link -> {} target", sizes.link(), target),
+///     Some(size) => println!("{} bytes", size),
+///     None => println!("{} link, dangling", sizes.link()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn link_sizes<P: AsRef<Path>>(path: P) -> Result<LinkSizes> {
+    let path = path.as_ref();
+    let (metadata, link) = crate::stat_with_size(path)?;
+
+    if !metadata.file_type().is_symlink() {
+        return Ok(LinkSizes {
+            is_symlink: false,
+            link,
+            target: Ok(link),
+        });
+    }
+
+    Ok(LinkSizes {
+        is_symlink: true,
+        link,
+        target: crate::file_real_size_follow(path),
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn links_and_targets_are_sized() {
+    let dir = crate::test_dir("link-sizes");
+    std::os::unix::fs::symlink(
+        std::env::current_dir().unwrap().join("Cargo.toml"),
+        dir.join("link"),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+
+    let link = link_sizes(dir.join("link")).unwrap();
+    let dangling = link_sizes(dir.join("dangling")).unwrap();
+    let file = link_sizes("Cargo.toml").unwrap();
+    let missing = link_sizes(dir.join("missing"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let real = crate::file_real_size("Cargo.toml").unwrap();
+    assert!(link.is_symlink());
+    assert_eq!(link.target(), Some(real));
+
+    assert!(dangling.is_symlink());
+    assert_eq!(dangling.target(), None);
+    assert!(matches!(
+        dangling.target_error(),
+        Some(Error::NotFound { .. })
+    ));
+
+    assert!(!file.is_symlink());
+    assert_eq!((file.link(), file.target()), (real, Some(real)));
+
+    assert!(matches!(missing, Err(Error::NotFound { .. })));
+}
@@ -0,0 +1,171 @@
+//! Throughput counters for long-running scans.
+//!
+//! A [`MetricsSink`] given to [`Scanner::metrics`](crate::Scanner::metrics) is
+//! told about each entry accounted for, each filesystem query issued, and each
+//! error encountered, so the health of a scan can be exported to a metrics
+//! system or logged.  [`ScanMetrics`] is a simple sink keeping running totals
+//! which can be read from another thread while the scan runs.
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use filesize::{ScanMetrics, Scanner};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let metrics = Arc::new(ScanMetrics::new());
+//! Scanner::new("src").metrics(Arc::clone(&metrics)).total()?;
+//!
+//! println!(
+//!     "{:.0} files/sec, {} queries",
+//!     metrics.files_per_sec(),
+//!     metrics.queries()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{Entry, Error};
+
+/// A receiver for counters from a running scan.
+///
+/// All methods have empty default implementations, so sinks need only
+/// implement those they are interested in.  They are called from the thread
+/// driving the scan, and should be cheap.
+pub trait MetricsSink: Send + Sync {
+    /// Called for each entry counted in the scan's usage totals.
+    fn entry(&self, _entry: &Entry) {}
+
+    /// Called when the scan issues filesystem queries, such as `stat` calls,
+    /// directory opens, and size lookups.  Each may take more than one
+    /// system call.
+    fn queried(&self, _count: u64) {}
+
+    /// Called for each error encountered, whether or not it is yielded.
+    fn error(&self, _error: &Error) {}
+}
+
+impl<T: MetricsSink + ?Sized> MetricsSink for Arc<T> {
+    fn entry(&self, entry: &Entry) {
+        (**self).entry(entry)
+    }
+
+    fn queried(&self, count: u64) {
+        (**self).queried(count)
+    }
+
+    fn error(&self, error: &Error) {
+        (**self).error(error)
+    }
+}
+
+/// A [`MetricsSink`] keeping running totals, and rates since its creation.
+#[derive(Debug)]
+pub struct ScanMetrics {
+    started: Instant,
+    files: AtomicU64,
+    directories: AtomicU64,
+    bytes: AtomicU64,
+    queries: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl Default for ScanMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScanMetrics {
+    /// Create a new set of counters, starting the clock for rates now.
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            files: AtomicU64::new(0),
+            directories: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            queries: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of non-directory entries accounted for.
+    pub fn files(&self) -> u64 {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    /// The number of directories accounted for.
+    pub fn directories(&self) -> u64 {
+        self.directories.load(Ordering::Relaxed)
+    }
+
+    /// The total size on disk accounted for.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The number of filesystem queries issued.
+    pub fn queries(&self) -> u64 {
+        self.queries.load(Ordering::Relaxed)
+    }
+
+    /// The number of errors encountered.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// The time since these counters were created.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The mean number of files accounted for per second.
+    pub fn files_per_sec(&self) -> f64 {
+        self.files() as f64 / self.elapsed().as_secs_f64()
+    }
+
+    /// The mean number of bytes on disk accounted for per second.
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes() as f64 / self.elapsed().as_secs_f64()
+    }
+}
+
+impl MetricsSink for ScanMetrics {
+    fn entry(&self, entry: &Entry) {
+        if entry.file_type().is_dir() {
+            self.directories.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.files.fetch_add(1, Ordering::Relaxed);
+        }
+        let size = entry.size_on_disk();
+        let _ = self
+            .bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bytes| {
+                Some(bytes.saturating_add(size))
+            });
+    }
+
+    fn queried(&self, count: u64) {
+        self.queries.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn error(&self, _error: &Error) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn metrics_are_counted() {
+    let metrics = Arc::new(ScanMetrics::new());
+    let usage = crate::Scanner::new("src")
+        .metrics(Arc::clone(&metrics))
+        .total()
+        .unwrap();
+
+    assert_eq!(metrics.files(), usage.files() + usage.symlinks());
+    assert_eq!(metrics.directories(), usage.directories());
+    assert_eq!(metrics.bytes(), usage.physical());
+    assert!(metrics.queries() >= usage.entries());
+}
@@ -0,0 +1,149 @@
+//! Substituting fake sizes for tests.
+//!
+//! A [`MockSizes`] installed on a thread overrides the on-disk size reported
+//! for the paths it knows about, through every path-based function in this
+//! crate and through [`Scanner`](crate::Scanner), so code built on it can be
+//! tested deterministically without creating sparse or compressed files.
+//! Paths it doesn't know about are sized as normal.
+//!
+//! ```rust
+//! use filesize::{MockSizes, PathExt};
+//! use std::path::Path;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let _mock = MockSizes::new().size("Cargo.toml", 1 << 20).install();
+//! assert_eq!(Path::new("Cargo.toml").size_on_disk()?, 1 << 20);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::{OsBackend, SizeAccuracy, SizeBackend};
+
+thread_local! {
+    static INSTALLED: RefCell<Option<Rc<MockSizes>>> = const { RefCell::new(None) };
+}
+
+/// A set of fake on-disk sizes, keyed by path.
+///
+/// Paths are matched exactly as they are passed in, or as they are joined
+/// onto the root of a [`Scanner`](crate::Scanner), without canonicalization.
+#[derive(Debug, Clone, Default)]
+pub struct MockSizes {
+    sizes: HashMap<PathBuf, u64>,
+}
+
+impl MockSizes {
+    /// Create an empty set of sizes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report `physical` bytes on disk for the file at `path`.
+    pub fn size<P: AsRef<Path>>(mut self, path: P, physical: u64) -> Self {
+        self.sizes.insert(path.as_ref().to_path_buf(), physical);
+        self
+    }
+
+    /// Use these sizes on the calling thread until the returned guard is
+    /// dropped.
+    ///
+    /// Other threads, such as those used by a parallel scan, are unaffected.
+    pub fn install(self) -> MockGuard {
+        let previous = INSTALLED.with(|installed| installed.replace(Some(Rc::new(self))));
+        MockGuard { previous }
+    }
+}
+
+/// Sizes paths it knows about from the mock, and others with [`OsBackend`],
+/// for use with [`Scanner::backend`](crate::Scanner::backend) when the mock
+/// needs to apply across threads.
+impl SizeBackend for MockSizes {
+    fn accuracy(&self) -> SizeAccuracy {
+        OsBackend.accuracy()
+    }
+
+    fn size_on_disk(&self, path: &Path) -> io::Result<u64> {
+        match self.sizes.get(path) {
+            Some(&size) => Ok(size),
+            None => OsBackend.size_on_disk(path),
+        }
+    }
+
+    fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+        match self.sizes.get(path) {
+            Some(&size) => Ok(size),
+            None => OsBackend.size_on_disk_follow(path),
+        }
+    }
+
+    fn size_on_disk_fast(&self, path: &Path, metadata: &Metadata) -> io::Result<u64> {
+        match self.sizes.get(path) {
+            Some(&size) => Ok(size),
+            None => OsBackend.size_on_disk_fast(path, metadata),
+        }
+    }
+}
+
+/// Keeps a [`MockSizes`] installed, restoring whatever was installed before
+/// when dropped.
+#[derive(Debug)]
+#[must_use = "the sizes are uninstalled when the guard is dropped"]
+pub struct MockGuard {
+    previous: Option<Rc<MockSizes>>,
+}
+
+impl Drop for MockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        INSTALLED.with(|installed| installed.replace(previous));
+    }
+}
+
+/// The fake size of `path`, if one is installed on this thread.
+pub(crate) fn size_of(path: &Path) -> Option<u64> {
+    INSTALLED.with(|installed| {
+        installed
+            .borrow()
+            .as_ref()
+            .and_then(|mock| mock.sizes.get(path).copied())
+    })
+}
+
+#[test]
+fn mocked_sizes_are_scoped() {
+    use crate::PathExt;
+
+    let real = Path::new("Cargo.toml").size_on_disk().unwrap();
+    {
+        let _outer = MockSizes::new().size("Cargo.toml", 1).install();
+        {
+            let _inner = MockSizes::new().size("Cargo.toml", 2).install();
+            assert_eq!(Path::new("Cargo.toml").size_on_disk().unwrap(), 2);
+        }
+        assert_eq!(Path::new("Cargo.toml").size_on_disk().unwrap(), 1);
+        assert_eq!(Path::new("README.md").size_on_disk().unwrap(), {
+            let meta = std::fs::symlink_metadata("README.md").unwrap();
+            Path::new("README.md").size_on_disk_fast(&meta).unwrap()
+        });
+    }
+    assert_eq!(Path::new("Cargo.toml").size_on_disk().unwrap(), real);
+
+    let mock = MockSizes::new().size("./src/lib.rs", 1 << 30);
+    let usage = crate::Scanner::new(".")
+        .max_depth(2)
+        .backend(mock.clone())
+        .total()
+        .unwrap();
+    assert!(usage.physical() >= 1 << 30);
+
+    let _mock = mock.install();
+    let usage = crate::Scanner::new(".").max_depth(2).total().unwrap();
+    assert!(usage.physical() >= 1 << 30);
+}
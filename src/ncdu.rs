@@ -0,0 +1,115 @@
+//! Exporting a [`DiskUsageTree`] in ncdu's JSON format.
+
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{DiskUsageTree, Node};
+
+impl DiskUsageTree {
+    /// Write the tree in the JSON format ncdu exports with `-o` and imports
+    /// with `-f`, so a scan taken elsewhere can be browsed with `ncdu -f`.
+    ///
+    /// Only the first root is written, as ncdu expects one per file.  Names
+    /// which are not valid Unicode are written lossily, and no device or
+    /// inode numbers are included, so ncdu won't recognise hard links.
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// let tree = filesize::Scanner::new("/var").tree()?;
+    /// let file = std::fs::File::create("var.ncdu")?;
+    /// tree.write_ncdu(std::io::BufWriter::new(file))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_ncdu<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let root = self.root().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "cannot export an empty tree")
+        })?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        writeln!(
+            writer,
+            "[1,2,{{\"progname\":\"filesize\",\"progver\":\"{}\",\"timestamp\":{}}},",
+            env!("CARGO_PKG_VERSION"),
+            timestamp
+        )?;
+        write_directory(&mut writer, root)?;
+        writeln!(writer, "]")?;
+        writer.flush()
+    }
+}
+
+/// Write a directory as ncdu does, as an array of its own information followed
+/// by its contents.  The root is always written as a directory.
+fn write_directory<W: Write>(writer: &mut W, node: Node<'_>) -> io::Result<()> {
+    writer.write_all(b"[")?;
+    write_info(writer, node)?;
+    for child in node.children() {
+        writer.write_all(b",\n")?;
+        if child.is_dir() {
+            write_directory(writer, child)?;
+        } else {
+            write_info(writer, child)?;
+        }
+    }
+    writer.write_all(b"]")
+}
+
+fn write_info<W: Write>(writer: &mut W, node: Node<'_>) -> io::Result<()> {
+    let info = node.size_info();
+    writer.write_all(b"{\"name\":")?;
+    write_string(writer, &node.name().to_string_lossy())?;
+    write!(
+        writer,
+        ",\"asize\":{},\"dsize\":{}",
+        info.logical(),
+        info.physical()
+    )?;
+    if !node.is_dir() && !node.is_file() {
+        writer.write_all(b",\"notreg\":true")?;
+    }
+    writer.write_all(b"}")
+}
+
+fn write_string<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            '\n' => writer.write_all(b"\\n")?,
+            '\r' => writer.write_all(b"\\r")?,
+            '\t' => writer.write_all(b"\\t")?,
+            c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    writer.write_all(b"\"")
+}
+
+#[test]
+fn trees_are_exported_for_ncdu() {
+    let dir = crate::test_dir("ncdu");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/a \"quoted\" name"), b"hello").unwrap();
+    std::fs::write(dir.join("file"), b"!").unwrap();
+
+    let tree = crate::Scanner::new(&dir).tree().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut out = vec![];
+    tree.write_ncdu(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("[1,2,{\"progname\":\"filesize\""));
+    assert!(out.ends_with("]]\n"));
+    assert!(out.contains(&format!("[{{\"name\":\"{}\",", dir.display())));
+    assert!(out.contains("[{\"name\":\"sub\","));
+    assert!(out.contains("{\"name\":\"a \\\"quoted\\\" name\",\"asize\":5,"));
+    assert!(out.contains("{\"name\":\"file\",\"asize\":1,"));
+    assert_eq!(out.matches('[').count(), out.matches(']').count());
+
+    assert!(DiskUsageTree::new().write_ncdu(vec![]).is_err());
+}
@@ -0,0 +1,723 @@
+use std::fs::{FileType, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{Error, OsBackend, ReparseTag, Result, SizeAccuracy, SizeBackend, SizeInfo};
+
+/// How to report the size of special files: FIFOs, sockets, and character
+/// and block devices.
+///
+/// These have no meaningful size on disk, and what the platform reports for
+/// them varies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialFilePolicy {
+    /// Report them as using no space.
+    Zero,
+    /// Fail with `Error::UnsupportedFileType`.
+    Error,
+    /// Report whatever the platform does, as the standalone functions do.
+    Raw,
+}
+
+/// Options for querying the on-disk size of a file, in the style of
+/// `std::fs::OpenOptions`.
+///
+/// ```rust
+/// use filesize::{SizeOptions, SpecialFilePolicy};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let realsize = SizeOptions::new()
+///     .special_files(SpecialFilePolicy::Zero)
+///     .size_on_disk("Cargo.toml")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeOptions {
+    special_files: SpecialFilePolicy,
+    block_device_capacity: bool,
+    allocation_unit: Option<u64>,
+    force_sync: bool,
+    retries: u32,
+    retry_delay: Duration,
+    backup_semantics: bool,
+    strict: bool,
+}
+
+impl Default for SizeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SizeOptions {
+    /// Create a new set of options, matching the behaviour of `file_real_size`.
+    pub fn new() -> Self {
+        Self {
+            special_files: SpecialFilePolicy::Raw,
+            block_device_capacity: false,
+            allocation_unit: None,
+            force_sync: false,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            backup_semantics: false,
+            strict: false,
+        }
+    }
+
+    /// Set how special files are reported.  Defaults to `SpecialFilePolicy::Raw`.
+    pub fn special_files(mut self, policy: SpecialFilePolicy) -> Self {
+        self.special_files = policy;
+        self
+    }
+
+    /// Report the capacity of block devices, rather than treating them as
+    /// special files.  Defaults to `false`.
+    ///
+    /// On Windows, device namespace paths such as `\\.\PhysicalDrive0` are
+    /// considered block devices.
+    pub fn block_device_capacity(mut self, enable: bool) -> Self {
+        self.block_device_capacity = enable;
+        self
+    }
+
+    /// Where the real allocation of a file is unavailable, estimate it by
+    /// rounding its logical length up to a multiple of `unit`, rather than
+    /// reporting the length as-is.
+    ///
+    /// This applies on platforms without a specific implementation, and on
+    /// Windows where the filesystem reports no more than the logical length,
+    /// as is the case on FAT and exFAT volumes.
+    pub fn estimate_allocation(mut self, unit: u64) -> Self {
+        self.allocation_unit = Some(unit.max(1));
+        self
+    }
+
+    /// Synchronise with the storage holding each file before finding its size,
+    /// ignoring any metadata given.  Defaults to `false`.
+    ///
+    /// Block counts cached by network filesystems such as NFS are frequently
+    /// stale.  On Linux and Android this fetches fresh attributes from the
+    /// server with `AT_STATX_FORCE_SYNC`, at the cost of a round trip for each
+    /// file.  On Unix platforms regular files are also flushed with `fsync()`,
+    /// so filesystems such as ZFS, which allocate space as data is written back,
+    /// count recent writes.  It has no effect elsewhere.
+    pub fn force_sync(mut self, enable: bool) -> Self {
+        self.force_sync = enable;
+        self
+    }
+
+    /// Retry up to `retries` times after transient failures, waiting `delay`
+    /// before the first retry and doubling it before each one after.  Defaults
+    /// to no retries.
+    ///
+    /// Failures are transient if they are interrupted system calls or would
+    /// block, or on Windows are sharing or lock violations, as antivirus and
+    /// backup software holding files open often cause.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use filesize::SizeOptions;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let realsize = SizeOptions::new()
+    ///     .retry(3, Duration::from_millis(10))
+    ///     .size_on_disk("Cargo.toml")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn retry(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// On Windows, retry files which can't be sized for lack of access by
+    /// opening them with `FILE_FLAG_BACKUP_SEMANTICS`.  Defaults to `false`.
+    ///
+    /// With `SeBackupPrivilege` enabled, as by [`enable_backup_privilege`],
+    /// this measures files the user couldn't otherwise read, such as those in
+    /// other users' profiles.  It only applies to the operating system's sizes,
+    /// whatever the backend, and has no effect elsewhere.
+    ///
+    /// [`enable_backup_privilege`]: crate::enable_backup_privilege
+    pub fn backup_semantics(mut self, enable: bool) -> Self {
+        self.backup_semantics = enable;
+        self
+    }
+
+    /// Fail with `Error::PlatformLimitation` wherever the real allocation of
+    /// a file is unavailable, rather than reporting an estimate from its
+    /// logical length.  Defaults to `false`.
+    ///
+    /// This applies on platforms without a specific implementation, such as
+    /// WASI, to backends reporting `SizeAccuracy::Length`, and overrides
+    /// `estimate_allocation`.  Sizes substituted by a `SpecialFilePolicy` are
+    /// still reported.
+    ///
+    /// ```rust
+    /// use filesize::{Error, SizeOptions};
+    ///
+    /// match SizeOptions::new().strict(true).size_on_disk("Cargo.toml") {
+    ///     Ok(size) => println!("{} bytes on disk", size),
+    ///     Err(Error::PlatformLimitation { .. }) => println!("size on disk unknown"),
+    ///     Err(e) => println!("{}", e),
+    /// }
+    /// ```
+    pub fn strict(mut self, enable: bool) -> Self {
+        self.strict = enable;
+        self
+    }
+
+    /// Call `f` until it succeeds, fails with a permanent error, or runs out
+    /// of retries.
+    fn retrying<T, F>(&self, mut f: F) -> io::Result<T>
+    where
+        F: FnMut() -> io::Result<T>,
+    {
+        let mut delay = self.retry_delay;
+        let mut retries = self.retries;
+        loop {
+            match f() {
+                Err(e) if retries > 0 && is_transient(&e) => {
+                    event!(tracing::Level::DEBUG, error = %e, ?delay, "retrying");
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                    retries -= 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Get the on-disk size of the file at the given `path`.
+    pub fn size_on_disk<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+        if *self == Self::new() && !is_descriptor_link(path.as_ref()) {
+            return crate::file_real_size(path);
+        }
+
+        self.size_info(path).map(|info| info.physical())
+    }
+
+    /// Get the on-disk size of the file at the given `path`, using the provided
+    /// `std::fs::Metadata` instance if possible.
+    pub fn size_on_disk_fast<P: AsRef<Path>>(&self, path: P, metadata: &Metadata) -> Result<u64> {
+        self.size_info_fast(path, metadata)
+            .map(|info| info.physical())
+    }
+
+    /// Get the on-disk and logical sizes of the file at the given `path`, along
+    /// with how they were determined.
+    ///
+    /// ```rust
+    /// # fn main() -> std::io::Result<()> {
+    /// let info = filesize::SizeOptions::new().size_info("Cargo.toml")?;
+    /// if !info.accuracy().is_exact() {
+    ///     println!("{} bytes (estimated)", info.physical());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn size_info<P: AsRef<Path>>(&self, path: P) -> Result<SizeInfo> {
+        let path = path.as_ref();
+
+        if self.block_device_capacity && is_device_path(path) {
+            let size = crate::imp::block_device_size(path).map_err(|e| Error::from_io(path, e))?;
+            return Ok(SizeInfo::new(size, size, SizeAccuracy::DeviceCapacity));
+        }
+
+        let mut metadata = self
+            .retrying(|| path.symlink_metadata())
+            .map_err(|e| Error::from_io(path, e))?;
+        // A descriptor link stands for the open file, which may be a memfd or
+        // a deleted file with no other name to size it by.
+        if metadata.file_type().is_symlink() && is_descriptor_link(path) {
+            metadata = self
+                .retrying(|| path.metadata())
+                .map_err(|e| Error::from_io(path, e))?;
+        }
+        self.size_info_fast(path, &metadata)
+    }
+
+    /// Get the on-disk and logical sizes of the file at the given `path`, along
+    /// with how they were determined, using the provided `std::fs::Metadata`
+    /// instance if possible.
+    pub fn size_info_fast<P: AsRef<Path>>(&self, path: P, metadata: &Metadata) -> Result<SizeInfo> {
+        let path = path.as_ref();
+        // Only files in /dev/shm and descriptor links are checked, sparing a
+        // statfs() for every file on btrfs, overlayfs, or NFS, which have
+        // anonymous device numbers just as memory filesystems do.
+        let memory_backed = (path.starts_with("/dev/shm") || is_descriptor_link(path))
+            && crate::filesystem::is_memory_backed(path, metadata);
+        self.size_info_with(&OsBackend, path, metadata)
+            .map(|info| info.with_memory_backed(memory_backed))
+    }
+
+    /// As `size_info_fast`, but finding the on-disk size of regular files,
+    /// directories, and symlinks with `backend`.
+    pub fn size_info_with<B, P>(
+        &self,
+        backend: &B,
+        path: P,
+        metadata: &Metadata,
+    ) -> Result<SizeInfo>
+    where
+        B: SizeBackend + ?Sized,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        if self.block_device_capacity
+            && (is_block_device(metadata.file_type()) || is_device_path(path))
+        {
+            let size = crate::imp::block_device_size(path).map_err(|e| Error::from_io(path, e))?;
+            event!(tracing::Level::TRACE, path = %path.display(), size, "sized block device");
+            return Ok(SizeInfo::new(size, size, SizeAccuracy::DeviceCapacity));
+        }
+
+        if is_special(metadata.file_type()) {
+            match self.special_files {
+                SpecialFilePolicy::Zero => {
+                    return Ok(SizeInfo::new(0, metadata.len(), SizeAccuracy::Policy))
+                }
+                SpecialFilePolicy::Error => {
+                    return Err(Error::UnsupportedFileType {
+                        path: path.to_path_buf(),
+                    })
+                }
+                SpecialFilePolicy::Raw => (),
+            }
+        }
+
+        let physical = self
+            .retrying(|| {
+                if self.force_sync {
+                    backend.size_on_disk_synced(path)
+                } else {
+                    backend.size_on_disk_fast(path, metadata)
+                }
+            })
+            .or_else(|e| self.backup_fallback(path, e))
+            .map_err(|e| Error::from_io(path, e))?;
+        let logical = metadata.len();
+
+        let reparse_tag = crate::reparse::tag_of(path, metadata);
+        let accuracy = match backend.accuracy() {
+            SizeAccuracy::CompressedSize if reparse_tag == Some(ReparseTag::Dedup) => {
+                SizeAccuracy::Deduplicated
+            }
+            accuracy => accuracy,
+        };
+
+        // Strict sizes are never rounded, so exact sizes equal to the length
+        // are kept rather than rejected as estimates.
+        let info = match (self.allocation_unit, accuracy) {
+            (Some(unit), SizeAccuracy::Length) | (Some(unit), SizeAccuracy::CompressedSize)
+                if physical == logical && !self.strict =>
+            {
+                let rounded = logical.div_ceil(unit).saturating_mul(unit);
+                SizeInfo::new(rounded, logical, SizeAccuracy::RoundedLength)
+            }
+            (_, SizeAccuracy::Blocks) => SizeInfo::new(physical, logical, SizeAccuracy::Blocks)
+                .with_preallocated(preallocated(path, metadata, physical)),
+            (_, accuracy) => SizeInfo::new(physical, logical, accuracy),
+        }
+        .with_reparse_tag(reparse_tag);
+
+        if self.strict && info.accuracy() == SizeAccuracy::Length {
+            return Err(Error::from_io(
+                path,
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "the allocated size is unavailable",
+                ),
+            ));
+        }
+
+        event!(
+            tracing::Level::TRACE,
+            path = %path.display(),
+            physical = info.physical(),
+            accuracy = ?info.accuracy(),
+            "sized file"
+        );
+
+        Ok(info)
+    }
+}
+
+impl SizeOptions {
+    /// Size a file which failed with `err` by opening it for backup, if that
+    /// is enabled and might help.
+    #[cfg(windows)]
+    fn backup_fallback(&self, path: &Path, err: io::Error) -> io::Result<u64> {
+        if self.backup_semantics && err.kind() == io::ErrorKind::PermissionDenied {
+            crate::imp::stat_with_size(path).map(|(_, size)| size)
+        } else {
+            Err(err)
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn backup_fallback(&self, _path: &Path, err: io::Error) -> io::Result<u64> {
+        Err(err)
+    }
+}
+
+/// Whether `err` is likely to go away if the operation is simply retried.
+fn is_transient(err: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        use crate::win::{ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION};
+
+        if let Some(code) = err.raw_os_error() {
+            if code == ERROR_SHARING_VIOLATION as i32 || code == ERROR_LOCK_VIOLATION as i32 {
+                return true;
+            }
+        }
+    }
+
+    // EINTR and EAGAIN on Unix.
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(unix)]
+fn is_special(file_type: FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    file_type.is_fifo()
+        || file_type.is_socket()
+        || file_type.is_char_device()
+        || file_type.is_block_device()
+}
+
+#[cfg(not(unix))]
+fn is_special(_file_type: FileType) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_block_device(file_type: FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    file_type.is_block_device()
+}
+
+#[cfg(not(unix))]
+fn is_block_device(_file_type: FileType) -> bool {
+    false
+}
+
+/// The space allocated to a regular file beyond the block containing its end,
+/// as by `fallocate()` with `FALLOC_FL_KEEP_SIZE`.
+///
+/// This is whatever `physical` exceeds the length rounded up to the block
+/// size by, confirmed on Linux and Android by mapping the extents past the end
+/// of the file, which also discounts blocks used for indirect block maps.
+#[cfg(unix)]
+fn preallocated(path: &Path, metadata: &Metadata, physical: u64) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    if !metadata.is_file() {
+        return 0;
+    }
+
+    let block = metadata.blksize().max(512);
+    let end = metadata.len().div_ceil(block).saturating_mul(block);
+    match physical.saturating_sub(end) {
+        0 => 0,
+        excess => mapped_beyond(path, end).unwrap_or(excess),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mapped_beyond(path: &Path, end: u64) -> Option<u64> {
+    crate::extents::mapped_from(path, end).ok()
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn mapped_beyond(_path: &Path, _end: u64) -> Option<u64> {
+    None
+}
+
+#[cfg(not(unix))]
+fn preallocated(_path: &Path, _metadata: &Metadata, _physical: u64) -> u64 {
+    0
+}
+
+#[cfg(windows)]
+fn is_device_path(path: &Path) -> bool {
+    use std::os::windows::ffi::OsStrExt;
+
+    let prefix: Vec<u16> = r"\\.\".encode_utf16().collect();
+    path.as_os_str().encode_wide().take(4).eq(prefix)
+}
+
+#[cfg(not(windows))]
+fn is_device_path(_path: &Path) -> bool {
+    false
+}
+
+/// Whether `path` is a link to an open file descriptor, such as
+/// `/proc/self/fd/3` or `/dev/fd/3`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_descriptor_link(path: &Path) -> bool {
+    use std::ffi::OsStr;
+
+    let is_number = |part: &OsStr| {
+        part.to_str()
+            .is_some_and(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+    };
+    let parts: Vec<&OsStr> = path.iter().collect();
+    match parts.as_slice() {
+        [root, dev, fd, n] => *root == "/" && *dev == "dev" && *fd == "fd" && is_number(n),
+        [root, proc, pid, fd, n] => {
+            *root == "/"
+                && *proc == "proc"
+                && (is_number(pid) || *pid == "self" || *pid == "thread-self")
+                && *fd == "fd"
+                && is_number(n)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn is_descriptor_link(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+#[test]
+fn special_file_policy() {
+    let null = Path::new("/dev/null");
+    let options = SizeOptions::new();
+
+    assert_eq!(
+        options
+            .special_files(SpecialFilePolicy::Zero)
+            .size_on_disk(null)
+            .unwrap(),
+        0
+    );
+    assert!(matches!(
+        options
+            .special_files(SpecialFilePolicy::Error)
+            .size_on_disk(null),
+        Err(Error::UnsupportedFileType { .. })
+    ));
+}
+
+#[test]
+fn forced_sync_matches_cached_sizes() {
+    let options = SizeOptions::new();
+    assert_eq!(
+        options.force_sync(true).size_on_disk("Cargo.toml").unwrap(),
+        options.size_on_disk("Cargo.toml").unwrap()
+    );
+}
+
+#[test]
+fn estimated_allocation_only_applies_without_real_sizes() {
+    let info = SizeOptions::new()
+        .estimate_allocation(1 << 20)
+        .size_info("Cargo.toml")
+        .unwrap();
+
+    match crate::imp::ACCURACY {
+        SizeAccuracy::Length => {
+            assert_eq!(info.accuracy(), SizeAccuracy::RoundedLength);
+            assert_eq!(info.physical(), 1 << 20);
+        }
+        accuracy if accuracy != SizeAccuracy::CompressedSize => {
+            assert_eq!(info.accuracy(), accuracy);
+        }
+        _ => (),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn preallocated_space_is_reported() {
+    use std::os::unix::io::AsRawFd;
+
+    let dir = crate::test_dir("preallocated");
+    let path = dir.join("file");
+    let file = std::fs::File::create(&path).unwrap();
+    std::io::Write::write_all(&mut &file, b"data").unwrap();
+    let ok = unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, 1 << 20) };
+    drop(file);
+
+    let info = SizeOptions::new().size_info(&path).unwrap();
+    let plain = SizeOptions::new().size_info("Cargo.toml").unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(plain.preallocated_bytes(), 0);
+    if ok == 0 {
+        assert_eq!(info.logical(), 4);
+        assert!(info.preallocated_bytes() >= 1 << 19);
+        assert!(info.preallocated_bytes() < info.physical());
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn memfds_are_sized_through_descriptor_links() {
+    use std::os::unix::io::FromRawFd;
+
+    let fd = unsafe { libc::memfd_create(b"filesize\0".as_ptr().cast(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return;
+    }
+    let file = unsafe { std::fs::File::from_raw_fd(fd) };
+    std::io::Write::write_all(&mut &file, &[1; 64 << 10]).unwrap();
+
+    let options = SizeOptions::new();
+    let link = format!("/proc/self/fd/{}", fd);
+    let info = options.size_info(&link).unwrap();
+    let size = options.size_on_disk(&link).unwrap();
+    let dev_fd = options.size_info(format!("/dev/fd/{}", fd)).unwrap();
+    drop(file);
+
+    assert_eq!(info.logical(), 64 << 10);
+    assert!(info.physical() >= 64 << 10);
+    assert!(info.is_memory_backed());
+    assert_eq!(size, info.physical());
+    assert_eq!(dev_fd, info);
+    assert!(!options.size_info("Cargo.toml").unwrap().is_memory_backed());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn shared_memory_files_are_flagged() {
+    if crate::filesystem_kind("/dev/shm").ok() != Some(crate::FilesystemKind::Memory) {
+        return;
+    }
+    let path = Path::new("/dev/shm").join(format!("filesize-shm-{}", std::process::id()));
+    std::fs::write(&path, vec![1u8; 8192]).unwrap();
+
+    let info = SizeOptions::new().size_info(&path);
+    let _ = std::fs::remove_file(&path);
+
+    assert!(info.unwrap().is_memory_backed());
+}
+
+#[test]
+fn transient_errors_are_retried() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails with `EINTR` a given number of times before reporting a size.
+    struct Flaky(AtomicU32);
+
+    impl SizeBackend for Flaky {
+        fn accuracy(&self) -> SizeAccuracy {
+            SizeAccuracy::Length
+        }
+
+        fn size_on_disk(&self, _path: &Path) -> io::Result<u64> {
+            let failed = self
+                .0
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+            match failed {
+                Ok(_) => Err(io::ErrorKind::Interrupted.into()),
+                Err(_) => Ok(42),
+            }
+        }
+
+        fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+            self.size_on_disk(path)
+        }
+    }
+
+    let metadata = Path::new("Cargo.toml").symlink_metadata().unwrap();
+    let options = SizeOptions::new().retry(2, Duration::ZERO);
+    let size = |failures| {
+        options
+            .size_info_with(&Flaky(AtomicU32::new(failures)), "Cargo.toml", &metadata)
+            .map(|info| info.physical())
+    };
+
+    assert_eq!(size(2).unwrap(), 42);
+    assert!(size(3).is_err());
+    assert!(!is_transient(&io::ErrorKind::NotFound.into()));
+}
+
+#[test]
+fn strict_sizes_are_never_estimated() {
+    /// Reports the logical length, as platforms without allocation sizes do.
+    struct Lengths;
+
+    impl SizeBackend for Lengths {
+        fn accuracy(&self) -> SizeAccuracy {
+            SizeAccuracy::Length
+        }
+
+        fn size_on_disk(&self, path: &Path) -> io::Result<u64> {
+            Ok(path.symlink_metadata()?.len())
+        }
+
+        fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+            Ok(path.metadata()?.len())
+        }
+    }
+
+    let metadata = Path::new("Cargo.toml").symlink_metadata().unwrap();
+    let strict = SizeOptions::new().strict(true);
+    let estimated = strict.estimate_allocation(4096);
+
+    assert!(SizeOptions::new()
+        .size_info_with(&Lengths, "Cargo.toml", &metadata)
+        .is_ok());
+    assert!(matches!(
+        strict.size_info_with(&Lengths, "Cargo.toml", &metadata),
+        Err(Error::PlatformLimitation { .. })
+    ));
+    assert!(matches!(
+        estimated.size_info_with(&Lengths, "Cargo.toml", &metadata),
+        Err(Error::PlatformLimitation { .. })
+    ));
+    assert_eq!(
+        strict.size_info("Cargo.toml").is_ok(),
+        crate::imp::ACCURACY.is_exact()
+    );
+}
+
+#[test]
+fn strict_sizes_keep_exact_lengths() {
+    /// Reports an exact size equal to the logical length, as Windows does for
+    /// files on FAT.
+    struct Compressed;
+
+    impl SizeBackend for Compressed {
+        fn accuracy(&self) -> SizeAccuracy {
+            SizeAccuracy::CompressedSize
+        }
+
+        fn size_on_disk(&self, path: &Path) -> io::Result<u64> {
+            Ok(path.symlink_metadata()?.len())
+        }
+
+        fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+            Ok(path.metadata()?.len())
+        }
+    }
+
+    let metadata = Path::new("Cargo.toml").symlink_metadata().unwrap();
+    let options = SizeOptions::new().estimate_allocation(1 << 20);
+    let rounded = options
+        .clone()
+        .size_info_with(&Compressed, "Cargo.toml", &metadata)
+        .unwrap();
+    let strict = options
+        .strict(true)
+        .size_info_with(&Compressed, "Cargo.toml", &metadata)
+        .unwrap();
+
+    assert_eq!(rounded.accuracy(), SizeAccuracy::RoundedLength);
+    assert_eq!(strict.accuracy(), SizeAccuracy::CompressedSize);
+    assert_eq!(strict.physical(), metadata.len());
+}
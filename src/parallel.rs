@@ -0,0 +1,221 @@
+//! Totalling directory trees across multiple threads.
+//!
+//! A [`Parallelism`] given to [`Scanner::parallelism`] splits the tree into
+//! tasks run on a `rayon` thread pool when it is totalled.
+//!
+//! ```rust
+//! use filesize::{Parallelism, Scanner};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let usage = Scanner::new("src")
+//!     .parallelism(Parallelism::new().threads(4))
+//!     .total()?;
+//! println!("{} bytes on disk", usage.physical());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rayon::{Scope, ThreadPool, ThreadPoolBuilder};
+
+use crate::scan::Scan;
+use crate::{DiskUsage, Error, Result, Scanner};
+
+/// How to divide a scan between threads.
+#[derive(Debug, Clone)]
+pub struct Parallelism {
+    threads: Option<usize>,
+    pool: Option<Arc<ThreadPool>>,
+    split_depth: usize,
+}
+
+impl Default for Parallelism {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parallelism {
+    /// Run on the global `rayon` thread pool, with a task for each directory
+    /// in the top two levels of the tree.
+    pub fn new() -> Self {
+        Self {
+            threads: None,
+            pool: None,
+            split_depth: 2,
+        }
+    }
+
+    /// Run on a new thread pool of `threads` threads, created for each scan.
+    /// Ignored if a [`pool`](Self::pool) is given.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// Run on an existing thread pool.
+    pub fn pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Give each directory less than `depth` levels below the root a task of
+    /// its own, with everything deeper scanned by the task of its ancestor at
+    /// that depth.  Defaults to 2.
+    ///
+    /// Deeper splits balance work between threads better on uneven trees, at
+    /// the cost of more tasks.  A depth of 0 runs the scan in a single task.
+    pub fn split_depth(mut self, depth: usize) -> Self {
+        self.split_depth = depth;
+        self
+    }
+
+    pub(crate) fn total(&self, scanner: Scanner) -> Result<DiskUsage> {
+        let totals = Totals {
+            usage: Mutex::new(DiskUsage::default()),
+            error: Mutex::new(None),
+            failed: AtomicBool::new(false),
+        };
+        let pool = match (&self.pool, self.threads) {
+            (None, Some(threads)) => Some(Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|e| Error::from_io(scanner.root(), io::Error::other(e)))?,
+            )),
+            (pool, _) => pool.clone(),
+        };
+        let scan = Scan::new(Arc::new(scanner), Arc::default())
+            .root()
+            .split_above(self.split_depth);
+
+        let run = || rayon::scope(|scope| self.run(scope, scan, &totals));
+        match pool {
+            Some(pool) => pool.install(run),
+            None => run(),
+        }
+
+        match totals.error.into_inner() {
+            Ok(Some(error)) => Err(error),
+            _ => Ok(totals.usage.into_inner().unwrap_or_default()),
+        }
+    }
+
+    fn run<'s>(&'s self, scope: &Scope<'s>, mut scan: Scan, totals: &'s Totals) {
+        while !totals.failed.load(Ordering::Relaxed) {
+            let next = scan.next();
+            for (path, depth) in scan.take_split() {
+                self.spawn(scope, &scan, path, depth, totals);
+            }
+
+            match next {
+                Some(Ok(_)) => (),
+                Some(Err(error)) => {
+                    totals.fail(error);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        if let Ok(mut usage) = totals.usage.lock() {
+            usage.merge(&scan.usage());
+        }
+    }
+
+    fn spawn<'s>(
+        &'s self,
+        scope: &Scope<'s>,
+        parent: &Scan,
+        path: PathBuf,
+        depth: usize,
+        totals: &'s Totals,
+    ) {
+        let mut scan = parent.sibling().split_above(self.split_depth);
+        scan.add_contents(path, depth);
+        scope.spawn(move |scope| self.run(scope, scan, totals));
+    }
+}
+
+struct Totals {
+    usage: Mutex<DiskUsage>,
+    error: Mutex<Option<Error>>,
+    failed: AtomicBool,
+}
+
+impl Totals {
+    fn fail(&self, error: Error) {
+        self.failed.store(true, Ordering::Relaxed);
+        if let Ok(mut first) = self.error.lock() {
+            first.get_or_insert(error);
+        }
+    }
+}
+
+#[test]
+fn parallel_totals_match() {
+    let sequential = Scanner::new("src").total().unwrap();
+
+    for depth in 0..4 {
+        let parallel = Scanner::new("src")
+            .parallelism(Parallelism::new().threads(4).split_depth(depth))
+            .total()
+            .unwrap();
+        assert_eq!(parallel, sequential);
+    }
+
+    let pool = Arc::new(ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+    let parallel = Scanner::new("src")
+        .parallelism(Parallelism::new().pool(pool))
+        .total()
+        .unwrap();
+    assert_eq!(parallel, sequential);
+}
+
+#[test]
+fn parallel_errors_are_returned() {
+    let result = Scanner::new("does-not-exist")
+        .parallelism(Parallelism::new())
+        .total();
+    assert!(result.is_err());
+}
+
+#[test]
+fn parallel_scans_share_open_directories() {
+    let sequential = Scanner::new("src").total().unwrap();
+    let parallel = Scanner::new("src")
+        .max_open_dirs(1)
+        .parallelism(Parallelism::new().threads(4).split_depth(1))
+        .total()
+        .unwrap();
+    assert_eq!(parallel, sequential);
+}
+
+#[test]
+fn parallel_progress_covers_the_whole_tree() {
+    use std::time::Duration;
+
+    let sequential = Scanner::new("src").total().unwrap();
+    let seen = Arc::new(Mutex::new(vec![]));
+    let record = Arc::clone(&seen);
+    Scanner::new("src")
+        .count_first(true)
+        .progress(Duration::from_secs(3600), move |progress| {
+            record
+                .lock()
+                .unwrap()
+                .push((progress.entries(), progress.percent()));
+        })
+        .parallelism(Parallelism::new().threads(4).split_depth(2))
+        .total()
+        .unwrap();
+    let seen = seen.lock().unwrap();
+
+    // One report as the scan starts, and one once every task is done.
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[1], (sequential.entries(), Some(100.0)));
+}
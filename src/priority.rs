@@ -0,0 +1,126 @@
+//! Lowering the I/O priority of the scanning thread.
+
+/// Holds the calling thread at idle I/O priority, restoring its previous
+/// priority when dropped.
+#[derive(Debug)]
+pub(crate) struct BackgroundIo {
+    previous: i32,
+}
+
+impl BackgroundIo {
+    /// Lower the I/O priority of the calling thread, if the platform supports
+    /// it.
+    pub(crate) fn enter() -> Option<Self> {
+        imp::enter().map(|previous| Self { previous })
+    }
+}
+
+impl Drop for BackgroundIo {
+    fn drop(&mut self) {
+        imp::leave(self.previous);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+
+    pub fn enter() -> Option<i32> {
+        // A `who` of 0 refers to the calling thread.
+        let previous = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+        if previous < 0 {
+            return None;
+        }
+
+        let idle = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, idle) } != 0 {
+            return None;
+        }
+
+        Some(previous as i32)
+    }
+
+    pub fn leave(previous: i32) {
+        unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                libc::c_long::from(previous),
+            )
+        };
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod imp {
+    const IOPOL_TYPE_DISK: libc::c_int = 0;
+    const IOPOL_SCOPE_THREAD: libc::c_int = 1;
+    const IOPOL_THROTTLE: libc::c_int = 3;
+
+    extern "C" {
+        fn getiopolicy_np(iotype: libc::c_int, scope: libc::c_int) -> libc::c_int;
+        fn setiopolicy_np(
+            iotype: libc::c_int,
+            scope: libc::c_int,
+            policy: libc::c_int,
+        ) -> libc::c_int;
+    }
+
+    pub fn enter() -> Option<i32> {
+        let previous = unsafe { getiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_THREAD) };
+        if previous < 0 {
+            return None;
+        }
+
+        if unsafe { setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_THREAD, IOPOL_THROTTLE) } != 0 {
+            return None;
+        }
+
+        Some(previous)
+    }
+
+    pub fn leave(previous: i32) {
+        unsafe { setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_THREAD, previous) };
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use crate::win::{
+        GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN,
+        THREAD_MODE_BACKGROUND_END,
+    };
+
+    // Background mode lowers both the CPU and I/O priority of the thread, and
+    // cannot be nested, so there's no previous state to record.
+    pub fn enter() -> Option<i32> {
+        if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN as _) } == 0
+        {
+            return None;
+        }
+
+        Some(0)
+    }
+
+    pub fn leave(_previous: i32) {
+        unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_END as _) };
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    windows
+)))]
+mod imp {
+    pub fn enter() -> Option<i32> {
+        None
+    }
+
+    pub fn leave(_previous: i32) {}
+}
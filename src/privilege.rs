@@ -0,0 +1,92 @@
+//! Enabling the backup privilege on Windows.
+
+use std::io;
+
+use crate::win::{
+    AdjustTokenPrivileges, CloseHandle, GetCurrentProcess, LookupPrivilegeValueW, OpenProcessToken,
+    ERROR_NOT_ALL_ASSIGNED, HANDLE, SE_PRIVILEGE_ENABLED, TOKEN_ADJUST_PRIVILEGES,
+    TOKEN_PRIVILEGES, TOKEN_QUERY,
+};
+
+/// Enable `SeBackupPrivilege` for the current process, so files can be opened
+/// for backup regardless of their security descriptors.
+///
+/// Returns `false` if the process token doesn't hold the privilege, which
+/// normally requires running elevated as an administrator or member of Backup
+/// Operators.  Once enabled, directory listings honour it, and
+/// [`SizeOptions::backup_semantics`](crate::SizeOptions::backup_semantics)
+/// sizes files which would otherwise fail with access denied.  It remains
+/// enabled for the life of the process.
+///
+/// ```rust,no_run
+/// use filesize::{Scanner, SizeOptions};
+///
+/// # fn main() -> std::io::Result<()> {
+/// if !filesize::enable_backup_privilege()? {
+///     eprintln!("not elevated: some files may be unreadable");
+/// }
+/// let usage = Scanner::new(r"C:\Users")
+///     .size_options(SizeOptions::new().backup_semantics(true))
+///     .total()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn enable_backup_privilege() -> io::Result<bool> {
+    let mut token: HANDLE = std::ptr::null_mut();
+    let ok = unsafe {
+        OpenProcessToken(
+            GetCurrentProcess(),
+            TOKEN_ADJUST_PRIVILEGES | TOKEN_QUERY,
+            &mut token,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let result = enable(token);
+    unsafe { CloseHandle(token) };
+    result
+}
+
+fn enable(token: HANDLE) -> io::Result<bool> {
+    let name: Vec<u16> = "SeBackupPrivilege\0".encode_utf16().collect();
+    let mut privileges: TOKEN_PRIVILEGES = unsafe { std::mem::zeroed() };
+    privileges.PrivilegeCount = 1;
+    privileges.Privileges[0].Attributes = SE_PRIVILEGE_ENABLED;
+
+    let ok = unsafe {
+        LookupPrivilegeValueW(
+            std::ptr::null(),
+            name.as_ptr(),
+            &mut privileges.Privileges[0].Luid,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let ok = unsafe {
+        AdjustTokenPrivileges(
+            token,
+            0,
+            &mut privileges,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Success is reported even if the privilege wasn't held.
+    let err = io::Error::last_os_error();
+    Ok(err.raw_os_error() != Some(ERROR_NOT_ALL_ASSIGNED as i32))
+}
+
+#[test]
+fn backup_privilege_can_be_requested() {
+    // Whether it is granted depends on how the tests are run.
+    enable_backup_privilege().unwrap();
+}
@@ -0,0 +1,230 @@
+//! Projecting the space files would occupy once copied to another volume.
+//!
+//! A tree of many small files can need far more space on a volume with large
+//! clusters than on the one it came from, as each file is rounded up to a
+//! whole number of them: ten thousand 1 KiB files fill 40 MiB in 4 KiB
+//! clusters, but 640 MiB in the 64 KiB clusters of some ReFS volumes.
+//! [`SizeProjector`] takes the length of each file a [`Scanner`] finds and
+//! rounds it up to the clusters of the target, optionally after an assumed
+//! compression ratio, for planning a migration before starting it.
+//!
+//! ```rust
+//! use filesize::{Scanner, SizeProjector};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let projection = SizeProjector::new(64 * 1024).project(Scanner::new("src"))?;
+//! println!(
+//!     "{} bytes now, {} on a 64 KiB-cluster volume",
+//!     projection.physical(),
+//!     projection.projected()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use crate::{Result, Scanner};
+
+/// The space a set of files is projected to occupy on a target volume, from
+/// [`SizeProjector`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SizeProjection {
+    files: u64,
+    directories: u64,
+    logical: u64,
+    physical: u64,
+    projected: u64,
+}
+
+impl SizeProjection {
+    fn add(&mut self, other: &SizeProjection) {
+        self.files += other.files;
+        self.directories += other.directories;
+        self.logical = self.logical.saturating_add(other.logical);
+        self.physical = self.physical.saturating_add(other.physical);
+        self.projected = self.projected.saturating_add(other.projected);
+    }
+
+    /// The number of regular files projected.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The number of directories projected.
+    pub fn directories(&self) -> u64 {
+        self.directories
+    }
+
+    /// The total length of the files.
+    pub fn logical(&self) -> u64 {
+        self.logical
+    }
+
+    /// The space the files use where they are now.
+    pub fn physical(&self) -> u64 {
+        self.physical
+    }
+
+    /// The space the files and directories are projected to use on the
+    /// target volume.
+    pub fn projected(&self) -> u64 {
+        self.projected
+    }
+
+    /// The space the target needs beyond what the files use now, or zero if
+    /// they would take up less there.
+    pub fn growth(&self) -> u64 {
+        self.projected.saturating_sub(self.physical)
+    }
+}
+
+/// Projects the space files would occupy on a volume allocating space in
+/// clusters of a given size.
+#[derive(Debug, Clone)]
+pub struct SizeProjector {
+    cluster_size: u64,
+    compression_ratio: Option<f64>,
+    inline_limit: u64,
+    directory_size: u64,
+}
+
+impl SizeProjector {
+    /// Create a projector for a volume with clusters of `cluster_size`
+    /// bytes, with no compression, no files stored inline, and directories
+    /// taking no space.
+    pub fn new(cluster_size: u64) -> Self {
+        Self {
+            cluster_size: cluster_size.max(1),
+            compression_ratio: None,
+            inline_limit: 0,
+            directory_size: 0,
+        }
+    }
+
+    /// Create a projector for the volume holding `path`, with clusters of
+    /// its [`block_size`](crate::Granularity::block_size), for a target which
+    /// is already mounted.
+    pub fn like<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(crate::allocation_granularity(path)?.block_size()))
+    }
+
+    /// Assume each file compresses to `ratio` of its length before being
+    /// rounded up to clusters, such as `0.5` for data which halves in size.
+    /// The ratio is clamped to between zero and one.
+    ///
+    /// Files are compressed independently, as by NTFS, btrfs, or ZFS, so the
+    /// small files of a tree gain little, each still needing a cluster.
+    pub fn compression_ratio(mut self, ratio: f64) -> Self {
+        self.compression_ratio = Some(if ratio.is_nan() {
+            1.0
+        } else {
+            ratio.clamp(0.0, 1.0)
+        });
+        self
+    }
+
+    /// Count files of up to `bytes` as taking no clusters, as the target
+    /// stores them in its metadata: NTFS keeps files of up to around 700
+    /// bytes in their MFT record, and ext4 with `inline_data` up to 60 bytes
+    /// in the inode.
+    pub fn inline_limit(mut self, bytes: u64) -> Self {
+        self.inline_limit = bytes;
+        self
+    }
+
+    /// Count each directory as taking `bytes`, such as one 4 KiB block on
+    /// ext4.  This is zero by default, as small directories often live in
+    /// metadata, like files below the inline limit.
+    pub fn directory_size(mut self, bytes: u64) -> Self {
+        self.directory_size = bytes;
+        self
+    }
+
+    /// The space a file of length `logical` is projected to occupy.
+    pub fn projected_size(&self, logical: u64) -> u64 {
+        if logical <= self.inline_limit {
+            return 0;
+        }
+        let stored = match self.compression_ratio {
+            Some(ratio) => (logical as f64 * ratio).ceil() as u64,
+            None => logical,
+        };
+        stored
+            .div_ceil(self.cluster_size)
+            .saturating_mul(self.cluster_size)
+    }
+
+    /// Project the space every regular file and directory `scanner` finds
+    /// would occupy.  Errors are handled as with [`Scanner::total`].
+    pub fn project(&self, scanner: Scanner) -> Result<SizeProjection> {
+        let mut total = SizeProjection::default();
+        for entry in scanner {
+            let entry = entry?;
+            let file_type = entry.file_type();
+            if file_type.is_dir() {
+                total.add(&SizeProjection {
+                    directories: 1,
+                    projected: self.directory_size,
+                    ..SizeProjection::default()
+                });
+            } else if file_type.is_file() {
+                let info = entry.size_info();
+                total.add(&self.project_sizes(info.logical(), info.physical()));
+            }
+        }
+        Ok(total)
+    }
+
+    /// Project the space the file at `path` would occupy.
+    pub fn project_file<P: AsRef<Path>>(&self, path: P) -> Result<SizeProjection> {
+        let path = path.as_ref();
+        let info = crate::SizeOptions::new().size_info(path)?;
+        Ok(self.project_sizes(info.logical(), info.physical()))
+    }
+
+    fn project_sizes(&self, logical: u64, physical: u64) -> SizeProjection {
+        SizeProjection {
+            files: 1,
+            directories: 0,
+            logical,
+            physical,
+            projected: self.projected_size(logical),
+        }
+    }
+}
+
+#[test]
+fn sizes_are_projected() {
+    let dir = crate::test_dir("projection");
+    std::fs::create_dir(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("tiny"), [1; 10]).unwrap();
+    std::fs::write(dir.join("sub/small"), [1; 5000]).unwrap();
+    std::fs::write(dir.join("empty"), []).unwrap();
+
+    let projector = SizeProjector::new(64 * 1024);
+    let tree = projector.project(Scanner::new(&dir)).unwrap();
+    let inline = SizeProjector::new(4096)
+        .inline_limit(100)
+        .directory_size(4096)
+        .project(Scanner::new(&dir))
+        .unwrap();
+    let small = projector.project_file(dir.join("sub/small")).unwrap();
+    let local = SizeProjector::like(&dir).unwrap();
+    let missing = projector.project_file(dir.join("missing"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!((tree.files(), tree.directories()), (3, 2));
+    assert_eq!(tree.logical(), 5010);
+    assert_eq!(tree.projected(), 2 * 64 * 1024);
+    assert_eq!(inline.projected(), 2 * 4096 + 8192);
+    assert_eq!(small.projected(), 64 * 1024);
+    assert_eq!(small.growth(), 64 * 1024 - small.physical());
+    assert!(local.projected_size(1) > 0);
+    assert!(missing.is_err());
+
+    let compressed = SizeProjector::new(4096).compression_ratio(0.5);
+    assert_eq!(compressed.projected_size(5000), 4096);
+    assert_eq!(compressed.projected_size(10_000), 8192);
+    assert_eq!(compressed.projected_size(0), 0);
+}
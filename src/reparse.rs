@@ -0,0 +1,152 @@
+use std::fs::Metadata;
+use std::path::Path;
+
+use crate::{Error, Result};
+
+const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xa000_0003;
+const IO_REPARSE_TAG_SYMLINK: u32 = 0xa000_000c;
+const IO_REPARSE_TAG_DEDUP: u32 = 0x8000_0013;
+const IO_REPARSE_TAG_WOF: u32 = 0x8000_0017;
+const IO_REPARSE_TAG_CLOUD: u32 = 0x9000_001a;
+const IO_REPARSE_TAG_APPEXECLINK: u32 = 0x8000_001b;
+
+// The cloud tags differ only in these bits, one for each of 16 providers.
+const CLOUD_PROVIDER_MASK: u32 = 0x0000_f000;
+
+/// The kind of reparse point a Windows file is, which explains why its size
+/// may not be what it seems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ReparseTag {
+    /// A symbolic link, which occupies no space of its own beyond its entry.
+    SymbolicLink,
+    /// A junction or volume mount point, leading to another directory.
+    MountPoint,
+    /// A file compressed by the Windows Overlay Filter, as `compact /exe`
+    /// does, whose compressed size is reported.
+    Wof,
+    /// A file optimized by Windows Server Data Deduplication, whose data lives
+    /// in a shared chunk store.
+    Dedup,
+    /// A placeholder for a file held by a cloud provider such as OneDrive,
+    /// which may have little or no data on disk until it is opened.
+    Cloud,
+    /// An app execution alias, such as those Store apps install in
+    /// `%LOCALAPPDATA%\Microsoft\WindowsApps`, which cannot be opened.
+    AppExecLink,
+    /// Any other reparse tag, as its raw value.
+    Other(u32),
+}
+
+impl ReparseTag {
+    /// The raw `IO_REPARSE_TAG_*` value of the tag.  Cloud placeholders are
+    /// reported as `IO_REPARSE_TAG_CLOUD`, regardless of provider.
+    pub fn raw(&self) -> u32 {
+        match self {
+            ReparseTag::SymbolicLink => IO_REPARSE_TAG_SYMLINK,
+            ReparseTag::MountPoint => IO_REPARSE_TAG_MOUNT_POINT,
+            ReparseTag::Wof => IO_REPARSE_TAG_WOF,
+            ReparseTag::Dedup => IO_REPARSE_TAG_DEDUP,
+            ReparseTag::Cloud => IO_REPARSE_TAG_CLOUD,
+            ReparseTag::AppExecLink => IO_REPARSE_TAG_APPEXECLINK,
+            ReparseTag::Other(tag) => *tag,
+        }
+    }
+}
+
+impl From<u32> for ReparseTag {
+    fn from(tag: u32) -> Self {
+        match tag {
+            IO_REPARSE_TAG_SYMLINK => ReparseTag::SymbolicLink,
+            IO_REPARSE_TAG_MOUNT_POINT => ReparseTag::MountPoint,
+            IO_REPARSE_TAG_WOF => ReparseTag::Wof,
+            IO_REPARSE_TAG_DEDUP => ReparseTag::Dedup,
+            IO_REPARSE_TAG_APPEXECLINK => ReparseTag::AppExecLink,
+            tag if tag & !CLOUD_PROVIDER_MASK == IO_REPARSE_TAG_CLOUD => ReparseTag::Cloud,
+            tag => ReparseTag::Other(tag),
+        }
+    }
+}
+
+/// Find the reparse tag of the file at the given `path`, or `None` if it is not
+/// a reparse point.  Symlinks are not followed.
+///
+/// Reparse points only exist on Windows, so this is always `None` elsewhere.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// assert_eq!(filesize::reparse_tag("Cargo.toml")?, None);
+/// # Ok(())
+/// # }
+/// ```
+pub fn reparse_tag<P: AsRef<Path>>(path: P) -> Result<Option<ReparseTag>> {
+    let path = path.as_ref();
+    let metadata = path
+        .symlink_metadata()
+        .map_err(|e| Error::from_io(path, e))?;
+    if !is_reparse_point(&metadata) {
+        return Ok(None);
+    }
+    imp::reparse_tag(path)
+        .map(|tag| Some(ReparseTag::from(tag)))
+        .map_err(|e| Error::from_io(path, e))
+}
+
+/// The reparse tag of a file already known to have `metadata`, or `None` if it
+/// is not a reparse point or its tag can't be read.
+pub(crate) fn tag_of(path: &Path, metadata: &Metadata) -> Option<ReparseTag> {
+    if is_reparse_point(metadata) {
+        imp::reparse_tag(path).ok().map(ReparseTag::from)
+    } else {
+        None
+    }
+}
+
+#[cfg(windows)]
+fn is_reparse_point(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    use crate::win::FILE_ATTRIBUTE_REPARSE_POINT;
+
+    metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0
+}
+
+#[cfg(not(windows))]
+fn is_reparse_point(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(windows)]
+mod imp {
+    pub use crate::imp::reparse_tag;
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    pub fn reparse_tag(_path: &Path) -> io::Result<u32> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "reparse points are not supported on this platform",
+        ))
+    }
+}
+
+#[test]
+fn reparse_tags_are_classified() {
+    for tag in [
+        ReparseTag::SymbolicLink,
+        ReparseTag::MountPoint,
+        ReparseTag::Wof,
+        ReparseTag::Dedup,
+        ReparseTag::Cloud,
+        ReparseTag::AppExecLink,
+        ReparseTag::Other(0x8000_0023),
+    ] {
+        assert_eq!(ReparseTag::from(tag.raw()), tag);
+    }
+    assert_eq!(ReparseTag::from(0x9000_701a), ReparseTag::Cloud);
+    assert_eq!(reparse_tag("Cargo.toml").unwrap(), None);
+}
@@ -0,0 +1,246 @@
+//! Estimating the size of a tree from a random sample of its entries.
+//!
+//! [`Scanner::estimate`] takes a number of random walks from the root of a
+//! tree down to a file or empty directory, picking each step uniformly from
+//! the entries of the directory it is in.  Weighting the size of each entry
+//! passed by the number of choices it took to reach it gives an unbiased
+//! estimate of the size of the whole tree, as in Knuth's estimate of the size
+//! of a backtracking tree, and the spread of those estimates gives a
+//! confidence interval.  Each walk only lists the directories along its path,
+//! so a few thousand walks of a tree of millions of files take seconds.
+//!
+//! Trees with a few very large files hidden deep in one branch have a wide
+//! interval, and need many more walks to narrow it than evenly spread trees.
+//!
+//! ```rust
+//! # fn main() -> std::io::Result<()> {
+//! let estimate = filesize::Scanner::new("src").estimate(1000)?;
+//! println!(
+//!     "about {} bytes, within {} either way",
+//!     estimate.bytes(),
+//!     estimate.margin()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result, Scanner};
+
+/// The z-score of a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// An estimate of the size of a tree, from [`Scanner::estimate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeEstimate {
+    bytes: f64,
+    margin: f64,
+    entries: f64,
+    probes: u64,
+}
+
+impl SizeEstimate {
+    /// The estimated size of the tree, as counted by
+    /// [`Scanner::apparent_size`].
+    pub fn bytes(&self) -> u64 {
+        self.bytes.round() as u64
+    }
+
+    /// The margin of error of [`bytes`](Self::bytes), with 95% confidence
+    /// that the size of the tree lies within it either way.
+    pub fn margin(&self) -> u64 {
+        self.margin.round() as u64
+    }
+
+    /// The sizes the tree lies between with 95% confidence.
+    pub fn range(&self) -> RangeInclusive<u64> {
+        let low = (self.bytes - self.margin).max(0.0);
+        low.round() as u64..=(self.bytes + self.margin).round() as u64
+    }
+
+    /// The estimated number of files and directories in the tree.
+    pub fn entries(&self) -> u64 {
+        self.entries.round() as u64
+    }
+
+    /// The number of random walks taken through the tree.
+    pub fn probes(&self) -> u64 {
+        self.probes
+    }
+}
+
+impl Scanner {
+    /// Estimate the size of the tree from `probes` random walks through it,
+    /// rather than scanning every entry, for a rough answer for trees of
+    /// millions of files in seconds.  At least one walk is taken.
+    ///
+    /// Walks respect [`max_depth`](Self::max_depth), excluded paths, and the
+    /// size options, but not other filters, and do not follow symlinks.
+    /// Entries which can't be listed or sized count as empty, while the
+    /// roots of the scan must be found.
+    pub fn estimate(self, probes: u64) -> Result<SizeEstimate> {
+        let roots: Vec<PathBuf> = std::iter::once(self.root())
+            .chain(self.roots.iter().map(PathBuf::as_path))
+            .map(Path::to_path_buf)
+            .collect();
+        for root in &roots {
+            fs::symlink_metadata(root).map_err(|e| Error::from_io(root, e))?;
+        }
+
+        let mut sampler = Sampler {
+            opts: &self,
+            listings: HashMap::new(),
+            rng: RandomState::new().build_hasher().finish() | 1,
+        };
+
+        let probes = probes.max(1);
+        let (mut bytes, mut spread, mut entries) = (0.0, 0.0, 0.0);
+        for probe in 1..=probes {
+            let (probe_bytes, probe_entries) = roots
+                .iter()
+                .map(|root| sampler.walk(root))
+                .fold((0.0, 0.0), |(b, e), (rb, re)| (b + rb, e + re));
+
+            // Welford's method, for the variance without holding every probe.
+            let delta = probe_bytes - bytes;
+            bytes += delta / probe as f64;
+            spread += delta * (probe_bytes - bytes);
+            entries += (probe_entries - entries) / probe as f64;
+        }
+
+        let variance = if probes > 1 {
+            spread / (probes - 1) as f64
+        } else {
+            0.0
+        };
+
+        Ok(SizeEstimate {
+            bytes,
+            margin: Z_95 * (variance / probes as f64).sqrt(),
+            entries,
+            probes,
+        })
+    }
+}
+
+struct Sampler<'a> {
+    opts: &'a Scanner,
+    listings: HashMap<PathBuf, Vec<PathBuf>>,
+    rng: u64,
+}
+
+impl Sampler<'_> {
+    /// Walk from `root` to a file or empty directory, returning the estimated
+    /// size and number of entries of the tree.
+    fn walk(&mut self, root: &Path) -> (f64, f64) {
+        let (mut bytes, mut entries) = (0.0, 0.0);
+        let mut weight = 1.0;
+        let mut path = root.to_path_buf();
+
+        for depth in 0.. {
+            entries += weight;
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => break,
+            };
+            if !metadata.is_dir() {
+                bytes += weight * self.size(&path, &metadata) as f64;
+                break;
+            }
+            if depth >= self.opts.max_depth {
+                break;
+            }
+
+            let children = list(&mut self.listings, self.opts, &path);
+            if children.is_empty() {
+                break;
+            }
+            weight *= children.len() as f64;
+            path = children[below(&mut self.rng, children.len())].clone();
+        }
+
+        (bytes, entries)
+    }
+
+    fn size(&self, path: &Path, metadata: &fs::Metadata) -> u64 {
+        match self.opts.query_size(path, metadata) {
+            Ok((info, _)) if self.opts.apparent_size => info.logical(),
+            Ok((info, _)) => info.physical(),
+            Err(_) => 0,
+        }
+    }
+}
+
+/// The entries of the directory at `path`, listed once and kept in
+/// `listings`, as every walk passes through the directories nearest the root.
+fn list<'a>(
+    listings: &'a mut HashMap<PathBuf, Vec<PathBuf>>,
+    opts: &Scanner,
+    path: &Path,
+) -> &'a [PathBuf] {
+    listings.entry(path.to_path_buf()).or_insert_with(|| {
+        fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|dent| dent.path())
+            .filter(|path| !opts.is_excluded_path(path))
+            .collect()
+    })
+}
+
+/// A random index below `n`, from the xorshift64* generator `state`.
+fn below(state: &mut u64, n: usize) -> usize {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    (state.wrapping_mul(0x2545_f491_4f6c_dd1d) % n as u64) as usize
+}
+
+#[test]
+fn even_trees_are_estimated_exactly() {
+    let dir = crate::test_dir("sample");
+    for sub in ["a", "b", "c"] {
+        fs::create_dir(dir.join(sub)).unwrap();
+        for file in ["1", "2", "3", "4"] {
+            fs::write(dir.join(sub).join(file), [0; 1000]).unwrap();
+        }
+    }
+
+    let estimate = Scanner::new(&dir).apparent_size(true).estimate(50).unwrap();
+    let shallow = Scanner::new(&dir).max_depth(1).estimate(10).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(estimate.bytes(), 12_000);
+    assert_eq!(estimate.margin(), 0);
+    assert_eq!(estimate.range(), 12_000..=12_000);
+    assert_eq!(estimate.entries(), 16);
+    assert_eq!(estimate.probes(), 50);
+    assert_eq!((shallow.bytes(), shallow.entries()), (0, 4));
+}
+
+#[test]
+fn uneven_trees_are_estimated_with_a_margin() {
+    let dir = crate::test_dir("sample-uneven");
+    fs::create_dir(dir.join("big")).unwrap();
+    fs::write(dir.join("big/file"), [0; 100_000]).unwrap();
+    for file in 0..9 {
+        fs::write(dir.join(file.to_string()), [0; 10]).unwrap();
+    }
+
+    let estimate = Scanner::new(&dir)
+        .apparent_size(true)
+        .estimate(2000)
+        .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    // The mean of 100,090 is more than seven standard errors inside these.
+    assert!(estimate.margin() > 0);
+    assert!(estimate.bytes() > 50_000 && estimate.bytes() < 150_000);
+}
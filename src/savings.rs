@@ -0,0 +1,65 @@
+//! Reporting the space saved by compression in a [`DiskUsageTree`].
+
+use std::io::{self, Write};
+
+use crate::{DiskUsageTree, DuFormat};
+
+impl DiskUsageTree {
+    /// Write a report of the space compression saves in each directory, in
+    /// the manner of `compsize`: the percentage of its logical size saved,
+    /// the space it uses on disk, its logical size, and its path.
+    ///
+    /// Sizes are in powers of 1024, as from `du -h`.  Savings come from
+    /// whatever makes files occupy less than their lengths, so include sparse
+    /// files and, on filesystems reporting them, clones.  See
+    /// [`DiskUsage::savings_percent`](crate::DiskUsage::savings_percent).
+    ///
+    /// ```rust
+    /// # fn main() -> std::io::Result<()> {
+    /// let tree = filesize::Scanner::new("src").tree()?;
+    /// tree.write_savings(std::io::stdout())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_savings<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let du = DuFormat::new().human_readable(true);
+        writeln!(writer, "Saved  Disk Usage  Uncompressed  Path")?;
+        for node in self.walk().filter(|node| node.is_dir()) {
+            let usage = node.usage();
+            writeln!(
+                writer,
+                "{:>4.0}%  {:>10}  {:>12}  {}",
+                usage.savings_percent(),
+                du.format_size(usage.physical()),
+                du.format_size(usage.logical()),
+                node.path().display()
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+#[test]
+fn savings_are_reported_per_directory() {
+    let dir = crate::test_dir("savings");
+    std::fs::create_dir(dir.join("sparse")).unwrap();
+    std::fs::File::create(dir.join("sparse/file"))
+        .and_then(|file| file.set_len(1 << 24))
+        .unwrap();
+
+    let tree = crate::Scanner::new(&dir).tree().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut out = vec![];
+    tree.write_savings(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+
+    let usage = tree.get(dir.join("sparse")).unwrap().usage();
+    assert_eq!(usage.saved_bytes(), usage.logical() - usage.physical());
+    assert!(usage.savings_percent() > 0.0 && usage.savings_percent() <= 100.0);
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("Saved"));
+    assert!(lines[2].ends_with(&*dir.join("sparse").to_string_lossy()));
+    assert!(lines[2].contains("16M"));
+}
@@ -0,0 +1,2864 @@
+//! Recursive disk usage scanning.
+//!
+//! A [`Scanner`] walks a directory tree, yielding an [`Entry`] for each file
+//! and directory it finds along with its size on disk, and keeping a running
+//! [`DiskUsage`] total of everything it has seen.
+//!
+//! ```rust
+//! use filesize::Scanner;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let usage = Scanner::new("src").total()?;
+//! println!("{} bytes on disk", usage.physical());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::{self, FileType, Metadata, ReadDir};
+use std::io;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::filesystem::device_id;
+use crate::priority::BackgroundIo;
+use crate::timeout::TimeoutPool;
+use crate::{
+    Checkpoint, Error, FilesystemKind, MetricsSink, OsBackend, Result, ScanSummary, SizeAccuracy,
+    SizeBackend, SizeCache, SizeInfo, SizeOptions,
+};
+
+/// Paths a [`Scanner`] leaves out by default, holding pseudo-files or system
+/// data which would hang a naive scan or be miscounted by it.
+///
+/// On Unix these are `/proc`, `/sys`, `/dev`, and `/run`.  On Windows they are
+/// `$Recycle.Bin` and `System Volume Information`, relative paths which match
+/// at the root of every drive.
+pub const DEFAULT_EXCLUDED_PATHS: &[&str] = EXCLUDED_PATHS;
+
+#[cfg(unix)]
+const EXCLUDED_PATHS: &[&str] = &["/proc", "/sys", "/dev", "/run"];
+
+#[cfg(windows)]
+const EXCLUDED_PATHS: &[&str] = &["$Recycle.Bin", "System Volume Information"];
+
+#[cfg(not(any(unix, windows)))]
+const EXCLUDED_PATHS: &[&str] = &[];
+
+/// A builder for a recursive scan of a directory tree.
+#[derive(Debug)]
+pub struct Scanner {
+    root: PathBuf,
+    pub(crate) roots: Vec<PathBuf>,
+    current_dir: Option<PathBuf>,
+    follow_symlinks: bool,
+    directory_links: Option<LinkPolicy>,
+    options: SizeOptions,
+    skip_virtual_filesystems: bool,
+    skip_memory_filesystems: bool,
+    progress: Option<ProgressHook>,
+    cancel: Option<Arc<AtomicBool>>,
+    errors: ErrorPolicy,
+    min_depth: usize,
+    pub(crate) max_depth: usize,
+    min_size: u64,
+    predicates: Vec<Predicate>,
+    excluded_paths: Vec<PathBuf>,
+    exclude_caches: bool,
+    time_windows: Vec<TimeWindow>,
+    order: Order,
+    sorted: bool,
+    max_open_dirs: usize,
+    directory_sizes: bool,
+    pub(crate) apparent_size: bool,
+    block_size: u64,
+    dedup_hard_links: bool,
+    metrics: Option<Metrics>,
+    max_stats_per_sec: Option<u32>,
+    background_io: bool,
+    cache: Option<Arc<SizeCache>>,
+    backend: Option<Backend>,
+    #[cfg(feature = "rayon")]
+    parallelism: Option<crate::Parallelism>,
+    threads: usize,
+    timeouts: Option<TimeoutPool>,
+    expected_entries: Option<u64>,
+    count_first: bool,
+    #[cfg(feature = "globset")]
+    include: Option<globset::GlobSet>,
+    #[cfg(feature = "globset")]
+    exclude: Option<globset::GlobSet>,
+}
+
+struct Metrics(Box<dyn MetricsSink>);
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Metrics")
+    }
+}
+
+struct Backend(Box<dyn SizeBackend>);
+
+impl fmt::Debug for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Backend")
+    }
+}
+
+type PredicateFn = dyn Fn(&Path, &Metadata) -> bool + Send + Sync;
+
+struct Predicate(Box<PredicateFn>);
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Predicate")
+    }
+}
+
+type ProgressFn = dyn FnMut(&Progress<'_>) + Send;
+
+struct ProgressHook {
+    interval: Duration,
+    callback: Mutex<Box<ProgressFn>>,
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressHook")
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+/// A snapshot of the progress of a scan, passed to the callback given to
+/// [`Scanner::progress`].
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    files: u64,
+    bytes: u64,
+    entries: u64,
+    expected: Option<u64>,
+    elapsed: Duration,
+    path: &'a Path,
+}
+
+impl Progress<'_> {
+    /// The number of files, excluding directories, seen so far.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The number of files and directories visited so far, including any
+    /// filtered out of the scan or failing with an error.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// The number of entries the scan is expected to visit, from
+    /// [`Scanner::expected_entries`] or [`Scanner::count_first`].
+    pub fn expected_entries(&self) -> Option<u64> {
+        self.expected
+    }
+
+    /// The percentage of the expected entries visited so far, up to 100, if
+    /// the number expected is known.
+    pub fn percent(&self) -> Option<f64> {
+        self.expected.map(|expected| match expected {
+            0 => 100.0,
+            expected => (self.entries as f64 * 100.0 / expected as f64).min(100.0),
+        })
+    }
+
+    /// The time since the scan started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// An estimate of the time left until the scan completes, from the rate
+    /// entries have been visited at so far, if the number expected is known
+    /// and any have been visited.  Scans finding more entries than expected
+    /// are estimated to be finishing now.
+    pub fn eta(&self) -> Option<Duration> {
+        let expected = self.expected?;
+        if self.entries == 0 {
+            return None;
+        }
+        let remaining = expected.saturating_sub(self.entries);
+        Some(self.elapsed.mul_f64(remaining as f64 / self.entries as f64))
+    }
+
+    /// The total size of the files seen so far, as counted by
+    /// [`Scanner::apparent_size`].
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The path most recently yielded, or the root once the scan is complete.
+    pub fn current_path(&self) -> &Path {
+        self.path
+    }
+}
+
+/// How a [`Scanner`] should treat links to directories.
+///
+/// On Windows this covers directory symlinks, junctions, and volume mount
+/// points.  On Unix it covers symlinks with a directory as their target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// Leave them out of the scan entirely.
+    Skip,
+    /// Yield them as entries with a size of zero, without descending into them.
+    Zero,
+    /// Descend into them as if they were ordinary directories.
+    Traverse,
+}
+
+/// How a [`Scanner`] should handle errors encountered during a scan, such as
+/// unreadable directories or files that vanish mid-scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Yield them from the iterator and carry on.  `total` fails with the first.
+    Yield,
+    /// Yield the first and end the scan there.
+    Abort,
+    /// Leave them out of the scan silently.
+    Skip,
+    /// Leave them out of the scan, but keep them for inspection with
+    /// [`Scan::errors`] alongside the partial results.
+    Collect,
+}
+
+/// The order in which a [`Scanner`] visits a directory tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Descend into each directory as it is found, finishing it before
+    /// moving on to its siblings.
+    DepthFirst,
+    /// Visit every entry at one depth before any at the next, giving an early
+    /// picture of large top-level directories.  The path of every directory
+    /// of the next depth is held until it is reached, so this takes more
+    /// memory on wide trees.
+    BreadthFirst,
+}
+
+/// A timestamp of a file, for use with [`Scanner::time_window`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamp {
+    /// The last modification time.
+    Modified,
+    /// The last access time, which many systems only update lazily if at all.
+    Accessed,
+    /// The creation time, where the platform and filesystem record it.
+    Created,
+    /// The last status change time, `ctime` on Unix.  This is unavailable
+    /// elsewhere.
+    Changed,
+}
+
+impl Timestamp {
+    fn of(self, metadata: &Metadata) -> Option<SystemTime> {
+        match self {
+            Timestamp::Modified => metadata.modified().ok(),
+            Timestamp::Accessed => metadata.accessed().ok(),
+            Timestamp::Created => metadata.created().ok(),
+            #[cfg(unix)]
+            Timestamp::Changed => {
+                use std::os::unix::fs::MetadataExt;
+
+                let secs = metadata.ctime();
+                let nanos = Duration::from_nanos(metadata.ctime_nsec() as u64);
+                if secs >= 0 {
+                    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64) + nanos)
+                } else {
+                    SystemTime::UNIX_EPOCH
+                        .checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+                        .checked_add(nanos)
+                }
+            }
+            #[cfg(not(unix))]
+            Timestamp::Changed => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TimeWindow {
+    timestamp: Timestamp,
+    start: Bound<SystemTime>,
+    end: Bound<SystemTime>,
+}
+
+impl TimeWindow {
+    fn contains(&self, metadata: &Metadata) -> bool {
+        self.timestamp
+            .of(metadata)
+            .is_some_and(|time| (self.start, self.end).contains(&time))
+    }
+}
+
+impl Scanner {
+    /// Create a new `Scanner` rooted at the given `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            root: path.as_ref().to_path_buf(),
+            roots: vec![],
+            current_dir: std::env::current_dir().ok(),
+            follow_symlinks: false,
+            directory_links: None,
+            options: SizeOptions::new(),
+            skip_virtual_filesystems: false,
+            skip_memory_filesystems: false,
+            progress: None,
+            cancel: None,
+            errors: ErrorPolicy::Yield,
+            min_depth: 0,
+            max_depth: usize::MAX,
+            min_size: 0,
+            predicates: vec![],
+            excluded_paths: DEFAULT_EXCLUDED_PATHS.iter().map(PathBuf::from).collect(),
+            exclude_caches: false,
+            time_windows: vec![],
+            order: Order::DepthFirst,
+            sorted: false,
+            max_open_dirs: 128,
+            directory_sizes: false,
+            apparent_size: false,
+            block_size: 512,
+            dedup_hard_links: false,
+            metrics: None,
+            max_stats_per_sec: None,
+            background_io: false,
+            cache: None,
+            backend: None,
+            #[cfg(feature = "rayon")]
+            parallelism: None,
+            threads: 1,
+            timeouts: None,
+            expected_entries: None,
+            count_first: false,
+            #[cfg(feature = "globset")]
+            include: None,
+            #[cfg(feature = "globset")]
+            exclude: None,
+        }
+    }
+
+    /// Scan the tree at `path` as well, as another root with a depth of 0.
+    ///
+    /// All the roots share one set of totals, and directories reached from
+    /// more than one root, such as when one root lies within another, are only
+    /// counted once.  With [`dedup_hard_links`](Self::dedup_hard_links), files
+    /// linked from more than one root are only counted once too, so the total
+    /// is the unique usage of all the trees.
+    ///
+    /// ```rust
+    /// use filesize::Scanner;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let usage = Scanner::new("src")
+    ///     .add_root("examples")
+    ///     .dedup_hard_links(true)
+    ///     .total()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_root<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.roots.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Follow symbolic links, reporting and descending into their targets
+    /// instead of reporting the links themselves.  Defaults to `false`.
+    ///
+    /// Directories are only visited once per scan, so symlink loops and
+    /// multiple links to the same directory are neither followed forever nor
+    /// counted twice.
+    ///
+    /// On macOS this is always the case for scans of `/`, `/System`, or
+    /// `/System/Volumes`, so that directories reachable through both a
+    /// firmlink and its target, such as `/Users` and
+    /// `/System/Volumes/Data/Users`, are only counted once.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Set a policy for links to directories, overriding `follow_symlinks` for
+    /// them.
+    ///
+    /// As with `follow_symlinks`, directories reached through traversed links
+    /// are only visited once, so junction loops like `Application Data` on
+    /// Windows cannot be followed forever or counted twice.
+    pub fn directory_links(mut self, policy: LinkPolicy) -> Self {
+        self.directory_links = Some(policy);
+        self
+    }
+
+    /// Set the options used to determine the size of each file.
+    pub fn size_options(mut self, options: SizeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Leave files on pseudo-filesystems such as procfs and sysfs out of the
+    /// scan, rather than yielding them with their meaningless sizes.  Defaults
+    /// to `false`.
+    pub fn skip_virtual_filesystems(mut self, skip: bool) -> Self {
+        self.skip_virtual_filesystems = skip;
+        self
+    }
+
+    /// Leave files on memory filesystems such as tmpfs out of the scan, so
+    /// they are not counted as disk usage.  Defaults to `false`.
+    ///
+    /// Without this they are still flagged with `FilesystemKind::Memory`,
+    /// and [`DiskUsage::disk_bytes`] totals everything else.
+    pub fn skip_memory_filesystems(mut self, skip: bool) -> Self {
+        self.skip_memory_filesystems = skip;
+        self
+    }
+
+    /// Call `callback` with the progress of the scan at most once every
+    /// `interval`, and once more when the scan is complete.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use filesize::Scanner;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let usage = Scanner::new("src")
+    ///     .progress(Duration::from_millis(100), |progress| {
+    ///         eprint!("\r{} files, {} bytes", progress.files(), progress.bytes());
+    ///     })
+    ///     .total()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn progress<F>(mut self, interval: Duration, callback: F) -> Self
+    where
+        F: FnMut(&Progress<'_>) + Send + 'static,
+    {
+        self.progress = Some(ProgressHook {
+            interval,
+            callback: Mutex::new(Box::new(callback)),
+        });
+        self
+    }
+
+    /// Stop the scan promptly once `flag` is set, as if the tree had been
+    /// exhausted.  The usage of everything seen up to that point remains
+    /// available, so `total` returns a partial result.
+    ///
+    /// ```rust
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use filesize::Scanner;
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let mut scan = Scanner::new("src").cancel_on(Arc::clone(&cancel)).into_iter();
+    ///
+    /// cancel.store(true, Ordering::Relaxed);
+    /// assert!(scan.next().is_none());
+    /// assert!(scan.is_cancelled());
+    /// ```
+    pub fn cancel_on(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Expect the scan to visit about `entries` files and directories, as
+    /// counted by [`DiskUsage::entries`] for a previous scan of the tree, so
+    /// its [`Progress`] can give a percentage complete and an estimate of the
+    /// time left.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use filesize::Scanner;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let previous = Scanner::new("src").total()?;
+    /// let usage = Scanner::new("src")
+    ///     .expected_entries(previous.entries())
+    ///     .progress(Duration::from_millis(100), |progress| {
+    ///         if let (Some(percent), Some(eta)) = (progress.percent(), progress.eta()) {
+    ///             eprint!("\r{:.0}%, {}s left", percent, eta.as_secs());
+    ///         }
+    ///     })
+    ///     .total()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn expected_entries(mut self, entries: u64) -> Self {
+        self.expected_entries = Some(entries);
+        self
+    }
+
+    /// Count the entries in the tree before scanning it, unless a count was
+    /// given to [`expected_entries`](Self::expected_entries), so its
+    /// [`Progress`] can give a percentage complete and an estimate of the
+    /// time left.
+    ///
+    /// The count only lists directories, without finding the size or
+    /// metadata of each entry where the listing gives its file type, as on
+    /// Linux, macOS, and Windows, so is much quicker than the scan itself.
+    /// Filters other than [`max_depth`](Self::max_depth) and excluded paths
+    /// are not applied to it.  It is only taken for scans reporting their
+    /// progress to a callback given to [`progress`](Self::progress).
+    pub fn count_first(mut self, enable: bool) -> Self {
+        self.count_first = enable;
+        self
+    }
+
+    /// Count the entries below the roots by listing directories alone, as
+    /// for `count_first`.
+    fn count_entries(&self) -> u64 {
+        let mut count = 0;
+        let mut dirs = vec![];
+        for root in std::iter::once(&self.root).chain(&self.roots) {
+            count += 1;
+            if fs::metadata(root).is_ok_and(|metadata| metadata.is_dir()) {
+                dirs.push((root.clone(), 0));
+            }
+        }
+
+        while let Some((dir, depth)) = dirs.pop() {
+            if depth >= self.max_depth {
+                continue;
+            }
+            if self
+                .cancel
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::Relaxed))
+            {
+                break;
+            }
+
+            for dent in fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = dent.path();
+                if self.is_excluded_path(&path) {
+                    continue;
+                }
+                count += 1;
+                if dent.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                    dirs.push((path, depth + 1));
+                }
+            }
+        }
+        count
+    }
+
+    /// Set how errors encountered during the scan are handled.  Defaults to
+    /// `ErrorPolicy::Yield`.
+    ///
+    /// ```rust
+    /// use filesize::{ErrorPolicy, Scanner};
+    ///
+    /// let mut scan = Scanner::new("src").errors(ErrorPolicy::Collect).into_iter();
+    /// let files = scan.by_ref().count();
+    /// for err in scan.errors() {
+    ///     eprintln!("{}", err);
+    /// }
+    /// ```
+    pub fn errors(mut self, policy: ErrorPolicy) -> Self {
+        self.errors = policy;
+        self
+    }
+
+    /// Only yield entries at least `depth` levels below the root.  Defaults
+    /// to 0, which includes the root itself.
+    ///
+    /// Shallower entries are still traversed, and counted in the usage totals.
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Do not descend into directories more than `depth` levels below the
+    /// root.  Defaults to no limit.
+    ///
+    /// A limit of 1 yields the root and its immediate children, without
+    /// walking anything below them.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Only yield files of at least `bytes`, as counted by
+    /// [`apparent_size`](Self::apparent_size).  Defaults to 0.
+    ///
+    /// Smaller files are still counted in the usage totals, and directories
+    /// are yielded regardless.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Only include entries for which `predicate` returns `true`.  Directories
+    /// it rejects are not descended into.
+    ///
+    /// Multiple predicates may be given, and all must accept an entry for it to
+    /// be included.  Excluded entries are not counted in the usage totals.  The
+    /// root of the scan is never excluded.
+    ///
+    /// ```rust
+    /// use filesize::Scanner;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let usage = Scanner::new(".")
+    ///     .filter(|path, _metadata| !path.ends_with("target"))
+    ///     .total()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Path, &Metadata) -> bool + Send + Sync + 'static,
+    {
+        self.predicates.push(Predicate(Box::new(predicate)));
+        self
+    }
+
+    /// Replace the paths left out of the scan, which default to
+    /// [`DEFAULT_EXCLUDED_PATHS`], such as `/proc` and `/sys` on Unix.  Pass an
+    /// empty list to scan everything.
+    ///
+    /// Absolute paths match the entry at that location, with relative roots
+    /// taken relative to the working directory when the `Scanner` was created.
+    /// Relative paths match directly below the root of any volume.  The root
+    /// of the scan is never excluded, so `/proc` can still be scanned
+    /// explicitly.
+    ///
+    /// ```rust
+    /// use filesize::{Scanner, DEFAULT_EXCLUDED_PATHS};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut excluded: Vec<&str> = DEFAULT_EXCLUDED_PATHS.to_vec();
+    /// excluded.push("/var/cache");
+    /// let usage = Scanner::new("src").excluded_paths(excluded).total()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn excluded_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        self.excluded_paths = paths
+            .into_iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect();
+        self
+    }
+
+    /// Leave `path` out of the scan, as well as those already given to
+    /// [`excluded_paths`](Self::excluded_paths) or excluded by default.
+    pub fn exclude_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.excluded_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Leave out the contents of cache directories marked by a `CACHEDIR.TAG`
+    /// file, like `tar --exclude-caches`.  Defaults to `false`.
+    ///
+    /// The directory and its tag are still counted, so the totals match what
+    /// backup tools honouring the [Cache Directory Tagging
+    /// Specification](https://bford.info/cachedir/) would save.
+    pub fn exclude_caches(mut self, enable: bool) -> Self {
+        self.exclude_caches = enable;
+        self
+    }
+
+    pub(crate) fn is_excluded_path(&self, path: &Path) -> bool {
+        self.excluded_paths.iter().any(|excluded| {
+            if excluded.is_absolute() {
+                path == excluded
+                    || (path.is_relative()
+                        && self
+                            .current_dir
+                            .as_ref()
+                            .is_some_and(|dir| dir.join(path) == *excluded))
+            } else {
+                // Directly below a volume root, itself without a parent.
+                path.parent()
+                    .is_some_and(|parent| parent.has_root() && parent.parent().is_none())
+                    && path.file_name() == Some(excluded.as_os_str())
+            }
+        })
+    }
+
+    /// Only include files matching `globs`, relative to the root of the scan.
+    /// Directories are traversed regardless.
+    ///
+    /// Requires the `globset` feature.
+    #[cfg(feature = "globset")]
+    pub fn include(mut self, globs: globset::GlobSet) -> Self {
+        self.include = Some(globs);
+        self
+    }
+
+    /// Exclude files and directories matching `globs`, relative to the root of
+    /// the scan.  Excluded directories are not descended into.
+    ///
+    /// Requires the `globset` feature.
+    #[cfg(feature = "globset")]
+    pub fn exclude(mut self, globs: globset::GlobSet) -> Self {
+        self.exclude = Some(globs);
+        self
+    }
+
+    fn includes(&self, path: &Path, metadata: &Metadata) -> bool {
+        if self.is_excluded_path(path) {
+            return false;
+        }
+
+        #[cfg(feature = "globset")]
+        {
+            let relative = path.strip_prefix(&self.root).unwrap_or(path);
+
+            if self.exclude.as_ref().is_some_and(|g| g.is_match(relative)) {
+                return false;
+            }
+
+            if !metadata.is_dir() && self.include.as_ref().is_some_and(|g| !g.is_match(relative)) {
+                return false;
+            }
+        }
+
+        if !metadata.is_dir() && !self.time_windows.iter().all(|w| w.contains(metadata)) {
+            return false;
+        }
+
+        self.predicates.iter().all(|p| (p.0)(path, metadata))
+    }
+
+    /// Only include files whose `timestamp` falls within `range`.
+    /// Directories are traversed regardless.
+    ///
+    /// Multiple windows may be given, and all must contain a file for it to be
+    /// included.  Files outside them are not counted in the usage totals, and
+    /// nor are files whose timestamp is unavailable, as
+    /// [`Timestamp::Changed`] is on every file outside Unix.
+    ///
+    /// ```rust
+    /// use std::time::{Duration, SystemTime};
+    /// use filesize::{Scanner, Timestamp};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let month_ago = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+    /// let recent = Scanner::new("src")
+    ///     .time_window(Timestamp::Modified, month_ago..)
+    ///     .total()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn time_window<R: RangeBounds<SystemTime>>(
+        mut self,
+        timestamp: Timestamp,
+        range: R,
+    ) -> Self {
+        self.time_windows.push(TimeWindow {
+            timestamp,
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+        });
+        self
+    }
+
+    /// Set the order the tree is visited in.  Defaults to `Order::DepthFirst`.
+    ///
+    /// Breadth-first scans keep a queue of every directory found but not yet
+    /// read, which may use more memory on wide trees.
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Visit the entries of each directory in order of file name, rather than
+    /// the order the filesystem lists them in.  Defaults to `false`.
+    ///
+    /// Listing order varies between filesystems, and even between runs on the
+    /// same one, so this makes the entries of a scan, and trees and reports
+    /// built from it, reproducible across runs and machines.  Each directory
+    /// is read in full and sorted before its entries are visited.
+    pub fn sorted(mut self, enable: bool) -> Self {
+        self.sorted = enable;
+        self
+    }
+
+    /// Limit the number of directories held open at once during depth-first
+    /// scans.  Defaults to 128.
+    ///
+    /// Each open directory costs a file descriptor or handle and a buffer of
+    /// pending entries, so without a limit a pathologically deep tree could
+    /// exhaust either.  Directories found beyond the limit are remembered by
+    /// path and scanned once the open ones are finished, so the scan is no
+    /// longer strictly depth-first.  The same happens if opening a directory
+    /// fails for want of descriptors while others are open.
+    ///
+    /// This bounds descriptors, not memory: the paths of directories waiting
+    /// to be scanned are kept however many there are, and sorted scans hold
+    /// each open directory's entries in full.
+    ///
+    /// Parallel scans share the limit between their threads, though each
+    /// thread may always hold one directory open to make progress, so keep it
+    /// comfortably below `ulimit -n` where that is low.
+    pub fn max_open_dirs(mut self, limit: usize) -> Self {
+        self.max_open_dirs = limit.max(1);
+        self
+    }
+
+    /// Count the space allocated to directories themselves, as `du` does,
+    /// rather than reporting them with a size of 0.  Defaults to `false`.
+    ///
+    /// Directories with very many entries can take a significant amount of
+    /// space.  This is only available on Unix platforms, and has no effect
+    /// elsewhere.
+    pub fn directory_sizes(mut self, enable: bool) -> Self {
+        self.directory_sizes = enable;
+        self
+    }
+
+    /// Count the logical lengths of files rather than their disk usage, like
+    /// `du --apparent-size`.  Defaults to `false`.
+    ///
+    /// This changes the size [`Entry::size`] and [`DiskUsage::size`] report,
+    /// and so the size progress reports, [`min_size`](Self::min_size)
+    /// filters by, and collectors such as [`TopK`](crate::TopK) rank by.
+    /// Both sizes are still found and totalled.
+    pub fn apparent_size(mut self, enable: bool) -> Self {
+        self.apparent_size = enable;
+        self
+    }
+
+    /// Count sizes in blocks of `unit` bytes, with each file rounded up to a
+    /// whole block, like `du -B`.  Defaults to 512, the unit POSIX `du` and
+    /// `st_blocks` count in.
+    ///
+    /// Blocks are reported by [`Entry::blocks`] and [`DiskUsage::blocks`],
+    /// from the sizes counted by [`apparent_size`](Self::apparent_size).
+    /// Since each file is rounded separately, the total may exceed the total
+    /// size divided by `unit`.  The cluster size of a volume can be found with
+    /// [`allocation_granularity`](crate::allocation_granularity).
+    ///
+    /// ```rust
+    /// use filesize::Scanner;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let usage = Scanner::new("src").apparent_size(true).block_size(1024).total()?;
+    /// assert!(usage.blocks() >= usage.size() / 1024);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_size(mut self, unit: u64) -> Self {
+        self.block_size = unit.max(1);
+        self
+    }
+
+    /// Count files with more than one hard link only the first time the scan
+    /// finds them, like `du`, leaving the others out of the scan.  Defaults
+    /// to `false`, counting every link.
+    ///
+    /// Links are matched by device and inode number, shared between all the
+    /// roots of the scan and the threads of a parallel one.  This is only
+    /// available on Unix platforms, and has no effect elsewhere.  Copies
+    /// sharing extents through reflinks are separate files, and are still
+    /// counted in full.
+    pub fn dedup_hard_links(mut self, enable: bool) -> Self {
+        self.dedup_hard_links = enable;
+        self
+    }
+
+    /// Report counters from the scan to `sink` as it runs.
+    pub fn metrics<M: MetricsSink + 'static>(mut self, sink: M) -> Self {
+        self.metrics = Some(Metrics(Box::new(sink)));
+        self
+    }
+
+    /// Limit the scan to at most `rate` entries per second, sleeping between
+    /// them as needed, so background scans don't starve other workloads of
+    /// I/O.  Defaults to no limit.
+    pub fn max_stats_per_sec(mut self, rate: u32) -> Self {
+        self.max_stats_per_sec = Some(rate.max(1));
+        self
+    }
+
+    /// Run the scan at idle I/O priority.  Defaults to `false`.
+    ///
+    /// This uses `ioprio_set` on Linux and Android, `setiopolicy_np` on macOS
+    /// and iOS, and background mode on Windows, which also lowers CPU
+    /// priority.  It has no effect on other platforms.
+    ///
+    /// The priority applies to the thread iterating the scan, from the first
+    /// call to `next` until the `Scan` is dropped.
+    pub fn background_io(mut self, enable: bool) -> Self {
+        self.background_io = enable;
+        self
+    }
+
+    /// Look up the sizes of files in `cache` before querying the filesystem,
+    /// and record those found in it, so unchanged files are not queried
+    /// again by later scans sharing the cache.
+    pub fn cache(mut self, cache: Arc<SizeCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Find the on-disk sizes of files with `backend`, rather than asking the
+    /// operating system.
+    pub fn backend<B: SizeBackend + 'static>(mut self, backend: B) -> Self {
+        self.backend = Some(Backend(Box::new(backend)));
+        self
+    }
+
+    /// Run [`total`](Self::total) across multiple threads, as configured by
+    /// `parallelism`.  Iterating the scan is unaffected.
+    ///
+    /// Progress is reported on the whole tree, with one final report once
+    /// every thread is done, and [`max_stats_per_sec`](Self::max_stats_per_sec)
+    /// limits the threads together.
+    #[cfg(feature = "rayon")]
+    pub fn parallelism(mut self, parallelism: crate::Parallelism) -> Self {
+        self.parallelism = Some(parallelism);
+        self
+    }
+
+    /// Run [`total`](Self::total) across `threads` threads of the standard
+    /// library, for those avoiding the `rayon` dependency.  Each directory is
+    /// a task of its own, and idle threads steal tasks from busy ones.
+    /// Iterating the scan is unaffected, and 1 thread, the default, totals
+    /// on the current thread.
+    ///
+    /// Progress is reported on the whole tree, and `max_stats_per_sec` limits
+    /// the threads together, as with `parallelism` under the `rayon` feature,
+    /// which takes precedence if both are given.  Should threads fail to
+    /// start, as on platforms without them, the scan runs on those that did,
+    /// and at least the current thread.
+    ///
+    /// ```rust
+    /// # fn main() -> std::io::Result<()> {
+    /// let usage = filesize::Scanner::new("src").threads(4).total()?;
+    /// println!("{} bytes on disk", usage.physical());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Give up on any query of the metadata or size of a file, or read of the
+    /// entries of a directory, taking longer than `limit`, failing it with an
+    /// error of kind `io::ErrorKind::TimedOut`, handled as the
+    /// [`errors`](Self::errors) policy directs.
+    ///
+    /// Each query runs on a pooled worker thread, so a hung NFS server or a
+    /// disconnected SMB share stalls only the part of the tree on it, rather
+    /// than the whole scan.  Queries which time out are left running on their
+    /// workers, and once 64 are outstanding, further queries fail at once.
+    /// Directories are read in full on a worker before their entries are
+    /// visited, as when [`sorted`](Self::sorted).
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let usage = filesize::Scanner::new("src")
+    ///     .timeout(Duration::from_secs(10))
+    ///     .total()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timeout(mut self, limit: Duration) -> Self {
+        self.timeouts = Some(TimeoutPool::new(limit));
+        self
+    }
+
+    /// Run `call` on `path` on a worker under the [`timeout`](Self::timeout),
+    /// if one is set, or on this thread.
+    fn timed<T: Send + 'static>(&self, path: &Path, call: fn(&Path) -> Result<T>) -> Result<T> {
+        match &self.timeouts {
+            Some(pool) => {
+                let owned = path.to_path_buf();
+                pool.run(move || call(&owned))
+                    .map_err(|e| Error::from_io(path, e))?
+            }
+            None => call(path),
+        }
+    }
+
+    /// Find the size of the file at `path`, and whether the filesystem was
+    /// queried for it, rather than the cache.
+    pub(crate) fn query_size(&self, path: &Path, metadata: &Metadata) -> Result<(SizeInfo, bool)> {
+        let backend: &dyn SizeBackend = match &self.backend {
+            Some(Backend(backend)) => backend.as_ref(),
+            None => &OsBackend,
+        };
+        match &self.cache {
+            Some(cache) => cache.lookup(backend, &self.options, path, metadata),
+            None => Ok((self.options.size_info_with(backend, path, metadata)?, true)),
+        }
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The depth of `path` below the root, if it lies within the scan's
+    /// depth limit.
+    #[cfg(feature = "notify")]
+    pub(crate) fn depth_of(&self, path: &Path) -> Option<usize> {
+        let depth = path.strip_prefix(&self.root).ok()?.components().count();
+        Some(depth).filter(|&depth| depth <= self.max_depth)
+    }
+
+    fn yields(&self, entry: &Entry) -> bool {
+        entry.depth >= self.min_depth && (entry.file_type.is_dir() || entry.size() >= self.min_size)
+    }
+
+    fn tracks_directories(&self) -> bool {
+        !self.roots.is_empty()
+            || self.follow_symlinks
+            || self.directory_links == Some(LinkPolicy::Traverse)
+            || reaches_firmlinks(&self.root)
+    }
+
+    /// Run the scan to completion, returning the total disk usage of the tree.
+    ///
+    /// With `ErrorPolicy::Yield` or `ErrorPolicy::Abort` the scan is aborted on
+    /// the first error, otherwise errors are passed over.  If it is cancelled,
+    /// the usage of the part of the tree seen so far is returned.
+    pub fn total(self) -> Result<DiskUsage> {
+        #[cfg(feature = "rayon")]
+        if let Some(parallelism) = self.parallelism.clone() {
+            return parallelism.total(self);
+        }
+        if self.threads > 1 {
+            let threads = self.threads;
+            return crate::threads::total(self, threads);
+        }
+
+        let mut scan = self.into_iter();
+        for entry in &mut scan {
+            entry?;
+        }
+        Ok(scan.usage())
+    }
+}
+
+impl IntoIterator for Scanner {
+    type Item = Result<Entry>;
+    type IntoIter = Scan;
+
+    fn into_iter(self) -> Scan {
+        Scan::new(Arc::new(self), Arc::default()).root()
+    }
+}
+
+/// A file or directory found during a scan.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+    info: SizeInfo,
+    filesystem: FilesystemKind,
+    metadata: Metadata,
+    pub(crate) apparent_size: bool,
+    block_size: u64,
+}
+
+impl Entry {
+    /// The path of this entry, which is joined onto the root of the scan.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The depth of this entry relative to the root, which has a depth of 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The file type of this entry.  If symlinks are being followed this is
+    /// the type of the target.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// The on-disk size of this entry.
+    ///
+    /// Directories are reported with a size of 0, unless
+    /// [`Scanner::directory_sizes`] is enabled.
+    pub fn size_on_disk(&self) -> u64 {
+        self.info.physical()
+    }
+
+    /// The size this entry counts towards the scan: its on-disk size, or its
+    /// logical length if [`Scanner::apparent_size`] is enabled.
+    pub fn size(&self) -> u64 {
+        if self.apparent_size {
+            self.info.logical()
+        } else {
+            self.info.physical()
+        }
+    }
+
+    /// The size of this entry in blocks of the unit given to
+    /// [`Scanner::block_size`], rounded up.
+    pub fn blocks(&self) -> u64 {
+        self.size().div_ceil(self.block_size)
+    }
+
+    /// The on-disk and logical sizes of this entry, and how they were
+    /// determined.
+    pub fn size_info(&self) -> SizeInfo {
+        self.info
+            .with_memory_backed(self.filesystem == FilesystemKind::Memory)
+    }
+
+    /// The kind of filesystem this entry resides on.
+    pub fn filesystem(&self) -> FilesystemKind {
+        self.filesystem
+    }
+
+    /// The metadata found for this entry during the scan, so permissions,
+    /// timestamps, and the like can be read without another `stat()`.  If
+    /// symlinks are being followed this is the metadata of the target.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+}
+
+/// Aggregated disk usage of a set of files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiskUsage {
+    physical: u128,
+    logical: u128,
+    preallocated: u128,
+    memory: u128,
+    files: u64,
+    directories: u64,
+    symlinks: u64,
+    apparent_size: bool,
+    blocks: u128,
+}
+
+impl DiskUsage {
+    /// Add the usage of `other` to this, as when totalling scans of separate
+    /// trees run on different threads or machines.
+    ///
+    /// Nothing is deduplicated, so trees which overlap or share hard links
+    /// are counted in full by each.  Totals saturate rather than overflow.
+    pub fn merge(&mut self, other: &DiskUsage) {
+        self.physical = self.physical.saturating_add(other.physical);
+        self.logical = self.logical.saturating_add(other.logical);
+        self.preallocated = self.preallocated.saturating_add(other.preallocated);
+        self.memory = self.memory.saturating_add(other.memory);
+        self.files = self.files.saturating_add(other.files);
+        self.directories = self.directories.saturating_add(other.directories);
+        self.symlinks = self.symlinks.saturating_add(other.symlinks);
+        self.apparent_size |= other.apparent_size;
+        self.blocks = self.blocks.saturating_add(other.blocks);
+    }
+
+    /// Take away the usage of `other`, which must have been merged or added
+    /// into this.
+    pub(crate) fn subtract(&mut self, other: &DiskUsage) {
+        self.physical = self.physical.saturating_sub(other.physical);
+        self.logical = self.logical.saturating_sub(other.logical);
+        self.preallocated = self.preallocated.saturating_sub(other.preallocated);
+        self.memory = self.memory.saturating_sub(other.memory);
+        self.files = self.files.saturating_sub(other.files);
+        self.directories = self.directories.saturating_sub(other.directories);
+        self.symlinks = self.symlinks.saturating_sub(other.symlinks);
+        self.blocks = self.blocks.saturating_sub(other.blocks);
+    }
+
+    pub(crate) fn add(&mut self, entry: &Entry) {
+        self.apparent_size |= entry.apparent_size;
+        self.blocks = self.blocks.saturating_add(u128::from(entry.blocks()));
+        self.add_sizes(&entry.info);
+        if entry.filesystem == FilesystemKind::Memory {
+            self.memory = self
+                .memory
+                .saturating_add(u128::from(entry.info.physical()));
+        }
+
+        if entry.file_type.is_dir() {
+            self.directories = self.directories.saturating_add(1);
+        } else if entry.file_type.is_symlink() {
+            self.symlinks = self.symlinks.saturating_add(1);
+        } else {
+            self.files = self.files.saturating_add(1);
+        }
+    }
+
+    fn add_sizes(&mut self, info: &SizeInfo) {
+        self.physical = self.physical.saturating_add(u128::from(info.physical()));
+        self.logical = self.logical.saturating_add(u128::from(info.logical()));
+        self.preallocated = self
+            .preallocated
+            .saturating_add(u128::from(info.preallocated_bytes()));
+    }
+
+    /// The total size counted by the scan: [`physical`](Self::physical), or
+    /// [`logical`](Self::logical) if [`Scanner::apparent_size`] is enabled.
+    pub fn size(&self) -> u64 {
+        if self.apparent_size {
+            self.logical()
+        } else {
+            self.physical()
+        }
+    }
+
+    /// The total size in blocks of the unit given to [`Scanner::block_size`],
+    /// with each file rounded up to a whole block, or `u64::MAX` if that is
+    /// exceeded.
+    pub fn blocks(&self) -> u64 {
+        saturate(self.blocks)
+    }
+
+    /// The total space used on disk, or `u64::MAX` if that is exceeded.
+    pub fn physical(&self) -> u64 {
+        saturate(self.physical)
+    }
+
+    /// The total space used on disk, without limit.
+    pub fn physical_u128(&self) -> u128 {
+        self.physical
+    }
+
+    /// The total logical length of the files, as reported by `Metadata::len()`,
+    /// or `u64::MAX` if that is exceeded.
+    pub fn logical(&self) -> u64 {
+        saturate(self.logical)
+    }
+
+    /// The total logical length of the files, without limit.
+    pub fn logical_u128(&self) -> u128 {
+        self.logical
+    }
+
+    /// The total space preallocated beyond the ends of files, as given by
+    /// [`SizeInfo::preallocated_bytes`].
+    pub fn preallocated_bytes(&self) -> u64 {
+        saturate(self.preallocated)
+    }
+
+    /// The part of the physical total held on memory filesystems such as
+    /// tmpfs, which consumes RAM and swap rather than disk space.
+    pub fn memory_bytes(&self) -> u64 {
+        saturate(self.memory)
+    }
+
+    /// The physical total excluding that held on memory filesystems, or
+    /// `u64::MAX` if that is exceeded.
+    pub fn disk_bytes(&self) -> u64 {
+        saturate(self.physical.saturating_sub(self.memory))
+    }
+
+    /// The space saved by compression, deduplication, and sparse files: how
+    /// far the logical total exceeds the physical total, or 0 if it doesn't.
+    pub fn saved_bytes(&self) -> u64 {
+        saturate(self.logical.saturating_sub(self.physical))
+    }
+
+    /// The percentage of the logical total saved on disk, as `compsize`
+    /// reports for btrfs.  This is negative when files use more space than
+    /// their lengths, as small files rounded up to whole blocks do, and 0 if
+    /// the logical total is 0.
+    pub fn savings_percent(&self) -> f64 {
+        if self.logical == 0 {
+            return 0.0;
+        }
+        (1.0 - self.physical as f64 / self.logical as f64) * 100.0
+    }
+
+    /// The number of files other than directories and symlinks.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The number of directories.
+    pub fn directories(&self) -> u64 {
+        self.directories
+    }
+
+    /// The number of symlinks, or other links on Windows, that were counted
+    /// as links rather than followed.
+    pub fn symlinks(&self) -> u64 {
+        self.symlinks
+    }
+
+    /// The total number of entries, and so approximately the number of inodes
+    /// used.  Hard links are counted once for each link.
+    pub fn entries(&self) -> u64 {
+        self.files
+            .saturating_add(self.directories)
+            .saturating_add(self.symlinks)
+    }
+}
+
+fn saturate(total: u128) -> u64 {
+    total.min(u128::from(u64::MAX)) as u64
+}
+
+/// Whether a scan of `root` can reach directories on the macOS data volume
+/// both through the firmlinks of the system volume, such as `/Users`, and
+/// through `/System/Volumes/Data`, where it is mounted.  Firmlinks don't
+/// appear as links, so only tracking directories counts them once.
+#[cfg(target_os = "macos")]
+fn reaches_firmlinks(root: &Path) -> bool {
+    let data = Path::new("/System/Volumes/Data");
+    fs::canonicalize(root).is_ok_and(|root| data.starts_with(&root) && root != data)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn reaches_firmlinks(_root: &Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+pub(crate) type DirId = (u64, u64);
+
+#[cfg(unix)]
+fn dir_id(_path: &Path, metadata: &Metadata) -> io::Result<DirId> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(windows)]
+pub(crate) type DirId = (u32, u64);
+
+#[cfg(windows)]
+fn dir_id(path: &Path, _metadata: &Metadata) -> io::Result<DirId> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+
+    use crate::win::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS,
+    };
+
+    let file = fs::OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?;
+
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle() as _, &mut info) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok((
+        info.dwVolumeSerialNumber,
+        u64::from(info.nFileIndexHigh) << 32 | u64::from(info.nFileIndexLow),
+    ))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) type DirId = PathBuf;
+
+#[cfg(not(any(unix, windows)))]
+fn dir_id(path: &Path, _metadata: &Metadata) -> io::Result<DirId> {
+    fs::canonicalize(path)
+}
+
+/// A count of the directories held open by a scan and its siblings.
+type OpenDirs = Arc<AtomicUsize>;
+
+/// The state a scan shares with its siblings, so a tree split between
+/// threads is throttled and reported on as a whole.
+#[derive(Debug)]
+struct Shared {
+    /// The number of scans yet to finish, the last of which gives the final
+    /// progress report.
+    running: AtomicUsize,
+    /// The totals of every sibling, and when they were last reported.
+    progress: Mutex<(Tally, Option<Instant>)>,
+    next_stat: Mutex<Option<Instant>>,
+    filesystems: Mutex<HashMap<u64, FilesystemKind>>,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Shared {
+            running: AtomicUsize::new(1),
+            progress: Mutex::default(),
+            next_stat: Mutex::default(),
+            filesystems: Mutex::default(),
+        }
+    }
+}
+
+/// Counts of what a scan and its siblings have seen, for progress reports.
+#[derive(Debug, Default, Clone, Copy)]
+struct Tally {
+    usage: DiskUsage,
+    skipped: u64,
+    errors: u64,
+}
+
+/// One directory's share of an `OpenDirs` count, released when dropped.
+#[derive(Debug)]
+struct OpenSlot(OpenDirs);
+
+impl OpenSlot {
+    /// Take a slot if fewer than `limit` are held.
+    fn reserve(open: &OpenDirs, limit: usize) -> Option<Self> {
+        open.fetch_update(Ordering::AcqRel, Ordering::Acquire, |held| {
+            if held < limit {
+                Some(held + 1)
+            } else {
+                None
+            }
+        })
+        .ok()
+        .map(|_| OpenSlot(Arc::clone(open)))
+    }
+
+    /// Take a slot regardless of the limit, so a scan can always progress.
+    fn force(open: &OpenDirs) -> Self {
+        open.fetch_add(1, Ordering::AcqRel);
+        OpenSlot(Arc::clone(open))
+    }
+}
+
+impl Drop for OpenSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Whether `err` means the process or system is out of file descriptors or
+/// handles, so opening a directory may succeed later once others are closed.
+fn is_out_of_handles(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(err.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE))
+    }
+    #[cfg(windows)]
+    {
+        err.raw_os_error() == Some(crate::win::ERROR_TOO_MANY_OPEN_FILES as i32)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = err;
+        false
+    }
+}
+
+/// A directory being read during a scan.
+#[derive(Debug)]
+struct OpenDir {
+    path: PathBuf,
+    rd: DirEntries,
+    depth: usize,
+    _slot: OpenSlot,
+    #[cfg(feature = "tracing")]
+    entries: u64,
+    #[cfg(feature = "tracing")]
+    started: Instant,
+}
+
+impl OpenDir {
+    fn new(path: PathBuf, rd: DirEntries, depth: usize, slot: OpenSlot) -> Self {
+        Self {
+            path,
+            rd,
+            depth,
+            _slot: slot,
+            #[cfg(feature = "tracing")]
+            entries: 0,
+            #[cfg(feature = "tracing")]
+            started: Instant::now(),
+        }
+    }
+}
+
+/// The paths of the entries of an open directory, as they are listed, or
+/// read in full beforehand.
+#[derive(Debug)]
+enum DirEntries {
+    Listed(Box<ReadDir>),
+    Read(std::vec::IntoIter<io::Result<PathBuf>>),
+}
+
+impl DirEntries {
+    /// Open the directory at `path` for `opts`, reading it in full if its
+    /// entries are to be sorted, or on a worker under a timeout.
+    fn open(opts: &Scanner, path: &Path) -> io::Result<Self> {
+        let sorted = opts.sorted;
+        let paths = match &opts.timeouts {
+            Some(pool) => {
+                let path = path.to_path_buf();
+                pool.run(move || fs::read_dir(path).map(|rd| read_paths(rd, sorted)))??
+            }
+            None if sorted => read_paths(fs::read_dir(path)?, sorted),
+            None => return Ok(DirEntries::Listed(Box::new(fs::read_dir(path)?))),
+        };
+        Ok(DirEntries::Read(paths.into_iter()))
+    }
+}
+
+fn read_paths(rd: ReadDir, sorted: bool) -> Vec<io::Result<PathBuf>> {
+    let mut paths: Vec<io::Result<PathBuf>> = rd.map(|dent| dent.map(|dent| dent.path())).collect();
+    if sorted {
+        // Siblings share a parent, so their paths sort as their names do,
+        // sparing another allocation per entry for a name to sort by.
+        paths.sort_by(|a, b| a.as_ref().ok().cmp(&b.as_ref().ok()));
+    }
+    paths
+}
+
+impl Iterator for DirEntries {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            DirEntries::Listed(rd) => rd.next().map(|dent| dent.map(|dent| dent.path())),
+            DirEntries::Read(paths) => paths.next(),
+        }
+    }
+}
+
+/// An iterator over the entries of a [`Scanner`].
+#[derive(Debug)]
+pub struct Scan {
+    opts: Arc<Scanner>,
+    start: Option<(PathBuf, usize)>,
+    stack: Vec<OpenDir>,
+    pending: Vec<(PathBuf, usize)>,
+    pending_errors: Vec<Error>,
+    queue: VecDeque<(PathBuf, usize)>,
+    split_depth: Option<usize>,
+    split: Vec<(PathBuf, usize)>,
+    visited: Arc<Mutex<HashSet<DirId>>>,
+    tracks_directories: bool,
+    #[cfg(unix)]
+    links: Arc<Mutex<HashSet<(u64, u64)>>>,
+    open_dirs: OpenDirs,
+    filesystems: HashMap<u64, FilesystemKind>,
+    shared: Arc<Shared>,
+    usage: DiskUsage,
+    reported: Tally,
+    finished: bool,
+    errors: Vec<Error>,
+    error_count: u64,
+    skipped: u64,
+    expected: Option<u64>,
+    started: Instant,
+    background: Option<BackgroundIo>,
+}
+
+impl Scan {
+    /// Create a scan with nothing to do, sharing `opts` and the set of
+    /// `visited` directories with any others.
+    pub(crate) fn new(opts: Arc<Scanner>, visited: Arc<Mutex<HashSet<DirId>>>) -> Self {
+        Scan {
+            tracks_directories: opts.tracks_directories(),
+            opts,
+            start: None,
+            stack: vec![],
+            pending: vec![],
+            pending_errors: vec![],
+            queue: VecDeque::new(),
+            split_depth: None,
+            split: vec![],
+            visited,
+            #[cfg(unix)]
+            links: Arc::default(),
+            open_dirs: OpenDirs::default(),
+            filesystems: HashMap::new(),
+            shared: Arc::default(),
+            usage: DiskUsage::default(),
+            reported: Tally::default(),
+            finished: false,
+            errors: vec![],
+            error_count: 0,
+            skipped: 0,
+            expected: None,
+            started: Instant::now(),
+            background: None,
+        }
+    }
+
+    /// Create a scan with nothing to do, sharing options, visited
+    /// directories, the budget of open directories, the stat throttle, and
+    /// progress reports with this one.
+    pub(crate) fn sibling(&self) -> Self {
+        let mut sibling = Scan::new(Arc::clone(&self.opts), Arc::clone(&self.visited));
+        sibling.open_dirs = Arc::clone(&self.open_dirs);
+        self.shared.running.fetch_add(1, Ordering::AcqRel);
+        sibling.shared = Arc::clone(&self.shared);
+        sibling.expected = self.expected;
+        sibling.started = self.started;
+        #[cfg(unix)]
+        {
+            sibling.links = Arc::clone(&self.links);
+        }
+        sibling
+    }
+
+    /// Start the scan from the roots of the tree.
+    pub(crate) fn root(mut self) -> Self {
+        self.expected = match self.opts.expected_entries {
+            Some(entries) => Some(entries),
+            None if self.opts.count_first && self.opts.progress.is_some() => {
+                Some(self.opts.count_entries())
+            }
+            None => None,
+        };
+        self.start = Some((self.opts.root.clone(), 0));
+        self.pending = self
+            .opts
+            .roots
+            .iter()
+            .rev()
+            .map(|root| (root.clone(), 0))
+            .collect();
+        self
+    }
+
+    /// Start the scan from `path`, which is `depth` levels below the root.
+    #[cfg(feature = "notify")]
+    pub(crate) fn starting_at(mut self, path: PathBuf, depth: usize) -> Self {
+        self.start = Some((path, depth));
+        self
+    }
+
+    /// Scan the contents of the directory at `path`, which is `depth` levels
+    /// below the root, but not the directory itself, adding to any work the
+    /// scan has left.
+    pub(crate) fn add_contents(&mut self, path: PathBuf, depth: usize) {
+        self.queue.push_back((path, depth + 1));
+    }
+
+    /// Rather than descending into directories less than `depth` levels below
+    /// the root, set them aside to be retrieved with `take_split`.
+    pub(crate) fn split_above(mut self, depth: usize) -> Self {
+        self.split_depth = Some(depth);
+        self
+    }
+
+    /// The directories set aside by `split_above`.
+    pub(crate) fn take_split(&mut self) -> Vec<(PathBuf, usize)> {
+        std::mem::take(&mut self.split)
+    }
+
+    /// The disk usage of all the entries seen so far, whether or not they were
+    /// yielded.
+    pub fn usage(&self) -> DiskUsage {
+        self.usage
+    }
+
+    /// The errors passed over so far under `ErrorPolicy::Collect`.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Record where the scan has got to, so it can be resumed with
+    /// [`Scanner::resume`] after the process exits.
+    ///
+    /// Directories the scan is partway through are read to the end, and their
+    /// remaining entries recorded, since a position within a directory can't
+    /// be saved.  The scan carries on from the same state.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        for dir in std::mem::take(&mut self.stack) {
+            let mut entries = vec![];
+            for path in dir.rd {
+                match path {
+                    Ok(path) => entries.push((path, dir.depth)),
+                    Err(e) => self.pending_errors.push(Error::from_io(&dir.path, e)),
+                }
+            }
+            self.pending.extend(entries.into_iter().rev());
+        }
+
+        let mut visited: Vec<DirId> = match self.visited.lock() {
+            Ok(visited) => visited.iter().cloned().collect(),
+            Err(_) => vec![],
+        };
+        visited.sort();
+        #[cfg(unix)]
+        let mut links: Vec<(u64, u64)> = match self.links.lock() {
+            Ok(links) => links.iter().copied().collect(),
+            Err(_) => vec![],
+        };
+        #[cfg(not(unix))]
+        let mut links = vec![];
+        links.sort();
+
+        Checkpoint {
+            root: self.opts.root.clone(),
+            usage: self.usage,
+            pending: self.start.iter().chain(&self.pending).cloned().collect(),
+            queue: self.queue.iter().cloned().collect(),
+            errors: self.error_count,
+            skipped: self.skipped,
+            visited,
+            links,
+        }
+    }
+
+    /// A scan carrying on from `checkpoint`.
+    pub(crate) fn resumed(mut self, checkpoint: Checkpoint) -> Self {
+        self.usage = checkpoint.usage;
+        self.pending = checkpoint.pending;
+        self.queue = checkpoint.queue.into();
+        self.error_count = checkpoint.errors;
+        self.skipped = checkpoint.skipped;
+        if let Ok(mut visited) = self.visited.lock() {
+            visited.extend(checkpoint.visited);
+        }
+        #[cfg(unix)]
+        if let Ok(mut links) = self.links.lock() {
+            links.extend(checkpoint.links);
+        }
+        self
+    }
+
+    /// A summary of the scan so far.
+    pub fn summary(&self) -> ScanSummary {
+        let accuracy = match &self.opts.backend {
+            Some(Backend(backend)) => backend.accuracy(),
+            None => OsBackend.accuracy(),
+        };
+        ScanSummary {
+            usage: self.usage,
+            errors: self.error_count,
+            skipped: self.skipped,
+            elapsed: self.started.elapsed(),
+            accuracy,
+            cancelled: self.is_cancelled(),
+        }
+    }
+
+    /// Whether the scan was stopped early by [`Scanner::cancel_on`].
+    pub fn is_cancelled(&self) -> bool {
+        self.opts
+            .cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    fn account(&mut self, entry: &Entry) {
+        self.usage.add(entry);
+        if let Some(Metrics(sink)) = &self.opts.metrics {
+            sink.entry(entry);
+        }
+    }
+
+    fn queried(&self, count: u64) {
+        if let Some(Metrics(sink)) = &self.opts.metrics {
+            sink.queried(count);
+        }
+    }
+
+    fn throttle(&mut self) {
+        let rate = match self.opts.max_stats_per_sec {
+            Some(rate) => rate,
+            None => return,
+        };
+
+        // Siblings take turns, so the rate holds across threads.
+        let interval = Duration::from_secs(1) / rate;
+        let now = Instant::now();
+        let at = match self.shared.next_stat.lock() {
+            Ok(mut next) => {
+                let at = next.filter(|&next| next > now).unwrap_or(now);
+                *next = Some(at + interval);
+                at
+            }
+            Err(_) => return,
+        };
+        if at > now {
+            std::thread::sleep(at - now);
+        }
+    }
+
+    fn visit(&mut self, path: PathBuf, depth: usize) -> Result<Option<Entry>> {
+        self.throttle();
+        self.queried(1);
+        let mut metadata = self.opts.timed(&path, |path| {
+            fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))
+        })?;
+        let mut zeroed = false;
+
+        if metadata.file_type().is_symlink() {
+            let follow = match self
+                .opts
+                .directory_links
+                .filter(|_| is_directory_link(&path, &metadata))
+            {
+                Some(LinkPolicy::Skip) => return Ok(None),
+                Some(LinkPolicy::Zero) => {
+                    zeroed = true;
+                    false
+                }
+                Some(LinkPolicy::Traverse) => true,
+                None => self.opts.follow_symlinks,
+            };
+
+            if follow {
+                self.queried(1);
+                metadata = self.opts.timed(&path, |path| {
+                    fs::metadata(path).map_err(|e| Error::from_io(path, e))
+                })?;
+            }
+        }
+
+        if depth > 0 && !self.opts.includes(&path, &metadata) {
+            return Ok(None);
+        }
+
+        let filesystem = self.filesystem(&path, &metadata)?;
+        if (filesystem == FilesystemKind::Virtual && self.opts.skip_virtual_filesystems)
+            || (filesystem == FilesystemKind::Memory && self.opts.skip_memory_filesystems)
+        {
+            return Ok(None);
+        }
+
+        let mut entry = Entry {
+            path,
+            depth,
+            file_type: metadata.file_type(),
+            info: SizeInfo::new(0, 0, SizeAccuracy::Policy),
+            filesystem,
+            metadata,
+            apparent_size: self.opts.apparent_size,
+            block_size: self.opts.block_size,
+        };
+
+        if zeroed {
+            self.account(&entry);
+            return Ok(Some(entry));
+        }
+
+        if entry.metadata.is_dir() {
+            if self.opts.directory_sizes && cfg!(unix) {
+                entry.info = self.size(&entry.path, &entry.metadata)?;
+            }
+
+            if depth >= self.opts.max_depth {
+                self.account(&entry);
+                return Ok(Some(entry));
+            }
+
+            if self.tracks_directories {
+                self.queried(1);
+                let id = dir_id(&entry.path, &entry.metadata)
+                    .map_err(|e| Error::from_io(&entry.path, e))?;
+                let first = match self.visited.lock() {
+                    Ok(mut visited) => visited.insert(id),
+                    Err(_) => true,
+                };
+                if !first {
+                    return Ok(None);
+                }
+            }
+
+            if self.opts.exclude_caches && is_cache_directory(&entry.path) {
+                self.queried(1);
+                self.pending
+                    .push((entry.path.join(CACHEDIR_TAG), depth + 1));
+                self.account(&entry);
+                return Ok(Some(entry));
+            }
+
+            match self.opts.order {
+                _ if self.split_depth.is_some_and(|split| depth < split) => {
+                    self.split.push((entry.path.clone(), depth));
+                }
+                // The directory is yielded and counted before any error
+                // opening it, as when breadth-first.
+                Order::DepthFirst => match self.open(&entry.path, depth + 1) {
+                    Ok(Some(dir)) => self.stack.push(dir),
+                    Ok(None) => self.queue.push_back((entry.path.clone(), depth + 1)),
+                    Err(e) => self.pending_errors.push(e),
+                },
+                Order::BreadthFirst => self.queue.push_back((entry.path.clone(), depth + 1)),
+            }
+
+            self.account(&entry);
+            return Ok(Some(entry));
+        }
+
+        if self.opts.dedup_hard_links && !self.first_link(&entry.metadata) {
+            return Ok(None);
+        }
+
+        entry.info = self.size(&entry.path, &entry.metadata)?;
+
+        self.account(&entry);
+
+        Ok(Some(entry))
+    }
+
+    /// Open the directory at `path` for a depth-first descent, or return
+    /// `None` if the budget of open directories is spent, or the process has
+    /// run out of handles while others are open, so it should be queued.
+    fn open(&mut self, path: &Path, depth: usize) -> Result<Option<OpenDir>> {
+        let slot = match OpenSlot::reserve(&self.open_dirs, self.opts.max_open_dirs) {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+
+        self.queried(1);
+        match DirEntries::open(&self.opts, path) {
+            Ok(rd) => Ok(Some(OpenDir::new(path.to_path_buf(), rd, depth, slot))),
+            Err(e) if is_out_of_handles(&e) && !self.stack.is_empty() => {
+                event!(tracing::Level::DEBUG, path = %path.display(), "deferred directory");
+                Ok(None)
+            }
+            Err(e) => Err(Error::from_io(path, e)),
+        }
+    }
+
+    /// Whether this is the first time the scan has found the file with
+    /// `metadata`, if it has more than one hard link.
+    #[cfg(unix)]
+    fn first_link(&self, metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        if metadata.nlink() < 2 {
+            return true;
+        }
+        match self.links.lock() {
+            Ok(mut links) => links.insert((metadata.dev(), metadata.ino())),
+            Err(_) => true,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn first_link(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn size(&self, path: &Path, metadata: &Metadata) -> Result<SizeInfo> {
+        let (info, queried) = match &self.opts.timeouts {
+            Some(pool) => {
+                let opts = Arc::clone(&self.opts);
+                let (owned, metadata) = (path.to_path_buf(), metadata.clone());
+                pool.run(move || opts.query_size(&owned, &metadata))
+                    .map_err(|e| Error::from_io(path, e))??
+            }
+            None => self.opts.query_size(path, metadata)?,
+        };
+        if queried {
+            self.queried(1);
+        }
+        Ok(info)
+    }
+
+    fn filesystem(&mut self, path: &Path, metadata: &Metadata) -> Result<FilesystemKind> {
+        let dev = match device_id(metadata) {
+            Some(dev) => dev,
+            None => return Ok(FilesystemKind::Regular),
+        };
+
+        if let Some(&kind) = self.filesystems.get(&dev) {
+            return Ok(kind);
+        }
+
+        let known = match self.shared.filesystems.lock() {
+            Ok(filesystems) => filesystems.get(&dev).copied(),
+            Err(_) => None,
+        };
+        let kind = match known {
+            Some(kind) => kind,
+            None => {
+                self.queried(1);
+                let kind = self.opts.timed(path, |path| crate::filesystem_kind(path))?;
+                if let Ok(mut filesystems) = self.shared.filesystems.lock() {
+                    filesystems.insert(dev, kind);
+                }
+                kind
+            }
+        };
+        self.filesystems.insert(dev, kind);
+        Ok(kind)
+    }
+
+    /// Mark the scan finished, giving a final progress report if its
+    /// siblings are finished too.
+    pub(crate) fn finish(&mut self) {
+        self.background = None;
+        if !self.finished {
+            self.finished = true;
+            let last = self.shared.running.fetch_sub(1, Ordering::AcqRel) == 1;
+            let root = self.opts.root.clone();
+            self.report(&root, last);
+        }
+    }
+
+    fn report(&mut self, path: &Path, force: bool) {
+        let hook = match &self.opts.progress {
+            Some(hook) => hook,
+            None => return,
+        };
+
+        // Add what this scan has seen since it last reported to the totals
+        // it shares with its siblings, and report on those.
+        let now = Instant::now();
+        let tally = match self.shared.progress.lock() {
+            Ok(mut progress) => {
+                let (tally, last) = &mut *progress;
+                let mut usage = self.usage;
+                usage.subtract(&self.reported.usage);
+                tally.usage.merge(&usage);
+                tally.skipped += self.skipped - self.reported.skipped;
+                tally.errors += self.error_count - self.reported.errors;
+
+                let due = last.is_none_or(|last| now.duration_since(last) >= hook.interval);
+                if due || force {
+                    *last = Some(now);
+                    Some(*tally)
+                } else {
+                    None
+                }
+            }
+            Err(_) => return,
+        };
+        self.reported = Tally {
+            usage: self.usage,
+            skipped: self.skipped,
+            errors: self.error_count,
+        };
+
+        if let (Some(tally), Ok(mut callback)) = (tally, hook.callback.lock()) {
+            callback(&Progress {
+                files: tally.usage.files + tally.usage.symlinks,
+                bytes: tally.usage.size(),
+                entries: tally.usage.entries() + tally.skipped + tally.errors,
+                expected: self.expected,
+                elapsed: self.started.elapsed(),
+                path,
+            });
+        }
+    }
+
+    /// The next entry, without finishing the scan once there are none left,
+    /// so more work can be added with `add_contents`.
+    pub(crate) fn next_entry(&mut self) -> Option<Result<Entry>> {
+        if self.opts.background_io && self.background.is_none() {
+            self.background = BackgroundIo::enter();
+        }
+
+        loop {
+            match self.advance() {
+                Some(Ok(entry)) => {
+                    self.report(&entry.path, false);
+                    if self.opts.yields(&entry) {
+                        return Some(Ok(entry));
+                    }
+                }
+                Some(Err(e)) => {
+                    self.error_count += 1;
+                    if let Some(Metrics(sink)) = &self.opts.metrics {
+                        sink.error(&e);
+                    }
+
+                    match self.opts.errors {
+                        ErrorPolicy::Yield => return Some(Err(e)),
+                        ErrorPolicy::Abort => {
+                            // Drop everything left to visit, including other
+                            // roots and entries queued for later, so nothing
+                            // follows the error.
+                            self.start = None;
+                            self.stack.clear();
+                            self.pending.clear();
+                            self.pending_errors.clear();
+                            self.queue.clear();
+                            self.split.clear();
+                            self.finish();
+                            return Some(Err(e));
+                        }
+                        ErrorPolicy::Skip => {
+                            event!(tracing::Level::WARN, error = %e, "skipped error");
+                        }
+                        ErrorPolicy::Collect => {
+                            event!(tracing::Level::WARN, error = %e, "collected error");
+                            self.errors.push(e);
+                        }
+                    }
+                }
+                None => return None,
+            }
+        }
+    }
+
+    fn advance(&mut self) -> Option<Result<Entry>> {
+        if self.is_cancelled() {
+            return None;
+        }
+
+        if let Some((start, depth)) = self.start.take() {
+            match self.visit(start, depth).transpose() {
+                Some(res) => return Some(res),
+                None => self.skipped += 1,
+            }
+        }
+
+        loop {
+            if let Some(e) = self.pending_errors.pop() {
+                return Some(Err(e));
+            }
+
+            if self.stack.is_empty() {
+                if let Some((path, depth)) = self.pending.pop() {
+                    match self.visit(path, depth).transpose() {
+                        Some(res) => return Some(res),
+                        None => self.skipped += 1,
+                    }
+                    if self.is_cancelled() {
+                        return None;
+                    }
+                    continue;
+                }
+
+                let (dir, depth) = self.queue.pop_front()?;
+                self.queried(1);
+                match DirEntries::open(&self.opts, &dir) {
+                    Ok(rd) => {
+                        let slot = OpenSlot::force(&self.open_dirs);
+                        self.stack.push(OpenDir::new(dir, rd, depth, slot));
+                    }
+                    Err(e) => return Some(Err(Error::from_io(&dir, e))),
+                }
+            }
+
+            let dir = self.stack.last_mut()?;
+            let depth = dir.depth;
+            match dir.rd.next() {
+                None => {
+                    if let Some(_dir) = self.stack.pop() {
+                        event!(
+                            tracing::Level::DEBUG,
+                            path = %_dir.path.display(),
+                            entries = _dir.entries,
+                            elapsed = ?_dir.started.elapsed(),
+                            "finished directory"
+                        );
+                    }
+                }
+                Some(Err(e)) => return Some(Err(Error::from_io(&dir.path, e))),
+                Some(Ok(path)) => {
+                    #[cfg(feature = "tracing")]
+                    {
+                        dir.entries += 1;
+                    }
+                    match self.visit(path, depth).transpose() {
+                        Some(res) => return Some(res),
+                        None => self.skipped += 1,
+                    }
+                }
+            }
+
+            if self.is_cancelled() {
+                return None;
+            }
+        }
+    }
+}
+
+const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+
+/// Whether the directory at `path` holds a valid `CACHEDIR.TAG`, which must
+/// start with this signature.
+fn is_cache_directory(path: &Path) -> bool {
+    use std::io::Read;
+
+    const SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+    let mut buf = [0; SIGNATURE.len()];
+    fs::File::open(path.join(CACHEDIR_TAG))
+        .and_then(|mut file| file.read_exact(&mut buf))
+        .is_ok_and(|()| buf == SIGNATURE)
+}
+
+#[cfg(windows)]
+fn is_directory_link(_path: &Path, metadata: &Metadata) -> bool {
+    use std::os::windows::fs::FileTypeExt;
+
+    metadata.file_type().is_symlink_dir()
+}
+
+#[cfg(not(windows))]
+fn is_directory_link(path: &Path, _metadata: &Metadata) -> bool {
+    fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false)
+}
+
+impl Iterator for Scan {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let next = self.next_entry();
+        if next.is_none() {
+            self.finish();
+        }
+        next
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_loops_are_broken() {
+    let dir = crate::test_dir("scan-loop");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/file"), b"hello").unwrap();
+    std::os::unix::fs::symlink(&dir, dir.join("sub/parent")).unwrap();
+    std::os::unix::fs::symlink(dir.join("sub"), dir.join("sibling")).unwrap();
+
+    let usage = Scanner::new(&dir).follow_symlinks(true).total().unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(usage.logical(), 5);
+}
+
+#[cfg(unix)]
+#[test]
+fn directory_link_policy() {
+    let dir = crate::test_dir("scan-links");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/file"), b"hello").unwrap();
+    std::os::unix::fs::symlink(dir.join("sub"), dir.join("link")).unwrap();
+
+    let links = |policy| {
+        Scanner::new(&dir)
+            .directory_links(policy)
+            .into_iter()
+            .map(Result::unwrap)
+            .filter(|e| e.path() == dir.join("link"))
+            .map(|e| e.size_on_disk())
+            .collect::<Vec<_>>()
+    };
+    let skipped = links(LinkPolicy::Skip);
+    let zeroed = links(LinkPolicy::Zero);
+    let traversed = Scanner::new(&dir)
+        .directory_links(LinkPolicy::Traverse)
+        .total()
+        .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(skipped, vec![]);
+    assert_eq!(zeroed, vec![0]);
+    assert_eq!(traversed.logical(), 5);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn virtual_filesystems_are_flagged() {
+    let entry = Scanner::new("/proc/self/status")
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.filesystem(), FilesystemKind::Virtual);
+
+    let skipped = Scanner::new("/proc/self/status")
+        .skip_virtual_filesystems(true)
+        .into_iter()
+        .count();
+    assert_eq!(skipped, 0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn memory_filesystems_are_flagged() {
+    if crate::filesystem_kind("/dev/shm").ok() != Some(FilesystemKind::Memory) {
+        return;
+    }
+    let dir = Path::new("/dev/shm").join(format!("filesize-memory-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("file"), vec![1u8; 8192]).unwrap();
+
+    let usage = Scanner::new(&dir).total().unwrap();
+    let skipped = Scanner::new(&dir)
+        .skip_memory_filesystems(true)
+        .into_iter()
+        .count();
+    let flagged = Scanner::new(&dir)
+        .into_iter()
+        .all(|entry| entry.unwrap().size_info().is_memory_backed());
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(usage.memory_bytes(), usage.physical());
+    assert_eq!(usage.disk_bytes(), 0);
+    assert!(flagged);
+    assert_eq!(skipped, 0);
+}
+
+#[test]
+fn excluded_paths_are_skipped() {
+    let dir = crate::test_dir("excluded-paths");
+    std::fs::create_dir(dir.join("skip")).unwrap();
+    std::fs::write(dir.join("skip").join("file"), b"data").unwrap();
+    std::fs::write(dir.join("keep"), b"data").unwrap();
+
+    let names = |scanner: Scanner| -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = scanner
+            .into_iter()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path()
+                    .strip_prefix(&dir)
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect();
+        paths.sort();
+        paths
+    };
+    let skipped = names(Scanner::new(&dir).excluded_paths(vec![dir.join("skip")]));
+    let everything = names(Scanner::new(&dir).excluded_paths(Vec::<PathBuf>::new()));
+    let root = names(Scanner::new(dir.join("skip")).excluded_paths(vec![dir.join("skip")]));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(skipped, vec![PathBuf::new(), PathBuf::from("keep")]);
+    assert_eq!(everything.len(), 4);
+    assert_eq!(root.len(), 2);
+
+    let scanner = Scanner::new("/").excluded_paths(vec!["System Volume Information"]);
+    let volume = Path::new("/").join("System Volume Information");
+    assert!(scanner.is_excluded_path(&volume));
+    assert!(!scanner.is_excluded_path(&volume.join("System Volume Information")));
+}
+
+#[test]
+fn progress_is_reported() {
+    use std::sync::{Arc, Mutex};
+
+    let reports = Arc::new(Mutex::new(vec![]));
+    let log = Arc::clone(&reports);
+    let usage = Scanner::new("src")
+        .progress(Duration::from_secs(3600), move |p| {
+            log.lock().unwrap().push((p.files(), p.bytes()))
+        })
+        .total()
+        .unwrap();
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[1].1, usage.physical());
+}
+
+#[test]
+fn cancellation_stops_the_scan() {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let mut scan = Scanner::new(".").cancel_on(Arc::clone(&cancel)).into_iter();
+
+    assert!(scan.next().is_some());
+    cancel.store(true, Ordering::Relaxed);
+    assert!(scan.next().is_none());
+    assert!(scan.is_cancelled());
+}
+
+#[cfg(unix)]
+#[test]
+fn error_policy() {
+    let dir = crate::test_dir("scan-errors");
+    fs::write(dir.join("file"), b"hello").unwrap();
+    std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+
+    let scan = |policy| {
+        let mut scan = Scanner::new(&dir)
+            .follow_symlinks(true)
+            .errors(policy)
+            .into_iter();
+        let results = scan.by_ref().collect::<Vec<_>>();
+        (results, scan)
+    };
+    let (skipped, _) = scan(ErrorPolicy::Skip);
+    let (collected, collector) = scan(ErrorPolicy::Collect);
+    let yielded = Scanner::new(&dir)
+        .follow_symlinks(true)
+        .errors(ErrorPolicy::Yield)
+        .into_iter()
+        .filter(Result::is_err)
+        .count();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(skipped.iter().all(Result::is_ok));
+    assert_eq!(skipped.len(), 2);
+    assert!(collected.iter().all(Result::is_ok));
+    assert_eq!(collector.errors().len(), 1);
+    assert_eq!(collector.usage().logical(), 5);
+    assert_eq!(yielded, 1);
+}
+
+#[test]
+fn depth_limits() {
+    let depths = |scanner: Scanner| {
+        scanner
+            .into_iter()
+            .map(|e| e.unwrap().depth())
+            .collect::<Vec<_>>()
+    };
+
+    let children = depths(Scanner::new(".").min_depth(1).max_depth(1));
+    assert!(!children.is_empty());
+    assert!(children.iter().all(|&d| d == 1));
+
+    assert_eq!(depths(Scanner::new(".").max_depth(0)), vec![0]);
+}
+
+#[test]
+fn min_size_filters_but_still_counts() {
+    let everything = Scanner::new("src").total().unwrap();
+    let mut scan = Scanner::new("src").min_size(u64::MAX).into_iter();
+
+    assert!(scan.by_ref().all(|e| e.unwrap().file_type().is_dir()));
+    assert_eq!(scan.usage(), everything);
+}
+
+#[test]
+fn apparent_sizes_are_counted() {
+    let dir = crate::test_dir("apparent");
+    std::fs::write(dir.join("small"), b"hi").unwrap();
+    std::fs::write(dir.join("large"), vec![1u8; 10_000]).unwrap();
+
+    let physical = Scanner::new(&dir).total().unwrap();
+    let apparent = Scanner::new(&dir).apparent_size(true).total().unwrap();
+    let filtered: Vec<_> = Scanner::new(&dir)
+        .apparent_size(true)
+        .min_size(100)
+        .into_iter()
+        .map(Result::unwrap)
+        .filter(|e| !e.file_type().is_dir())
+        .collect();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(physical.size(), physical.physical());
+    assert_eq!(apparent.size(), 10_002);
+    assert_eq!(apparent.physical(), physical.physical());
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].size(), 10_000);
+}
+
+#[test]
+fn blocks_are_rounded_per_file() {
+    let dir = crate::test_dir("blocks");
+    for name in &["a", "b", "c"] {
+        std::fs::write(dir.join(name), b"x").unwrap();
+    }
+
+    let usage = Scanner::new(&dir)
+        .apparent_size(true)
+        .block_size(1024)
+        .total()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(usage.size(), 3);
+    assert_eq!(usage.blocks(), 3);
+}
+
+#[test]
+fn roots_share_totals() {
+    let dir = crate::test_dir("roots");
+    fs::create_dir_all(dir.join("a/inner")).unwrap();
+    fs::create_dir(dir.join("b")).unwrap();
+    fs::write(dir.join("a/inner/file"), b"hello").unwrap();
+    fs::write(dir.join("b/file"), b"hi").unwrap();
+    #[cfg(unix)]
+    fs::hard_link(dir.join("a/inner/file"), dir.join("b/link")).unwrap();
+
+    let usage = Scanner::new(dir.join("a"))
+        .add_root(dir.join("b"))
+        .add_root(dir.join("a/inner"))
+        .dedup_hard_links(true)
+        .total()
+        .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(usage.logical(), 7);
+    assert_eq!(usage.directories(), 3);
+    assert_eq!(usage.files(), 2);
+}
+
+#[test]
+fn caches_are_excluded() {
+    let dir = crate::test_dir("cachedir");
+    fs::create_dir_all(dir.join("cache/sub")).unwrap();
+    fs::create_dir(dir.join("fake")).unwrap();
+    fs::write(
+        dir.join("cache/CACHEDIR.TAG"),
+        b"Signature: 8a477f597d28d172789f06886806bc55\n# a cache\n",
+    )
+    .unwrap();
+    fs::write(dir.join("cache/sub/file"), b"data").unwrap();
+    fs::write(dir.join("fake/CACHEDIR.TAG"), b"Signature: nope").unwrap();
+
+    let usage = Scanner::new(&dir).exclude_caches(true).total().unwrap();
+    let everything = Scanner::new(&dir).total().unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(usage.files(), 2);
+    assert_eq!(usage.directories(), 3);
+    assert_eq!(everything.files(), 3);
+}
+
+#[test]
+fn absolute_exclusions_match_relative_roots() {
+    let bin = std::env::current_dir().unwrap().join("src/bin");
+    let everything = Scanner::new("src").total().unwrap();
+    let excluded = Scanner::new("src").exclude_path(bin).total().unwrap();
+
+    assert!(excluded.files() < everything.files());
+}
+
+#[test]
+fn predicates_prune_directories() {
+    let usage = Scanner::new(".")
+        .filter(|path, _| !path.ends_with("src"))
+        .into_iter()
+        .map(Result::unwrap)
+        .filter(|e| e.path().starts_with("./src"))
+        .count();
+
+    assert_eq!(usage, 0);
+}
+
+#[cfg(feature = "globset")]
+#[test]
+fn glob_filters() {
+    let globs = |pattern| {
+        globset::GlobSetBuilder::new()
+            .add(globset::Glob::new(pattern).unwrap())
+            .build()
+            .unwrap()
+    };
+
+    let files = Scanner::new(".")
+        .include(globs("*.rs"))
+        .exclude(globs("target"))
+        .into_iter()
+        .map(Result::unwrap)
+        .filter(|e| !e.file_type().is_dir())
+        .collect::<Vec<_>>();
+
+    assert!(!files.is_empty());
+    assert!(files
+        .iter()
+        .all(|e| e.path().extension() == Some("rs".as_ref())));
+    assert!(files.iter().all(|e| !e.path().starts_with("./target")));
+}
+
+#[test]
+fn breadth_first_order() {
+    let depths = Scanner::new(".")
+        .order(Order::BreadthFirst)
+        .into_iter()
+        .map(|e| e.unwrap().depth())
+        .collect::<Vec<_>>();
+
+    assert!(depths.windows(2).all(|w| w[0] <= w[1]));
+}
+
+#[test]
+fn unopenable_directories_are_yielded_in_either_order() {
+    let dir = crate::test_dir("scan-unopenable");
+
+    for order in [Order::DepthFirst, Order::BreadthFirst] {
+        fs::create_dir_all(dir.join("gone")).unwrap();
+        fs::write(dir.join("file"), b"hello").unwrap();
+
+        // Remove the directory once it has been stat'd, so opening it fails.
+        let (mut paths, mut errors) = (vec![], 0);
+        let scan = Scanner::new(&dir)
+            .order(order)
+            .errors(ErrorPolicy::Yield)
+            .filter(|path, _| {
+                if path.ends_with("gone") {
+                    let _ = fs::remove_dir(path);
+                }
+                true
+            });
+        for entry in scan {
+            match entry {
+                Ok(entry) => paths.push(entry.path().strip_prefix(&dir).unwrap().to_path_buf()),
+                Err(_) => errors += 1,
+            }
+        }
+        paths.sort();
+
+        assert_eq!(paths, [Path::new(""), Path::new("file"), Path::new("gone")]);
+        assert_eq!(errors, 1);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sorted_scans_are_in_path_order() {
+    let paths = Scanner::new("src")
+        .sorted(true)
+        .into_iter()
+        .map(|e| e.unwrap().path().to_path_buf())
+        .collect::<Vec<_>>();
+
+    assert_eq!(paths.len(), Scanner::new("src").into_iter().count());
+    assert!(paths.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[cfg(unix)]
+#[test]
+fn open_directories_are_bounded() {
+    let dir = crate::test_dir("scan-bounded");
+    fs::create_dir_all(dir.join("a/b/c")).unwrap();
+    fs::create_dir_all(dir.join("d/e")).unwrap();
+    fs::write(dir.join("a/b/c/file"), b"hello").unwrap();
+    fs::write(dir.join("d/e/file"), b"world").unwrap();
+
+    let mut scan = Scanner::new(&dir).max_open_dirs(1).into_iter();
+    let mut entries = 0;
+    while let Some(entry) = scan.next() {
+        entry.unwrap();
+        entries += 1;
+        assert!(scan.stack.len() <= 1);
+        assert!(scan.open_dirs.load(Ordering::Relaxed) <= 1);
+    }
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(scan.open_dirs.load(Ordering::Relaxed), 0);
+    assert_eq!(entries, 8);
+    assert_eq!(scan.usage().logical(), 10);
+}
+
+#[test]
+fn time_windows_filter_files() {
+    let now = SystemTime::now();
+    let future = Scanner::new("src")
+        .time_window(Timestamp::Modified, now + Duration::from_secs(3600)..)
+        .total()
+        .unwrap();
+    let past = Scanner::new("src")
+        .time_window(Timestamp::Modified, ..now + Duration::from_secs(3600))
+        .total()
+        .unwrap();
+    let changed = Scanner::new("src")
+        .time_window(Timestamp::Changed, ..now + Duration::from_secs(3600))
+        .total()
+        .unwrap();
+
+    assert_eq!(future.files(), 0);
+    assert_eq!(changed.files() > 0, cfg!(unix));
+    assert_eq!(future.logical(), 0);
+    assert_eq!(past, Scanner::new("src").total().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn entries_are_counted() {
+    let dir = crate::test_dir("scan-counts");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/file"), b"hello").unwrap();
+    std::os::unix::fs::symlink("sub/file", dir.join("link")).unwrap();
+
+    let usage = Scanner::new(&dir).total().unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(usage.files(), 1);
+    assert_eq!(usage.directories(), 2);
+    assert_eq!(usage.symlinks(), 1);
+    assert_eq!(usage.entries(), 4);
+}
+
+#[test]
+fn entries_carry_metadata() {
+    for entry in Scanner::new("src").max_depth(1) {
+        let entry = entry.unwrap();
+        let metadata = fs::symlink_metadata(entry.path()).unwrap();
+
+        assert_eq!(entry.metadata().len(), metadata.len());
+        assert_eq!(entry.metadata().modified().ok(), metadata.modified().ok());
+        assert_eq!(entry.metadata().file_type(), entry.file_type());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn directory_sizes_are_counted() {
+    let files = Scanner::new("src").total().unwrap();
+    let all = Scanner::new("src").directory_sizes(true).total().unwrap();
+
+    assert!(all.physical() > files.physical());
+    assert!(all.logical() > files.logical());
+}
+
+#[test]
+fn stats_are_throttled() {
+    let started = Instant::now();
+    let entries = Scanner::new("src")
+        .max_stats_per_sec(100)
+        .background_io(true)
+        .into_iter()
+        .count();
+
+    assert!(started.elapsed() >= Duration::from_millis(10) * (entries as u32 - 1));
+}
+
+#[test]
+fn aborting_skips_remaining_roots() {
+    let dir = crate::test_dir("scan-abort");
+    fs::create_dir(dir.join("b")).unwrap();
+    fs::write(dir.join("b/f"), b"hello").unwrap();
+
+    let results: Vec<_> = Scanner::new(dir.join("missing"))
+        .add_root(dir.join("b"))
+        .errors(ErrorPolicy::Abort)
+        .into_iter()
+        .collect();
+    let mut scan = Scanner::new(dir.join("b"))
+        .add_root(dir.join("missing"))
+        .add_root(&dir)
+        .errors(ErrorPolicy::Abort)
+        .into_iter();
+    let before: Vec<_> = scan.by_ref().take_while(Result::is_ok).collect();
+    let after = scan.next();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(results.len(), 1);
+    assert!(matches!(&results[0], Err(e) if e.kind() == io::ErrorKind::NotFound));
+    assert_eq!(before.len(), 2);
+    assert!(after.is_none());
+}
+
+#[test]
+fn usage_totals_do_not_overflow() {
+    let mut usage = DiskUsage::default();
+    let info = SizeInfo::new(u64::MAX, u64::MAX - 1, SizeAccuracy::Blocks);
+    usage.add_sizes(&info);
+    usage.add_sizes(&info);
+
+    assert_eq!(usage.physical(), u64::MAX);
+    assert_eq!(usage.physical_u128(), u128::from(u64::MAX) * 2);
+    assert_eq!(usage.logical_u128(), u128::from(u64::MAX - 1) * 2);
+}
+
+#[test]
+fn inconsistent_usage_saturates() {
+    // As could be deserialized, or merged from a buggy remote scan.
+    let mut usage = DiskUsage {
+        physical: 10,
+        memory: 20,
+        files: u64::MAX,
+        directories: 1,
+        ..DiskUsage::default()
+    };
+    assert_eq!(usage.disk_bytes(), 0);
+    assert_eq!(usage.entries(), u64::MAX);
+
+    let other = usage;
+    usage.merge(&other);
+    assert_eq!(usage.files(), u64::MAX);
+    assert_eq!(usage.memory_bytes(), 40);
+
+    usage.subtract(&DiskUsage {
+        physical: 100,
+        directories: 5,
+        ..DiskUsage::default()
+    });
+    assert_eq!((usage.physical(), usage.directories()), (0, 0));
+}
+
+#[test]
+fn progress_is_estimated() {
+    let dir = crate::test_dir("scan-eta");
+    fs::create_dir(dir.join("sub")).unwrap();
+    for name in ["a", "b", "sub/c"] {
+        fs::write(dir.join(name), b"hello").unwrap();
+    }
+
+    let progress = |scanner: Scanner| {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let record = Arc::clone(&seen);
+        scanner
+            .progress(Duration::ZERO, move |progress| {
+                record.lock().unwrap().push((
+                    progress.entries(),
+                    progress.expected_entries(),
+                    progress.percent(),
+                    progress.eta(),
+                ));
+            })
+            .total()
+            .unwrap();
+        Arc::try_unwrap(seen).unwrap().into_inner().unwrap()
+    };
+
+    let counted = progress(Scanner::new(&dir).count_first(true));
+    let halfway = progress(Scanner::new(&dir).expected_entries(10));
+    let unknown = progress(Scanner::new(&dir));
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(counted.first().unwrap().0, 1);
+    assert_eq!(
+        counted.last().unwrap(),
+        &(5, Some(5), Some(100.0), Some(Duration::ZERO))
+    );
+    assert_eq!(halfway.last().unwrap().2, Some(50.0));
+    assert!(halfway.last().unwrap().3.is_some());
+    assert!(unknown
+        .iter()
+        .all(|seen| seen.2.is_none() && seen.3.is_none()));
+}
+
+#[test]
+fn slow_queries_time_out() {
+    struct Slow;
+
+    impl SizeBackend for Slow {
+        fn accuracy(&self) -> SizeAccuracy {
+            SizeAccuracy::Length
+        }
+
+        fn size_on_disk(&self, path: &Path) -> io::Result<u64> {
+            if path.ends_with("slow") {
+                std::thread::sleep(Duration::from_secs(2));
+            }
+            Ok(1)
+        }
+
+        fn size_on_disk_follow(&self, path: &Path) -> io::Result<u64> {
+            self.size_on_disk(path)
+        }
+    }
+
+    let dir = crate::test_dir("scan-timeout");
+    fs::write(dir.join("slow"), b"hello").unwrap();
+    fs::write(dir.join("fast"), b"hello").unwrap();
+
+    let mut scan = Scanner::new(&dir)
+        .backend(Slow)
+        .timeout(Duration::from_millis(100))
+        .errors(ErrorPolicy::Collect)
+        .into_iter();
+    let found: Vec<PathBuf> = scan.by_ref().map(|e| e.unwrap().path).collect();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&dir.join("fast")));
+    assert_eq!(scan.errors().len(), 1);
+    assert_eq!(scan.errors()[0].kind(), io::ErrorKind::TimedOut);
+    assert_eq!(scan.errors()[0].path(), dir.join("slow"));
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn only_scans_above_the_data_volume_reach_firmlinks() {
+    assert!(reaches_firmlinks(Path::new("/")));
+    assert!(reaches_firmlinks(Path::new("/System/Volumes")));
+    assert!(!reaches_firmlinks(Path::new("/System/Volumes/Data")));
+    assert!(!reaches_firmlinks(Path::new("/Users")));
+    assert!(!reaches_firmlinks(Path::new("src")));
+}
@@ -0,0 +1,139 @@
+//! One trait for the on-disk size of anything which can be sized.
+//!
+//! [`SizeOnDisk`] is implemented for paths, open files, directory entries,
+//! and on Unix metadata, so each can be sized in the same way, rather than
+//! with a function for some and an extension trait for others.
+//!
+//! ```rust
+//! use filesize::SizeOnDisk;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let file = std::fs::File::open("Cargo.toml")?;
+//! assert_eq!(file.real_size()?, std::path::Path::new("Cargo.toml").real_size()?);
+//!
+//! for entry in std::fs::read_dir("src")? {
+//!     let entry = entry?;
+//!     println!("{:>10} {:?}", entry.real_size()?, entry.file_name());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::{DirEntry, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{file_real_size, file_real_size_fast, Error, Result};
+
+/// Something whose on-disk size can be found.
+///
+/// This offers the same size as [`file_real_size`] for every type it is
+/// implemented for.  [`PathExt`](crate::PathExt) remains for paths, with
+/// `_fast` and `_follow` variants, and its methods are named apart from these,
+/// so both traits can be imported together.
+pub trait SizeOnDisk {
+    /// Get the on-disk size of this file.  Symlinks are not followed, except
+    /// by open files, which are always of the target.
+    fn real_size(&self) -> Result<u64>;
+
+    /// Get the on-disk size of this file, or `default` if it can't be found,
+    /// for user interfaces which would rather show a best-effort number, such
+    /// as the logical length from metadata, than handle errors.
+    ///
+    /// ```rust
+    /// use filesize::SizeOnDisk;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let file = std::fs::File::open("Cargo.toml")?;
+    /// let size = file.real_size_or(file.metadata()?.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn real_size_or(&self, default: u64) -> u64 {
+        self.real_size().unwrap_or(default)
+    }
+}
+
+impl SizeOnDisk for Path {
+    fn real_size(&self) -> Result<u64> {
+        file_real_size(self)
+    }
+}
+
+impl SizeOnDisk for PathBuf {
+    fn real_size(&self) -> Result<u64> {
+        file_real_size(self)
+    }
+}
+
+#[cfg(feature = "camino")]
+impl SizeOnDisk for camino::Utf8Path {
+    fn real_size(&self) -> Result<u64> {
+        file_real_size(self)
+    }
+}
+
+/// Errors are [`Unnamed`](Error::Unnamed), as the file may have been opened
+/// by any name.
+impl SizeOnDisk for File {
+    fn real_size(&self) -> Result<u64> {
+        file_size(self).map_err(Error::unnamed)
+    }
+}
+
+/// The metadata of the entry is used where the platform provides it cheaply,
+/// as with [`file_real_size_fast`].
+impl SizeOnDisk for DirEntry {
+    fn real_size(&self) -> Result<u64> {
+        let path = self.path();
+        let metadata = self.metadata().map_err(|e| Error::from_io(&path, e))?;
+        file_real_size_fast(&path, &metadata)
+    }
+}
+
+/// Unix metadata includes the allocation of the file, so needs no further
+/// query.  Elsewhere the path is needed, for [`file_real_size_fast`].
+#[cfg(unix)]
+impl SizeOnDisk for std::fs::Metadata {
+    fn real_size(&self) -> Result<u64> {
+        crate::imp::file_real_size_fast("", self).map_err(Error::unnamed)
+    }
+}
+
+#[cfg(windows)]
+fn file_size(file: &File) -> io::Result<u64> {
+    use std::os::windows::io::AsRawHandle;
+
+    crate::imp::file_real_size_handle(file.as_raw_handle())
+}
+
+#[cfg(not(windows))]
+fn file_size(file: &File) -> io::Result<u64> {
+    crate::imp::file_real_size_fast("", &file.metadata()?)
+}
+
+#[test]
+fn everything_is_sized_alike() {
+    let path = Path::new("Cargo.toml");
+    let size = file_real_size(path).unwrap();
+
+    assert_eq!(path.real_size().unwrap(), size);
+    assert_eq!(path.to_path_buf().real_size().unwrap(), size);
+    assert_eq!(File::open(path).unwrap().real_size().unwrap(), size);
+    #[cfg(unix)]
+    assert_eq!(path.symlink_metadata().unwrap().real_size().unwrap(), size);
+
+    let entry = std::fs::read_dir(".")
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .find(|entry| entry.file_name() == "Cargo.toml")
+        .unwrap();
+    assert_eq!(entry.real_size().unwrap(), size);
+
+    assert_eq!(path.real_size_or(1), size);
+
+    // Both traits can be in scope at once.
+    use crate::PathExt;
+    assert_eq!(path.size_on_disk().unwrap(), size);
+    assert_eq!(Path::new("no such file").real_size_or(1), 1);
+}
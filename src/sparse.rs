@@ -0,0 +1,445 @@
+//! Portable descriptions of which parts of a file hold data.
+//!
+//! A [`SparseMap`] records the length of a file and the ranges of it which
+//! hold data, with everything else being holes.  [`sparse_map`] reads one
+//! from a file, and with the `serde` feature maps can be persisted or sent
+//! elsewhere, so backup tools can recreate a file's layout when restoring it.
+//!
+//! ```rust
+//! # fn main() -> std::io::Result<()> {
+//! let map = filesize::sparse_map("Cargo.toml")?;
+//! assert_eq!(map.data_bytes() + map.hole_bytes(), map.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::{Error, ReparseTag, Result};
+
+/// The data ranges of a file, and its length.
+///
+/// Ranges are kept sorted, non-empty, non-overlapping, and within the length
+/// of the file, with adjacent ranges merged, so maps describing the same
+/// layout compare equal.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "RawSparseMap"))]
+pub struct SparseMap {
+    len: u64,
+    data: Vec<Range<u64>>,
+}
+
+/// A `SparseMap` as deserialized, before its ranges are normalized.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSparseMap {
+    len: u64,
+    data: Vec<Range<u64>>,
+}
+
+#[cfg(feature = "serde")]
+impl From<RawSparseMap> for SparseMap {
+    fn from(raw: RawSparseMap) -> Self {
+        SparseMap::new(raw.len, raw.data)
+    }
+}
+
+impl SparseMap {
+    /// Create a map of a file `len` bytes long, holding data in the given
+    /// ranges.
+    ///
+    /// Ranges may be in any order, and are merged where they overlap or
+    /// touch, and clipped to `len`.
+    pub fn new<I: IntoIterator<Item = Range<u64>>>(len: u64, data: I) -> Self {
+        let mut ranges: Vec<Range<u64>> = data
+            .into_iter()
+            .map(|r| r.start.min(len)..r.end.min(len))
+            .filter(|r| r.start < r.end)
+            .collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        Self { len, data: merged }
+    }
+
+    /// The length of the file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The ranges of the file holding data, in order.
+    pub fn data(&self) -> &[Range<u64>] {
+        &self.data
+    }
+
+    /// The ranges of the file which are holes, in order.
+    pub fn holes(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        let starts = std::iter::once(0).chain(self.data.iter().map(|r| r.end));
+        let ends = self
+            .data
+            .iter()
+            .map(|r| r.start)
+            .chain(std::iter::once(self.len));
+        starts
+            .zip(ends)
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| start..end)
+    }
+
+    /// The number of bytes of the file holding data.
+    ///
+    /// Filesystems allocate whole blocks, so this may be a little less than
+    /// the space the file occupies on disk.
+    pub fn data_bytes(&self) -> u64 {
+        self.data.iter().map(|r| r.end - r.start).sum()
+    }
+
+    /// The number of bytes of the file which are holes.
+    pub fn hole_bytes(&self) -> u64 {
+        self.len - self.data_bytes()
+    }
+
+    /// Whether the file has any holes.
+    pub fn is_sparse(&self) -> bool {
+        self.hole_bytes() > 0
+    }
+
+    /// The ranges which hold data in one map but are holes in the other, in
+    /// order, treating anything beyond the end of a file as a hole.
+    pub fn differences(&self, other: &SparseMap) -> Vec<Range<u64>> {
+        // Walk the boundaries of both maps, toggling whether each has data.
+        let mut edges: Vec<(u64, bool)> = self
+            .data
+            .iter()
+            .chain(other.data.iter())
+            .flat_map(|r| vec![(r.start, true), (r.end, false)])
+            .collect();
+        edges.sort_unstable();
+
+        let mut differences: Vec<Range<u64>> = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+        for (offset, opening) in edges {
+            let was_different = depth == 1;
+            depth = if opening { depth + 1 } else { depth - 1 };
+            if !was_different && depth == 1 {
+                start = offset;
+            } else if was_different && depth != 1 && start < offset {
+                match differences.last_mut() {
+                    Some(last) if last.end == start => last.end = offset,
+                    _ => differences.push(start..offset),
+                }
+            }
+        }
+        differences
+    }
+}
+
+/// Find which parts of the file at `path` hold data.
+///
+/// On Linux, Android, macOS, iOS, FreeBSD, DragonFly, and illumos this uses
+/// `lseek()` with `SEEK_DATA` and `SEEK_HOLE`, and on Windows
+/// `FSCTL_QUERY_ALLOCATED_RANGES`.  Filesystems which don't track holes, and
+/// other platforms, report the whole file as data.  Symlinks are followed.
+pub fn sparse_map<P: AsRef<Path>>(path: P) -> Result<SparseMap> {
+    let path = path.as_ref();
+    File::open(path)
+        .and_then(|file| {
+            let len = file.metadata()?.len();
+            imp::data_ranges(&file, len).map(|data| SparseMap::new(len, data))
+        })
+        .map_err(|e| Error::from_io(path, e))
+}
+
+/// Check whether the allocation of the file at `path` covers its whole
+/// length: it has no holes, and isn't a cloud placeholder whose data lives
+/// elsewhere.  Symlinks are followed, and anything other than a regular file
+/// fails with [`Error::UnsupportedFileType`].
+///
+/// Space reserved by `fallocate()` or `SetFileValidData()` counts as
+/// allocated, so this verifies preallocation before files are handed to
+/// `mmap()`-heavy or realtime workloads, which could otherwise stall or fail
+/// allocating blocks on first write.
+///
+/// On Linux and Android the extents of the file are read with
+/// `FS_IOC_FIEMAP`, as `SEEK_HOLE` reports preallocated space never written
+/// as holes.  Elsewhere holes are found as by [`sparse_map`], so filesystems
+/// which don't track them report every file as fully allocated.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// if !filesize::is_fully_allocated("Cargo.toml")? {
+///     println!("Cargo.toml has holes");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn is_fully_allocated<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = path.as_ref();
+    let metadata = path.metadata().map_err(|e| Error::from_io(path, e))?;
+    if !metadata.is_file() {
+        return Err(Error::UnsupportedFileType {
+            path: path.to_path_buf(),
+        });
+    }
+    if crate::reparse::tag_of(path, &metadata) == Some(ReparseTag::Cloud) {
+        return Ok(false);
+    }
+
+    File::open(path)
+        .and_then(|file| is_covered(&file, metadata.len()))
+        .map_err(|e| Error::from_io(path, e))
+}
+
+/// Whether the extents of `file` leave no gaps before `len`, falling back to
+/// looking for holes on filesystems without extent maps.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_covered(file: &File, len: u64) -> io::Result<bool> {
+    let mut covered = 0u64;
+    let mut gap = false;
+    let mapped = crate::extents::for_each(file, 0, |extent| {
+        if extent.logical > covered && covered < len {
+            gap = true;
+        }
+        covered = covered.max(extent.logical.saturating_add(extent.length));
+    });
+
+    match mapped {
+        Ok(()) => Ok(!gap && covered >= len),
+        Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => has_no_holes(file, len),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn is_covered(file: &File, len: u64) -> io::Result<bool> {
+    has_no_holes(file, len)
+}
+
+fn has_no_holes(file: &File, len: u64) -> io::Result<bool> {
+    imp::data_ranges(file, len).map(|data| !SparseMap::new(len, data).is_sparse())
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "illumos",
+    target_os = "solaris"
+))]
+mod imp {
+    use super::*;
+
+    use std::os::unix::io::AsRawFd;
+
+    pub fn data_ranges(file: &File, len: u64) -> io::Result<Vec<Range<u64>>> {
+        let fd = file.as_raw_fd();
+        let mut ranges = vec![];
+        let mut pos = 0;
+
+        while pos < len {
+            let data = unsafe { libc::lseek(fd, pos as libc::off_t, libc::SEEK_DATA) };
+            if data < 0 {
+                let err = io::Error::last_os_error();
+                return match err.raw_os_error() {
+                    // No data beyond pos.
+                    Some(libc::ENXIO) => Ok(ranges),
+                    // No support for holes, so it's all data.
+                    Some(libc::EINVAL) if pos == 0 => Ok(std::iter::once(0..len).collect()),
+                    _ => Err(err),
+                };
+            }
+
+            let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+            if hole < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            ranges.push(data as u64..hole as u64);
+            pos = hole as u64;
+        }
+
+        Ok(ranges)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+
+    use std::os::windows::io::AsRawHandle;
+
+    use crate::win::{DeviceIoControl, ERROR_MORE_DATA, FSCTL_QUERY_ALLOCATED_RANGES};
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct AllocatedRange {
+        offset: i64,
+        length: i64,
+    }
+
+    pub fn data_ranges(file: &File, len: u64) -> io::Result<Vec<Range<u64>>> {
+        let mut ranges = vec![];
+        let mut query = AllocatedRange {
+            offset: 0,
+            length: len as i64,
+        };
+        let mut buf = [AllocatedRange::default(); 64];
+
+        while query.length > 0 {
+            let mut returned: u32 = 0;
+            let ok = unsafe {
+                DeviceIoControl(
+                    file.as_raw_handle() as _,
+                    FSCTL_QUERY_ALLOCATED_RANGES,
+                    &mut query as *mut _ as *mut _,
+                    std::mem::size_of::<AllocatedRange>() as u32,
+                    buf.as_mut_ptr() as *mut _,
+                    std::mem::size_of_val(&buf) as u32,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                )
+            };
+
+            let more = if ok == 0 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() != Some(ERROR_MORE_DATA as i32) {
+                    return Err(err);
+                }
+                true
+            } else {
+                false
+            };
+
+            let count = returned as usize / std::mem::size_of::<AllocatedRange>();
+            let found = &buf[..count.min(buf.len())];
+            ranges.extend(
+                found
+                    .iter()
+                    .map(|r| r.offset as u64..(r.offset + r.length) as u64),
+            );
+
+            match found.last() {
+                Some(last) if more => {
+                    let end = last.offset + last.length;
+                    query.length -= end - query.offset;
+                    query.offset = end;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(ranges)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "illumos",
+    target_os = "solaris",
+    windows
+)))]
+mod imp {
+    use super::*;
+
+    pub fn data_ranges(_file: &File, len: u64) -> io::Result<Vec<Range<u64>>> {
+        Ok(std::iter::once(0..len).collect())
+    }
+}
+
+#[test]
+fn sparse_maps_are_normalized_and_compared() {
+    let map = SparseMap::new(100, vec![50..60, 0..10, 5..20, 20..30, 90..200, 40..40]);
+    assert_eq!(map.data(), &[0..30, 50..60, 90..100]);
+    assert_eq!(map.holes().collect::<Vec<_>>(), vec![30..50, 60..90]);
+    assert_eq!((map.data_bytes(), map.hole_bytes()), (50, 50));
+    assert!(map.is_sparse());
+
+    let other = SparseMap::new(120, vec![0..40, 90..120]);
+    assert_eq!(map.differences(&other), vec![30..40, 50..60, 100..120]);
+    assert_eq!(map.differences(&map), vec![]);
+    assert!(!SparseMap::new(10, Some(0..10)).is_sparse());
+}
+
+#[test]
+fn sparse_files_are_mapped() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let dir = crate::test_dir("sparse-map");
+    let path = dir.join("sparse");
+    let mut file = crate::testing::create_sparse(&path, 16 << 20).unwrap();
+    file.seek(SeekFrom::Start(8 << 20)).unwrap();
+    file.write_all(b"data").unwrap();
+    file.sync_all().unwrap();
+    drop(file);
+
+    let map = sparse_map(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(map.len(), 16 << 20);
+    assert!(map
+        .data()
+        .iter()
+        .any(|r| r.start <= 8 << 20 && r.end >= (8 << 20) + 4));
+    assert_eq!(map.data_bytes() + map.hole_bytes(), map.len());
+}
+
+#[test]
+fn full_allocation_is_checked() {
+    let dir = crate::test_dir("fully-allocated");
+    let dense = dir.join("dense");
+    let sparse = dir.join("sparse");
+    std::fs::write(&dense, vec![1u8; 64 << 10]).unwrap();
+    drop(crate::testing::create_sparse(&sparse, 16 << 20).unwrap());
+
+    let dense_allocated = is_fully_allocated(&dense).unwrap();
+    let sparse_allocated = is_fully_allocated(&sparse).unwrap();
+    let has_holes = sparse_map(&sparse).unwrap().is_sparse();
+    let empty = std::fs::File::create(dir.join("empty")).is_ok()
+        && is_fully_allocated(dir.join("empty")).unwrap();
+    let of_dir = is_fully_allocated(&dir);
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let preallocated = {
+        use std::os::unix::io::AsRawFd;
+
+        let path = dir.join("preallocated");
+        let file = std::fs::File::create(&path).unwrap();
+        let ok = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, 1 << 20) } == 0;
+        !ok || is_fully_allocated(&path).unwrap()
+    };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let preallocated = true;
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(dense_allocated);
+    assert_eq!(sparse_allocated, !has_holes);
+    assert!(empty);
+    assert!(matches!(of_dir, Err(Error::UnsupportedFileType { .. })));
+    assert!(preallocated);
+}
@@ -0,0 +1,155 @@
+//! Consuming a scan as an asynchronous `Stream`.
+//!
+//! [`Scanner::into_stream`] runs the scan on a thread of its own, passing
+//! entries to a [`ScanStream`] through a bounded buffer.  Once the buffer is
+//! full the thread waits for the stream to be polled, so a slow consumer
+//! holds the scan back rather than entries piling up in memory.
+//!
+//! ```rust
+//! use futures_core::Stream;
+//! use filesize::{Entry, Result, Scanner};
+//!
+//! fn entries() -> impl Stream<Item = Result<Entry>> {
+//!     Scanner::new("src").into_stream()
+//! }
+//! ```
+
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use futures_core::Stream;
+
+use crate::{Entry, Error, Result, Scanner};
+
+/// The number of entries buffered ahead of the consumer.
+const BUFFER: usize = 1024;
+
+/// A `Stream` of the entries of a scan, from [`Scanner::into_stream`].
+#[derive(Debug)]
+pub struct ScanStream {
+    entries: Receiver<Result<Entry>>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl Scanner {
+    /// Run the scan on a new thread, yielding its entries as a `Stream`.
+    ///
+    /// Up to 1024 entries are buffered ahead of the consumer before the scan
+    /// waits for it, and dropping the stream stops the scan.  Should the
+    /// thread fail to start, the stream yields that error alone.
+    pub fn into_stream(self) -> ScanStream {
+        let (sender, entries) = mpsc::sync_channel(BUFFER);
+        let waker: Arc<Mutex<Option<Waker>>> = Arc::default();
+        let root = self.root().to_path_buf();
+
+        let wake = Arc::clone(&waker);
+        let notify = move || {
+            if let Some(waker) = wake.lock().ok().and_then(|mut waker| waker.take()) {
+                waker.wake();
+            }
+        };
+        let sending = sender.clone();
+        let spawned = thread::Builder::new()
+            .name("filesize-stream".into())
+            .spawn(move || {
+                for entry in self {
+                    if sending.send(entry).is_err() {
+                        break;
+                    }
+                    notify();
+                }
+                drop(sending);
+                notify();
+            });
+        if let Err(e) = spawned {
+            let _ = sender.send(Err(Error::from_io(root, e)));
+        }
+
+        ScanStream { entries, waker }
+    }
+}
+
+impl Stream for ScanStream {
+    type Item = Result<Entry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.entries.try_recv() {
+            Ok(entry) => return Poll::Ready(Some(entry)),
+            Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+            Err(TryRecvError::Empty) => (),
+        }
+
+        if let Ok(mut waker) = self.waker.lock() {
+            *waker = Some(cx.waker().clone());
+        }
+
+        // Look again, in case an entry arrived before the waker was stored.
+        match self.entries.try_recv() {
+            Ok(entry) => Poll::Ready(Some(entry)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+#[test]
+fn scans_are_streamed() {
+    use std::future::Future;
+    use std::task::Wake;
+
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    struct Collect(ScanStream, Vec<Entry>);
+
+    impl Future for Collect {
+        type Output = Result<Vec<Entry>>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            loop {
+                match Pin::new(&mut self.0).poll_next(cx) {
+                    Poll::Ready(Some(Ok(entry))) => self.1.push(entry),
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                    Poll::Ready(None) => return Poll::Ready(Ok(std::mem::take(&mut self.1))),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+        }
+    }
+
+    fn block_on<F: Future + Unpin>(mut future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = Pin::new(&mut future).poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    let streamed = block_on(Collect(Scanner::new("src").into_stream(), vec![])).unwrap();
+    let iterated: Vec<Entry> = Scanner::new("src")
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    let missing = block_on(Collect(
+        Scanner::new("does-not-exist").into_stream(),
+        vec![],
+    ));
+
+    assert_eq!(streamed.len(), iterated.len());
+    assert!(streamed
+        .iter()
+        .zip(&iterated)
+        .all(|(a, b)| a.path() == b.path()));
+    assert!(missing.is_err());
+}
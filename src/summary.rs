@@ -0,0 +1,120 @@
+//! Summaries of how a scan went.
+//!
+//! A [`ScanSummary`] gives the totals of a scan along with what it passed
+//! over and how long it took, so tools can report on the health of a scan
+//! without instrumenting it with a [`MetricsSink`](crate::MetricsSink).
+//!
+//! ```rust
+//! use filesize::{ErrorPolicy, Scanner};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let summary = Scanner::new("src").errors(ErrorPolicy::Skip).summary()?;
+//! println!(
+//!     "{} bytes in {:?}, {} errors, {} skipped",
+//!     summary.usage().physical(),
+//!     summary.elapsed(),
+//!     summary.errors(),
+//!     summary.skipped()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use crate::{DiskUsage, Result, Scanner, SizeAccuracy};
+
+/// A summary of a scan, from [`Scanner::summary`] or
+/// [`Scan::summary`](crate::Scan::summary).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanSummary {
+    pub(crate) usage: DiskUsage,
+    pub(crate) errors: u64,
+    pub(crate) skipped: u64,
+    pub(crate) elapsed: Duration,
+    pub(crate) accuracy: SizeAccuracy,
+    pub(crate) cancelled: bool,
+}
+
+impl ScanSummary {
+    /// The usage of everything the scan counted.
+    pub fn usage(&self) -> DiskUsage {
+        self.usage
+    }
+
+    /// The number of errors encountered, whether or not they were yielded.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// The number of entries left out of the scan and its totals: those
+    /// excluded by filters, on skipped filesystems, skipped directory links,
+    /// and directories already visited through another path.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// How long the scan has been running.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// How the scan's backend finds sizes, such as
+    /// [`SizeAccuracy::Blocks`] from `st_blocks` on Unix, or
+    /// [`SizeAccuracy::CompressedSize`] from `GetCompressedFileSizeW` on
+    /// Windows.  Individual entries may differ, as reported by
+    /// [`SizeInfo::accuracy`](crate::SizeInfo::accuracy).
+    pub fn accuracy(&self) -> SizeAccuracy {
+        self.accuracy
+    }
+
+    /// Whether the scan was stopped early by
+    /// [`Scanner::cancel_on`](crate::Scanner::cancel_on).
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl Scanner {
+    /// Run the scan to completion on the current thread, returning a summary
+    /// of it.
+    ///
+    /// Errors are handled as with [`Scanner::total`], so are only counted
+    /// with `ErrorPolicy::Skip` or `ErrorPolicy::Collect`.
+    pub fn summary(self) -> Result<ScanSummary> {
+        let mut scan = self.into_iter();
+        for entry in &mut scan {
+            entry?;
+        }
+        Ok(scan.summary())
+    }
+}
+
+#[test]
+fn scans_are_summarized() {
+    use crate::ErrorPolicy;
+
+    let dir = crate::test_dir("summary");
+    std::fs::create_dir(dir.join("skipped")).unwrap();
+    std::fs::write(dir.join("skipped/file"), b"hidden").unwrap();
+    std::fs::write(dir.join("file"), b"hello").unwrap();
+
+    let summary = Scanner::new(&dir)
+        .filter(|path, _| !path.ends_with("skipped"))
+        .errors(ErrorPolicy::Skip)
+        .summary()
+        .unwrap();
+    let missing = Scanner::new(dir.join("missing"))
+        .errors(ErrorPolicy::Skip)
+        .summary()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(summary.usage().logical(), 5);
+    assert_eq!(summary.usage().files(), 1);
+    assert_eq!(summary.skipped(), 1);
+    assert_eq!(summary.errors(), 0);
+    assert_eq!(summary.accuracy(), crate::imp::ACCURACY);
+    assert!(!summary.is_cancelled());
+    assert_eq!(missing.errors(), 1);
+}
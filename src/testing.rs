@@ -0,0 +1,154 @@
+//! Helpers for creating files with interesting on-disk sizes in tests.
+//!
+//! Files whose on-disk size matches their length exercise little of the code
+//! built on this crate.  These helpers create sparse and compressed files on
+//! any filesystem which supports them, so tests can check how such files are
+//! reported.
+//!
+//! ```rust
+//! use std::io::{Seek, SeekFrom, Write};
+//! use filesize::PathExt;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! # let path = std::env::temp_dir().join("filesize-testing-doc");
+//! let mut file = filesize::testing::create_sparse(&path, 64 << 20)?;
+//! file.seek(SeekFrom::Start(1 << 20))?;
+//! file.write_all(b"data")?;
+//! drop(file);
+//!
+//! assert!(path.size_on_disk()? < 64 << 20);
+//! # std::fs::remove_file(&path)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Create a sparse file at `path`, `len` bytes long with no data allocated.
+///
+/// Ranges written to the returned file are allocated, while the rest remain
+/// holes.  On Windows the file is marked sparse with `FSCTL_SET_SPARSE`, which
+/// NTFS and ReFS require before they leave unwritten ranges unallocated.
+/// Elsewhere it is simply extended, which leaves a hole on most filesystems.
+pub fn create_sparse<P: AsRef<Path>>(path: P, len: u64) -> io::Result<File> {
+    let file = create(path.as_ref())?;
+    imp::set_sparse(&file)?;
+    file.set_len(len)?;
+    Ok(file)
+}
+
+/// Create an empty file at `path` which the filesystem compresses, and write
+/// `data` to it.
+///
+/// On Windows this uses NTFS compression, set with `FSCTL_SET_COMPRESSION`.
+/// On Linux and Android it sets the `FS_COMPR_FL` attribute, as `chattr +c`
+/// does, which btrfs honours.  Writes may not be compressed until they reach
+/// the disk, so sizes should be checked after the file has been synced.
+///
+/// Fails with `ErrorKind::Unsupported` on other platforms, and with an error
+/// from the filesystem where compression is unavailable.
+pub fn create_compressed<P: AsRef<Path>>(path: P, data: &[u8]) -> io::Result<File> {
+    let mut file = create(path.as_ref())?;
+    imp::set_compressed(&file)?;
+    io::Write::write_all(&mut file, data)?;
+    Ok(file)
+}
+
+fn create(path: &Path) -> io::Result<File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+
+    use std::os::windows::io::AsRawHandle;
+
+    use crate::win::{
+        DeviceIoControl, COMPRESSION_FORMAT_DEFAULT, FSCTL_SET_COMPRESSION, FSCTL_SET_SPARSE,
+    };
+
+    fn control(file: &File, code: u32, input: &[u8]) -> io::Result<()> {
+        let mut returned: u32 = 0;
+        let ok = unsafe {
+            DeviceIoControl(
+                file.as_raw_handle() as _,
+                code,
+                input.as_ptr() as *mut _,
+                input.len() as u32,
+                std::ptr::null_mut(),
+                0,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn set_sparse(file: &File) -> io::Result<()> {
+        control(file, FSCTL_SET_SPARSE, &[])
+    }
+
+    pub fn set_compressed(file: &File) -> io::Result<()> {
+        control(
+            file,
+            FSCTL_SET_COMPRESSION,
+            &COMPRESSION_FORMAT_DEFAULT.to_ne_bytes(),
+        )
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use super::*;
+
+    use std::os::unix::io::AsRawFd;
+
+    const FS_COMPR_FL: libc::c_int = 0x0000_0004;
+
+    pub fn set_sparse(_file: &File) -> io::Result<()> {
+        Ok(())
+    }
+
+    // The kernel reads and writes an int, despite the ioctls being defined
+    // against a long.
+    pub fn set_compressed(file: &File) -> io::Result<()> {
+        let mut flags: libc::c_int = 0;
+        if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        flags |= FS_COMPR_FL;
+        if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "android")))]
+mod imp {
+    use super::*;
+
+    pub fn set_sparse(_file: &File) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn set_compressed(_file: &File) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "compressed files are not supported on this platform",
+        ))
+    }
+}
@@ -0,0 +1,204 @@
+//! Totalling directory trees across threads without `rayon`.
+//!
+//! [`Scanner::threads`] makes the contents of each directory a task of its
+//! own, run by a fixed set of scoped threads.  Each thread keeps one scan for
+//! the whole of the total, feeding it tasks from its own queue, newest first,
+//! and when that runs dry stealing the oldest tasks from the others, so
+//! threads stay busy on uneven trees.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::scan::Scan;
+use crate::{DiskUsage, Error, Result, Scanner};
+
+pub(crate) fn total(scanner: Scanner, threads: usize) -> Result<DiskUsage> {
+    let mut root = Scan::new(Arc::new(scanner), Arc::default())
+        .root()
+        .split_above(usize::MAX);
+    let pool = Pool {
+        queues: (0..threads).map(|_| Mutex::default()).collect(),
+        idle: Mutex::new(
+            (1..threads)
+                .map(|_| root.sibling().split_above(usize::MAX))
+                .collect(),
+        ),
+        outstanding: AtomicUsize::new(1),
+        usage: Mutex::default(),
+        error: Mutex::new(None),
+        failed: AtomicBool::new(false),
+        wakeup: (Mutex::new(()), Condvar::new()),
+    };
+
+    thread::scope(|scope| {
+        let pool = &pool;
+        for worker in 1..threads {
+            // Should a thread fail to start, the rest carry on without it.
+            let _ = thread::Builder::new()
+                .name(format!("filesize-{}", worker))
+                .spawn_scoped(scope, move || {
+                    if let Some(scan) = pool.idle.lock().ok().and_then(|mut idle| idle.pop()) {
+                        pool.work(worker, scan);
+                    }
+                });
+        }
+
+        // The root is the first task, already counted as outstanding.
+        pool.run(0, &mut root);
+        pool.done();
+        pool.work(0, root);
+    });
+
+    // The scans of threads which failed to start still count towards the
+    // final progress report.
+    for mut scan in pool.idle.into_inner().unwrap_or_default() {
+        scan.finish();
+    }
+
+    match pool.error.into_inner() {
+        Ok(Some(error)) => Err(error),
+        _ => Ok(pool.usage.into_inner().unwrap_or_default()),
+    }
+}
+
+struct Pool {
+    /// The directories whose contents are yet to be scanned, for each worker.
+    queues: Vec<Mutex<VecDeque<(PathBuf, usize)>>>,
+    /// The scans of workers yet to start.
+    idle: Mutex<Vec<Scan>>,
+    /// The number of tasks queued or running, which reaching 0 ends the scan.
+    outstanding: AtomicUsize,
+    usage: Mutex<DiskUsage>,
+    error: Mutex<Option<Error>>,
+    failed: AtomicBool,
+    wakeup: (Mutex<()>, Condvar),
+}
+
+impl Pool {
+    fn push(&self, worker: usize, task: (PathBuf, usize)) {
+        self.outstanding.fetch_add(1, Ordering::AcqRel);
+        if let Ok(mut queue) = self.queues[worker].lock() {
+            queue.push_back(task);
+        }
+        self.wakeup.1.notify_one();
+    }
+
+    /// The newest task of this worker, or failing that the oldest of another.
+    fn take(&self, worker: usize) -> Option<(PathBuf, usize)> {
+        let own = self.queues[worker].lock().ok()?.pop_back();
+        own.or_else(|| {
+            (1..self.queues.len())
+                .map(|offset| (worker + offset) % self.queues.len())
+                .find_map(|other| self.queues[other].lock().ok()?.pop_front())
+        })
+    }
+
+    fn done(&self) {
+        if self.outstanding.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.wakeup.1.notify_all();
+        }
+    }
+
+    /// Feed tasks to the worker's `scan` until every task is done.
+    fn work(&self, worker: usize, mut scan: Scan) {
+        while !self.failed.load(Ordering::Relaxed) && self.outstanding.load(Ordering::Acquire) > 0 {
+            match self.take(worker) {
+                Some((path, depth)) => {
+                    scan.add_contents(path, depth);
+                    self.run(worker, &mut scan);
+                    self.done();
+                }
+                None => {
+                    // A short timeout covers wakeups sent between looking
+                    // for work and waiting.
+                    if let Ok(guard) = self.wakeup.0.lock() {
+                        let _ = self.wakeup.1.wait_timeout(guard, Duration::from_millis(1));
+                    }
+                }
+            }
+        }
+
+        scan.finish();
+        if let Ok(mut total) = self.usage.lock() {
+            total.merge(&scan.usage());
+        }
+    }
+
+    /// Run `scan` until it has nothing left to do, queueing the directories
+    /// it finds as tasks of their own.
+    fn run(&self, worker: usize, scan: &mut Scan) {
+        while !self.failed.load(Ordering::Relaxed) {
+            let next = scan.next_entry();
+            for task in scan.take_split() {
+                self.push(worker, task);
+            }
+
+            match next {
+                Some(Ok(_)) => (),
+                Some(Err(error)) => {
+                    self.fail(error);
+                    break;
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn fail(&self, error: Error) {
+        self.failed.store(true, Ordering::Relaxed);
+        if let Ok(mut first) = self.error.lock() {
+            first.get_or_insert(error);
+        }
+        self.wakeup.1.notify_all();
+    }
+}
+
+#[test]
+fn threaded_totals_match() {
+    let sequential = Scanner::new("src").total().unwrap();
+
+    for threads in 1..5 {
+        let threaded = Scanner::new("src").threads(threads).total().unwrap();
+        assert_eq!(threaded, sequential);
+    }
+
+    let limited = Scanner::new("src")
+        .max_open_dirs(1)
+        .threads(4)
+        .total()
+        .unwrap();
+    assert_eq!(limited, sequential);
+}
+
+#[test]
+fn threaded_progress_covers_the_whole_tree() {
+    let sequential = Scanner::new("src").total().unwrap();
+    let seen = Arc::new(Mutex::new(vec![]));
+    let record = Arc::clone(&seen);
+    Scanner::new("src")
+        .count_first(true)
+        .progress(Duration::from_secs(3600), move |progress| {
+            record
+                .lock()
+                .unwrap()
+                .push((progress.entries(), progress.percent()));
+        })
+        .threads(4)
+        .total()
+        .unwrap();
+    let seen = seen.lock().unwrap();
+
+    // One report as the scan starts, and one once every thread is done.
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[1], (sequential.entries(), Some(100.0)));
+}
+
+#[test]
+fn threaded_errors_are_returned() {
+    let result = Scanner::new("does-not-exist").threads(4).total();
+    assert!(result.is_err());
+}
@@ -0,0 +1,119 @@
+//! Running filesystem calls on worker threads, abandoning those which take
+//! too long.
+//!
+//! A call to a hung NFS server or a disconnected SMB share can block for
+//! minutes, or indefinitely, and can't be interrupted.  [`Scanner::timeout`]
+//! runs each query on a pooled worker thread instead, and stops waiting for
+//! it once the limit has passed, leaving the worker to finish or hang alone
+//! while the scan carries on without that file or directory.
+//!
+//! [`Scanner::timeout`]: crate::Scanner::timeout
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The number of calls which may be outstanding at once, most of them hung,
+/// before further calls fail without being attempted, so a scan of a tree
+/// full of dead mounts doesn't leave ever more threads behind.
+const MAX_OUTSTANDING: usize = 64;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of worker threads running calls under a time limit.
+#[derive(Debug)]
+pub(crate) struct TimeoutPool {
+    limit: Duration,
+    idle: Mutex<Vec<Sender<Job>>>,
+    outstanding: Arc<AtomicUsize>,
+}
+
+impl TimeoutPool {
+    pub(crate) fn new(limit: Duration) -> Self {
+        Self {
+            limit,
+            idle: Mutex::default(),
+            outstanding: Arc::default(),
+        }
+    }
+
+    /// Run `call` on a worker, failing with `io::ErrorKind::TimedOut` if it
+    /// takes longer than the limit.
+    pub(crate) fn run<T, F>(&self, call: F) -> io::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if self.outstanding.fetch_add(1, Ordering::AcqRel) >= MAX_OUTSTANDING {
+            self.outstanding.fetch_sub(1, Ordering::AcqRel);
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "too many filesystem calls have timed out",
+            ));
+        }
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let outstanding = Arc::clone(&self.outstanding);
+        let job: Job = Box::new(move || {
+            let _ = tx.send(call());
+            outstanding.fetch_sub(1, Ordering::AcqRel);
+        });
+
+        let worker = match self.idle.lock().ok().and_then(|mut idle| idle.pop()) {
+            Some(worker) => worker,
+            None => spawn_worker().inspect_err(|_| {
+                self.outstanding.fetch_sub(1, Ordering::AcqRel);
+            })?,
+        };
+        // The job is dropped unrun if the worker has gone, which the
+        // receiver then sees as a disconnection.
+        let _ = worker.send(job);
+
+        match rx.recv_timeout(self.limit) {
+            Ok(value) => {
+                if let Ok(mut idle) = self.idle.lock() {
+                    idle.push(worker);
+                }
+                Ok(value)
+            }
+            // The worker is left to finish alone, exiting once it does, as
+            // nothing can send it another job.
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "filesystem call timed out",
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::other("filesystem call failed")),
+        }
+    }
+}
+
+fn spawn_worker() -> io::Result<Sender<Job>> {
+    let (tx, jobs) = mpsc::channel::<Job>();
+    thread::Builder::new()
+        .name("filesize-timeout".into())
+        .spawn(move || {
+            for job in jobs {
+                job();
+            }
+        })?;
+    Ok(tx)
+}
+
+#[test]
+fn slow_calls_time_out() {
+    let pool = TimeoutPool::new(Duration::from_millis(50));
+
+    assert_eq!(pool.run(|| 42).unwrap(), 42);
+    assert_eq!(pool.idle.lock().unwrap().len(), 1);
+
+    let err = pool
+        .run(|| thread::sleep(Duration::from_secs(1)))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(pool.idle.lock().unwrap().is_empty());
+
+    assert_eq!(pool.run(|| 7).unwrap(), 7);
+}
@@ -0,0 +1,87 @@
+//! Size queries for the entries of [`tokio::fs::read_dir`], available with
+//! the `async` feature.
+//!
+//! As with the `_fast` functions, the entry's metadata is used to find its
+//! size, which on Windows and most Unix platforms the directory listing has
+//! already provided.  Where a further query is needed it runs on tokio's
+//! blocking thread pool.
+//!
+//! ```rust
+//! use filesize::tokio::DirEntryExt;
+//!
+//! # async fn sizes() -> std::io::Result<()> {
+//! let mut entries = tokio::fs::read_dir("src").await?;
+//! while let Some(entry) = entries.next_entry().await? {
+//!     println!("{} {:?}", entry.size_on_disk().await?, entry.file_name());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`tokio::fs::read_dir`]: https://docs.rs/tokio/latest/tokio/fs/fn.read_dir.html
+
+use std::future::Future;
+use std::io;
+
+use ::tokio::fs::DirEntry;
+
+use crate::{Error, Result, SizeInfo, SizeOptions};
+
+/// An extension trait for `tokio::fs::DirEntry` to retrieve its on-disk size.
+pub trait DirEntryExt {
+    /// Get the on-disk size of this entry.  Symlinks are not followed.
+    fn size_on_disk(&self) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Get the on-disk and logical sizes of this entry, along with how they
+    /// were determined, as [`SizeOptions::size_info_fast`] does.
+    fn size_info(&self) -> impl Future<Output = Result<SizeInfo>> + Send;
+}
+
+impl DirEntryExt for DirEntry {
+    fn size_on_disk(&self) -> impl Future<Output = Result<u64>> + Send {
+        let size_info = self.size_info();
+        async move { Ok(size_info.await?.physical()) }
+    }
+
+    fn size_info(&self) -> impl Future<Output = Result<SizeInfo>> + Send {
+        let path = self.path();
+        let metadata = self.metadata();
+        async move {
+            let metadata = metadata.await.map_err(|e| Error::from_io(&path, e))?;
+
+            // Unix sizes come from the metadata alone, so needn't leave this
+            // thread.
+            if cfg!(unix) {
+                return SizeOptions::new().size_info_fast(&path, &metadata);
+            }
+
+            ::tokio::task::spawn_blocking(move || {
+                SizeOptions::new().size_info_fast(&path, &metadata)
+            })
+            .await
+            .map_err(|e| Error::from_io("", io::Error::other(e)))?
+        }
+    }
+}
+
+#[test]
+fn tokio_entries_are_sized() {
+    let runtime = ::tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let sizes = runtime
+        .block_on(async {
+            let mut sizes = vec![];
+            let mut entries = ::tokio::fs::read_dir("src").await?;
+            while let Some(entry) = entries.next_entry().await? {
+                sizes.push((entry.path(), entry.size_on_disk().await?));
+            }
+            Ok::<_, io::Error>(sizes)
+        })
+        .unwrap();
+
+    assert_eq!(sizes.len(), std::fs::read_dir("src").unwrap().count());
+    for (path, size) in sizes {
+        assert_eq!(size, crate::file_real_size(&path).unwrap());
+    }
+}
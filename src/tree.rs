@@ -0,0 +1,587 @@
+//! The usage of every directory in a scanned tree.
+//!
+//! A [`DiskUsageTree`] is a [`Collector`] recording each entry of a scan in
+//! a hierarchy, with every directory totalling everything below it, so the
+//! whole tree can be browsed or drawn as a treemap once the scan is done.
+//!
+//! ```rust
+//! # fn main() -> std::io::Result<()> {
+//! let tree = filesize::Scanner::new("src").tree()?;
+//! let root = tree.root().expect("root");
+//!
+//! for child in root.children() {
+//!     println!("{:>10} {}", child.usage().physical(), child.path().display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{Collector, DiskUsage, Entry, Result, Scanner, SizeAccuracy, SizeInfo};
+
+/// A scanned directory tree, with the usage of every directory in it.
+#[derive(Debug, Clone, Default)]
+pub struct DiskUsageTree {
+    nodes: Vec<TreeEntry>,
+    names: Vec<u8>,
+    roots: Vec<usize>,
+    dirs: HashMap<PathBuf, usize>,
+    apparent_size: bool,
+}
+
+// Names are kept together in `DiskUsageTree::names`, and children linked
+// through their siblings, so adding an entry to a tree of millions needs no
+// allocations of its own.
+#[derive(Debug, Clone)]
+struct TreeEntry {
+    name: Range<usize>,
+    parent: Option<usize>,
+    first_child: Option<usize>,
+    last_child: Option<usize>,
+    next_sibling: Option<usize>,
+    is_dir: bool,
+    is_file: bool,
+    info: SizeInfo,
+    usage: DiskUsage,
+    modified: Option<SystemTime>,
+    newest: Option<SystemTime>,
+    largest: Option<usize>,
+}
+
+/// A file or directory in a [`DiskUsageTree`].
+#[derive(Debug, Clone, Copy)]
+pub struct Node<'a> {
+    tree: &'a DiskUsageTree,
+    index: usize,
+}
+
+impl DiskUsageTree {
+    /// Create an empty tree, to be filled by [`Scanner::collect_into`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The root of the first scan added to the tree.
+    pub fn root(&self) -> Option<Node<'_>> {
+        self.roots.first().map(|&index| self.node(index))
+    }
+
+    /// The roots of every scan added to the tree, in order.
+    pub fn roots(&self) -> impl Iterator<Item = Node<'_>> + '_ {
+        self.roots.iter().map(move |&index| self.node(index))
+    }
+
+    /// Find the directory at `path`, given as the scan found it.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<Node<'_>> {
+        self.dirs.get(path.as_ref()).map(|&index| self.node(index))
+    }
+
+    /// The number of files and directories in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Every file and directory in the tree, each directory before its
+    /// contents, and roots in order.
+    pub fn walk(&self) -> impl Iterator<Item = Node<'_>> + '_ {
+        let mut roots = self.roots.iter().copied();
+        let mut next = roots.next();
+        std::iter::from_fn(move || {
+            let index = next?;
+            next = self.following(index).or_else(|| roots.next());
+            Some(self.node(index))
+        })
+    }
+
+    /// The index of the node after `index` in a walk of its root: its first
+    /// child, or the next sibling of it or its nearest ancestor with one.
+    fn following(&self, index: usize) -> Option<usize> {
+        if let Some(child) = self.nodes[index].first_child {
+            return Some(child);
+        }
+
+        let mut node = Some(index);
+        while let Some(i) = node {
+            if let Some(sibling) = self.nodes[i].next_sibling {
+                return Some(sibling);
+            }
+            node = self.nodes[i].parent;
+        }
+        None
+    }
+
+    /// Combine `other` into this tree, as when parts of one tree are scanned
+    /// by separate threads or machines, or scans of different roots are to be
+    /// browsed together.
+    ///
+    /// Entries are matched by path, so anything found by both trees is only
+    /// counted once, and the usage of every directory is totalled afresh.
+    /// Roots lying within a directory of the other tree are joined onto it,
+    /// while the rest remain separate roots.  Children in the merged tree are
+    /// in order of path.
+    ///
+    /// Hard links are only recognised by path, so a file linked under
+    /// different names in each tree is counted by both.
+    pub fn merge(&mut self, other: &DiskUsageTree) {
+        let mut records: Vec<(PathBuf, Node<'_>, DiskUsage)> = self
+            .walk()
+            .chain(other.walk())
+            .map(|node| (node.path(), node, node.own_usage()))
+            .collect();
+
+        // The sort is stable, so where both trees have an entry this one's
+        // comes first, and is kept unless it is only an unyielded ancestor.
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+        records.dedup_by(|later, kept| {
+            if later.0 != kept.0 {
+                return false;
+            }
+            if kept.2.entries() == 0 && later.2.entries() > 0 {
+                std::mem::swap(later, kept);
+            }
+            true
+        });
+
+        let mut merged = DiskUsageTree {
+            apparent_size: self.apparent_size || other.apparent_size,
+            ..Self::default()
+        };
+        for (path, node, own) in records {
+            let parent = path.parent().and_then(|parent| merged.dirs.get(parent));
+            let kind = (node.is_dir(), node.is_file());
+            let index = merged.insert(&path, parent.copied(), kind, node.size_info());
+            if node.is_dir() {
+                merged.dirs.insert(path, index);
+            }
+            merged.nodes[index].modified = node.modified();
+            merged.account(index, &own);
+        }
+        *self = merged;
+    }
+
+    fn node(&self, index: usize) -> Node<'_> {
+        Node { tree: self, index }
+    }
+
+    /// The index of the directory at `path`, which is `depth` levels below
+    /// the root of its scan, creating it and its ancestors if they weren't
+    /// yielded.
+    fn directory(&mut self, path: &Path, depth: usize) -> usize {
+        if let Some(&index) = self.dirs.get(path) {
+            return index;
+        }
+
+        let parent = match path.parent() {
+            Some(parent) if depth > 0 => Some(self.directory(parent, depth - 1)),
+            _ => None,
+        };
+        let info = SizeInfo::new(0, 0, SizeAccuracy::Policy);
+        let index = self.insert(path, parent, (true, false), info);
+        self.dirs.insert(path.to_path_buf(), index);
+        index
+    }
+
+    fn insert(
+        &mut self,
+        path: &Path,
+        parent: Option<usize>,
+        (is_dir, is_file): (bool, bool),
+        info: SizeInfo,
+    ) -> usize {
+        let index = self.nodes.len();
+        let name = match (parent, path.file_name()) {
+            (Some(_), Some(name)) => name,
+            _ => path.as_os_str(),
+        };
+        let start = self.names.len();
+        self.names.extend_from_slice(name.as_encoded_bytes());
+        self.nodes.push(TreeEntry {
+            name: start..self.names.len(),
+            parent,
+            first_child: None,
+            last_child: None,
+            next_sibling: None,
+            is_dir,
+            is_file,
+            info,
+            usage: DiskUsage::default(),
+            modified: None,
+            newest: None,
+            largest: None,
+        });
+
+        match parent {
+            Some(parent) => match self.nodes[parent].last_child.replace(index) {
+                Some(last) => self.nodes[last].next_sibling = Some(index),
+                None => self.nodes[parent].first_child = Some(index),
+            },
+            None => self.roots.push(index),
+        }
+        index
+    }
+
+    /// Add `own`, the usage of the node at `index` itself, to it and its
+    /// ancestors, along with its modification time and, for a file, size.
+    fn account(&mut self, index: usize, own: &DiskUsage) {
+        let modified = self.nodes[index].modified;
+        let file = Some(index).filter(|&i| self.nodes[i].is_file);
+
+        let mut node = Some(index);
+        while let Some(i) = node {
+            let entry = &mut self.nodes[i];
+            entry.usage.merge(own);
+            entry.newest = entry.newest.max(modified);
+            let (largest, parent) = (entry.largest, entry.parent);
+
+            if file.is_some()
+                && largest.is_none_or(|largest| self.nodes[largest].usage.size() < own.size())
+            {
+                self.nodes[i].largest = file;
+            }
+            node = parent;
+        }
+    }
+}
+
+impl Collector for DiskUsageTree {
+    fn add(&mut self, entry: &Entry) {
+        self.apparent_size |= entry.apparent_size;
+        let is_dir = entry.file_type().is_dir();
+        let kind = (is_dir, entry.file_type().is_file());
+        let index = match entry.path().parent() {
+            _ if is_dir => {
+                let index = self.directory(entry.path(), entry.depth());
+                self.nodes[index].info = entry.size_info();
+                index
+            }
+            Some(parent) if entry.depth() > 0 => {
+                let parent = self.directory(parent, entry.depth() - 1);
+                self.insert(entry.path(), Some(parent), kind, entry.size_info())
+            }
+            _ => self.insert(entry.path(), None, kind, entry.size_info()),
+        };
+
+        let mut own = DiskUsage::default();
+        own.add(entry);
+        self.nodes[index].modified = entry.metadata().modified().ok();
+        self.account(index, &own);
+    }
+}
+
+impl<'a> Node<'a> {
+    /// The name of this node, or the path of the root as given to the scan.
+    pub fn name(&self) -> &'a OsStr {
+        let name = &self.tree.names[self.entry().name.clone()];
+        // Each name is sliced from the buffer exactly as it was copied in
+        // from `OsStr::as_encoded_bytes`.
+        unsafe { OsStr::from_encoded_bytes_unchecked(name) }
+    }
+
+    /// The path of this node, joined onto the root of its scan.
+    pub fn path(&self) -> PathBuf {
+        let mut names: Vec<&OsStr> = vec![];
+        let mut node = Some(*self);
+        while let Some(n) = node {
+            names.push(n.name());
+            node = n.parent();
+        }
+        names.iter().rev().collect()
+    }
+
+    /// Whether this node is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.entry().is_dir
+    }
+
+    /// Whether this node is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.entry().is_file
+    }
+
+    /// The size of this file or directory itself.
+    ///
+    /// Directories the scan did not yield, such as those above
+    /// [`Scanner::min_depth`], are reported with a size of 0.
+    pub fn size_info(&self) -> SizeInfo {
+        self.entry().info
+    }
+
+    /// The usage of this node and everything below it.
+    pub fn usage(&self) -> DiskUsage {
+        self.entry().usage
+    }
+
+    /// The size of this node and everything below it, as counted by
+    /// [`Scanner::apparent_size`].
+    pub fn size(&self) -> u64 {
+        self.entry().usage.size()
+    }
+
+    /// The modification time of this file or directory itself, if the
+    /// platform provides it.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.entry().modified
+    }
+
+    /// The most recent modification time of this node and everything below
+    /// it, as `du --time` shows, so directories nothing has touched in a long
+    /// time can be found.
+    ///
+    /// ```rust
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let year_ago = SystemTime::now() - Duration::from_secs(365 * 86400);
+    /// let tree = filesize::Scanner::new("src").tree()?;
+    /// let stale = tree.walk().filter(|node| {
+    ///     node.is_dir()
+    ///         && node.size() > 1 << 30
+    ///         && node.newest_modified().is_some_and(|time| time < year_ago)
+    /// });
+    /// for node in stale {
+    ///     println!("{}", node.path().display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn newest_modified(&self) -> Option<SystemTime> {
+        self.entry().newest
+    }
+
+    /// The largest regular file at or below this node, as counted by
+    /// [`Scanner::apparent_size`].
+    pub fn largest_file(&self) -> Option<Node<'a>> {
+        self.entry().largest.map(|index| self.tree.node(index))
+    }
+
+    /// The size of this file or directory itself, as counted by
+    /// [`Scanner::apparent_size`].
+    pub(crate) fn own_size(&self) -> u64 {
+        let info = self.entry().info;
+        if self.tree.apparent_size {
+            info.logical()
+        } else {
+            info.physical()
+        }
+    }
+
+    /// The usage of this node itself, without anything below it.
+    fn own_usage(&self) -> DiskUsage {
+        let mut usage = self.usage();
+        for child in self.children() {
+            usage.subtract(&child.usage());
+        }
+        usage
+    }
+
+    /// The directory containing this node, or `None` for a root.
+    pub fn parent(&self) -> Option<Node<'a>> {
+        self.entry().parent.map(|index| self.tree.node(index))
+    }
+
+    /// The files and directories directly within this one, in the order the
+    /// scan found them.
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> + 'a {
+        let tree = self.tree;
+        std::iter::successors(self.entry().first_child, move |&index| {
+            tree.nodes[index].next_sibling
+        })
+        .map(move |index| tree.node(index))
+    }
+
+    /// The number of directories between this node and its root.
+    pub fn depth(&self) -> usize {
+        std::iter::successors(self.parent(), Node::parent).count()
+    }
+
+    fn entry(&self) -> &'a TreeEntry {
+        &self.tree.nodes[self.index]
+    }
+}
+
+impl Scanner {
+    /// Run the scan to completion, recording every entry in a
+    /// [`DiskUsageTree`].
+    ///
+    /// Errors are handled as with [`Scanner::total`].
+    pub fn tree(self) -> Result<DiskUsageTree> {
+        let mut tree = DiskUsageTree::new();
+        self.collect_into(&mut tree)?;
+        Ok(tree)
+    }
+}
+
+#[test]
+fn trees_total_their_directories() {
+    let dir = crate::test_dir("tree");
+    std::fs::create_dir_all(dir.join("a/b")).unwrap();
+    std::fs::write(dir.join("a/b/file"), b"hello").unwrap();
+    std::fs::write(dir.join("a/file"), b"hi").unwrap();
+    std::fs::write(dir.join("file"), b"!").unwrap();
+
+    let tree = Scanner::new(&dir).tree().unwrap();
+    let shallow = Scanner::new(&dir).min_depth(2).tree().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let root = tree.root().unwrap();
+    assert_eq!(root.path(), dir);
+    assert_eq!(root.usage().logical(), 8);
+    assert_eq!(tree.len(), 6);
+
+    let a = tree.get(dir.join("a")).unwrap();
+    assert_eq!(a.usage().logical(), 7);
+    assert_eq!(a.usage().files(), 2);
+    assert_eq!(a.children().count(), 2);
+    assert_eq!(a.parent().unwrap().path(), dir);
+
+    let b = tree.get(dir.join("a/b")).unwrap();
+    assert_eq!(b.depth(), 2);
+    assert_eq!(b.usage().logical(), 5);
+    assert_eq!(b.children().next().unwrap().path(), dir.join("a/b/file"));
+
+    let walked: Vec<PathBuf> = tree.walk().map(|node| node.path()).collect();
+    assert_eq!(walked.len(), 6);
+    assert_eq!(walked[0], dir);
+    assert!(
+        walked.iter().position(|p| *p == dir.join("a"))
+            < walked.iter().position(|p| *p == dir.join("a/b"))
+    );
+
+    // Ancestors of entries below min_depth are filled in.
+    assert_eq!(shallow.root().unwrap().path(), dir);
+    assert_eq!(shallow.root().unwrap().usage().logical(), 7);
+}
+
+#[test]
+fn trees_are_merged() {
+    let dir = crate::test_dir("tree-merge");
+    let other = crate::test_dir("tree-merge-other");
+    std::fs::create_dir_all(dir.join("a/b")).unwrap();
+    std::fs::write(dir.join("a/b/file"), b"hello").unwrap();
+    std::fs::write(dir.join("a/file"), b"hi").unwrap();
+    std::fs::write(dir.join("file"), b"!").unwrap();
+    std::fs::write(other.join("file"), b"other").unwrap();
+
+    let whole = Scanner::new(&dir).tree().unwrap();
+    let mut merged = Scanner::new(&dir).max_depth(1).tree().unwrap();
+    let below = Scanner::new(dir.join("a")).min_depth(1).tree().unwrap();
+    let separate = Scanner::new(&other).tree().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&other);
+
+    merged.merge(&below);
+    assert_eq!(merged.len(), whole.len());
+    assert_eq!(merged.roots().count(), 1);
+    assert_eq!(
+        merged.root().unwrap().usage(),
+        whole.root().unwrap().usage()
+    );
+    assert_eq!(
+        merged.get(dir.join("a")).unwrap().usage(),
+        whole.get(dir.join("a")).unwrap().usage()
+    );
+    assert_eq!(
+        merged.get(dir.join("a")).unwrap().size_info(),
+        whole.get(dir.join("a")).unwrap().size_info()
+    );
+
+    // Entries found by both trees are not counted twice.
+    merged.merge(&below);
+    merged.merge(&whole);
+    assert_eq!(merged.len(), whole.len());
+    assert_eq!(
+        merged.root().unwrap().usage(),
+        whole.root().unwrap().usage()
+    );
+
+    // Roots within the merged tree are joined onto it, others kept apart.
+    let mut joined = below.clone();
+    joined.merge(&separate);
+    joined.merge(&whole);
+    assert_eq!(joined.roots().count(), 2);
+    assert_eq!(joined.root().unwrap().path(), dir);
+    assert_eq!(
+        joined.root().unwrap().usage(),
+        whole.root().unwrap().usage()
+    );
+    assert_eq!(joined.len(), whole.len() + separate.len());
+}
+
+#[test]
+fn trees_track_newest_and_largest() {
+    use std::time::Duration;
+
+    let dir = crate::test_dir("tree-newest");
+    std::fs::create_dir_all(dir.join("old")).unwrap();
+    std::fs::write(dir.join("old/big"), vec![1u8; 5000]).unwrap();
+    std::fs::write(dir.join("old/small"), b"!").unwrap();
+    std::fs::write(dir.join("new"), b"new").unwrap();
+
+    let year_ago = SystemTime::now() - Duration::from_secs(365 * 86400);
+    for path in &[dir.join("old/big"), dir.join("old/small"), dir.join("old")] {
+        std::fs::File::options()
+            .read(true)
+            .open(path)
+            .and_then(|file| file.set_modified(year_ago))
+            .unwrap();
+    }
+    let newest = std::fs::metadata(dir.join("new"))
+        .unwrap()
+        .modified()
+        .unwrap();
+
+    let tree = Scanner::new(&dir).apparent_size(true).tree().unwrap();
+    let mut merged = Scanner::new(&dir).max_depth(1).tree().unwrap();
+    merged.merge(&Scanner::new(dir.join("old")).min_depth(1).tree().unwrap());
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let old = tree.get(dir.join("old")).unwrap();
+    assert_eq!(old.modified(), Some(year_ago));
+    assert_eq!(old.newest_modified(), Some(year_ago));
+    assert_eq!(old.largest_file().unwrap().path(), dir.join("old/big"));
+
+    let root = tree.root().unwrap();
+    assert!(root.newest_modified() >= Some(newest));
+    assert_eq!(root.largest_file().unwrap().path(), dir.join("old/big"));
+
+    let old = merged.get(dir.join("old")).unwrap();
+    assert_eq!(old.newest_modified(), Some(year_ago));
+    assert_eq!(old.largest_file().unwrap().path(), dir.join("old/big"));
+    assert!(merged.root().unwrap().newest_modified() >= Some(newest));
+}
+
+#[test]
+fn trees_walk_directories_before_their_contents() {
+    let dir = crate::test_dir("tree_walk");
+    for sub in ["a/b", "a/c", "d"] {
+        std::fs::create_dir_all(dir.join(sub)).unwrap();
+        std::fs::write(dir.join(sub).join("file"), b"!").unwrap();
+    }
+
+    let tree = Scanner::new(&dir).sorted(true).tree().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let walked: Vec<PathBuf> = tree.walk().map(|node| node.path()).collect();
+    let expected: Vec<PathBuf> = std::iter::once(dir.clone())
+        .chain(
+            ["a", "a/b", "a/b/file", "a/c", "a/c/file", "d", "d/file"]
+                .iter()
+                .map(|path| dir.join(path)),
+        )
+        .collect();
+    assert_eq!(walked, expected);
+
+    let a = tree.get(dir.join("a")).unwrap();
+    let names: Vec<&OsStr> = a.children().map(|child| child.name()).collect();
+    assert_eq!(names, ["b", "c"]);
+}
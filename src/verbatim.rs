@@ -0,0 +1,371 @@
+//! Converting Windows paths to the verbatim form passed to the Win32 API.
+
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+
+use crate::win::{GetVolumePathNameW, MAX_PATH};
+use crate::{Error, Result};
+
+/// Get the verbatim form of `path`, with the `\\?\` prefix which lifts the
+/// `MAX_PATH` limit and turns off the Win32 API's path parsing, as used to
+/// query sizes on Windows.
+///
+/// Paths already in the verbatim or device namespaces, such as
+/// `\\?\Volume{GUID}\dir` or `\\?\GLOBALROOT\Device\HarddiskVolume1\dir`,
+/// are kept as they are.  UNC paths like `\\server\share\dir` become
+/// `\\?\UNC\server\share\dir`, and anything else is made absolute first.
+/// Unlike `std::fs::canonicalize`, this doesn't need the path to exist, or to
+/// be on a volume with a drive letter.
+///
+/// This is only available on Windows.
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let path = filesize::verbatim_path(r"\\server\share\file.txt")?;
+/// assert_eq!(path.to_str(), Some(r"\\?\UNC\server\share\file.txt"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn verbatim_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    let path = path.as_ref();
+    let wide = verbatim(path).map_err(|e| Error::from_io(path, e))?;
+    Ok(PathBuf::from(OsString::from_wide(wide.as_slice())))
+}
+
+/// Find where `path` lies within the Volume Shadow Copy `shadow`, such as
+/// `\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1`, so the allocation of a
+/// file can be compared with that of its snapshot.
+///
+/// `path` is made absolute, and its drive or share replaced by the shadow
+/// copy.  Shadow copy devices, as listed by `vssadmin list shadows`, can be
+/// scanned and queried like any other path, though their roots need a
+/// trailing backslash, as this adds.
+///
+/// This is only available on Windows.
+///
+/// ```rust,no_run
+/// use std::path::Path;
+/// use filesize::PathExt;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let live = Path::new(r"C:\Users\Public\data.db");
+/// let snapshot = filesize::shadow_copy_path(
+///     r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1",
+///     live,
+/// )?;
+/// println!(
+///     "{} bytes now, {} in the snapshot",
+///     live.size_on_disk()?,
+///     snapshot.size_on_disk()?
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn shadow_copy_path<S, P>(shadow: S, path: P) -> Result<PathBuf>
+where
+    S: AsRef<Path>,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let absolute = std::path::absolute(path).map_err(|e| Error::from_io(path, e))?;
+
+    let mut snapshot = shadow.as_ref().as_os_str().to_os_string();
+    if snapshot.encode_wide().last() != Some(u16::from(b'\\')) {
+        snapshot.push("\\");
+    }
+    let mut snapshot = PathBuf::from(snapshot);
+    snapshot.extend(
+        absolute
+            .components()
+            .filter(|c| !matches!(c, Component::Prefix(_) | Component::RootDir)),
+    );
+    Ok(snapshot)
+}
+
+/// The root of the volume `path` is on, as a NUL-terminated wide string.
+pub(crate) fn volume_root(path: &Path) -> io::Result<Vec<u16>> {
+    if let Some(root) = device_root(path) {
+        return Ok(root);
+    }
+
+    let mut pathw: Vec<u16> = path.as_os_str().encode_wide().collect();
+    pathw.push(0);
+    let mut root = vec![0u16; pathw.len().max(MAX_PATH + 1)];
+
+    if unsafe { GetVolumePathNameW(pathw.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(root)
+}
+
+/// The root of a path in the `\\?\GLOBALROOT\Device` namespace, such as a
+/// shadow copy, which is the device itself.  `GetVolumePathNameW()` can't
+/// resolve these.
+fn device_root(path: &Path) -> Option<Vec<u16>> {
+    let wide: Vec<u16> = path.as_os_str().encode_wide().collect();
+    let namespace: Vec<u16> = r"\\?\GLOBALROOT\Device\".encode_utf16().collect();
+    let matches = wide.len() > namespace.len()
+        && wide
+            .iter()
+            .zip(&namespace)
+            .all(|(&a, &b)| ascii_upper(a) == ascii_upper(b));
+    if !matches {
+        return None;
+    }
+
+    let device = wide[namespace.len()..]
+        .iter()
+        .position(|&c| c == u16::from(b'\\'))
+        .map_or(wide.len(), |end| namespace.len() + end);
+    let mut root = wide[..device].to_vec();
+    root.extend([u16::from(b'\\'), 0]);
+    Some(root)
+}
+
+/// The number of UTF-16 units of a path held without allocating, enough for
+/// all but unusually deep paths.
+const INLINE: usize = 512;
+
+/// A wide string, held on the stack unless it's too long.
+#[derive(Clone)]
+pub(crate) struct WidePath {
+    len: usize,
+    inline: [u16; INLINE],
+    heap: Vec<u16>,
+}
+
+impl WidePath {
+    fn new() -> Self {
+        Self {
+            len: 0,
+            inline: [0; INLINE],
+            heap: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, c: u16) {
+        if self.len < INLINE {
+            self.inline[self.len] = c;
+        } else {
+            if self.heap.is_empty() {
+                self.heap.reserve(INLINE * 2);
+                self.heap.extend_from_slice(&self.inline);
+            }
+            self.heap.push(c);
+        }
+        self.len += 1;
+    }
+
+    fn extend<I: IntoIterator<Item = u16>>(&mut self, units: I) {
+        for c in units {
+            self.push(c);
+        }
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u16] {
+        if self.len <= INLINE {
+            &self.inline[..self.len]
+        } else {
+            &self.heap
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const u16 {
+        self.as_slice().as_ptr()
+    }
+}
+
+thread_local! {
+    /// The last parent directory converted on this thread, and its verbatim
+    /// form.
+    static PARENT: RefCell<Option<(PathBuf, WidePath)>> = const { RefCell::new(None) };
+}
+
+/// The verbatim form of `path` as a NUL-terminated wide string.
+///
+/// Scans size every file of a directory in turn, so the verbatim form of the
+/// last parent directory is kept, and file names within it appended to that
+/// rather than resolving each path in full.  Like the rest of a scan, this
+/// assumes the working directory doesn't change beneath it.
+pub(crate) fn wide_path(path: &Path) -> io::Result<WidePath> {
+    let mut wide = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            match in_parent(parent, name)? {
+                Some(wide) => wide,
+                None => verbatim(path)?,
+            }
+        }
+        _ => verbatim(path)?,
+    };
+    wide.push(0);
+    Ok(wide)
+}
+
+/// The verbatim form of `name` within `parent`, or `None` if the name is
+/// one the Win32 API would normalize.
+fn in_parent(parent: &Path, name: &OsStr) -> io::Result<Option<WidePath>> {
+    PARENT.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        let dir = match &*cached {
+            Some((path, dir)) if path.as_os_str() == parent.as_os_str() => dir,
+            _ => &cached.insert((parent.to_path_buf(), verbatim(parent)?)).1,
+        };
+
+        let mut wide = dir.clone();
+        if wide.as_slice().last() != Some(&u16::from(b'\\')) {
+            wide.push(u16::from(b'\\'));
+        }
+        let start = wide.as_slice().len();
+        wide.extend(name.encode_wide());
+        Ok(is_plain(&wide.as_slice()[start..]).then_some(wide))
+    })
+}
+
+/// The verbatim form of `path`.
+///
+/// This is found for every file sized, so absolute paths needing no
+/// normalization are converted without allocating.  Others are resolved by
+/// `std::path::absolute()` first.
+fn verbatim(path: &Path) -> io::Result<WidePath> {
+    let mut wide = WidePath::new();
+    wide.extend(path.as_os_str().encode_wide());
+    let units = wide.as_slice();
+    if has_prefix(units, r"\\?\") || has_prefix(units, r"\\.\") || has_prefix(units, r"\??\") {
+        return Ok(wide);
+    }
+    if is_normalized(units) {
+        return Ok(prefixed(units));
+    }
+
+    let mut absolute = WidePath::new();
+    absolute.extend(std::path::absolute(path)?.as_os_str().encode_wide());
+    Ok(prefixed(absolute.as_slice()))
+}
+
+/// Add the verbatim prefix to an absolute path.
+fn prefixed(absolute: &[u16]) -> WidePath {
+    let mut wide = WidePath::new();
+    if has_prefix(absolute, r"\\?\") || has_prefix(absolute, r"\\.\") {
+        wide.extend(absolute.iter().copied());
+    } else if has_prefix(absolute, r"\\") {
+        wide.extend(r"\\?\UNC\".encode_utf16());
+        wide.extend(absolute[2..].iter().copied());
+    } else {
+        wide.extend(r"\\?\".encode_utf16());
+        wide.extend(absolute.iter().copied());
+    }
+    wide
+}
+
+/// Whether `path` has a drive or UNC root and nothing the Win32 API would
+/// normalize: no forward slashes, empty components, `.` or `..`, or
+/// components ending in a dot or space.
+fn is_normalized(path: &[u16]) -> bool {
+    let rest = match path {
+        [drive, colon, root, rest @ ..]
+            if *drive < 128
+                && (*drive as u8).is_ascii_alphabetic()
+                && *colon == u16::from(b':')
+                && *root == u16::from(b'\\') =>
+        {
+            rest
+        }
+        [a, b, rest @ ..] if *a == u16::from(b'\\') && *b == u16::from(b'\\') => rest,
+        _ => return false,
+    };
+
+    // A drive root alone, or a path with a trailing separator, is fine.
+    let rest = rest.strip_suffix(&[u16::from(b'\\')]).unwrap_or(rest);
+    rest.is_empty() || rest.split(|&c| c == u16::from(b'\\')).all(is_plain)
+}
+
+/// Whether a path component is used as it is: it is not empty, has no
+/// separators, and doesn't end in a dot or space.
+fn is_plain(component: &[u16]) -> bool {
+    !component.contains(&u16::from(b'/'))
+        && !component.contains(&u16::from(b'\\'))
+        && component
+            .last()
+            .is_some_and(|&c| c != u16::from(b'.') && c != u16::from(b' '))
+}
+
+fn ascii_upper(c: u16) -> u16 {
+    if (u16::from(b'a')..=u16::from(b'z')).contains(&c) {
+        c - 32
+    } else {
+        c
+    }
+}
+
+fn has_prefix(wide: &[u16], prefix: &str) -> bool {
+    let mut wide = wide.iter();
+    prefix.encode_utf16().all(|p| wide.next() == Some(&p))
+}
+
+#[test]
+fn paths_are_made_verbatim() {
+    let verbatim = |path: &str| verbatim_path(path).unwrap().into_os_string();
+
+    assert_eq!(verbatim(r"C:\Windows\..\Users"), r"\\?\C:\Users");
+    assert_eq!(verbatim(r"\\server\share\dir"), r"\\?\UNC\server\share\dir");
+    assert_eq!(verbatim("//server/share/dir"), r"\\?\UNC\server\share\dir");
+    assert_eq!(
+        verbatim(r"\\?\Volume{01234567-89ab-cdef-0123-456789abcdef}\dir"),
+        r"\\?\Volume{01234567-89ab-cdef-0123-456789abcdef}\dir"
+    );
+    assert_eq!(
+        verbatim(r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1\dir"),
+        r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1\dir"
+    );
+    assert_eq!(verbatim(r"\\.\PhysicalDrive0"), r"\\.\PhysicalDrive0");
+    assert!(verbatim("Cargo.toml")
+        .to_string_lossy()
+        .starts_with(r"\\?\"));
+    assert_eq!(verbatim(r"C:\Users\.\Public"), r"\\?\C:\Users\Public");
+    assert_eq!(verbatim("C:/Users/Public"), r"\\?\C:\Users\Public");
+    assert_eq!(verbatim(r"C:\"), r"\\?\C:\");
+
+    let long = format!(r"C:\{}file", "dir\\".repeat(200));
+    let paths = [
+        r"C:\Users",
+        r"\\server\share\dir",
+        "Cargo.toml",
+        &long,
+        "src/lib.rs",
+        "src/scan.rs",
+        r"src\..\Cargo.toml",
+        r"C:\Users\file.",
+        r"C:\",
+    ];
+    for path in paths {
+        let wide = wide_path(Path::new(path)).unwrap();
+        let (nul, wide) = wide.as_slice().split_last().unwrap();
+        assert_eq!(*nul, 0);
+        assert_eq!(OsString::from_wide(wide), verbatim(path));
+    }
+}
+
+#[test]
+fn shadow_copy_paths_are_found() {
+    let shadow = r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1";
+    let root = |path: &str| {
+        device_root(Path::new(path)).map(|wide| OsString::from_wide(&wide[..wide.len() - 1]))
+    };
+
+    assert_eq!(
+        shadow_copy_path(shadow, r"C:\Users\file").unwrap(),
+        Path::new(r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1\Users\file")
+    );
+    assert_eq!(
+        shadow_copy_path(format!(r"{}\", shadow), r"\\server\share\file").unwrap(),
+        Path::new(r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy1\file")
+    );
+    assert_eq!(
+        root(r"\\?\globalroot\Device\HarddiskVolumeShadowCopy1\Users\file"),
+        Some(r"\\?\globalroot\Device\HarddiskVolumeShadowCopy1\".into())
+    );
+    assert_eq!(root(r"C:\Users"), None);
+}
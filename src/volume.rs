@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use crate::{DiskUsage, Error, Node, Result};
+
+/// The capacity and free space of a volume, from [`volume_space`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VolumeSpace {
+    total: u64,
+    free: u64,
+    available: u64,
+}
+
+impl VolumeSpace {
+    /// The capacity of the volume in bytes.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The bytes not allocated to anything.
+    pub fn free(&self) -> u64 {
+        self.free
+    }
+
+    /// The free bytes the current user may use, which excludes space
+    /// reserved for root on Unix and quotas on Windows.
+    pub fn available(&self) -> u64 {
+        self.available
+    }
+
+    /// The bytes allocated, the capacity less the free space.
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// The percentage of the volume's capacity `bytes` makes up, or 0 for a
+    /// volume with no capacity.
+    pub fn percent(&self, bytes: u64) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            bytes as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+/// Find the capacity and free space of the volume holding the given `path`,
+/// from `statvfs()` on Unix and `GetDiskFreeSpaceExW()` on Windows.  Other
+/// platforms fail with [`Error::PlatformLimitation`].
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let usage = filesize::Scanner::new("src").total()?;
+/// let volume = filesize::volume_space("src")?;
+/// println!("src is {:.2}% of the disk", usage.percent_of_volume(&volume));
+/// # Ok(())
+/// # }
+/// ```
+pub fn volume_space<P: AsRef<Path>>(path: P) -> Result<VolumeSpace> {
+    let path = path.as_ref();
+    imp::volume_space(path).map_err(|e| Error::from_io(path, e))
+}
+
+impl DiskUsage {
+    /// The percentage of `volume`'s capacity taken up by this usage, as
+    /// counted by [`DiskUsage::disk_bytes`], so files on memory filesystems
+    /// are left out.
+    pub fn percent_of_volume(&self, volume: &VolumeSpace) -> f64 {
+        volume.percent(self.disk_bytes())
+    }
+}
+
+impl Node<'_> {
+    /// The percentage of `volume`'s capacity taken up by this node and
+    /// everything below it, as with [`DiskUsage::percent_of_volume`].
+    pub fn percent_of_volume(&self, volume: &VolumeSpace) -> f64 {
+        self.usage().percent_of_volume(volume)
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::*;
+
+    pub fn volume_space(path: &Path) -> std::io::Result<VolumeSpace> {
+        let cpath = crate::c_path(path)?;
+        let mut buf: libc::statvfs = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::statvfs(cpath.as_ptr(), &mut buf) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let fragment = match buf.f_frsize as u64 {
+            0 => buf.f_bsize as u64,
+            frsize => frsize,
+        };
+
+        Ok(VolumeSpace {
+            total: (buf.f_blocks as u64).saturating_mul(fragment),
+            free: (buf.f_bfree as u64).saturating_mul(fragment),
+            available: (buf.f_bavail as u64).saturating_mul(fragment),
+        })
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+
+    use crate::win::{ularge_integer, GetDiskFreeSpaceExW, ULARGE_INTEGER};
+
+    pub fn volume_space(path: &Path) -> std::io::Result<VolumeSpace> {
+        let root = crate::verbatim::volume_root(path)?;
+
+        let mut available: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+        let mut total: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+        let mut free: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+        let ok =
+            unsafe { GetDiskFreeSpaceExW(root.as_ptr(), &mut available, &mut total, &mut free) };
+
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(VolumeSpace {
+            total: ularge_integer(&total),
+            free: ularge_integer(&free),
+            available: ularge_integer(&available),
+        })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use super::*;
+
+    pub fn volume_space(path: &Path) -> std::io::Result<VolumeSpace> {
+        path.metadata()?;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "volume space is not available on this platform",
+        ))
+    }
+}
+
+#[cfg(any(unix, windows))]
+#[test]
+fn volumes_are_measured() {
+    let volume = volume_space("Cargo.toml").unwrap();
+    let usage = crate::Scanner::new("src").total().unwrap();
+
+    assert!(volume.total() > 0);
+    assert!(volume.free() <= volume.total());
+    assert!(usage.percent_of_volume(&volume) > 0.0);
+    assert!(usage.percent_of_volume(&volume) <= 100.0);
+    assert_eq!(volume.percent(volume.total()), 100.0);
+}
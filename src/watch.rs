@@ -0,0 +1,454 @@
+//! Keeping a completed scan up to date as files change.
+//!
+//! [`Scanner::watch`] runs a scan to completion, then watches the tree for
+//! changes with the `notify` crate.  The resulting [`Watch`] yields a
+//! [`SizeChange`] whenever a file's size on disk changes, so a running total
+//! can be maintained without rescanning the whole tree.
+//!
+//! Thresholds can be set on directories within the tree with
+//! [`Watch::add_threshold`], calling back whenever changes take a directory's
+//! total over or back under its threshold, for monitoring agents raising and
+//! clearing alerts.
+//!
+//! ```rust,no_run
+//! use filesize::Scanner;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let mut watch = Scanner::new("/var/log").watch()?;
+//! println!("{} bytes on disk", watch.physical());
+//!
+//! for change in &mut watch {
+//!     let change = change?;
+//!     println!(
+//!         "{}: {} -> {}",
+//!         change.path().display(),
+//!         change.old_physical(),
+//!         change.new_physical()
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::scan::Scan;
+use crate::{Error, Result, Scanner};
+
+/// A change in the size on disk of a file in a [`Watch`]ed tree.
+///
+/// Created files have an old size of 0, and removed files a new size of 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeChange {
+    path: PathBuf,
+    old_physical: u64,
+    new_physical: u64,
+}
+
+impl SizeChange {
+    /// The path of the file that changed.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size on disk of the file before the change.
+    pub fn old_physical(&self) -> u64 {
+        self.old_physical
+    }
+
+    /// The size on disk of the file after the change.
+    pub fn new_physical(&self) -> u64 {
+        self.new_physical
+    }
+}
+
+/// A directory's total size on disk crossing a threshold set with
+/// [`Watch::add_threshold`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThresholdCrossing {
+    dir: PathBuf,
+    threshold: u64,
+    old_physical: u64,
+    new_physical: u64,
+}
+
+impl ThresholdCrossing {
+    /// The directory the threshold was set on.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The threshold crossed.
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+
+    /// The total size on disk of the directory before the change.
+    pub fn old_physical(&self) -> u64 {
+        self.old_physical
+    }
+
+    /// The total size on disk of the directory after the change.
+    pub fn new_physical(&self) -> u64 {
+        self.new_physical
+    }
+
+    /// Whether the directory now exceeds the threshold, rather than having
+    /// fallen back to it or below.
+    pub fn exceeded(&self) -> bool {
+        self.new_physical > self.threshold
+    }
+}
+
+type ThresholdFn = dyn FnMut(&ThresholdCrossing) + Send;
+
+struct Threshold {
+    dir: PathBuf,
+    threshold: u64,
+    physical: u64,
+    callback: Box<ThresholdFn>,
+}
+
+impl Scanner {
+    /// Run the scan to completion, then watch the tree for changes.
+    ///
+    /// Errors are handled as by [`total`](Self::total), and the same filters
+    /// and limits apply to files found while watching.
+    pub fn watch(self) -> Result<Watch> {
+        let (tx, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).map_err(|e| notify_error(self.root(), e))?;
+        watcher
+            .watch(self.root(), RecursiveMode::Recursive)
+            .map_err(|e| notify_error(self.root(), e))?;
+
+        let mut watch = Watch {
+            opts: Arc::new(self),
+            sizes: BTreeMap::new(),
+            physical: 0,
+            pending: VecDeque::new(),
+            thresholds: Vec::new(),
+            events,
+            _watcher: watcher,
+        };
+
+        let mut scan = Scan::new(Arc::clone(&watch.opts), Arc::default()).root();
+        for entry in &mut scan {
+            let entry = entry?;
+            if !entry.file_type().is_dir() {
+                watch.physical = watch.physical.saturating_add(entry.size_on_disk());
+                watch
+                    .sizes
+                    .insert(entry.path().to_path_buf(), entry.size_on_disk());
+            }
+        }
+
+        Ok(watch)
+    }
+}
+
+/// A scanned tree kept up to date as files change, created by
+/// [`Scanner::watch`].
+///
+/// Iterating blocks until the next change, while [`poll`](Self::poll)
+/// returns immediately.
+pub struct Watch {
+    opts: Arc<Scanner>,
+    sizes: BTreeMap<PathBuf, u64>,
+    physical: u64,
+    pending: VecDeque<Result<SizeChange>>,
+    thresholds: Vec<Threshold>,
+    events: Receiver<notify::Result<notify::Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl fmt::Debug for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch")
+            .field("root", &self.opts.root())
+            .field("files", &self.sizes.len())
+            .field("physical", &self.physical)
+            .field("thresholds", &self.thresholds.len())
+            .finish()
+    }
+}
+
+impl Watch {
+    /// The current total size on disk of the files in the tree, including
+    /// changes not yet retrieved.
+    pub fn physical(&self) -> u64 {
+        self.physical
+    }
+
+    /// The number of files tracked in the tree.
+    pub fn files(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// The current total size on disk of the files below `dir`, including
+    /// changes not yet retrieved.
+    pub fn directory_physical<P: AsRef<Path>>(&self, dir: P) -> u64 {
+        let dir = dir.as_ref();
+        self.sizes
+            .range(dir.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(dir))
+            .fold(0, |total, (_, size)| total.saturating_add(*size))
+    }
+
+    /// Call `callback` whenever a change takes the total size on disk of the
+    /// files below `dir` over `threshold` bytes, or back to it or below.
+    ///
+    /// `dir` is compared with paths as the scan reports them, so should be
+    /// the root given to the [`Scanner`], or a path joined onto it.  The
+    /// callback is only called on crossings, not for a directory already
+    /// over its threshold, which [`directory_physical`](Self::directory_physical)
+    /// can check.  Callbacks run as changes are processed, by iterating or
+    /// [`poll`](Self::poll)ing.
+    pub fn add_threshold<P, F>(&mut self, dir: P, threshold: u64, callback: F)
+    where
+        P: AsRef<Path>,
+        F: FnMut(&ThresholdCrossing) + Send + 'static,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        self.thresholds.push(Threshold {
+            physical: self.directory_physical(&dir),
+            dir,
+            threshold,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// The next change, if one is available without blocking.
+    pub fn poll(&mut self) -> Option<Result<SizeChange>> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                return Some(change);
+            }
+
+            match self.events.try_recv() {
+                Ok(event) => self.handle(event),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+    }
+
+    fn handle(&mut self, event: notify::Result<notify::Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                let path = e.paths.first().cloned();
+                let path = path.as_deref().unwrap_or_else(|| self.opts.root());
+                let error = notify_error(path, e);
+                self.pending.push_back(Err(error));
+                return;
+            }
+        };
+
+        if let EventKind::Access(_) = event.kind {
+            return;
+        }
+
+        for path in event.paths {
+            self.refresh(path);
+        }
+    }
+
+    /// Rescan `path`, and everything below it if it's a directory, queueing
+    /// changes against what was previously seen.
+    fn refresh(&mut self, path: PathBuf) {
+        let depth = match self.opts.depth_of(&path) {
+            Some(depth) => depth,
+            None => return,
+        };
+
+        let mut found = BTreeMap::new();
+        let scan =
+            Scan::new(Arc::clone(&self.opts), Arc::default()).starting_at(path.clone(), depth);
+        for entry in scan {
+            match entry {
+                Ok(entry) if !entry.file_type().is_dir() => {
+                    found.insert(entry.path().to_path_buf(), entry.size_on_disk());
+                }
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => self.pending.push_back(Err(e)),
+            }
+        }
+
+        let removed: Vec<_> = self
+            .sizes
+            .range(path.clone()..)
+            .take_while(|(p, _)| p.starts_with(&path))
+            .filter(|(p, _)| !found.contains_key(*p))
+            .map(|(p, _)| p.clone())
+            .collect();
+        for p in removed {
+            self.update(p, None);
+        }
+
+        for (p, size) in found {
+            self.update(p, Some(size));
+        }
+    }
+
+    /// Record the new size of `path`, or `None` if it was removed.
+    fn update(&mut self, path: PathBuf, size: Option<u64>) {
+        let old_physical = match size {
+            Some(size) => self.sizes.insert(path.clone(), size),
+            None => self.sizes.remove(&path),
+        };
+        let old_physical = old_physical.unwrap_or(0);
+        let new_physical = size.unwrap_or(0);
+
+        if old_physical != new_physical {
+            self.physical = self
+                .physical
+                .saturating_sub(old_physical)
+                .saturating_add(new_physical);
+            for threshold in &mut self.thresholds {
+                if path.starts_with(&threshold.dir) {
+                    threshold.apply(old_physical, new_physical);
+                }
+            }
+            self.pending.push_back(Ok(SizeChange {
+                path,
+                old_physical,
+                new_physical,
+            }));
+        }
+    }
+}
+
+impl Threshold {
+    /// Account for a file below the directory changing size, calling back if
+    /// that crosses the threshold.
+    fn apply(&mut self, old_file: u64, new_file: u64) {
+        let old_physical = self.physical;
+        self.physical = old_physical
+            .saturating_sub(old_file)
+            .saturating_add(new_file);
+
+        if (old_physical > self.threshold) != (self.physical > self.threshold) {
+            (self.callback)(&ThresholdCrossing {
+                dir: self.dir.clone(),
+                threshold: self.threshold,
+                old_physical,
+                new_physical: self.physical,
+            });
+        }
+    }
+}
+
+impl Iterator for Watch {
+    type Item = Result<SizeChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                return Some(change);
+            }
+
+            let event = self.events.recv().ok()?;
+            self.handle(event);
+        }
+    }
+}
+
+fn notify_error(path: &Path, error: notify::Error) -> Error {
+    match error.kind {
+        notify::ErrorKind::Io(source) => Error::from_io(path, source),
+        _ => Error::from_io(path, io::Error::other(error)),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn changes_are_watched() {
+    use std::time::{Duration, Instant};
+
+    let dir = crate::test_dir("watch");
+    std::fs::write(dir.join("a"), vec![1; 8192]).unwrap();
+
+    let mut watch = Scanner::new(&dir).watch().unwrap();
+    let before = watch.physical();
+    assert_eq!(watch.files(), 1);
+
+    std::fs::write(dir.join("b"), vec![1; 8192]).unwrap();
+    std::fs::remove_file(dir.join("a")).unwrap();
+
+    let mut changes = vec![];
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while watch.files() != 1 || !watch.sizes.contains_key(&dir.join("b")) {
+        match watch.poll() {
+            Some(change) => changes.push(change.unwrap()),
+            None if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            None => panic!("timed out waiting for changes: {:?}", changes),
+        }
+    }
+    while let Some(change) = watch.poll() {
+        changes.push(change.unwrap());
+    }
+
+    assert!(changes
+        .iter()
+        .any(|c| c.path() == dir.join("a") && c.new_physical() == 0));
+    assert!(changes
+        .iter()
+        .any(|c| c.path() == dir.join("b") && c.old_physical() == 0));
+    assert_eq!(watch.physical(), watch.sizes.values().sum::<u64>());
+    assert!(before > 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn thresholds_are_crossed() {
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    let dir = crate::test_dir("watch-threshold");
+    std::fs::create_dir(dir.join("logs")).unwrap();
+    std::fs::write(dir.join("logs/a"), vec![1; 8192]).unwrap();
+    std::fs::write(dir.join("other"), vec![1; 65536]).unwrap();
+
+    let mut watch = Scanner::new(&dir).watch().unwrap();
+    let logs = watch.directory_physical(dir.join("logs"));
+    let crossings = Arc::new(Mutex::new(vec![]));
+    let seen = Arc::clone(&crossings);
+    watch.add_threshold(dir.join("logs"), logs, move |crossing| {
+        seen.lock().unwrap().push(crossing.clone())
+    });
+
+    let wait_for = |watch: &mut Watch, count: usize| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while crossings.lock().unwrap().len() < count {
+            match watch.poll() {
+                Some(change) => drop(change.unwrap()),
+                None if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+                None => panic!("timed out waiting for {} crossings", count),
+            }
+        }
+    };
+
+    std::fs::write(dir.join("logs/b"), vec![1; 8192]).unwrap();
+    wait_for(&mut watch, 1);
+    std::fs::remove_file(dir.join("logs/b")).unwrap();
+    wait_for(&mut watch, 2);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let crossings = crossings.lock().unwrap();
+    assert_eq!(crossings[0].dir(), dir.join("logs"));
+    assert_eq!(crossings[0].threshold(), logs);
+    assert_eq!(crossings[0].old_physical(), logs);
+    assert!(crossings[0].exceeded());
+    assert!(!crossings[1].exceeded());
+    assert_eq!(crossings[1].new_physical(), logs);
+}
@@ -0,0 +1,300 @@
+//! The Win32 API, as used by the Windows implementations.
+//!
+//! With the default `winapi` feature these are the definitions from the
+//! `winapi` crate.  Without it they're declared here, for builds which would
+//! rather not depend on it.  Both provide the same items with the same
+//! signatures, so the rest of the crate uses them alike.
+
+pub(crate) use self::imp::*;
+
+#[cfg(feature = "winapi")]
+mod imp {
+    pub use winapi::shared::minwindef::MAX_PATH;
+    pub use winapi::shared::ntdef::{LARGE_INTEGER, ULARGE_INTEGER};
+    pub use winapi::shared::sddl::ConvertSidToStringSidW;
+    pub use winapi::shared::winerror::{
+        ERROR_HANDLE_EOF, ERROR_LOCK_VIOLATION, ERROR_MORE_DATA, ERROR_NOT_ALL_ASSIGNED,
+        ERROR_SHARING_VIOLATION, ERROR_TOO_MANY_OPEN_FILES, NO_ERROR,
+    };
+    pub use winapi::um::accctrl::SE_FILE_OBJECT;
+    pub use winapi::um::aclapi::GetNamedSecurityInfoW;
+    pub use winapi::um::fileapi::{
+        GetCompressedFileSizeW, GetDiskFreeSpaceExW, GetDiskFreeSpaceW, GetDriveTypeW,
+        GetFileInformationByHandle, GetVolumePathNameW, BY_HANDLE_FILE_INFORMATION,
+        FILE_ATTRIBUTE_TAG_INFO, FILE_COMPRESSION_INFO, FILE_STANDARD_INFO, FILE_STORAGE_INFO,
+        INVALID_FILE_SIZE,
+    };
+    pub use winapi::um::handleapi::CloseHandle;
+    pub use winapi::um::ioapiset::DeviceIoControl;
+    pub use winapi::um::minwinbase::{
+        FileAttributeTagInfo, FileCompressionInfo, FileStandardInfo, FileStorageInfo,
+    };
+    pub use winapi::um::processthreadsapi::{
+        GetCurrentProcess, GetCurrentThread, OpenProcessToken, SetThreadPriority,
+    };
+    pub use winapi::um::securitybaseapi::AdjustTokenPrivileges;
+    pub use winapi::um::winbase::{
+        GetFileInformationByHandleEx, LocalFree, LookupPrivilegeValueW, DRIVE_REMOTE,
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, THREAD_MODE_BACKGROUND_BEGIN,
+        THREAD_MODE_BACKGROUND_END,
+    };
+    pub use winapi::um::winioctl::{
+        FSCTL_GET_RETRIEVAL_POINTERS, FSCTL_QUERY_ALLOCATED_RANGES, FSCTL_QUERY_FILE_REGIONS,
+        GET_LENGTH_INFORMATION, IOCTL_DISK_GET_LENGTH_INFO,
+    };
+    pub use winapi::um::winnt::{
+        FILE_ATTRIBUTE_REPARSE_POINT, FILE_READ_ATTRIBUTES, HANDLE, OWNER_SECURITY_INFORMATION,
+        PSECURITY_DESCRIPTOR, PSID, SE_PRIVILEGE_ENABLED, TOKEN_ADJUST_PRIVILEGES,
+        TOKEN_PRIVILEGES, TOKEN_QUERY,
+    };
+    #[cfg(any(test, feature = "testing"))]
+    pub use winapi::um::{
+        winioctl::{FSCTL_SET_COMPRESSION, FSCTL_SET_SPARSE},
+        winnt::COMPRESSION_FORMAT_DEFAULT,
+    };
+
+    /// The value of a `LARGE_INTEGER`, which `winapi` defines as a union.
+    pub fn large_integer(value: &LARGE_INTEGER) -> i64 {
+        unsafe { *value.QuadPart() }
+    }
+
+    /// The value of a `ULARGE_INTEGER`, which `winapi` defines as a union.
+    pub fn ularge_integer(value: &ULARGE_INTEGER) -> u64 {
+        unsafe { *value.QuadPart() }
+    }
+}
+
+#[cfg(not(feature = "winapi"))]
+#[allow(
+    non_camel_case_types,
+    non_snake_case,
+    non_upper_case_globals,
+    clippy::upper_case_acronyms
+)]
+mod imp {
+    use std::ffi::c_void;
+
+    pub type BOOL = i32;
+    pub type HANDLE = *mut c_void;
+    pub type LARGE_INTEGER = i64;
+    pub type PSID = *mut c_void;
+    pub type ULARGE_INTEGER = u64;
+    pub type PSECURITY_DESCRIPTOR = *mut c_void;
+
+    pub const MAX_PATH: usize = 260;
+    pub const INVALID_FILE_SIZE: u32 = 0xFFFF_FFFF;
+
+    pub const NO_ERROR: u32 = 0;
+    pub const ERROR_TOO_MANY_OPEN_FILES: u32 = 4;
+    pub const ERROR_SHARING_VIOLATION: u32 = 32;
+    pub const ERROR_LOCK_VIOLATION: u32 = 33;
+    pub const ERROR_HANDLE_EOF: u32 = 38;
+    pub const ERROR_MORE_DATA: u32 = 234;
+    pub const ERROR_NOT_ALL_ASSIGNED: u32 = 1300;
+
+    pub const FILE_READ_ATTRIBUTES: u32 = 0x0080;
+    pub const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x0400;
+    pub const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+    pub const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+    pub const DRIVE_REMOTE: u32 = 4;
+
+    pub const THREAD_MODE_BACKGROUND_BEGIN: u32 = 0x0001_0000;
+    pub const THREAD_MODE_BACKGROUND_END: u32 = 0x0002_0000;
+
+    pub const SE_FILE_OBJECT: u32 = 1;
+    pub const OWNER_SECURITY_INFORMATION: u32 = 1;
+    pub const SE_PRIVILEGE_ENABLED: u32 = 2;
+    pub const TOKEN_QUERY: u32 = 0x0008;
+    pub const TOKEN_ADJUST_PRIVILEGES: u32 = 0x0020;
+
+    #[cfg(any(test, feature = "testing"))]
+    pub const COMPRESSION_FORMAT_DEFAULT: u16 = 1;
+
+    // CTL_CODE(DeviceType, Function, Method, Access).
+    pub const IOCTL_DISK_GET_LENGTH_INFO: u32 = 0x0007_405C;
+    #[cfg(any(test, feature = "testing"))]
+    pub const FSCTL_SET_COMPRESSION: u32 = 0x0009_C040;
+    #[cfg(any(test, feature = "testing"))]
+    pub const FSCTL_SET_SPARSE: u32 = 0x0009_00C4;
+    pub const FSCTL_GET_RETRIEVAL_POINTERS: u32 = 0x0009_0073;
+    pub const FSCTL_QUERY_ALLOCATED_RANGES: u32 = 0x0009_40CF;
+    pub const FSCTL_QUERY_FILE_REGIONS: u32 = 0x0009_0284;
+
+    // FILE_INFO_BY_HANDLE_CLASS.
+    pub const FileStandardInfo: u32 = 1;
+    pub const FileCompressionInfo: u32 = 8;
+    pub const FileAttributeTagInfo: u32 = 9;
+    pub const FileStorageInfo: u32 = 16;
+
+    #[repr(C)]
+    pub struct FILE_STANDARD_INFO {
+        pub AllocationSize: LARGE_INTEGER,
+        pub EndOfFile: LARGE_INTEGER,
+        pub NumberOfLinks: u32,
+        pub DeletePending: u8,
+        pub Directory: u8,
+    }
+
+    #[repr(C)]
+    pub struct FILE_COMPRESSION_INFO {
+        pub CompressedFileSize: LARGE_INTEGER,
+        pub CompressionFormat: u16,
+        pub CompressionUnitShift: u8,
+        pub ChunkShift: u8,
+        pub ClusterShift: u8,
+        pub Reserved: [u8; 3],
+    }
+
+    #[repr(C)]
+    pub struct FILE_ATTRIBUTE_TAG_INFO {
+        pub FileAttributes: u32,
+        pub ReparseTag: u32,
+    }
+
+    #[repr(C)]
+    pub struct FILE_STORAGE_INFO {
+        pub LogicalBytesPerSector: u32,
+        pub PhysicalBytesPerSectorForAtomicity: u32,
+        pub PhysicalBytesPerSectorForPerformance: u32,
+        pub FileSystemEffectivePhysicalBytesPerSectorForAtomicity: u32,
+        pub Flags: u32,
+        pub ByteOffsetForSectorAlignment: u32,
+        pub ByteOffsetForPartitionAlignment: u32,
+    }
+
+    #[repr(C)]
+    pub struct FILETIME {
+        pub dwLowDateTime: u32,
+        pub dwHighDateTime: u32,
+    }
+
+    #[repr(C)]
+    pub struct BY_HANDLE_FILE_INFORMATION {
+        pub dwFileAttributes: u32,
+        pub ftCreationTime: FILETIME,
+        pub ftLastAccessTime: FILETIME,
+        pub ftLastWriteTime: FILETIME,
+        pub dwVolumeSerialNumber: u32,
+        pub nFileSizeHigh: u32,
+        pub nFileSizeLow: u32,
+        pub nNumberOfLinks: u32,
+        pub nFileIndexHigh: u32,
+        pub nFileIndexLow: u32,
+    }
+
+    #[repr(C)]
+    pub struct GET_LENGTH_INFORMATION {
+        pub Length: LARGE_INTEGER,
+    }
+
+    #[repr(C)]
+    pub struct LUID {
+        pub LowPart: u32,
+        pub HighPart: i32,
+    }
+
+    #[repr(C)]
+    pub struct LUID_AND_ATTRIBUTES {
+        pub Luid: LUID,
+        pub Attributes: u32,
+    }
+
+    #[repr(C)]
+    pub struct TOKEN_PRIVILEGES {
+        pub PrivilegeCount: u32,
+        pub Privileges: [LUID_AND_ATTRIBUTES; 1],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn CloseHandle(hObject: HANDLE) -> BOOL;
+        pub fn DeviceIoControl(
+            hDevice: HANDLE,
+            dwIoControlCode: u32,
+            lpInBuffer: *mut c_void,
+            nInBufferSize: u32,
+            lpOutBuffer: *mut c_void,
+            nOutBufferSize: u32,
+            lpBytesReturned: *mut u32,
+            lpOverlapped: *mut c_void,
+        ) -> BOOL;
+        pub fn GetCompressedFileSizeW(lpFileName: *const u16, lpFileSizeHigh: *mut u32) -> u32;
+        pub fn GetCurrentProcess() -> HANDLE;
+        pub fn GetCurrentThread() -> HANDLE;
+        pub fn GetDiskFreeSpaceW(
+            lpRootPathName: *const u16,
+            lpSectorsPerCluster: *mut u32,
+            lpBytesPerSector: *mut u32,
+            lpNumberOfFreeClusters: *mut u32,
+            lpTotalNumberOfClusters: *mut u32,
+        ) -> BOOL;
+        pub fn GetDiskFreeSpaceExW(
+            lpDirectoryName: *const u16,
+            lpFreeBytesAvailableToCaller: *mut ULARGE_INTEGER,
+            lpTotalNumberOfBytes: *mut ULARGE_INTEGER,
+            lpTotalNumberOfFreeBytes: *mut ULARGE_INTEGER,
+        ) -> BOOL;
+        pub fn GetDriveTypeW(lpRootPathName: *const u16) -> u32;
+        pub fn GetFileInformationByHandle(
+            hFile: HANDLE,
+            lpFileInformation: *mut BY_HANDLE_FILE_INFORMATION,
+        ) -> BOOL;
+        pub fn GetFileInformationByHandleEx(
+            hFile: HANDLE,
+            FileInformationClass: u32,
+            lpFileInformation: *mut c_void,
+            dwBufferSize: u32,
+        ) -> BOOL;
+        pub fn GetVolumePathNameW(
+            lpszFileName: *const u16,
+            lpszVolumePathName: *mut u16,
+            cchBufferLength: u32,
+        ) -> BOOL;
+        pub fn LocalFree(hMem: *mut c_void) -> *mut c_void;
+        pub fn SetThreadPriority(hThread: HANDLE, nPriority: i32) -> BOOL;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        pub fn AdjustTokenPrivileges(
+            TokenHandle: HANDLE,
+            DisableAllPrivileges: BOOL,
+            NewState: *mut TOKEN_PRIVILEGES,
+            BufferLength: u32,
+            PreviousState: *mut TOKEN_PRIVILEGES,
+            ReturnLength: *mut u32,
+        ) -> BOOL;
+        pub fn ConvertSidToStringSidW(Sid: PSID, StringSid: *mut *mut u16) -> BOOL;
+        pub fn GetNamedSecurityInfoW(
+            pObjectName: *const u16,
+            ObjectType: u32,
+            SecurityInfo: u32,
+            ppsidOwner: *mut PSID,
+            ppsidGroup: *mut PSID,
+            ppDacl: *mut *mut c_void,
+            ppSacl: *mut *mut c_void,
+            ppSecurityDescriptor: *mut PSECURITY_DESCRIPTOR,
+        ) -> u32;
+        pub fn LookupPrivilegeValueW(
+            lpSystemName: *const u16,
+            lpName: *const u16,
+            lpLuid: *mut LUID,
+        ) -> BOOL;
+        pub fn OpenProcessToken(
+            ProcessHandle: HANDLE,
+            DesiredAccess: u32,
+            TokenHandle: *mut HANDLE,
+        ) -> BOOL;
+    }
+
+    /// The value of a `LARGE_INTEGER`.
+    pub fn large_integer(value: &LARGE_INTEGER) -> i64 {
+        *value
+    }
+
+    /// The value of a `ULARGE_INTEGER`.
+    pub fn ularge_integer(value: &ULARGE_INTEGER) -> u64 {
+        *value
+    }
+}